@@ -0,0 +1,51 @@
+//! Lexes the game-of-life demo repeated 1, 2, 4, ... times,
+//! and prints the time taken per byte of input.
+//! Lexing should scale linearly, so the time per byte should stay roughly constant.
+//! Run with `cargo bench --bench lex`.
+
+use ruby_wasm::lexeme::LexemeKind;
+use ruby_wasm::lexer::Lexer;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DEMO: &str = include_str!("../demo/game_of_life.rb");
+
+/// Lex all of `text`, returning the number of lexemes.
+fn lex_all(text: &str) -> usize {
+    let mut lexer = Lexer::new(text);
+    let mut count = 0;
+    while lexer.next().kind != LexemeKind::Eof {
+        count += 1;
+    }
+    count
+}
+
+/// Fastest of several runs, to filter out noise.
+fn time(text: &str) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(lex_all(black_box(text)));
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>10} {:>12} {:>10}",
+        "copies", "lines", "time", "ns/byte"
+    );
+    for copies in [1, 2, 4, 8, 16, 32, 64] {
+        let text = DEMO.repeat(copies);
+        let elapsed = time(&text);
+        println!(
+            "{:>8} {:>10} {:>12.2?} {:>10.2}",
+            copies,
+            text.lines().count(),
+            elapsed,
+            elapsed.as_nanos() as f64 / text.len() as f64
+        );
+    }
+}
@@ -1,9 +1,15 @@
 use crate::corelib;
+use crate::corelib::class;
 use crate::corelib::class::Class;
-use crate::corelib::method::Method;
+use crate::corelib::method::{Method, local_identifier};
+use crate::lexeme::Span;
 use crate::node::{
-    And, Array, Call, ConstantRead, Def, Expr, For, GlobalVariableRead, GlobalVariableWrite, If,
-    LocalVariableRead, LocalVariableWrite, Or, Program, Statements, Subsequent, Until, While,
+    And, Array, Begin, Block, BlockParam, Call, CallBlock, Case, Class as ClassDef, ConstantRead,
+    ConstantWrite, Def, Else, Expr, ExprKind, Float, For, GlobalVariableRead, GlobalVariableWrite,
+    Hash as HashLiteral, If, InstanceVariableRead, InstanceVariableWrite, InterpolatedString, Jump,
+    LocalVariableRead, LocalVariableWrite, Module as ModuleDef, MultiTarget, MultiWrite,
+    OperatorWrite, Or, Params, Program, Range, RequiredParam, Rescue, Splat, Statements,
+    StringPart, Subsequent, Target, Until, When, While, WriteOperator, Yield,
 };
 use crate::unitype::Unitype;
 use std::hash::{DefaultHasher, Hash, Hasher};
@@ -16,13 +22,137 @@ use wat_macro::wat;
 
 pub const RUBY_TOP_LEVEL_FUNCTION_NAME: &str = "__ruby_top_level_function";
 
+/// A variable holding a new box each time its method is called,
+///     so that `return` inside the method's blocks can say which call to return from.
+/// Blocks capture it like any other variable. Ruby variables can't start with `%`.
+const RETURN_TARGET: &str = "%return";
+/// Stands for the current method's params in `visit_variables`,
+///     where `super` without args uses them.
+const SUPER_ARGS: &str = "%super";
+
 pub struct CompileCtx {
     pub module: Module,
     // Uh, additional objects that need to be considered when generating corelib...
     pub methods: Vec<Method>,
+    /// Classes and modules, starting with the corelib's.
     pub classes: Vec<Class>,
-    /// Locals defined in the middle of the method
-    pub method_locals: Vec<String>,
+    /// Variables of the function being compiled.
+    pub scope: Scope,
+    /// Names of symbols used by the program, to be interned as globals.
+    pub symbols: Vec<String>,
+    /// Name of the class or module whose body is being compiled, or `None` at the top level.
+    /// `def`s define instance methods of this class, or of `Object` at the top level.
+    pub current_class: Option<String>,
+    /// The method whose body is being compiled, or `None` outside of a `def`.
+    pub current_method: Option<CurrentMethod>,
+    /// Names of the constants the program reads, which may turn out to be classes defined later.
+    pub constant_reads: Vec<String>,
+}
+
+/// What `super` needs to know about the method it's called from.
+/// `super` without args passes on the method's params, with their current values.
+#[derive(Clone)]
+pub struct CurrentMethod {
+    pub name: String,
+    /// The required and optional params.
+    pub params: Vec<String>,
+    pub rest_param: Option<String>,
+    pub post_params: Vec<String>,
+    pub keyword_params: Vec<String>,
+    pub keyword_rest_param: Option<String>,
+}
+
+impl CurrentMethod {
+    /// The names of all the params that `super` without args passes on.
+    fn forwarded_params(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .chain(&self.rest_param)
+            .chain(&self.post_params)
+            .chain(&self.keyword_params)
+            .chain(&self.keyword_rest_param)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The Ruby variables of the wasm function being compiled: the top level, a method or a block.
+#[derive(Default)]
+pub struct Scope {
+    /// Bound from `$args` when the function is called.
+    pub params: Vec<String>,
+    /// Assigned in the function's body.
+    pub locals: Vec<String>,
+    /// For a block, the variables of enclosing scopes which it uses.
+    /// Their boxes are passed in its proc's `$env`.
+    pub captured: Vec<String>,
+    /// Variables that blocks share with this function.
+    /// Their locals hold a `$var` box, rather than the value.
+    pub boxed: Vec<String>,
+    pub is_block: bool,
+    /// Inside a `rescue` clause, the label of the loop that `retry` branches to,
+    ///     and how many `ensure` clauses it's inside of.
+    pub retry_label: Option<(String, usize)>,
+    /// The innermost `while`, `until` or `for` loop in this function.
+    /// Outside of one, `break`, `next` and `redo` act on the block, if this is one.
+    pub loop_labels: Option<LoopLabels>,
+    /// The `ensure` clauses around the code being compiled, innermost last.
+    pub ensures: Vec<EnsureFrame>,
+}
+
+/// Where `break`, `next` and `redo` branch to in a loop.
+#[derive(Clone)]
+pub struct LoopLabels {
+    /// The `block` around the loop, whose result is the loop's value.
+    pub break_label: String,
+    /// The `block` around one run of the body.
+    pub next_label: String,
+    /// The `loop` around one run of the body.
+    pub redo_label: String,
+    /// How many `ensure` clauses the loop is inside of.
+    /// A jump out of the ensure clauses after these runs them first.
+    pub ensure_depth: usize,
+}
+
+/// A `begin` with an `ensure` clause, whose body is being compiled.
+/// A jump out of the body branches out of it to run the `ensure` clause,
+///     then carries on with the jump.
+pub struct EnsureFrame {
+    /// The `block` around the body, after which the `ensure` clause runs.
+    pub exit_label: String,
+    /// Index in `jumps` of the jump to carry on with, as a fixnum, or `nil`.
+    pub jump_local: String,
+    /// The value the jump carries, if it has one.
+    pub value_local: String,
+    /// The jumps out of the body, and whether each carries a value.
+    pub jumps: Vec<(JumpKind, bool)>,
+}
+
+/// Where a jump goes, so it can be made after running `ensure` clauses.
+#[derive(Clone)]
+pub enum JumpKind {
+    /// `br` to a label, `ensure_depth` `ensure` clauses deep.
+    Branch { label: String, ensure_depth: usize },
+    /// Return from the wasm function.
+    Return,
+    /// Throw a `$jump` from a block. See `compile_block_jump`.
+    Block { target: Vec<Instr> },
+}
+
+impl JumpKind {
+    /// How many `ensure` clauses the jump's target is inside of.
+    fn ensure_depth(&self) -> usize {
+        match self {
+            JumpKind::Branch { ensure_depth, .. } => *ensure_depth,
+            JumpKind::Return | JumpKind::Block { .. } => 0,
+        }
+    }
+}
+
+impl Scope {
+    fn is_defined(&self, name: &String) -> bool {
+        self.params.contains(name) || self.locals.contains(name) || self.captured.contains(name)
+    }
 }
 
 impl CompileCtx {
@@ -30,33 +160,33 @@ impl CompileCtx {
         CompileCtx {
             module,
             methods: vec![],
-            classes: vec![],
-            method_locals: vec![],
+            classes: class::corelib_classes(),
+            scope: Scope::default(),
+            symbols: vec![],
+            current_class: None,
+            current_method: None,
+            constant_reads: vec![],
         }
     }
 }
 
 pub fn compile(ctx: &mut CompileCtx, program: &Program) {
     // TODO: exported.
+    ctx.scope.boxed = block_variables(&program.statements);
     let stmts = compile_program(ctx, program);
+    add_constant_global_defs(ctx);
 
-    let local_defs = ctx.method_locals
-        .iter()
-        .map(|l| {
-            wat! { (local ,(l.to_string()) (ref eq)) }
-        })
-        .collect();
-    let local_setters = ctx.method_locals
-        .iter()
-        .map(|l| {
-            wat! {
-                (local_set ,(l.to_string())
-                    (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
-            }
-        })
-        .flatten()
-        .collect();
-    let stmts = [local_setters, stmts].concat();
+    let (local_defs, local_setters) = corelib::method::variable_locals(&[], &ctx.scope.locals);
+    // `break` or `return` from a block whose call or method has already returned.
+    let stmts = wat! {
+        (throw $ruby_exception
+            (call $local_jump_error
+                (block $stray_jump (result (ref $jump))
+                    (return
+                        (try_table (result (ref eq)) (catch $ruby_jump $stray_jump)
+                            ,(stmts))))))
+    };
+    let stmts = [local_setters, box_variables(&ctx.scope), stmts].concat();
     let no_locals = wat! {
         (func ,(RUBY_TOP_LEVEL_FUNCTION_NAME.to_string())
             (export ,(RUBY_TOP_LEVEL_FUNCTION_NAME.to_string()))
@@ -87,11 +217,25 @@ pub fn compile(ctx: &mut CompileCtx, program: &Program) {
 }
 
 fn compile_program(ctx: &mut CompileCtx, program: &Program) -> Vec<Instr> {
+    if let Some(data) = &program.data {
+        compile_data_constant(ctx, data);
+    }
     compile_statements(ctx, &program.statements)
 }
 
+/// Defines the constant `DATA`, holding the text after `__END__`.
+/// TODO: In Ruby, `DATA` is a `File` positioned at the data section, not a `String`.
+fn compile_data_constant(ctx: &mut CompileCtx, data: &str) {
+    let string = string_global(ctx, data);
+    let global = wat! {
+        (global ,(Class::name_to_identifier("DATA")) (ref $str)
+            (global_get ,(string)))
+    };
+    ctx.module.globals.push(global);
+}
+
 fn compile_statements(ctx: &mut CompileCtx, statements: &Statements) -> Vec<Instr> {
-    let Statements { body } = statements;
+    let Statements { body, .. } = statements;
 
     // In Ruby, every expression returns a value or nil.
     // If there are no statements, return nil.
@@ -111,34 +255,100 @@ fn compile_statements(ctx: &mut CompileCtx, statements: &Statements) -> Vec<Inst
 }
 
 fn compile_expr(ctx: &mut CompileCtx, expr: &Expr) -> Vec<Instr> {
-    match expr {
-        &Expr::Integer(n) => compile_integer(ctx, n),
-        Expr::SingleQuoteString(s) => compile_single_quote_string(ctx, s),
-        Expr::False => vec![i31_const(Unitype::FALSE_BIT_PATTERN)],
-        Expr::True => vec![i31_const(Unitype::TRUE_BIT_PATTERN)],
-        Expr::Nil => vec![i31_const(Unitype::NIL_BIT_PATTERN)],
-        Expr::GlobalVariableWrite(global_write) => compile_global_variable_write(ctx, global_write),
-        Expr::GlobalVariableRead(global_read) => compile_global_variable_read(ctx, global_read),
-        Expr::ConstantWrite(_constant_write) => todo!(),
-        Expr::ConstantRead(constant_read_expr) => {
-            compile_constant_read_expr(ctx, &*constant_read_expr)
-        }
-
-        Expr::If(if_expr) => compile_if_expr(ctx, &*if_expr),
-        Expr::While(while_expr) => compile_while_expr(ctx, &*while_expr),
-        Expr::Until(until_expr) => compile_until_expr(ctx, &*until_expr),
-        Expr::Call(call_expr) => compile_call_expr(ctx, &*call_expr),
-        Expr::And(and_expr) => compile_and_expr(ctx, &*and_expr),
-        Expr::Or(or_expr) => compile_or_expr(ctx, &*or_expr),
-        Expr::Array(arr_expr) => compile_arr_expr(ctx, &*arr_expr),
-        Expr::LocalVariableRead(local_variable_read_expr) => {
-            compile_local_variable_read_expr(local_variable_read_expr)
-        }
-        Expr::LocalVariableWrite(local_variable_write_expr) => {
+    match &expr.kind {
+        &ExprKind::Integer(n) => compile_integer(ctx, n),
+        &ExprKind::Float(Float(n)) => compile_float(ctx, n),
+        ExprKind::String(s) => compile_string(ctx, s),
+        ExprKind::InterpolatedString(interpolated_string) => {
+            compile_interpolated_string(ctx, interpolated_string)
+        }
+        ExprKind::Symbol(name) => compile_symbol(ctx, name),
+        ExprKind::InterpolatedSymbol(interpolated_string) => {
+            let string = compile_interpolated_string(ctx, interpolated_string);
+            wat![ (call $intern ,(string)) ]
+        }
+        ExprKind::False => vec![i31_const(Unitype::FALSE_BIT_PATTERN)],
+        ExprKind::True => vec![i31_const(Unitype::TRUE_BIT_PATTERN)],
+        ExprKind::SelfKeyword => compile_self(ctx),
+        ExprKind::Nil => vec![i31_const(Unitype::NIL_BIT_PATTERN)],
+        ExprKind::GlobalVariableWrite(global_write) => {
+            compile_global_variable_write(ctx, global_write)
+        }
+        ExprKind::GlobalVariableRead(global_read) => compile_global_variable_read(ctx, global_read),
+        ExprKind::ConstantWrite(constant_write) => compile_constant_write(ctx, constant_write),
+        ExprKind::ConstantRead(constant_read_expr) => {
+            compile_constant_read_expr(ctx, constant_read_expr, expr.span)
+        }
+        ExprKind::InstanceVariableWrite(ivar_write) => {
+            compile_instance_variable_write(ctx, ivar_write)
+        }
+        ExprKind::InstanceVariableRead(ivar_read) => compile_instance_variable_read(ctx, ivar_read),
+        ExprKind::OperatorWrite(operator_write) => {
+            compile_operator_write(ctx, operator_write, expr.span)
+        }
+        ExprKind::MultiWrite(multi_write) => compile_multi_write(ctx, multi_write),
+
+        ExprKind::If(if_expr) => compile_if_expr(ctx, if_expr),
+        ExprKind::Case(case_expr) => compile_case_expr(ctx, case_expr),
+        ExprKind::Begin(begin_expr) => compile_begin_expr(ctx, begin_expr),
+        ExprKind::Retry => {
+            let (label, ensure_depth) = ctx.scope.retry_label.clone().unwrap();
+            compile_jump(
+                ctx,
+                JumpKind::Branch {
+                    label,
+                    ensure_depth,
+                },
+                None,
+            )
+        }
+        ExprKind::Return(jump) => compile_return(ctx, jump),
+        ExprKind::Break(jump) => compile_break(ctx, jump),
+        ExprKind::Next(jump) => compile_next(ctx, jump),
+        ExprKind::Redo => {
+            let jump = match &ctx.scope.loop_labels {
+                Some(labels) => JumpKind::Branch {
+                    label: labels.redo_label.clone(),
+                    ensure_depth: labels.ensure_depth,
+                },
+                None => JumpKind::Branch {
+                    label: "block_redo".to_string(),
+                    ensure_depth: 0,
+                },
+            };
+            compile_jump(ctx, jump, None)
+        }
+        ExprKind::While(while_expr) => compile_while_expr(ctx, while_expr),
+        ExprKind::Until(until_expr) => compile_until_expr(ctx, until_expr),
+        ExprKind::Call(call_expr) => compile_call_expr(ctx, call_expr, expr.span),
+        ExprKind::And(and_expr) => compile_and_expr(ctx, and_expr),
+        ExprKind::Or(or_expr) => compile_or_expr(ctx, or_expr),
+        ExprKind::Array(arr_expr) => compile_arr_expr(ctx, arr_expr),
+        ExprKind::Hash(hash_expr) => compile_hash_expr(ctx, hash_expr),
+        ExprKind::SplatArg(_) => unreachable!("Splats are only parsed in args."),
+        ExprKind::Range(range_expr) => compile_range_expr(ctx, range_expr),
+        ExprKind::LocalVariableRead(local_variable_read_expr) => {
+            compile_local_variable_read_expr(ctx, local_variable_read_expr)
+        }
+        ExprKind::LocalVariableWrite(local_variable_write_expr) => {
             compile_local_variable_write_expr(ctx, local_variable_write_expr)
         }
-        Expr::Def(def_expr) => compile_def_expr(ctx, def_expr),
-        Expr::For(for_expr) => compile_for_expr(ctx, for_expr),
+        ExprKind::Def(def_expr) => compile_def_expr(ctx, def_expr),
+        ExprKind::Class(class_expr) => compile_class_expr(ctx, class_expr, expr.span),
+        ExprKind::Module(module_expr) => compile_module_expr(ctx, module_expr, expr.span),
+        ExprKind::Super(super_expr) => {
+            let args = compile_args(ctx, &super_expr.args);
+            compile_super(ctx, args, expr.span)
+        }
+        ExprKind::ForwardingSuper => {
+            let args = forwarded_args(ctx);
+            compile_super(ctx, args, expr.span)
+        }
+        ExprKind::Yield(yield_expr) => compile_yield(ctx, yield_expr, expr.span),
+        ExprKind::Lambda(block) => compile_block(ctx, block, true),
+        ExprKind::Parentheses(statements) => compile_statements(ctx, statements),
+        ExprKind::For(for_expr) => compile_for_expr(ctx, for_expr),
+        ExprKind::Missing => unreachable!("Programs with syntax errors aren't compiled."),
     }
 }
 
@@ -151,25 +361,35 @@ fn compile_for_expr(ctx: &mut CompileCtx, for_expr: &For) -> Vec<Instr> {
     add_method_local(ctx, idx);
     let arr_name = Uuid::new_v4().to_string();
     let idx_name = Uuid::new_v4().to_string();
+    let val_name = Uuid::new_v4().to_string();
     add_method_local(ctx, &arr_name);
     add_method_local(ctx, &idx_name);
-    add_method_local(ctx, &idx);
-    let body = compile_statements(ctx, stmts);
+    add_method_local(ctx, &val_name);
+    let val = wat! { (local_get ,(local_identifier(&val_name))) };
+    let labels = new_loop_labels(ctx, "for");
+    let body = [
+        variable_set(ctx, idx, val),
+        vec![compile_loop_body(ctx, &labels, stmts)],
+    ]
+    .concat();
 
-    [
+    let for_loop = [
         wat! {
-            (local_set ,(arr_name.clone()) ,(compile_expr(ctx, collection)))
+            (local_set ,(local_identifier(&arr_name)) ,(compile_expr(ctx, collection)))
         },
         corelib::helpers::for_in_arr(
-            arr_name,
+            local_identifier(&arr_name),
             "arr_unitype".to_string(),
-            idx_name,
-            idx.to_string(),
+            local_identifier(&idx_name),
+            local_identifier(&val_name),
             body,
         ),
-        vec![wat! { ,(i31_const(Unitype::NIL_BIT_PATTERN)) }]
+        vec![wat! { ,(i31_const(Unitype::NIL_BIT_PATTERN)) }],
     ]
-    .concat()
+    .concat();
+    wat! {
+        (block ,(labels.break_label) (result (ref eq)) ,(for_loop))
+    }
 }
 
 fn compile_local_variable_write_expr(
@@ -178,23 +398,577 @@ fn compile_local_variable_write_expr(
 ) -> Vec<Instr> {
     let LocalVariableWrite { name, val } = local_variable_write_expr;
     add_method_local(ctx, name);
-    wat! {
-        (local_set ,(name.to_string()) ,(compile_expr(ctx, val)) )
-        ,(i31_const(Unitype::NIL_BIT_PATTERN))
-    }
+    let val = compile_expr(ctx, val);
+    [
+        variable_set(ctx, name, val),
+        vec![i31_const(Unitype::NIL_BIT_PATTERN)],
+    ]
+    .concat()
 }
 
-fn compile_local_variable_read_expr(local_variable_read_expr: &LocalVariableRead) -> Vec<Instr> {
+fn compile_local_variable_read_expr(
+    ctx: &CompileCtx,
+    local_variable_read_expr: &LocalVariableRead,
+) -> Vec<Instr> {
     let LocalVariableRead { name } = local_variable_read_expr;
-    wat! {
-        (local_get ,(name.to_string()))
+    variable_get(ctx, name)
+}
+
+/// `<TARGET> <OP>= <VALUE>`, which evaluates to the value written,
+///     or to the target's value if `||=` or `&&=` doesn't write.
+/// The receiver and args of an index or attribute target are evaluated once, into locals.
+fn compile_operator_write(
+    ctx: &mut CompileCtx,
+    operator_write: &OperatorWrite,
+    span: Span,
+) -> Vec<Instr> {
+    let OperatorWrite {
+        target,
+        operator,
+        value,
+    } = operator_write;
+    let mut setup = vec![];
+    let target = match &target.kind {
+        ExprKind::Call(call) => {
+            let receiver = call
+                .receiver
+                .as_ref()
+                .map(|receiver| evaluate_once(ctx, receiver, &mut setup));
+            let args = call
+                .args
+                .iter()
+                .map(|arg| evaluate_once(ctx, arg, &mut setup))
+                .collect();
+            let call = Call {
+                receiver,
+                name: call.name.clone(),
+                args,
+                block: None,
+            };
+            Expr::new(ExprKind::Call(Box::new(call)), target.span)
+        }
+        _ => target.clone(),
+    };
+
+    let new_value = match operator {
+        WriteOperator::Or | WriteOperator::And => value.clone(),
+        WriteOperator::Call(name) => {
+            let call = Call {
+                receiver: Some(target.clone()),
+                name: name.clone(),
+                args: vec![value.clone()],
+                block: None,
+            };
+            Expr::new(ExprKind::Call(Box::new(call)), span)
+        }
+    };
+    let new_value = compile_expr(ctx, &new_value);
+    let result = Uuid::new_v4().to_string();
+    add_method_local(ctx, &result);
+    let result_read = LocalVariableRead {
+        name: result.clone(),
+    };
+    let result_read = Expr::new(ExprKind::LocalVariableRead(Box::new(result_read)), span);
+    let write = compile_expr(ctx, &target_write(&target, result_read));
+    let write = wat! {
+        (local_set ,(local_identifier(&result)) ,(new_value))
+        (drop ,(write))
+        (local_get ,(local_identifier(&result)))
+    };
+
+    let write = match operator {
+        WriteOperator::Call(_) => write,
+        WriteOperator::Or | WriteOperator::And => {
+            let current = new_local(ctx);
+            let read = match (&target.kind, operator) {
+                // Like Ruby, `||=` assigns a constant that hasn't been, rather than raising.
+                (ExprKind::ConstantRead(read), WriteOperator::Or) => {
+                    compile_constant_read_or_nil(ctx, &read.name)
+                }
+                _ => compile_expr(ctx, &target),
+            };
+            let current_get = wat! { (local_get ,(current.clone())) };
+            let (then, otherwise) = match operator {
+                WriteOperator::Or => (current_get, write),
+                _ => (write, current_get),
+            };
+            wat! {
+                (local_set ,(current.clone()) ,(read))
+                (if (result (ref eq))
+                    (call $truthy (local_get ,(current)))
+                    (then ,(then))
+                    (else ,(otherwise)))
+            }
+        }
+    };
+    [setup, write].concat()
+}
+
+/// `<TARGETS> = <VALUE>`. The value is converted to an array with `$to_ary`,
+///     whose elements are written to the targets in order. Evaluates to that array.
+fn compile_multi_write(ctx: &mut CompileCtx, multi_write: &MultiWrite) -> Vec<Instr> {
+    let MultiWrite { targets, value } = multi_write;
+    let arr = new_local(ctx);
+    let value = compile_expr(ctx, value);
+    [
+        wat! { (local_set ,(arr.clone()) (call $to_ary ,(value))) },
+        compile_multi_target(ctx, targets, &arr),
+        wat! { (local_get ,(arr)) },
+    ]
+    .concat()
+}
+
+/// Write the elements of the array in the local `arr` to `multi_target`'s targets.
+/// Targets past the end of the array get `nil`, and a splat gets what the others don't.
+fn compile_multi_target(ctx: &mut CompileCtx, multi_target: &MultiTarget, arr: &str) -> Vec<Instr> {
+    let MultiTarget {
+        lefts,
+        rest,
+        rights,
+    } = multi_target;
+    let arr_get = || wat! { (ref_cast (ref $arr_unitype) (local_get ,(arr.to_string()))) };
+    let mut instrs = vec![];
+    for (idx, target) in lefts.iter().enumerate() {
+        let idx = wat! { (const_i32 ,(idx as i64)) };
+        let val = wat! { (call $arr_get_or_nil ,([arr_get(), idx].concat())) };
+        instrs.append(&mut compile_target_write(ctx, target, val));
+    }
+    let Some(Splat { target }) = rest else {
+        return instrs;
+    };
+    let splat_end = || {
+        let counts = wat! {
+            (const_i32 ,(lefts.len() as i64))
+            (const_i32 ,(rights.len() as i64))
+        };
+        wat! { (call $splat_end ,([arr_get(), counts].concat())) }
+    };
+    if let Some(target) = target {
+        let start = wat! { (const_i32 ,(lefts.len() as i64)) };
+        let val = wat! { (call $arr_slice ,([arr_get(), start, splat_end()].concat())) };
+        instrs.append(&mut compile_target_write(ctx, target, val));
     }
+    for (idx, target) in rights.iter().enumerate() {
+        let offset = wat! { (const_i32 ,(idx as i64)) };
+        let idx = wat! { (i32_add ,([splat_end(), offset].concat())) };
+        let val = wat! { (call $arr_get_or_nil ,([arr_get(), idx].concat())) };
+        instrs.append(&mut compile_target_write(ctx, target, val));
+    }
+    instrs
+}
+
+/// Write `val` to `target` of a multiple assignment, destructuring it again if it's nested.
+fn compile_target_write(ctx: &mut CompileCtx, target: &Target, val: Vec<Instr>) -> Vec<Instr> {
+    let name = Uuid::new_v4().to_string();
+    add_method_local(ctx, &name);
+    let local = local_identifier(&name);
+    match target {
+        Target::Single(target) => {
+            let read = LocalVariableRead { name };
+            let read = Expr::new(ExprKind::LocalVariableRead(Box::new(read)), target.span);
+            let write = compile_expr(ctx, &target_write(target, read));
+            wat! {
+                (local_set ,(local) ,(val))
+                (drop ,(write))
+            }
+        }
+        Target::Nested(multi_target) => [
+            wat! { (local_set ,(local.clone()) (call $to_ary ,(val))) },
+            compile_multi_target(ctx, multi_target, &local),
+        ]
+        .concat(),
+    }
+}
+
+/// Evaluate `expr` into a new local, adding the instructions to `setup`.
+/// Returns a read of the local.
+fn evaluate_once(ctx: &mut CompileCtx, expr: &Expr, setup: &mut Vec<Instr>) -> Expr {
+    let name = Uuid::new_v4().to_string();
+    add_method_local(ctx, &name);
+    let val = compile_expr(ctx, expr);
+    setup.append(&mut wat! { (local_set ,(local_identifier(&name)) ,(val)) });
+    let read = LocalVariableRead { name };
+    Expr::new(ExprKind::LocalVariableRead(Box::new(read)), expr.span)
+}
+
+/// The write of `val` to `target`: a variable or constant read, or an index or attribute call.
+fn target_write(target: &Expr, val: Expr) -> Expr {
+    let kind = match &target.kind {
+        ExprKind::LocalVariableRead(read) => {
+            ExprKind::LocalVariableWrite(Box::new(LocalVariableWrite {
+                name: read.name.clone(),
+                val,
+            }))
+        }
+        ExprKind::GlobalVariableRead(read) => {
+            ExprKind::GlobalVariableWrite(Box::new(GlobalVariableWrite {
+                name: read.name.clone(),
+                expr: val,
+            }))
+        }
+        ExprKind::ConstantRead(read) => ExprKind::ConstantWrite(Box::new(ConstantWrite {
+            name: read.name.clone(),
+            expr: val,
+        })),
+        ExprKind::InstanceVariableRead(read) => {
+            ExprKind::InstanceVariableWrite(Box::new(InstanceVariableWrite {
+                name: read.name.clone(),
+                expr: val,
+            }))
+        }
+        ExprKind::Call(call) => ExprKind::Call(Box::new(Call {
+            receiver: call.receiver.clone(),
+            name: format!("{}=", call.name),
+            args: [call.args.clone(), vec![val]].concat(),
+            block: None,
+        })),
+        other => unreachable!("{:?} can't be written", other),
+    };
+    Expr::new(kind, target.span)
+}
+
+/// The value of the variable `name`, taking it out of its box if blocks share it.
+fn variable_get(ctx: &CompileCtx, name: &str) -> Vec<Instr> {
+    let local = local_identifier(name);
+    if ctx.scope.boxed.iter().any(|boxed| boxed == name) {
+        wat! {
+            (struct_get $var $val (ref_cast (ref $var) (local_get ,(local))))
+        }
+    } else {
+        wat! { (local_get ,(local)) }
+    }
+}
+
+/// Set the variable `name` to `val`, in its box if blocks share it.
+fn variable_set(ctx: &CompileCtx, name: &str, val: Vec<Instr>) -> Vec<Instr> {
+    let local = local_identifier(name);
+    if ctx.scope.boxed.iter().any(|boxed| boxed == name) {
+        let var = wat! { (ref_cast (ref $var) (local_get ,(local))) };
+        wat! {
+            (struct_set $var $val ,([var, val].concat()))
+        }
+    } else {
+        wat! { (local_set ,(local) ,(val)) }
+    }
+}
+
+/// Put each of `scope`'s boxed params and locals in a new `$var`, holding its current value.
+fn box_variables(scope: &Scope) -> Vec<Instr> {
+    scope
+        .params
+        .iter()
+        .chain(&scope.locals)
+        .filter(|name| scope.boxed.contains(name))
+        .flat_map(|name| {
+            let local = local_identifier(name);
+            wat! {
+                (local_set ,(local.clone()) (struct_new $var (local_get ,(local))))
+            }
+        })
+        .collect()
 }
 
+/// Define an instance method of the current class.
+/// Redefining a method replaces it.
+/// Methods of `Object` are also exported, to be called from JS on `main`.
 fn compile_def_expr(ctx: &mut CompileCtx, def_expr: &Def) -> Vec<Instr> {
-    let Def { name, params, body } = def_expr;
+    let Def {
+        singleton,
+        name,
+        params,
+        body,
+    } = def_expr;
+    let class = match (singleton, ctx.current_class.clone()) {
+        (false, current_class) => current_class.unwrap_or(class::object().name),
+        (true, Some(current_class)) if ctx.current_method.is_none() => {
+            singleton_module(ctx, &current_class)
+        }
+        (true, _) => {
+            let message = "`def self.` outside of a class or module body isn't supported";
+            return compile_raise(ctx, &class::not_implemented_error(), message);
+        }
+    };
+
+    if class == class::object().name {
+        add_def_export(ctx, name, &params.required);
+    }
+
+    // The method body has its own variables.
+    let param_names = params.names();
+    let outer_method = ctx.current_method.replace(CurrentMethod {
+        name: name.to_string(),
+        params: params
+            .required
+            .iter()
+            .map(|param| param.name.clone())
+            .chain(params.optional.iter().map(|param| param.name.clone()))
+            .collect(),
+        rest_param: params.rest.as_ref().map(|param| param.name.clone()),
+        post_params: params.post.iter().map(|param| param.name.clone()).collect(),
+        keyword_params: params
+            .keyword
+            .iter()
+            .map(|param| param.name.clone())
+            .collect(),
+        keyword_rest_param: params.keyword_rest.as_ref().map(|param| param.name.clone()),
+    });
+    // Defaults are evaluated in the method, so blocks in them share its variables too.
+    let mut boxed = block_variables(body);
+    for default in params.defaults() {
+        visit_variables(default, false, &mut boxed);
+    }
+    let method_scope = Scope {
+        params: param_names,
+        boxed: with_super_args(ctx, boxed),
+        ..Scope::default()
+    };
+    let outer_scope = std::mem::replace(&mut ctx.scope, method_scope);
+    if let Some(block_param) = &params.block {
+        add_method_local(ctx, &block_param.name);
+    }
+    let returns_from_block = ctx.scope.boxed.iter().any(|name| name == RETURN_TARGET);
+    if returns_from_block {
+        add_method_local(ctx, &RETURN_TARGET.to_string());
+    }
+    let param_setters = bind_params(ctx, params, true);
+    let mut body = compile_statements(ctx, body);
+    if returns_from_block {
+        body = catch_jump(local_identifier(RETURN_TARGET), body);
+    }
+    ctx.current_method = outer_method;
+    let scope = std::mem::replace(&mut ctx.scope, outer_scope);
+
+    let block_param_setter = match &params.block {
+        Some(block_param) => wat! {
+            (local_set ,(local_identifier(&block_param.name))
+                (call $block_to_unitype (local_get $block)))
+        },
+        None => vec![],
+    };
+    // Params are bound after boxing, so that defaults can use the params before them.
+    let body = [
+        block_param_setter,
+        box_variables(&scope),
+        param_setters,
+        body,
+    ]
+    .concat();
+    let variables = [scope.params, scope.locals].concat();
+    let method_def = corelib::method::make_method_def(&class, name, &[], variables, body);
+
+    let method = Method {
+        class,
+        name: name.to_string(),
+        method_def,
+    };
+    ctx.methods
+        .retain(|m| m.identifier() != method.identifier());
+    ctx.methods.push(method);
+
+    wat! { (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))) }
+}
+
+/// The name of the module holding the `def self.` methods of the class `class_name`,
+///     defining it if this is the first.
+fn singleton_module(ctx: &mut CompileCtx, class_name: &str) -> String {
+    let name = Class::singleton_name(class_name);
+    if defined_class(ctx, &name).is_none() {
+        ctx.classes.push(class::user_module(&name));
+    }
+    name
+}
+
+/// The params of a `def` or block are bound from `$args` the same way.
+impl Params {
+    /// The variables the params bind, including the targets of destructuring params.
+    fn names(&self) -> Vec<String> {
+        let mut names = vec![];
+        for param in self.required.iter().chain(&self.post) {
+            names.push(param.name.clone());
+            if let Some(targets) = &param.targets {
+                visit_multi_target_variables(targets, true, &mut names);
+            }
+        }
+        let others = self
+            .optional
+            .iter()
+            .map(|param| &param.name)
+            .chain(self.rest.iter().map(|param| &param.name))
+            .chain(self.keyword.iter().map(|param| &param.name))
+            .chain(self.keyword_rest.iter().map(|param| &param.name));
+        names.extend(others.cloned());
+        names
+    }
+
+    /// The defaults of the optional and keyword params, which are evaluated when binding them.
+    fn defaults(&self) -> impl Iterator<Item = &Expr> {
+        self.optional
+            .iter()
+            .map(|param| &param.default)
+            .chain(self.keyword.iter().flat_map(|param| &param.default))
+    }
+
+    /// Are there keyword params, so that keyword args aren't positional?
+    fn takes_keywords(&self) -> bool {
+        !self.keyword.is_empty() || self.keyword_rest.is_some()
+    }
+}
+
+/// Bind the params from `$args`, in the order they're written.
+/// The keyword args are a `Hash` at the end of `$args`, which is taken off first.
+/// If `strict`, for a method or lambda, the number of positional args must fit the params.
+fn bind_params(ctx: &mut CompileCtx, params: &Params, strict: bool) -> Vec<Instr> {
+    if !params.takes_keywords() {
+        return bind_positional_params(ctx, params, strict);
+    }
+    let keyword_args = new_local(ctx);
+    [
+        wat! {
+            (local_set ,(keyword_args.clone()) (call $keyword_args (local_get $args) (const_i32 1)))
+        },
+        bind_positional_params(ctx, params, strict),
+        bind_keyword_params(ctx, params, keyword_args),
+    ]
+    .concat()
+}
+
+/// Bind the required, optional, rest and post params from `$args`,
+///     after checking there are enough args, and not too many, if `strict`.
+/// Otherwise, as for a proc, missing args are `nil` and extra ones are dropped.
+/// The post params take the last args, and the optional params are filled from the rest in order.
+/// If the params take keywords, their `Hash` is taken off the end of `$args` first.
+fn bind_positional_params(ctx: &mut CompileCtx, params: &Params, strict: bool) -> Vec<Instr> {
+    let Params {
+        required,
+        optional,
+        rest,
+        post,
+        ..
+    } = params;
+    let post_count = post.len() as i64;
+    let required_count = required.len() as i64 + post_count;
+    let positional_count = required_count + optional.len() as i64;
+    let max_count = match rest {
+        Some(_) => -1,
+        None => positional_count,
+    };
+    let takes_keywords = params.takes_keywords() as i64;
+    let mut instrs = wat! {
+        (local_set $args (call $positional_args (local_get $args) (const_i32 ,(takes_keywords))))
+    };
+    if strict {
+        instrs.append(&mut wat! {
+            (call $check_arity
+                (array_len (local_get $args))
+                (const_i32 ,(required_count))
+                (const_i32 ,(max_count)))
+        });
+    } else {
+        // As in Ruby, a lone array arg is spread over the params,
+        //     unless they're a single required param, which takes it whole.
+        let lone_param = required_count == 1 && optional.is_empty() && rest.is_none();
+        let splat = (required_count > 0 || optional.len() > 1) && !lone_param;
+        instrs.append(&mut wat! {
+            (local_set $args
+                (call $block_args
+                    (local_get $args)
+                    (const_i32 ,(splat as i64))
+                    (const_i32 ,(required_count))))
+        });
+    }
+    for (idx, param) in required.iter().enumerate() {
+        let arg = wat! { (array_get $arr_unitype (local_get $args) (const_i32 ,(idx as i64))) };
+        instrs.append(&mut bind_required_param(ctx, param, arg));
+    }
+    for (idx, param) in optional.iter().enumerate() {
+        let idx = required.len() as i64 + idx as i64;
+        let default = compile_expr(ctx, &param.default);
+        // The arg is only the optional param's if the post params still have theirs.
+        let arg = wat! {
+            (if (result (ref eq))
+                (i32_gt_u (array_len (local_get $args)) (const_i32 ,(idx + post_count)))
+                (then (array_get $arr_unitype (local_get $args) (const_i32 ,(idx))))
+                (else ,(default)))
+        };
+        instrs.append(&mut variable_set(ctx, &param.name, arg));
+    }
+    let post_start = wat! { (i32_sub (array_len (local_get $args)) (const_i32 ,(post_count))) };
+    if let Some(rest_param) = rest {
+        let from = wat! { (const_i32 ,(positional_count - post_count)) };
+        let rest = wat! {
+            (call $arr_slice ,([wat! { (local_get $args) }, from, post_start.clone()].concat()))
+        };
+        instrs.append(&mut variable_set(ctx, &rest_param.name, rest));
+    }
+    for (idx, param) in post.iter().enumerate() {
+        let idx = [post_start.clone(), wat! { (const_i32 ,(idx as i64)) }].concat();
+        let arg = wat! {
+            (array_get $arr_unitype (local_get $args) (i32_add ,(idx)))
+        };
+        instrs.append(&mut bind_required_param(ctx, param, arg));
+    }
+    instrs
+}
+
+/// Bind a required or post param to `arg`, then its targets if it destructures the arg.
+fn bind_required_param(ctx: &mut CompileCtx, param: &RequiredParam, arg: Vec<Instr>) -> Vec<Instr> {
+    let mut instrs = variable_set(ctx, &param.name, arg);
+    if let Some(targets) = &param.targets {
+        let arr = new_local(ctx);
+        let arg = variable_get(ctx, &param.name);
+        instrs.append(&mut wat! { (local_set ,(arr.clone()) (call $to_ary ,(arg))) });
+        instrs.append(&mut compile_multi_target(ctx, targets, &arr));
+    }
+    instrs
+}
+
+/// Bind the keyword params from the `Hash` of keyword args in `keyword_args`,
+///     which is empty if none were given.
+/// Without a `**` param, any other keyword is an `ArgumentError`.
+fn bind_keyword_params(ctx: &mut CompileCtx, params: &Params, keyword_args: String) -> Vec<Instr> {
+    let keyword_params = &params.keyword;
+    let hash = wat! { (ref_cast (ref $hash) (local_get ,(keyword_args))) };
+    let mut instrs = vec![];
+    for param in keyword_params {
+        let key = wat! { (global_get ,(symbol_identifier(ctx, &param.name))) };
+        let arg = match &param.default {
+            None => wat! {
+                (call $required_keyword_arg ,([hash.clone(), key].concat()))
+            },
+            Some(default) => {
+                let default = compile_expr(ctx, default);
+                let idx = wat! { (call $hash_index ,([hash.clone(), key.clone()].concat())) };
+                wat! {
+                    (if (result (ref eq))
+                        (i32_lt_s ,([idx, wat! { (const_i32 0) }].concat()))
+                        (then ,(default))
+                        (else (call $hash_get ,([hash.clone(), key].concat()))))
+                }
+            }
+        };
+        instrs.append(&mut variable_set(ctx, &param.name, arg));
+    }
+    let keys: Vec<_> = keyword_params
+        .iter()
+        .flat_map(|param| wat! { (global_get ,(symbol_identifier(ctx, &param.name))) })
+        .collect();
+    let keys = wat! {
+        (array_new_fixed $arr_unitype ,(keyword_params.len() as i64) ,(keys))
+    };
+    match &params.keyword_rest {
+        Some(keyword_rest_param) => {
+            let rest = wat! { (call $hash_except ,([hash, keys].concat())) };
+            instrs.append(&mut variable_set(ctx, &keyword_rest_param.name, rest));
+        }
+        None => instrs.append(&mut wat! { (call $check_keywords ,([hash, keys].concat())) }),
+    }
+    instrs
+}
 
+/// Export a JS-callable wrapper for the `Object` method `name`, calling it on `main`.
+fn add_def_export(ctx: &mut CompileCtx, name: &str, params: &[RequiredParam]) {
     let export_fn_name = [name, "_export"].concat();
+    ctx.module.funcs.retain(|func| func.name != export_fn_name);
     let export_params = params
         .iter()
         .map(|p| Param {
@@ -222,62 +996,876 @@ fn compile_def_expr(ctx: &mut CompileCtx, def_expr: &Def) -> Vec<Instr> {
                 (call ,(corelib::method::method_identifier("Object", name))
                     (global_get $main)
                     (array_new_fixed $arr_unitype ,(params.len() as i64)
-                        ,(args))))
+                        ,(args))
+                    (ref_null $proc)))
         },
     });
+}
 
-    let body = compile_statements(ctx, body);
-    let locals = ctx.method_locals.clone();
-    ctx.method_locals = vec![];
-
-    let method_def = corelib::method::make_method_def("Object", name, params, locals, body);
-
-    let method = Method {
-        class: "Object".to_string(),
-        name: name.to_string(),
-        method_def,
+/// Define a class, or reopen an existing one, then compile its body.
+/// The class and its superclass link are static: `corelib::class` defines its global,
+///     and `_start` wires up the links.
+/// Evaluates to the value of the body's last statement.
+/// A superclass that isn't a class, or doesn't match the one it was defined with, raises instead,
+///     as does reopening a module as a class.
+/// A superclass that isn't a constant raises a `NotImplementedError`,
+///     since it couldn't be known statically.
+fn compile_class_expr(ctx: &mut CompileCtx, class_expr: &ClassDef, span: Span) -> Vec<Instr> {
+    let ClassDef {
+        name,
+        superclass,
+        body,
+    } = class_expr;
+    let superclass_name = match superclass {
+        None => None,
+        Some(Expr {
+            kind: ExprKind::ConstantRead(constant_read),
+            ..
+        }) => Some(constant_read.name.clone()),
+        Some(superclass) => {
+            let message = format!(
+                "superclass of `{}` that isn't a constant isn't supported",
+                name
+            );
+            let superclass = compile_expr(ctx, superclass);
+            let raise = raise_at(ctx, &class::not_implemented_error(), &message, span);
+            return [superclass, wat! { (drop) }, raise].concat();
+        }
     };
-    ctx.methods.push(method);
+    if let Some(superclass_name) = &superclass_name {
+        match defined_class(ctx, superclass_name) {
+            None => {
+                let message = format!("uninitialized constant {}", superclass_name);
+                return raise_at(ctx, &class::name_error(), &message, span);
+            }
+            Some(superclass) if superclass.is_module() => {
+                let message = format!("superclass must be a Class ({} given)", superclass_name);
+                return raise_at(ctx, &class::type_error(), &message, span);
+            }
+            Some(_) => (),
+        }
+    }
 
-    wat! { (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))) }
+    match defined_class(ctx, name) {
+        Some(defined) => {
+            if defined.is_module() {
+                let message = format!("{} is not a class", name);
+                return raise_at(ctx, &class::type_error(), &message, span);
+            }
+            if superclass_name.is_some() && superclass_name != defined.superclass_name {
+                let message = format!("superclass mismatch for class {}", name);
+                return raise_at(ctx, &class::type_error(), &message, span);
+            }
+        }
+        None => {
+            let superclass_name = superclass_name.unwrap_or(class::object().name);
+            ctx.classes.push(class::user_class(name, &superclass_name))
+        }
+    }
+
+    compile_class_body(ctx, name, body)
 }
 
-fn compile_arr_expr(ctx: &mut CompileCtx, arr_expr: &Array) -> Vec<Instr> {
-    let Array { vals } = arr_expr;
-    let vals: Vec<Instr> = vals
-        .into_iter()
-        .map(|val| compile_expr(ctx, val))
-        .flatten()
-        .collect();
-    wat! {
-        (array_new_fixed $arr_unitype ,(vals.len() as i64)
-            ,(vals)
-        )
+/// Define a module, or reopen an existing one, then compile its body.
+/// Evaluates to the value of the body's last statement.
+/// Reopening a class as a module raises a `TypeError`.
+fn compile_module_expr(ctx: &mut CompileCtx, module_expr: &ModuleDef, span: Span) -> Vec<Instr> {
+    let ModuleDef { name, body } = module_expr;
+    match defined_class(ctx, name) {
+        Some(defined) => {
+            if !defined.is_module() {
+                let message = format!("{} is not a module", name);
+                return raise_at(ctx, &class::type_error(), &message, span);
+            }
+        }
+        None => ctx.classes.push(class::user_module(name)),
     }
+
+    compile_class_body(ctx, name, body)
 }
 
-fn compile_and_expr(ctx: &mut CompileCtx, and_expr: &And) -> Vec<Instr> {
-    let And { lhs, rhs } = and_expr;
-    let wat_args = {
-        let mut res = compile_expr(ctx, lhs);
-        res.append(&mut compile_expr(ctx, rhs));
-        res
-    };
-    wat![ (call $and ,(wat_args)) ]
+/// Compile `body` with `name` as the current class.
+fn compile_class_body(ctx: &mut CompileCtx, name: &str, body: &Statements) -> Vec<Instr> {
+    let outer_class = ctx.current_class.replace(name.to_string());
+    let body = compile_statements(ctx, body);
+    ctx.current_class = outer_class;
+    body
 }
 
-fn compile_or_expr(ctx: &mut CompileCtx, or_expr: &Or) -> Vec<Instr> {
-    let Or { lhs, rhs } = or_expr;
-    let wat_args = {
-        let mut res = compile_expr(ctx, lhs);
-        res.append(&mut compile_expr(ctx, rhs));
-        res
+/// The class or module named `name`, if the program or the corelib has defined it.
+fn defined_class<'a>(ctx: &'a CompileCtx, name: &str) -> Option<&'a Class> {
+    ctx.classes.iter().find(|class| class.name == name)
+}
+
+/// Call the method that the current method overrides: the next one with its name
+///     in the receiver's ancestors, after the current class.
+/// Outside of a method, raises a `RuntimeError`, like Ruby.
+fn compile_super(ctx: &mut CompileCtx, args: Vec<Instr>, span: Span) -> Vec<Instr> {
+    let Some(method) = &ctx.current_method else {
+        let raise = compile_raise(
+            ctx,
+            &class::runtime_error(),
+            "super called outside of method",
+        );
+        return [vec![source_position(span)], raise].concat();
     };
-    wat![ (call $or ,(wat_args)) ]
+    let class = ctx.current_class.clone().unwrap_or(class::object().name);
+    let name = symbol_identifier(ctx, &method.name.clone());
+    // The method's block is passed on implicitly.
+    let wat_args = [
+        compile_self(ctx),
+        wat! {
+            (global_get ,(Class::name_to_identifier(&class)))
+            (global_get ,(name))
+        },
+        args,
+        current_block(ctx),
+        vec![source_position(span)],
+    ]
+    .concat();
+    wat! {
+        (call $call_super ,(wat_args))
+    }
 }
 
-/// Convert the given integer into a Wasm fixnum or const global representation
-fn compile_integer(ctx: &mut CompileCtx, n: i64) -> Vec<Instr> {
+/// An args array of the current values of the current method's params, for `super`.
+/// The rest param's elements are passed on, and the keyword params are passed as a `Hash`, with the
+///     keyword rest param's entries.
+/// Outside of a method, there are none.
+fn forwarded_args(ctx: &mut CompileCtx) -> Vec<Instr> {
+    let Some(method) = ctx.current_method.clone() else {
+        return wat! { (array_new_fixed $arr_unitype 0) };
+    };
+    let params: Vec<_> = method
+        .params
+        .iter()
+        .flat_map(|param| variable_get(ctx, param))
+        .collect();
+    let mut args = wat! {
+        (array_new_fixed $arr_unitype ,(method.params.len() as i64)
+            ,(params))
+    };
+    if let Some(rest_param) = &method.rest_param {
+        let rest = wat! { (call $to_ary ,(variable_get(ctx, rest_param))) };
+        args = wat! { (call $arr_concat ,([args, rest].concat())) };
+    }
+    if !method.post_params.is_empty() {
+        let post: Vec<_> = method
+            .post_params
+            .iter()
+            .flat_map(|param| variable_get(ctx, param))
+            .collect();
+        let post = wat! {
+            (array_new_fixed $arr_unitype ,(method.post_params.len() as i64)
+                ,(post))
+        };
+        args = wat! { (call $arr_concat ,([args, post].concat())) };
+    }
+    if method.keyword_params.is_empty() && method.keyword_rest_param.is_none() {
+        return args;
+    }
+    let len = method.keyword_params.len() as i64;
+    let mut keys = vec![];
+    let mut vals = vec![];
+    for param in &method.keyword_params {
+        keys.append(&mut wat! { (global_get ,(symbol_identifier(ctx, param))) });
+        vals.append(&mut variable_get(ctx, param));
+    }
+    let mut keys = wat! { (array_new_fixed $arr_unitype ,(len) ,(keys)) };
+    let mut vals = wat! { (array_new_fixed $arr_unitype ,(len) ,(vals)) };
+    if let Some(keyword_rest_param) = &method.keyword_rest_param {
+        let rest = wat! { (ref_cast (ref $hash) ,(variable_get(ctx, keyword_rest_param))) };
+        let rest_keys = wat! { (struct_get $hash $keys ,(rest.clone())) };
+        let rest_vals = wat! { (struct_get $hash $vals ,(rest)) };
+        keys = wat! { (call $arr_concat ,([keys, rest_keys].concat())) };
+        vals = wat! { (call $arr_concat ,([vals, rest_vals].concat())) };
+    }
+    let fields = [
+        wat! {
+            (global_get ,(class::hash().identifier()))
+            (array_new_fixed $arr_unitype 0)
+        },
+        keys,
+        vals,
+    ]
+    .concat();
+    let keywords = wat! { (struct_new $hash ,(fields)) };
+    wat! {
+        (call $push_keyword_args ,([args, keywords].concat()))
+    }
+}
+
+/// `names`, with the current method's params in place of `SUPER_ARGS`.
+fn with_super_args(ctx: &CompileCtx, names: Vec<String>) -> Vec<String> {
+    if !names.iter().any(|name| name == SUPER_ARGS) {
+        return names;
+    }
+    let params = match &ctx.current_method {
+        Some(method) => method.forwarded_params(),
+        None => vec![],
+    };
+    let mut names: Vec<_> = names
+        .into_iter()
+        .filter(|name| name != SUPER_ARGS)
+        .collect();
+    for param in params {
+        if !names.contains(&param) {
+            names.push(param);
+        }
+    }
+    names
+}
+
+/// Call the current method's block.
+/// At the top level there's never one, so `yield` raises a `LocalJumpError`.
+fn compile_yield(ctx: &mut CompileCtx, yield_expr: &Yield, span: Span) -> Vec<Instr> {
+    let args = compile_args(ctx, &yield_expr.args);
+    let wat_args = [
+        current_block(ctx),
+        args,
+        wat! { (ref_null $proc) },
+        vec![source_position(span)],
+    ]
+    .concat();
+    wat! {
+        (call $call_block ,(wat_args))
+    }
+}
+
+/// `self`: the method's or block's `$self`, the class in a class or module body,
+///     otherwise `main`.
+fn compile_self(ctx: &CompileCtx) -> Vec<Instr> {
+    if ctx.current_method.is_some() || ctx.scope.is_block {
+        wat! { (local_get $self) }
+    } else if let Some(class_name) = &ctx.current_class {
+        wat! { (global_get ,(Class::name_to_identifier(class_name))) }
+    } else {
+        wat! { (global_get $main) }
+    }
+}
+
+/// The block passed to the current method, which may be null.
+/// At the top level there's never a block.
+fn current_block(ctx: &CompileCtx) -> Vec<Instr> {
+    if ctx.current_method.is_some() || ctx.scope.is_block {
+        wat! { (local_get $block) }
+    } else {
+        wat! { (ref_null $proc) }
+    }
+}
+
+/// The `$block` arg of a call: a new `Proc` for a literal block,
+///     a `Proc` passed with `&`, or null.
+fn compile_call_block(ctx: &mut CompileCtx, block: &Option<CallBlock>) -> Vec<Instr> {
+    match block {
+        None => wat! { (ref_null $proc) },
+        Some(CallBlock::Literal(block)) => compile_block(ctx, block, false),
+        Some(CallBlock::Argument(arg)) => {
+            let arg = compile_expr(ctx, arg);
+            wat! { (call $unitype_to_block ,(arg)) }
+        }
+    }
+}
+
+fn is_literal_block(block: &Option<CallBlock>) -> bool {
+    matches!(block, Some(CallBlock::Literal(_)))
+}
+
+/// `proc { ... }` or `Proc.new { ... }`: the block, as a `Proc`.
+/// Pre: `block` is `Some`.
+fn compile_proc_new(ctx: &mut CompileCtx, block: &Option<CallBlock>) -> Vec<Instr> {
+    let block = compile_call_block(ctx, block);
+    wat! {
+        (ref_as_non_null ,(block))
+    }
+}
+
+/// Compile a block to a wasm function, and evaluate to a new `Proc` that calls it.
+/// The proc holds the boxes of the variables it shares with enclosing scopes.
+/// A lambda checks its args like a method, but other blocks take whatever they're given.
+fn compile_block(ctx: &mut CompileCtx, block: &Block, is_lambda: bool) -> Vec<Instr> {
+    let block_params = &block.params;
+    let mut params = block_params.names();
+    params.extend(block_params.block.iter().map(|param| param.name.clone()));
+    let mut used = variables(&block.body);
+    let mut boxed = block_variables(&block.body);
+    for default in block_params.defaults() {
+        visit_variables(default, true, &mut used);
+        visit_variables(default, false, &mut boxed);
+    }
+    // Block params shadow variables of the same name.
+    let captured: Vec<_> = with_super_args(ctx, used)
+        .into_iter()
+        .filter(|name| !params.contains(name) && ctx.scope.is_defined(name))
+        .collect();
+    let block_scope = Scope {
+        params,
+        boxed: [boxed, captured.clone()].concat(),
+        captured,
+        is_block: true,
+        ..Scope::default()
+    };
+    let outer_scope = std::mem::replace(&mut ctx.scope, block_scope);
+    let param_setters = bind_params(ctx, block_params, is_lambda);
+    let body = compile_statements(ctx, &block.body);
+    let scope = std::mem::replace(&mut ctx.scope, outer_scope);
+
+    let name = format!("block_{}", Uuid::new_v4());
+    let func_ref = format!("{}_ref", name);
+    let env: Vec<_> = scope
+        .captured
+        .iter()
+        .flat_map(|name| wat! { (local_get ,(local_identifier(name))) })
+        .collect();
+    let block_def = block_def(&name, &scope, &block_params.block, param_setters, body);
+    ctx.module.funcs.push(block_def);
+    // Referencing the function from a global declares it, so it can be `ref.func`ed.
+    ctx.module.globals.push(wat! {
+        (global ,(func_ref.clone()) (ref $block_fn) (ref_func ,(name)))
+    });
+
+    let fields = [
+        wat! {
+            (global_get ,(class::proc().identifier()))
+            (array_new_fixed $arr_unitype 0)
+            (global_get ,(func_ref))
+        },
+        compile_self(ctx),
+        current_block(ctx),
+        wat! {
+            (array_new_fixed $arr_unitype ,(env.len() as i64)
+                ,(env))
+            (const_i32 ,(is_lambda as i64))
+            (const_i32 ,(block_arity(block, is_lambda)))
+        },
+    ]
+    .concat();
+    wat! {
+        (struct_new $proc ,(fields))
+    }
+}
+
+/// `Proc#arity`: the number of required args, or `-1 - <required>` if it can take more.
+/// As in Ruby, a proc's optional params only count with a rest param, but a lambda's always do.
+fn block_arity(block: &Block, is_lambda: bool) -> i64 {
+    let params = &block.params;
+    let required = (params.required.len() + params.post.len()) as i64;
+    let takes_optional = is_lambda && !params.optional.is_empty();
+    if params.rest.is_some() || takes_optional {
+        -1 - required
+    } else {
+        required
+    }
+}
+
+/// The wasm function of a block, called with its `Proc`, its args and the block passed to it.
+/// Takes `$self`, the method's `$block` and the captured variables' boxes from the proc,
+///     then binds its params with `param_setters`.
+fn block_def(
+    name: &str,
+    scope: &Scope,
+    block_param: &Option<BlockParam>,
+    param_setters: Vec<Instr>,
+    body: Vec<Instr>,
+) -> Func {
+    // Params are bound in the body, once the variables are boxed.
+    let variables = [scope.params.clone(), scope.locals.clone()].concat();
+    let (variable_defs, variable_setters) = corelib::method::variable_locals(&[], &variables);
+    let local_defs = [
+        vec![
+            wat! { (local $self (ref eq)) },
+            wat! { (local $block (ref null $proc)) },
+        ],
+        variable_defs,
+        scope
+            .captured
+            .iter()
+            .map(|name| wat! { (local ,(local_identifier(name)) (ref eq)) })
+            .collect(),
+    ]
+    .concat();
+    let captured_setters = scope.captured.iter().enumerate().flat_map(|(idx, name)| {
+        wat! {
+            (local_set ,(local_identifier(name))
+                (array_get $arr_unitype
+                    (struct_get $proc $env (local_get $proc))
+                    (const_i32 ,(idx as i64))))
+        }
+    });
+    let block_param_setter = match block_param {
+        Some(block_param) => wat! {
+            (local_set ,(local_identifier(&block_param.name))
+                (call $block_to_unitype (local_get $block_arg)))
+        },
+        None => vec![],
+    };
+    let instrs = [
+        wat! {
+            (local_set $self (struct_get $proc $self (local_get $proc)))
+            (local_set $block (struct_get $proc $block (local_get $proc)))
+        },
+        variable_setters,
+        block_param_setter,
+        box_variables(scope),
+        captured_setters.collect(),
+        param_setters,
+        wat! { (loop $block_redo (result (ref eq)) ,(body)) },
+    ]
+    .concat();
+
+    let no_locals = wat! {
+        (func ,(name.to_string())
+            (type $block_fn)
+            (param $proc (ref $proc))
+            (param $args (ref $arr_unitype))
+            (param $block_arg (ref null $proc))
+            (result (ref eq))
+            ,(instrs))
+    };
+    Func {
+        locals: local_defs,
+        ..no_locals
+    }
+}
+
+/// Names of the variables used in blocks in `statements`.
+/// Blocks share these with the enclosing function, so they're boxed.
+fn block_variables(statements: &Statements) -> Vec<String> {
+    let mut names = vec![];
+    visit_statements_variables(statements, false, &mut names);
+    names
+}
+
+/// Names of all the variables used in `statements`, including in blocks.
+fn variables(statements: &Statements) -> Vec<String> {
+    let mut names = vec![];
+    visit_statements_variables(statements, true, &mut names);
+    names
+}
+
+fn visit_statements_variables(statements: &Statements, in_block: bool, names: &mut Vec<String>) {
+    for expr in &statements.body {
+        visit_variables(expr, in_block, names);
+    }
+}
+
+/// Add the names of the variables used in `expr` to `names`,
+///     if `in_block`, or once inside a block.
+/// `def`s have their own variables, so aren't searched.
+fn visit_variables(expr: &Expr, in_block: bool, names: &mut Vec<String>) {
+    let mut add = |name: &String| {
+        if in_block && !names.contains(name) {
+            names.push(name.clone())
+        }
+    };
+    match &expr.kind {
+        ExprKind::LocalVariableRead(read) => add(&read.name),
+        ExprKind::LocalVariableWrite(write) => {
+            add(&write.name);
+            visit_variables(&write.val, in_block, names);
+        }
+        ExprKind::For(for_expr) => {
+            add(&for_expr.idx);
+            visit_variables(&for_expr.collection, in_block, names);
+            visit_statements_variables(&for_expr.stmts, in_block, names);
+        }
+        ExprKind::Call(call) => {
+            let Call {
+                receiver,
+                args,
+                block,
+                ..
+            } = &**call;
+            for expr in receiver.iter().chain(args) {
+                visit_variables(expr, in_block, names);
+            }
+            match block {
+                Some(CallBlock::Literal(block)) => visit_block_variables(block, names),
+                Some(CallBlock::Argument(arg)) => visit_variables(arg, in_block, names),
+                None => (),
+            }
+        }
+        ExprKind::Lambda(block) => visit_block_variables(block, names),
+        ExprKind::Parentheses(statements) => {
+            visit_statements_variables(statements, in_block, names)
+        }
+        ExprKind::InterpolatedString(string) | ExprKind::InterpolatedSymbol(string) => {
+            for part in &string.parts {
+                if let StringPart::Interpolation(statements) = part {
+                    visit_statements_variables(statements, in_block, names);
+                }
+            }
+        }
+        ExprKind::GlobalVariableWrite(write) => visit_variables(&write.expr, in_block, names),
+        ExprKind::ConstantWrite(write) => visit_variables(&write.expr, in_block, names),
+        ExprKind::InstanceVariableWrite(write) => visit_variables(&write.expr, in_block, names),
+        ExprKind::OperatorWrite(write) => {
+            visit_variables(&write.target, in_block, names);
+            visit_variables(&write.value, in_block, names);
+        }
+        ExprKind::MultiWrite(write) => {
+            visit_multi_target_variables(&write.targets, in_block, names);
+            visit_variables(&write.value, in_block, names);
+        }
+        ExprKind::If(if_expr) => visit_if_variables(if_expr, in_block, names),
+        ExprKind::Case(case_expr) => {
+            if let Some(subject) = &case_expr.subject {
+                visit_variables(subject, in_block, names);
+            }
+            for when in &case_expr.whens {
+                for pattern in &when.patterns {
+                    visit_variables(pattern, in_block, names);
+                }
+                visit_statements_variables(&when.statements, in_block, names);
+            }
+            if let Some(else_clause) = &case_expr.else_clause {
+                visit_statements_variables(&else_clause.statements, in_block, names);
+            }
+        }
+        ExprKind::Range(range) => {
+            visit_variables(&range.left, in_block, names);
+            visit_variables(&range.right, in_block, names);
+        }
+        ExprKind::Return(jump) => {
+            add(&RETURN_TARGET.to_string());
+            if let Some(value) = &jump.value {
+                visit_variables(value, in_block, names);
+            }
+        }
+        ExprKind::Break(jump) | ExprKind::Next(jump) => {
+            if let Some(value) = &jump.value {
+                visit_variables(value, in_block, names);
+            }
+        }
+        ExprKind::Begin(begin_expr) => {
+            for rescue in &begin_expr.rescues {
+                if let Some(variable) = &rescue.variable {
+                    add(variable);
+                }
+            }
+            visit_statements_variables(&begin_expr.statements, in_block, names);
+            for rescue in &begin_expr.rescues {
+                for class in &rescue.classes {
+                    visit_variables(class, in_block, names);
+                }
+                visit_statements_variables(&rescue.statements, in_block, names);
+            }
+            if let Some(else_clause) = &begin_expr.else_clause {
+                visit_statements_variables(&else_clause.statements, in_block, names);
+            }
+            if let Some(ensure_clause) = &begin_expr.ensure_clause {
+                visit_statements_variables(ensure_clause, in_block, names);
+            }
+        }
+        ExprKind::While(while_expr) => {
+            visit_variables(&while_expr.predicate, in_block, names);
+            visit_statements_variables(&while_expr.statements, in_block, names);
+        }
+        ExprKind::Until(until_expr) => {
+            visit_variables(&until_expr.predicate, in_block, names);
+            visit_statements_variables(&until_expr.statements, in_block, names);
+        }
+        ExprKind::And(and) => {
+            visit_variables(&and.lhs, in_block, names);
+            visit_variables(&and.rhs, in_block, names);
+        }
+        ExprKind::Or(or) => {
+            visit_variables(&or.lhs, in_block, names);
+            visit_variables(&or.rhs, in_block, names);
+        }
+        ExprKind::Array(array) => {
+            for val in &array.vals {
+                visit_variables(val, in_block, names);
+            }
+        }
+        ExprKind::Hash(hash) => {
+            for pair in &hash.pairs {
+                visit_variables(&pair.val, in_block, names);
+            }
+        }
+        ExprKind::SplatArg(val) => visit_variables(val, in_block, names),
+        ExprKind::Class(class) => {
+            if let Some(superclass) = &class.superclass {
+                visit_variables(superclass, in_block, names);
+            }
+            visit_statements_variables(&class.body, in_block, names);
+        }
+        ExprKind::Module(module) => visit_statements_variables(&module.body, in_block, names),
+        ExprKind::Super(super_expr) => {
+            for arg in &super_expr.args {
+                visit_variables(arg, in_block, names);
+            }
+        }
+        ExprKind::ForwardingSuper => add(&SUPER_ARGS.to_string()),
+        ExprKind::Yield(yield_expr) => {
+            for arg in &yield_expr.args {
+                visit_variables(arg, in_block, names);
+            }
+        }
+        ExprKind::Def(_)
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::String(_)
+        | ExprKind::Symbol(_)
+        | ExprKind::True
+        | ExprKind::SelfKeyword
+        | ExprKind::False
+        | ExprKind::Nil
+        | ExprKind::GlobalVariableRead(_)
+        | ExprKind::ConstantRead(_)
+        | ExprKind::InstanceVariableRead(_)
+        | ExprKind::Retry
+        | ExprKind::Redo
+        | ExprKind::Missing => (),
+    }
+}
+
+fn visit_multi_target_variables(
+    multi_target: &MultiTarget,
+    in_block: bool,
+    names: &mut Vec<String>,
+) {
+    let splat = multi_target
+        .rest
+        .iter()
+        .filter_map(|splat| splat.target.as_ref());
+    let targets = multi_target
+        .lefts
+        .iter()
+        .chain(splat)
+        .chain(&multi_target.rights);
+    for target in targets {
+        match target {
+            Target::Single(expr) => visit_variables(expr, in_block, names),
+            Target::Nested(nested) => visit_multi_target_variables(nested, in_block, names),
+        }
+    }
+}
+
+fn visit_block_variables(block: &Block, names: &mut Vec<String>) {
+    let params = &block.params;
+    let block_param = params.block.iter().map(|param| param.name.clone());
+    for name in params.names().into_iter().chain(block_param) {
+        if !names.contains(&name) {
+            names.push(name)
+        }
+    }
+    for default in params.defaults() {
+        visit_variables(default, true, names);
+    }
+    visit_statements_variables(&block.body, true, names);
+}
+
+fn visit_if_variables(if_expr: &If, in_block: bool, names: &mut Vec<String>) {
+    visit_variables(&if_expr.predicate, in_block, names);
+    visit_statements_variables(&if_expr.statements, in_block, names);
+    match &if_expr.subsequent {
+        Subsequent::None => (),
+        Subsequent::Elsif(elsif) => visit_if_variables(elsif, in_block, names),
+        Subsequent::Else(else_expr) => {
+            visit_statements_variables(&else_expr.statements, in_block, names)
+        }
+    }
+}
+
+/// `include`, `prepend` or `extend` the receiver with `args`,
+///     or the current class if there's no receiver.
+/// Which modules a class includes is static: `_start` builds each class's ancestors.
+/// As in Ruby, the modules are added in reverse argument order,
+///     so the first argument comes first in the ancestors.
+/// At the top level, `include` and `prepend` add to `Object`.
+/// Evaluates to the class, or raises if an arg isn't a module,
+///     or if including or prepending it would make the class its own ancestor.
+/// `extend` at the top level, receivers other than a class or module constant,
+///     and args that aren't constants or `self` in a class body, raise a `NotImplementedError`,
+///     since the ancestors couldn't be known before the program runs.
+fn compile_mixin(
+    ctx: &mut CompileCtx,
+    receiver: &Option<Expr>,
+    name: &str,
+    args: &[Expr],
+    span: Span,
+) -> Vec<Instr> {
+    let class_name = match (receiver, &ctx.current_class) {
+        (
+            Some(Expr {
+                kind: ExprKind::ConstantRead(constant_read),
+                ..
+            }),
+            _,
+        ) if defined_class(ctx, &constant_read.name).is_some() => constant_read.name.clone(),
+        (Some(receiver), _) => {
+            let message = format!(
+                "`{}` of a receiver that isn't a class or module constant isn't supported",
+                name
+            );
+            let receiver = compile_expr(ctx, receiver);
+            let raise = raise_at(ctx, &class::not_implemented_error(), &message, span);
+            return [receiver, wat! { (drop) }, raise].concat();
+        }
+        (None, Some(class_name)) => class_name.clone(),
+        (None, None) if name == "extend" => {
+            let message = "`extend` at the top level isn't supported";
+            return raise_at(ctx, &class::not_implemented_error(), message, span);
+        }
+        (None, None) => class::object().name,
+    };
+    let mut module_names = vec![];
+    for arg in args.iter().rev() {
+        let module_name = match &arg.kind {
+            ExprKind::ConstantRead(constant_read) => constant_read.name.clone(),
+            // In a class or module body, `self` is the class, like `extend self`.
+            ExprKind::SelfKeyword
+                if ctx.current_method.is_none() && ctx.current_class.is_some() =>
+            {
+                ctx.current_class.clone().unwrap()
+            }
+            _ => {
+                let message = format!(
+                    "`{}` of a module that isn't a constant isn't supported",
+                    name
+                );
+                let arg = compile_expr(ctx, arg);
+                let raise = raise_at(ctx, &class::not_implemented_error(), &message, span);
+                return [arg, wat! { (drop) }, raise].concat();
+            }
+        };
+        match defined_class(ctx, &module_name) {
+            None => {
+                let message = format!("uninitialized constant {}", module_name);
+                return raise_at(ctx, &class::name_error(), &message, span);
+            }
+            Some(module) if !module.is_module() => {
+                let message = "wrong argument type Class (expected Module)";
+                return raise_at(ctx, &class::type_error(), message, span);
+            }
+            Some(module) if name != "extend" && module.has_mixin(&ctx.classes, &class_name) => {
+                let message = format!("cyclic {} detected", name);
+                return raise_at(ctx, &class::argument_error(), &message, span);
+            }
+            Some(_) => module_names.push(module_name),
+        }
+    }
+    let class = ctx
+        .classes
+        .iter_mut()
+        .find(|class| class.name == class_name)
+        .unwrap();
+    let modules = match name {
+        "include" => &mut class.includes,
+        "prepend" => &mut class.prepends,
+        "extend" => &mut class.extends,
+        _ => unreachable!(),
+    };
+    modules.append(&mut module_names);
+    wat! { (global_get ,(Class::name_to_identifier(&class_name))) }
+}
+
+/// Raise a new `class` exception, whose message is `message`,
+///     recording `span` as where it was raised.
+fn raise_at(ctx: &mut CompileCtx, class: &Class, message: &str, span: Span) -> Vec<Instr> {
+    [
+        vec![source_position(span)],
+        compile_raise(ctx, class, message),
+    ]
+    .concat()
+}
+
+fn compile_range_expr(ctx: &mut CompileCtx, range_expr: &Range) -> Vec<Instr> {
+    let Range {
+        left,
+        right,
+        exclusive,
+    } = range_expr;
+    let fields = [
+        wat! {
+            (global_get ,(class::range().identifier()))
+            (array_new_fixed $arr_unitype 0)
+        },
+        compile_expr(ctx, left),
+        compile_expr(ctx, right),
+        wat! { (const_i32 ,(*exclusive as i64)) },
+    ]
+    .concat();
+    wat! {
+        (struct_new $range ,(fields))
+    }
+}
+
+fn compile_arr_expr(ctx: &mut CompileCtx, arr_expr: &Array) -> Vec<Instr> {
+    let Array { vals } = arr_expr;
+    // An expression can compile to several instrs, so count the expressions.
+    let len = vals.len() as i64;
+    let vals: Vec<Instr> = vals
+        .into_iter()
+        .map(|val| compile_expr(ctx, val))
+        .flatten()
+        .collect();
+    wat! {
+        (array_new_fixed $arr_unitype ,(len)
+            ,(vals)
+        )
+    }
+}
+
+/// A `Hash` of keyword args, whose keys are symbols.
+fn compile_hash_expr(ctx: &mut CompileCtx, hash_expr: &HashLiteral) -> Vec<Instr> {
+    let HashLiteral { pairs } = hash_expr;
+    let len = pairs.len() as i64;
+    let keys: Vec<Instr> = pairs
+        .iter()
+        .flat_map(|pair| wat! { (global_get ,(symbol_identifier(ctx, &pair.key))) })
+        .collect();
+    let vals: Vec<Instr> = pairs
+        .iter()
+        .flat_map(|pair| compile_expr(ctx, &pair.val))
+        .collect();
+    let fields = [
+        wat! {
+            (global_get ,(class::hash().identifier()))
+            (array_new_fixed $arr_unitype 0)
+        },
+        wat! { (array_new_fixed $arr_unitype ,(len) ,(keys)) },
+        wat! { (array_new_fixed $arr_unitype ,(len) ,(vals)) },
+    ]
+    .concat();
+    wat! {
+        (struct_new $hash ,(fields))
+    }
+}
+
+/// `lhs && rhs` is `lhs` if it's falsy, without evaluating `rhs`.
+fn compile_and_expr(ctx: &mut CompileCtx, and_expr: &And) -> Vec<Instr> {
+    let And { lhs, rhs } = and_expr;
+    let lhs_local = new_local(ctx);
+    let lhs = compile_expr(ctx, lhs);
+    let rhs = compile_expr(ctx, rhs);
+    wat! {
+        (local_set ,(lhs_local.clone()) ,(lhs))
+        (if (result (ref eq))
+            (call $truthy (local_get ,(lhs_local.clone())))
+            (then ,(rhs))
+            (else (local_get ,(lhs_local.clone()))))
+    }
+}
+
+/// `lhs || rhs` is `lhs` if it's truthy, without evaluating `rhs`.
+fn compile_or_expr(ctx: &mut CompileCtx, or_expr: &Or) -> Vec<Instr> {
+    let Or { lhs, rhs } = or_expr;
+    let lhs_local = new_local(ctx);
+    let lhs = compile_expr(ctx, lhs);
+    let rhs = compile_expr(ctx, rhs);
+    wat! {
+        (local_set ,(lhs_local.clone()) ,(lhs))
+        (if (result (ref eq))
+            (call $truthy (local_get ,(lhs_local.clone())))
+            (then (local_get ,(lhs_local.clone())))
+            (else ,(rhs)))
+    }
+}
+
+/// Convert the given integer into a Wasm fixnum or const global representation
+fn compile_integer(ctx: &mut CompileCtx, n: i64) -> Vec<Instr> {
     let unitype = Unitype::from_integer(n);
     match unitype {
         fixnum @ Unitype::Fixnum(_) => {
@@ -286,12 +1874,14 @@ fn compile_integer(ctx: &mut CompileCtx, n: i64) -> Vec<Instr> {
         Unitype::HeapNum(heapnum) => {
             // `heapnum` is a constant value.
             // So, create a global and get its value.
-
-            // TODO -- We need to dedup constant heapnums.
-            //  If you have 2 of the same-valued global this probably breaks at validation time.
-            let global_id = format!("global_i32_{}", heapnum);
-            let global = wat![ (global ,(global_id) (ref eq) (const_i64 ,(heapnum))) ];
-            ctx.module.globals.push(global);
+            let global_id = format!("boxnum_{}", heapnum);
+            if !ctx.module.globals.iter().any(|glob| glob.name == global_id) {
+                let global = wat! {
+                    (global ,(global_id.clone()) (ref $boxnum)
+                        (struct_new $boxnum (const_i64 ,(heapnum))))
+                };
+                ctx.module.globals.push(global);
+            }
 
             wat![(global_get, (global_id))]
         }
@@ -299,28 +1889,83 @@ fn compile_integer(ctx: &mut CompileCtx, n: i64) -> Vec<Instr> {
     }
 }
 
-fn compile_single_quote_string(ctx: &mut CompileCtx, str: &String) -> Vec<Instr> {
-    // TODO -- Dedup strings.
+/// Floats are always boxed, so each literal becomes a constant global.
+fn compile_float(ctx: &mut CompileCtx, n: f64) -> Vec<Instr> {
+    let global_id = format!("float_{}", n.to_bits());
+    if !ctx.module.globals.iter().any(|glob| glob.name == global_id) {
+        let global = wat! {
+            (global ,(global_id.clone()) (ref $boxfloat)
+                (struct_new $boxfloat (const_f64 ,(n))))
+        };
+        ctx.module.globals.push(global);
+    }
+
+    wat![(global_get, (global_id))]
+}
+
+fn compile_string(ctx: &mut CompileCtx, str: &str) -> Vec<Instr> {
+    wat! {
+       (global_get ,(string_global(ctx, str)))
+    }
+}
+
+/// Name of the global holding the string `str`, defining it if needed.
+fn string_global(ctx: &mut CompileCtx, str: &str) -> String {
     let mut hasher = DefaultHasher::new();
     str.hash(&mut hasher);
-    let global_id = format!("single_quote_string_{}", hasher.finish());
+    let global_id = format!("string_{}", hasher.finish());
 
-    let bytes: Vec<_> = str
-        .bytes()
-        .map(|b| wat![(const_i32, (b as i64))])
-        .flatten()
-        .collect();
-    let len = bytes.len() as i64;
-    let global = wat! {
-        (global ,(global_id.clone()) (ref $str)
-            (array_new_fixed $str ,(len)
-                ,(bytes)))
-    };
-    ctx.module.globals.push(global);
+    // Identical literals share one global.
+    if !ctx.module.globals.iter().any(|glob| glob.name == global_id) {
+        let bytes: Vec<_> = str
+            .bytes()
+            .flat_map(|b| wat![(const_i32, (b as i64))])
+            .collect();
+        let len = bytes.len() as i64;
+        let global = wat! {
+            (global ,(global_id.clone()) (ref $str)
+                (array_new_fixed $str ,(len)
+                    ,(bytes)))
+        };
+        ctx.module.globals.push(global);
+    }
+    global_id
+}
 
+/// Symbols are interned at compile time, so each name has exactly one global.
+fn compile_symbol(ctx: &mut CompileCtx, name: &str) -> Vec<Instr> {
     wat! {
-       (global_get ,(global_id))
+        (global_get ,(symbol_identifier(ctx, name)))
+    }
+}
+
+/// Get the identifier of the global holding symbol `name`,
+///     and make sure it will be defined.
+fn symbol_identifier(ctx: &mut CompileCtx, name: &str) -> String {
+    if !ctx.symbols.iter().any(|sym| sym == name) {
+        ctx.symbols.push(name.to_string());
+    }
+    corelib::global::symbol_identifier(name)
+}
+
+/// Convert each part to a string with `to_s`, then concatenate them.
+fn compile_interpolated_string(
+    ctx: &mut CompileCtx,
+    interpolated_string: &InterpolatedString,
+) -> Vec<Instr> {
+    let InterpolatedString { parts } = interpolated_string;
+    let mut res = compile_string(ctx, "");
+    for part in parts {
+        let part = match part {
+            StringPart::Literal(str) => compile_string(ctx, str),
+            StringPart::Interpolation(statements) => {
+                let statements = compile_statements(ctx, statements);
+                wat![ (call $to_s ,(statements)) ]
+            }
+        };
+        res = wat![ (call $str_concat ,([res, part].concat())) ];
     }
+    res
 }
 
 /// Add a global to the Module, setting its value to the write's rhs.
@@ -333,18 +1978,84 @@ fn compile_global_variable_write(
 
     let rhs = compile_expr(ctx, expr);
     wat! {
-       (global_set ,(name.to_string()) ,(rhs))
-       ,(i31_const(Unitype::NIL_BIT_PATTERN))
+       (global_set ,(name.to_string()) ,(rhs))
+       ,(i31_const(Unitype::NIL_BIT_PATTERN))
+    }
+}
+
+fn compile_global_variable_read(
+    ctx: &mut CompileCtx,
+    global_read: &GlobalVariableRead,
+) -> Vec<Instr> {
+    let GlobalVariableRead { name } = global_read;
+    add_nil_global_def(ctx, name);
+    wat![(global_get, (name.to_string()))]
+}
+
+/// Set `self`'s instance variable, see `$obj`.
+fn compile_instance_variable_write(
+    ctx: &mut CompileCtx,
+    ivar_write: &InstanceVariableWrite,
+) -> Vec<Instr> {
+    let InstanceVariableWrite { name, expr } = ivar_write;
+    let name = symbol_identifier(ctx, &format!("@{}", name));
+    let rhs = compile_expr(ctx, expr);
+    wat! {
+        (call $ivar_set ,([compile_self(ctx), wat![ (global_get ,(name)) ], rhs].concat()))
+    }
+}
+
+/// `self`'s instance variable, or `nil` if it hasn't been set.
+fn compile_instance_variable_read(
+    ctx: &mut CompileCtx,
+    ivar_read: &InstanceVariableRead,
+) -> Vec<Instr> {
+    let InstanceVariableRead { name } = ivar_read;
+    let name = symbol_identifier(ctx, &format!("@{}", name));
+    wat! {
+        (call $ivar_get ,([compile_self(ctx), wat![ (global_get ,(name)) ]].concat()))
+    }
+}
+
+/// Constants share their globals' names with classes, see `compile_constant_read_expr`.
+/// A class's constant can't be reassigned, since its global is the class itself.
+fn compile_constant_write(ctx: &mut CompileCtx, constant_write: &ConstantWrite) -> Vec<Instr> {
+    let ConstantWrite { name, expr } = constant_write;
+    if defined_class(ctx, name).is_some() {
+        let message = format!("{} is a class or module, and can't be reassigned", name);
+        return compile_raise(ctx, &class::type_error(), &message);
+    }
+    let identifier = Class::name_to_identifier(name);
+    add_constant_global_def(ctx, &identifier);
+
+    let rhs = compile_expr(ctx, expr);
+    let val = new_local(ctx);
+    wat! {
+       (local_set ,(val.clone()) ,(rhs))
+       (global_set ,(identifier) (local_get ,(val.clone())))
+       (local_get ,(val))
+    }
+}
+
+/// Raise a new `class` exception, whose message is `message`.
+fn compile_raise(ctx: &mut CompileCtx, class: &Class, message: &str) -> Vec<Instr> {
+    let message = string_global(ctx, message);
+    wat! {
+        (throw $ruby_exception
+            (call $new_exception
+                (global_get ,(class.identifier()))
+                (global_get ,(message))))
     }
 }
 
-fn compile_global_variable_read(
-    ctx: &mut CompileCtx,
-    global_read: &GlobalVariableRead,
-) -> Vec<Instr> {
-    let GlobalVariableRead { name } = global_read;
-    add_nil_global_def(ctx, name);
-    wat![(global_get, (name.to_string()))]
+/// If `ctx` has no global named `name`, add one for a constant, null until it's assigned.
+fn add_constant_global_def(ctx: &mut CompileCtx, name: &str) {
+    if !ctx.module.globals.iter().any(|glob| glob.name == *name) {
+        let global = wat! {
+           (global ,(name) (mut (ref null eq)) (ref_null eq))
+        };
+        ctx.module.globals.push(global);
+    }
 }
 
 /// If `ctx` has no global named `name`, add an empty definition.
@@ -359,14 +2070,55 @@ fn add_nil_global_def(ctx: &mut CompileCtx, name: &str) {
     }
 }
 
+/// A class's global is named after it, and so is any other constant's.
+/// A constant that isn't a class raises a `NameError` if it's read before it's assigned.
 fn compile_constant_read_expr(
     ctx: &mut CompileCtx,
     constant_read_expr: &ConstantRead,
+    span: Span,
 ) -> Vec<Instr> {
-    // TODO -- Assuming all constants are classes.
     let ConstantRead { name } = constant_read_expr;
-    let name = Class::name_to_identifier(name);
-    wat![(global_get, (name))]
+    let identifier = Class::name_to_identifier(name);
+    if defined_class(ctx, name).is_some() {
+        return wat![(global_get, (identifier))];
+    }
+    add_constant_read(ctx, name);
+    let name = string_global(ctx, name);
+    let get = wat! {
+        (call $constant_get (global_get ,(identifier)) (global_get ,(name)))
+    };
+    [vec![source_position(span)], get].concat()
+}
+
+/// The constant `name`, or `nil` if it hasn't been assigned, for `||=` to read.
+fn compile_constant_read_or_nil(ctx: &mut CompileCtx, name: &str) -> Vec<Instr> {
+    let identifier = Class::name_to_identifier(name);
+    if defined_class(ctx, name).is_some() {
+        return wat![(global_get, (identifier))];
+    }
+    add_constant_read(ctx, name);
+    let get = wat! { (global_get ,(identifier)) };
+    wat! {
+        (if (result (ref eq))
+            (ref_test (ref eq) ,(get))
+            (then (ref_as_non_null ,(get.clone())))
+            (else ,(vec![i31_const(Unitype::NIL_BIT_PATTERN)])))
+    }
+}
+
+fn add_constant_read(ctx: &mut CompileCtx, name: &str) {
+    if !ctx.constant_reads.iter().any(|read| read == name) {
+        ctx.constant_reads.push(name.to_string());
+    }
+}
+
+/// Add a global for each constant read that isn't a class, once all the classes are known.
+fn add_constant_global_defs(ctx: &mut CompileCtx) {
+    for name in std::mem::take(&mut ctx.constant_reads) {
+        if defined_class(ctx, &name).is_none() {
+            add_constant_global_def(ctx, &Class::name_to_identifier(&name));
+        }
+    }
 }
 
 fn compile_if_expr(ctx: &mut CompileCtx, if_expr: &If) -> Vec<Instr> {
@@ -384,145 +2136,801 @@ fn compile_if_expr(ctx: &mut CompileCtx, if_expr: &If) -> Vec<Instr> {
 
     wat! {
         (if (result (ref eq))
-            (call $from_bool ,(predicate))
+            (call $truthy ,(predicate))
             (then ,(compile_statements(ctx, statements)))
             (else ,(else_branch))
         )
     }
 }
 
+/// The longest jump table a `case` is compiled to.
+const MAX_CASE_JUMP_TABLE_LEN: i64 = 64;
+
+/// A `case` with a subject calls `pattern === subject` for each pattern in turn,
+///     until one is truthy, and evaluates that `when`'s statements.
+/// If every pattern is an integer literal, and they're close together,
+///     it jumps straight to the matching `when` with a `br_table` instead.
+fn compile_case_expr(ctx: &mut CompileCtx, case_expr: &Case) -> Vec<Instr> {
+    let Case {
+        subject,
+        whens,
+        else_clause,
+    } = case_expr;
+    let Some(subject) = subject else {
+        // Without a subject, each pattern is a condition.
+        let mut res = compile_else_clause(ctx, else_clause);
+        for when in whens.iter().rev() {
+            let conditions = when
+                .patterns
+                .iter()
+                .map(|pattern| compile_expr_to_wasm_predicate(ctx, pattern))
+                .collect();
+            let statements = compile_statements(ctx, &when.statements);
+            res = compile_if_any(conditions, statements, res);
+        }
+        return res;
+    };
+
+    // The subject is evaluated once, before any patterns.
+    let subject_local = new_local(ctx);
+    let set_subject = wat! {
+        (local_set ,(subject_local.clone()) ,(compile_expr(ctx, subject)))
+    };
+    if let Some((min, table)) = case_jump_table(whens) {
+        let jump = compile_case_jump(ctx, case_expr, &subject_local, min, table);
+        return [set_subject, jump].concat();
+    }
+
+    let mut res = compile_else_clause(ctx, else_clause);
+    for when in whens.iter().rev() {
+        let conditions = when
+            .patterns
+            .iter()
+            .map(|pattern| {
+                let wat_args = [
+                    compile_expr(ctx, pattern),
+                    wat! { (local_get ,(subject_local.clone())) },
+                    vec![source_position(pattern.span)],
+                ]
+                .concat();
+                wat! { (call $truthy (call $case_eq ,(wat_args))) }
+            })
+            .collect();
+        let statements = compile_statements(ctx, &when.statements);
+        res = compile_if_any(conditions, statements, res);
+    }
+    [set_subject, res].concat()
+}
+
+fn compile_else_clause(ctx: &mut CompileCtx, else_clause: &Option<Else>) -> Vec<Instr> {
+    match else_clause {
+        Some(else_clause) => compile_statements(ctx, &else_clause.statements),
+        None => vec![i31_const(Unitype::NIL_BIT_PATTERN)],
+    }
+}
+
+/// The body runs inside a `try_table`, which catches every Ruby exception.
+/// A caught exception is matched against each `rescue`'s classes with `===`, like a `case`,
+///     and one that no `rescue` matches is thrown again.
+/// `retry` branches back to the start of the `loop` around the body.
+/// The `ensure` clause runs after everything else. See `compile_ensure`.
+fn compile_begin_expr(ctx: &mut CompileCtx, begin_expr: &Begin) -> Vec<Instr> {
+    match &begin_expr.ensure_clause {
+        Some(ensure_clause) => compile_ensure(ctx, begin_expr, ensure_clause),
+        None => {
+            let (inner, res) = compile_rescues(ctx, begin_expr);
+            [inner, wat! { (local_get ,(res)) }].concat()
+        }
+    }
+}
+
+/// The body, rescues and `else` clause of `begin_expr`,
+///     and the local they leave the `begin`'s value in.
+fn compile_rescues(ctx: &mut CompileCtx, begin_expr: &Begin) -> (Vec<Instr>, String) {
+    let Begin {
+        statements,
+        rescues,
+        else_clause,
+        ensure_clause: _,
+    } = begin_expr;
+    let res = new_local(ctx);
+    let body = compile_statements(ctx, statements);
+    let else_statements = match else_clause {
+        Some(else_clause) => wat! {
+            (local_set ,(res.clone()) ,(compile_statements(ctx, &else_clause.statements)))
+        },
+        None => vec![],
+    };
+
+    if rescues.is_empty() {
+        let inner = [wat! { (local_set ,(res.clone()) ,(body)) }, else_statements].concat();
+        return (inner, res);
+    }
+    let id = Uuid::new_v4();
+    let retry_label = format!("begin_{}_retry", id);
+    let caught_label = format!("begin_{}_caught", id);
+    let done_label = format!("begin_{}_done", id);
+    let exception = new_local(ctx);
+    let previous_exception = new_local(ctx);
+
+    let ensure_depth = ctx.scope.ensures.len();
+    let outer_retry_label = ctx
+        .scope
+        .retry_label
+        .replace((retry_label.clone(), ensure_depth));
+    let mut rescued = wat! {
+        (throw $ruby_exception (ref_cast (ref $exception) (local_get ,(exception.clone()))))
+    };
+    for rescue in rescues.iter().rev() {
+        rescued = compile_rescue(ctx, rescue, &exception, rescued);
+    }
+    ctx.scope.retry_label = outer_retry_label;
+
+    let try_body = wat! {
+        (try_table (catch $ruby_exception ,(caught_label.clone()))
+            (local_set ,(res.clone()) ,(body)))
+    };
+    let caught = [
+        try_body,
+        else_statements,
+        wat! { (br ,(done_label.clone())) },
+    ]
+    .concat();
+    // While rescuing, `$current_exception` is the exception, for `raise` without args.
+    let inner = wat! {
+        (block ,(done_label)
+            (loop ,(retry_label)
+                (local_set ,(exception.clone())
+                    (block ,(caught_label) (result (ref $exception)) ,(caught)))
+                (local_set ,(previous_exception.clone()) (global_get $current_exception))
+                (global_set $current_exception (local_get ,(exception)))
+                (local_set ,(res.clone()) ,(rescued))
+                (global_set $current_exception (local_get ,(previous_exception)))))
+    };
+    (inner, res)
+}
+
+/// The `ensure` clause runs after the rest of `begin_expr`, however it's left:
+/// - An exception or a `$jump` thrown out of it is caught, and thrown again after the clause.
+/// - A jump out of it branches out of it, and is made after the clause. See `compile_jump`.
+fn compile_ensure(
+    ctx: &mut CompileCtx,
+    begin_expr: &Begin,
+    ensure_clause: &Statements,
+) -> Vec<Instr> {
+    let id = Uuid::new_v4();
+    let ensured_label = format!("begin_{}_ensured", id);
+    let caught_label = format!("begin_{}_ensure_caught", id);
+    let caught_jump_label = format!("begin_{}_ensure_caught_jump", id);
+    let pending = new_local(ctx);
+    let frame = EnsureFrame {
+        exit_label: ensured_label.clone(),
+        jump_local: new_local(ctx),
+        value_local: new_local(ctx),
+        jumps: vec![],
+    };
+    let jump_local = frame.jump_local.clone();
+    let value_local = frame.value_local.clone();
+
+    ctx.scope.ensures.push(frame);
+    let (inner, res) = compile_rescues(ctx, begin_expr);
+    let frame = ctx.scope.ensures.pop().unwrap();
+    let ensure_statements = compile_statements(ctx, ensure_clause);
+
+    let mut jumps = vec![];
+    for (idx, (jump, has_value)) in frame.jumps.into_iter().enumerate() {
+        let value = has_value.then(|| wat! { (local_get ,(value_local.clone())) });
+        let jump = compile_jump(ctx, jump, value);
+        let is_jump = [
+            wat! { (local_get ,(jump_local.clone())) },
+            vec![i31_const(Unitype::from_integer(idx as i64).to_i31_bits())],
+        ]
+        .concat();
+        jumps.extend(wat! { (if (ref_eq ,(is_jump)) (then ,(jump))) });
+    }
+
+    let try_body = wat! {
+        (try_table
+            (catch $ruby_exception ,(caught_label.clone()))
+            (catch $ruby_jump ,(caught_jump_label.clone()))
+            ,(inner))
+    };
+    let caught = [try_body, wat! { (br ,(ensured_label.clone())) }].concat();
+    let nil = vec![i31_const(Unitype::NIL_BIT_PATTERN)];
+    [
+        wat! {
+            (local_set ,(pending.clone()) ,(nil.clone()))
+            (local_set ,(jump_local) ,(nil))
+            (block ,(ensured_label.clone())
+                (local_set ,(pending.clone())
+                    (block ,(caught_jump_label) (result (ref $jump))
+                        (local_set ,(pending.clone())
+                            (block ,(caught_label) (result (ref $exception)) ,(caught)))
+                        (br ,(ensured_label)))))
+            (drop ,(ensure_statements))
+            (if (ref_test (ref $exception) (local_get ,(pending.clone())))
+                (then (throw $ruby_exception (ref_cast (ref $exception) (local_get ,(pending.clone()))))))
+            (if (ref_test (ref $jump) (local_get ,(pending.clone())))
+                (then (throw $ruby_jump (ref_cast (ref $jump) (local_get ,(pending.clone()))))))
+        },
+        jumps,
+        wat! { (local_get ,(res)) },
+    ]
+    .concat()
+}
+
+/// The rescue's statements if `exception` is one of its classes, otherwise `otherwise`.
+fn compile_rescue(
+    ctx: &mut CompileCtx,
+    rescue: &Rescue,
+    exception: &str,
+    otherwise: Vec<Instr>,
+) -> Vec<Instr> {
+    let Rescue {
+        classes,
+        variable,
+        statements,
+    } = rescue;
+    let conditions = if classes.is_empty() {
+        let standard_error = Class::name_to_identifier(&class::standard_error().name);
+        vec![wat! {
+            (call $truthy
+                (call $case_eq
+                    (global_get ,(standard_error))
+                    (local_get ,(exception.to_string()))))
+        }]
+    } else {
+        classes
+            .iter()
+            .map(|class| {
+                let wat_args = [
+                    compile_expr(ctx, class),
+                    wat! { (local_get ,(exception.to_string())) },
+                    vec![source_position(class.span)],
+                ]
+                .concat();
+                wat! { (call $truthy (call $case_eq ,(wat_args))) }
+            })
+            .collect()
+    };
+    let bind_variable = match variable {
+        Some(variable) => {
+            add_method_local(ctx, variable);
+            variable_set(ctx, variable, wat! { (local_get ,(exception.to_string())) })
+        }
+        None => vec![],
+    };
+    let then = [bind_variable, compile_statements(ctx, statements)].concat();
+    compile_if_any(conditions, then, otherwise)
+}
+
+/// `then` if any of the `conditions` holds, otherwise `otherwise`.
+/// Conditions after the first that holds aren't evaluated.
+fn compile_if_any(
+    conditions: Vec<Vec<Instr>>,
+    then: Vec<Instr>,
+    otherwise: Vec<Instr>,
+) -> Vec<Instr> {
+    let condition = conditions
+        .into_iter()
+        .rev()
+        .reduce(|rest, condition| {
+            wat! {
+                (if (result i32) ,(condition)
+                    (then (const_i32 1))
+                    (else ,(rest)))
+            }
+        })
+        .unwrap();
+    wat! {
+        (if (result (ref eq))
+            ,(condition)
+            (then ,(then))
+            (else ,(otherwise)))
+    }
+}
+
+/// If every pattern of `whens` is an integer literal, and they're close together:
+///     the smallest, and the index of the `when` matching each integer from it.
+fn case_jump_table(whens: &[When]) -> Option<(i64, Vec<Option<usize>>)> {
+    let mut patterns = vec![];
+    for (idx, when) in whens.iter().enumerate() {
+        for pattern in &when.patterns {
+            let ExprKind::Integer(n) = pattern.kind else {
+                return None;
+            };
+            patterns.push((n, idx));
+        }
+    }
+    let min = patterns.iter().map(|(n, _)| *n).min()?;
+    let max = patterns.iter().map(|(n, _)| *n).max()?;
+    let len = max
+        .checked_sub(min)
+        .filter(|span| *span < MAX_CASE_JUMP_TABLE_LEN)?
+        + 1;
+
+    let mut table = vec![None; len as usize];
+    for (n, idx) in patterns {
+        // The first `when` with a pattern wins.
+        table[(n - min) as usize].get_or_insert(idx);
+    }
+    Some((min, table))
+}
+
+/// Jump to the `when` at `table[subject - min]`, or to the `else`.
+/// Each `when`'s statements follow the end of a `block`, which its entries in `table` break out of.
+fn compile_case_jump(
+    ctx: &mut CompileCtx,
+    case_expr: &Case,
+    subject_local: &str,
+    min: i64,
+    table: Vec<Option<usize>>,
+) -> Vec<Instr> {
+    let id = Uuid::new_v4();
+    let when_label = |idx: usize| format!("case_{}_when_{}", id, idx);
+    let else_label = format!("case_{}_else", id);
+    let end_label = format!("case_{}_end", id);
+    let res = new_local(ctx);
+
+    let labels: Vec<_> = table
+        .iter()
+        .map(|entry| match entry {
+            Some(idx) => when_label(*idx),
+            None => else_label.clone(),
+        })
+        .collect();
+    let mut body = wat! {
+        (br_table ,(labels) ,(else_label.clone())
+            (call $case_index
+                (local_get ,(subject_local.to_string()))
+                (const_i64 ,(min))
+                (const_i32 ,(table.len() as i64))))
+    };
+    for (idx, when) in case_expr.whens.iter().enumerate() {
+        let statements = compile_statements(ctx, &when.statements);
+        body = [
+            wat! { (block ,(when_label(idx)) ,(body)) },
+            wat! {
+                (local_set ,(res.clone()) ,(statements))
+                (br ,(end_label.clone()))
+            },
+        ]
+        .concat();
+    }
+    let else_statements = compile_else_clause(ctx, &case_expr.else_clause);
+    let body = [
+        wat! { (block ,(else_label) ,(body)) },
+        wat! { (local_set ,(res.clone()) ,(else_statements)) },
+    ]
+    .concat();
+
+    wat! {
+        (block ,(end_label) ,(body))
+        (local_get ,(res))
+    }
+}
+
 fn compile_while_expr(ctx: &mut CompileCtx, while_expr: &While) -> Vec<Instr> {
     let While {
         predicate,
         statements,
+        do_while,
     } = while_expr;
-    let predicate = compile_expr(ctx, predicate);
-    let stmts = compile_statements(ctx, statements);
-
-    wat! {
-        (loop $while (result (ref eq))
-            (if (result (ref eq))
-                (call $from_bool ,(predicate))
-                (then ,(vec![stmts, wat! { (br $while) }].concat()))
-                (else ,(vec![i31_const(Unitype::NIL_BIT_PATTERN)]))))
-    }
+    let predicate = compile_expr_to_wasm_predicate(ctx, predicate);
+    compile_loop(ctx, wat! { (i32_eqz ,(predicate)) }, statements, *do_while)
 }
 
 fn compile_until_expr(ctx: &mut CompileCtx, until_expr: &Until) -> Vec<Instr> {
-    // TODO -- It might be nicer to have an IR where `until` is lowered to `while`
     let Until {
         predicate,
         statements,
+        do_while,
     } = until_expr;
     let predicate = compile_expr_to_wasm_predicate(ctx, predicate);
-    let stmts = compile_statements(ctx, statements);
+    compile_loop(ctx, predicate, statements, *do_while)
+}
+
+/// A loop that runs `statements` until `exit` is true.
+/// A `do_while` loop runs them once before checking `exit`.
+/// Evaluates to `nil`, or the value it `break`s with.
+fn compile_loop(
+    ctx: &mut CompileCtx,
+    exit: Vec<Instr>,
+    statements: &Statements,
+    do_while: bool,
+) -> Vec<Instr> {
+    let labels = new_loop_labels(ctx, "while");
+    let loop_label = format!("{}_loop", labels.break_label);
+    let body = compile_loop_body(ctx, &labels, statements);
+    let exit_with_nil = [vec![i31_const(Unitype::NIL_BIT_PATTERN)], exit].concat();
+    let check = wat! { (drop (br_if ,(labels.break_label.clone()) ,(exit_with_nil))) };
+    let iteration = if do_while {
+        [vec![body], check].concat()
+    } else {
+        [check, vec![body]].concat()
+    };
+    let iteration = [iteration, wat! { (br ,(loop_label.clone())) }].concat();
+    // The loop only ends by branching out, so its result type is never used.
     wat! {
-        (loop $until (result (ref eq))
-            (if (result (ref eq))
-                (i32_eqz (call $from_bool ,(predicate)))
-                (then ,(vec![stmts, wat! { (br $until) }].concat()))
-                (else ,(vec![i31_const(Unitype::NIL_BIT_PATTERN)]))))
+        (block ,(labels.break_label) (result (ref eq))
+            (loop ,(loop_label) (result (ref eq)) ,(iteration)))
+    }
+}
+
+fn new_loop_labels(ctx: &CompileCtx, kind: &str) -> LoopLabels {
+    let id = Uuid::new_v4();
+    LoopLabels {
+        break_label: format!("{}_{}_break", kind, id),
+        next_label: format!("{}_{}_next", kind, id),
+        redo_label: format!("{}_{}_redo", kind, id),
+        ensure_depth: ctx.scope.ensures.len(),
+    }
+}
+
+/// One run of a loop's body, which `next` leaves and `redo` starts again.
+fn compile_loop_body(ctx: &mut CompileCtx, labels: &LoopLabels, statements: &Statements) -> Instr {
+    let outer_labels = ctx.scope.loop_labels.replace(labels.clone());
+    let statements = compile_statements(ctx, statements);
+    ctx.scope.loop_labels = outer_labels;
+    wat! {
+        (block ,(labels.next_label.clone())
+            (drop (loop ,(labels.redo_label.clone()) (result (ref eq)) ,(statements))))
+    }
+    .remove(0)
+}
+
+/// The value of a `return`, `break` or `next`.
+fn compile_jump_value(ctx: &mut CompileCtx, jump: &Jump) -> Vec<Instr> {
+    match &jump.value {
+        Some(value) => compile_expr(ctx, value),
+        None => vec![i31_const(Unitype::NIL_BIT_PATTERN)],
+    }
+}
+
+/// In a block, `return` leaves the method the block is in.
+/// If the method has already returned, it raises a `LocalJumpError`, and so does `return` in a
+///     block at the top level.
+fn compile_return(ctx: &mut CompileCtx, jump: &Jump) -> Vec<Instr> {
+    let value = compile_jump_value(ctx, jump);
+    if !ctx.scope.is_block {
+        return compile_jump(ctx, JumpKind::Return, Some(value));
+    }
+    let target = if ctx.scope.is_defined(&RETURN_TARGET.to_string()) {
+        wat! { (local_get ,(local_identifier(RETURN_TARGET))) }
+    } else {
+        vec![i31_const(Unitype::NIL_BIT_PATTERN)]
+    };
+    compile_jump(ctx, JumpKind::Block { target }, Some(value))
+}
+
+/// In a block, `break` leaves the call that the block was passed to.
+/// The parser reports `break` outside of a loop or block.
+fn compile_break(ctx: &mut CompileCtx, jump: &Jump) -> Vec<Instr> {
+    let value = compile_jump_value(ctx, jump);
+    let jump = match &ctx.scope.loop_labels {
+        Some(labels) => JumpKind::Branch {
+            label: labels.break_label.clone(),
+            ensure_depth: labels.ensure_depth,
+        },
+        None => JumpKind::Block {
+            target: wat! { (local_get $proc) },
+        },
+    };
+    compile_jump(ctx, jump, Some(value))
+}
+
+/// In a block, `next` leaves the block.
+fn compile_next(ctx: &mut CompileCtx, jump: &Jump) -> Vec<Instr> {
+    let value = compile_jump_value(ctx, jump);
+    match &ctx.scope.loop_labels {
+        Some(labels) => {
+            let jump = JumpKind::Branch {
+                label: labels.next_label.clone(),
+                ensure_depth: labels.ensure_depth,
+            };
+            [wat! { (drop ,(value)) }, compile_jump(ctx, jump, None)].concat()
+        }
+        None => compile_jump(ctx, JumpKind::Return, Some(value)),
+    }
+}
+
+/// Jump to `jump`, with `value` if it has one.
+/// If that leaves the body of a `begin` with an `ensure` clause, branch out of the body instead,
+///     leaving the jump to make after the clause runs. See `compile_ensure`.
+fn compile_jump(ctx: &mut CompileCtx, jump: JumpKind, value: Option<Vec<Instr>>) -> Vec<Instr> {
+    if jump.ensure_depth() < ctx.scope.ensures.len() {
+        let frame = ctx.scope.ensures.last_mut().unwrap();
+        let idx = frame.jumps.len();
+        frame.jumps.push((jump, value.is_some()));
+        let set_value = match value {
+            Some(value) => wat! { (local_set ,(frame.value_local.clone()) ,(value)) },
+            None => vec![],
+        };
+        let jump_idx = Unitype::from_integer(idx as i64).to_i31_bits();
+        return [
+            set_value,
+            wat! {
+                (local_set ,(frame.jump_local.clone()) ,(vec![i31_const(jump_idx)]))
+                (br ,(frame.exit_label.clone()))
+            },
+        ]
+        .concat();
+    }
+    match (jump, value) {
+        (JumpKind::Branch { label, .. }, Some(value)) => wat! { (br ,(label) ,(value)) },
+        (JumpKind::Branch { label, .. }, None) => wat! { (br ,(label)) },
+        (JumpKind::Return, value) => {
+            let value = value.unwrap_or_else(|| vec![i31_const(Unitype::NIL_BIT_PATTERN)]);
+            wat! { (return ,(value)) }
+        }
+        (JumpKind::Block { target }, value) => {
+            let value = value.unwrap_or_else(|| vec![i31_const(Unitype::NIL_BIT_PATTERN)]);
+            compile_block_jump(ctx, value, target)
+        }
+    }
+}
+
+/// Throw a `$jump` with `value` to `target`, from a block.
+/// A lambda returns `value` instead, like a method.
+fn compile_block_jump(ctx: &mut CompileCtx, value: Vec<Instr>, target: Vec<Instr>) -> Vec<Instr> {
+    let val = new_local(ctx);
+    let jump = [target, wat! { (local_get ,(val.clone())) }].concat();
+    wat! {
+        (local_set ,(val.clone()) ,(value))
+        (if (struct_get $proc $lambda (local_get $proc))
+            (then (return (local_get ,(val)))))
+        (throw $ruby_jump (struct_new $jump ,(jump)))
+    }
+}
+
+/// Evaluate `body`, or the value of a `$jump` to the value of the local `target` out of it.
+fn catch_jump(target: String, body: Vec<Instr>) -> Vec<Instr> {
+    let id = Uuid::new_v4();
+    let done_label = format!("jump_{}_done", id);
+    let caught_label = format!("jump_{}_caught", id);
+    let try_body = wat! {
+        (try_table (result (ref eq)) (catch $ruby_jump ,(caught_label.clone())) ,(body))
+    };
+    wat! {
+        (block ,(done_label.clone()) (result (ref eq))
+            (call $jump_value
+                (block ,(caught_label) (result (ref $jump))
+                    (br ,(done_label) ,(try_body)))
+                (local_get ,(target))))
     }
 }
 
-fn compile_call_expr(ctx: &mut CompileCtx, call_expr: &Call) -> Vec<Instr> {
+/// Method calls, and operators which the corelib implements directly.
+/// The call's position is recorded just before calling, so runtime errors can report it.
+/// With a `*` splat arg, the number of args isn't known, so it's always a `$call`.
+fn compile_call_expr(ctx: &mut CompileCtx, call_expr: &Call, span: Span) -> Vec<Instr> {
     let Call {
         receiver,
         name,
         args,
+        block,
     } = call_expr;
 
+    if args.iter().any(is_splat_arg) {
+        return compile_method_call(ctx, call_expr, span);
+    }
+    if let (Some(receiver), Some(func), None) = (receiver, operator_func(name, args.len()), block) {
+        return compile_operator(ctx, func, receiver, args, span);
+    }
+
     match name.as_str() {
-        "==" => {
-            assert_eq!(1, args.len());
-            compile_binop(ctx, wat!($eq_eq), receiver.as_ref().unwrap(), &args[0])
-        }
-        "+" => {
-            assert_eq!(1, args.len());
-            compile_binop(ctx, wat!($add), receiver.as_ref().unwrap(), &args[0])
-        }
-        "-" => {
-            assert_eq!(1, args.len());
-            compile_binop(ctx, wat!($sub), receiver.as_ref().unwrap(), &args[0])
-        }
-        "-@" => {
-            assert!(args.is_empty());
-            wat![ (call $negate ,(compile_expr(ctx, receiver.as_ref().unwrap())))]
-        }
-        ">" => {
-            assert_eq!(1, args.len());
-            compile_binop(ctx, wat!($gt), receiver.as_ref().unwrap(), &args[0])
-        }
-        "<" => {
-            assert_eq!(1, args.len());
-            compile_binop(ctx, wat!($lt), receiver.as_ref().unwrap(), &args[0])
-        }
-        "[]" => {
-            assert_eq!(1, args.len());
-            compile_array_index(ctx, receiver.as_ref().unwrap(), &args[0])
-        }
-        "[]=" => {
-            assert_eq!(2, args.len());
-            compile_array_index_assign(ctx, receiver.as_ref().unwrap(), &args[0], &args[1])
-        }
-        "push" => {
-            assert_eq!(1, args.len());
-            let arr = wat! {
-                (ref_cast (ref $arr_unitype)
-                    ,(compile_expr(ctx, receiver.as_ref().unwrap())))
-            };
-            let val = compile_expr(ctx, &args[0]);
-            let wat_args = [arr, val].concat();
-            wat! {
-                (call $push ,(wat_args))
-            }
+        // With other numbers of args, these are calls of the receiver's own methods.
+        "[]" if receiver.is_some() && args.len() == 1 => {
+            compile_index(ctx, receiver.as_ref().unwrap(), &args[0], span)
         }
-        _ => {
-            let name = corelib::global::string_identifier(name);
-            let mut receiver = match receiver {
-                Some(receiver) => compile_expr(ctx, receiver),
-                None => wat! { (global_get $main) },
-            };
-
-            let mut message = wat! {
-                (global_get ,(name))
+        "[]=" if receiver.is_some() && args.len() == 2 => {
+            compile_array_index_assign(ctx, receiver.as_ref().unwrap(), &args[0], &args[1], span)
+        }
+        "push" if receiver.is_some() => compile_push(ctx, receiver.as_ref().unwrap(), args, span),
+        "include" | "prepend" | "extend" if block.is_none() => {
+            compile_mixin(ctx, receiver, name, args, span)
+        }
+        // Without a block, these are calls of methods the program may define itself.
+        "lambda" if receiver.is_none() && args.is_empty() && is_literal_block(block) => {
+            let Some(CallBlock::Literal(block)) = block else {
+                unreachable!()
             };
-
-            let args: Vec<_> = args
-                .iter()
-                .map(|arg| compile_expr(ctx, arg))
-                .flatten()
-                .collect();
-            let mut args = wat! {
-                (array_new_fixed $arr_unitype ,(args.len() as i64)
-                    ,(args))
+            compile_block(ctx, block, true)
+        }
+        "proc" if receiver.is_none() && args.is_empty() && block.is_some() => {
+            compile_proc_new(ctx, block)
+        }
+        "new" if is_constant(receiver, "Proc") && args.is_empty() && block.is_some() => {
+            compile_proc_new(ctx, block)
+        }
+        "block_given?" if receiver.is_none() => {
+            // It takes no args, so any raise an `ArgumentError`, after they're evaluated.
+            let arity_check = if args.is_empty() {
+                vec![]
+            } else {
+                let given = args.len() as i64;
+                let args = compile_args(ctx, args);
+                let check = wat! {
+                    (call $check_arity (const_i32 ,(given)) (const_i32 0) (const_i32 0))
+                };
+                [args, wat! { (drop) }, vec![source_position(span)], check].concat()
             };
-            let wat_args = {
-                receiver.append(&mut message);
-                receiver.append(&mut args);
-                receiver
+            let block_given = wat! {
+                (call $to_bool (ref_test (ref $proc) ,(current_block(ctx))))
             };
-            wat! {
-                (call $call
-                    ,(wat_args))
-            }
+            [arity_check, block_given].concat()
         }
+        _ => compile_method_call(ctx, call_expr, span),
     }
 }
 
-fn compile_array_index(ctx: &mut CompileCtx, receiver: &Expr, idx: &Expr) -> Vec<Instr> {
-    let receiver = compile_expr(ctx, receiver);
-    let mut receiver = wat![ (ref_cast (ref $arr_unitype) ,(receiver)) ];
-    let idx = compile_expr(ctx, idx);
-    let mut idx = wat![ (i32_wrap_i64 (call $integer_to_i64 ,(idx))) ];
+/// `$call` the method `name` on the receiver, or on `self` if there's none.
+fn compile_method_call(ctx: &mut CompileCtx, call_expr: &Call, span: Span) -> Vec<Instr> {
+    let Call {
+        receiver,
+        name,
+        args,
+        block,
+    } = call_expr;
+    let name = symbol_identifier(ctx, name);
+    let mut receiver = match receiver {
+        Some(receiver) => compile_expr(ctx, receiver),
+        None => compile_self(ctx),
+    };
+
+    let mut message = wat! {
+        (global_get ,(name))
+    };
+
+    let mut args = compile_args(ctx, args);
+    // `break` in a literal block leaves this call, by throwing a `$jump` to its proc.
+    let proc_local = match block {
+        Some(CallBlock::Literal(_)) => Some(new_local(ctx)),
+        _ => None,
+    };
+    let mut block = compile_call_block(ctx, block);
+    let set_proc = match &proc_local {
+        Some(proc_local) => {
+            let set_proc = wat! { (local_set ,(proc_local.clone()) ,(block)) };
+            block = wat! { (ref_cast (ref $proc) (local_get ,(proc_local.clone()))) };
+            set_proc
+        }
+        None => vec![],
+    };
     let wat_args = {
-        receiver.append(&mut idx);
+        receiver.append(&mut message);
+        receiver.append(&mut args);
+        receiver.append(&mut block);
+        receiver.push(source_position(span));
         receiver
     };
+    let call = wat! {
+        (call $call
+            ,(wat_args))
+    };
+    match proc_local {
+        Some(proc_local) => [set_proc, catch_jump(proc_local, call)].concat(),
+        None => call,
+    }
+}
+
+/// Operators compiled to a call of a corelib function, rather than a `$call`.
+/// The function does the operation itself for builtin values,
+///     and calls the method for an `$obj`, which can define its own.
+const OPERATOR_FUNCS: [(&str, &str); 23] = [
+    ("==", "eq_eq"),
+    ("!=", "not_eq"),
+    ("===", "case_eq"),
+    ("<=>", "cmp"),
+    ("<", "lt"),
+    ("<=", "le"),
+    (">", "gt"),
+    (">=", "ge"),
+    ("+", "add"),
+    ("-", "sub"),
+    ("*", "mul"),
+    ("/", "div"),
+    ("%", "modulo"),
+    ("**", "pow"),
+    ("&", "bit_and"),
+    ("|", "bit_or"),
+    ("^", "bit_xor"),
+    ("<<", "shl"),
+    (">>", "shr"),
+    ("-@", "negate"),
+    ("+@", "unary_plus"),
+    ("~", "bit_not"),
+    ("!", "not"),
+];
+
+/// The corelib function of the operator `name`, if it has one and takes `arg_count` args.
+fn operator_func(name: &str, arg_count: usize) -> Option<&'static str> {
+    let (_, func) = OPERATOR_FUNCS.iter().find(|(op, _)| *op == name)?;
+    let unary = matches!(name, "-@" | "+@" | "~" | "!");
+    let expected_count = if unary { 0 } else { 1 };
+    (arg_count == expected_count).then_some(*func)
+}
+
+/// Call the operator's corelib function `func` with the receiver, then the arg if it has one.
+fn compile_operator(
+    ctx: &mut CompileCtx,
+    func: &str,
+    receiver: &Expr,
+    args: &[Expr],
+    span: Span,
+) -> Vec<Instr> {
+    let operands: Vec<_> = std::iter::once(receiver)
+        .chain(args)
+        .flat_map(|operand| compile_expr(ctx, operand))
+        .chain([source_position(span)])
+        .collect();
+    wat! {
+        (call ,(func.to_string()) ,(operands))
+    }
+}
 
+/// An `$arr_unitype` of the values of `args`.
+/// The args of a call, as an array.
+/// With `*` splats, it's built when the call is made,
+///     concatenating each splatted array with the args between them.
+/// Like a multiple assignment's value, a splatted value that isn't an array is passed as itself.
+fn compile_args(ctx: &mut CompileCtx, args: &[Expr]) -> Vec<Instr> {
+    if !args.iter().any(is_splat_arg) {
+        return compile_fixed_args(ctx, args);
+    }
+    let mut arr = wat! { (array_new_fixed $arr_unitype 0) };
+    for group in args.chunk_by(|a, b| !is_splat_arg(a) && !is_splat_arg(b)) {
+        let group = match &group[0].kind {
+            ExprKind::SplatArg(val) => wat! { (call $to_ary ,(compile_expr(ctx, val))) },
+            _ => compile_fixed_args(ctx, group),
+        };
+        arr = wat! { (call $arr_concat ,([arr, group].concat())) };
+    }
+    arr
+}
+
+/// Is `arg` a `*` splat?
+fn is_splat_arg(arg: &Expr) -> bool {
+    matches!(arg.kind, ExprKind::SplatArg(_))
+}
+
+/// `args`, none of which are splats, as an array.
+fn compile_fixed_args(ctx: &mut CompileCtx, args: &[Expr]) -> Vec<Instr> {
+    let len = args.len() as i64;
+    let args: Vec<_> = args.iter().flat_map(|arg| compile_expr(ctx, arg)).collect();
+    wat! {
+        (array_new_fixed $arr_unitype ,(len)
+            ,(args))
+    }
+}
+
+/// Is `expr` a read of the constant `name`?
+fn is_constant(expr: &Option<Expr>, name: &str) -> bool {
+    matches!(expr, Some(Expr { kind: ExprKind::ConstantRead(constant), .. }) if constant.name == name)
+}
+
+/// `receiver[idx]`. Arrays are indexed directly, anything else has its `[]` method called.
+fn compile_index(ctx: &mut CompileCtx, receiver: &Expr, idx: &Expr, span: Span) -> Vec<Instr> {
+    let receiver = compile_expr(ctx, receiver);
+    let idx = compile_expr(ctx, idx);
+    let wat_args = [receiver, idx, vec![source_position(span)]].concat();
     wat! {
-        (array_get $arr_unitype ,(wat_args))
+        (call $index ,(wat_args))
+    }
+}
+
+/// `arr.push(val, ...)`: a new array with each `val` after the elements of `arr`.
+fn compile_push(ctx: &mut CompileCtx, receiver: &Expr, args: &[Expr], span: Span) -> Vec<Instr> {
+    let receiver = compile_expr(ctx, receiver);
+    let mut arr = wat! { (ref_cast (ref $arr_unitype) ,(receiver)) };
+    for arg in args {
+        let val = compile_expr(ctx, arg);
+        let wat_args = [arr, val, vec![source_position(span)]].concat();
+        arr = wat! {
+            (ref_cast (ref $arr_unitype) (call $push ,(wat_args)))
+        };
     }
+    arr
 }
 
 fn compile_array_index_assign(
@@ -530,6 +2938,7 @@ fn compile_array_index_assign(
     receiver: &Expr,
     idx: &Expr,
     val: &Expr,
+    span: Span,
 ) -> Vec<Instr> {
     let receiver = compile_expr(ctx, receiver);
     let mut receiver = wat![ (ref_cast (ref $arr_unitype) ,(receiver)) ];
@@ -539,6 +2948,7 @@ fn compile_array_index_assign(
     let wat_args = {
         receiver.append(&mut idx);
         receiver.append(&mut val);
+        receiver.push(source_position(span));
         receiver
     };
     wat! {
@@ -547,37 +2957,37 @@ fn compile_array_index_assign(
     }
 }
 
-fn compile_binop(ctx: &mut CompileCtx, name: String, lhs: &Expr, rhs: &Expr) -> Vec<Instr> {
-    let mut lhs = compile_expr(ctx, lhs);
-    let mut rhs = compile_expr(ctx, rhs);
-    let wat_args = {
-        lhs.append(&mut rhs);
-        lhs
-    };
-
-    wat! {
-        (call ,(name)
-            ,(wat_args))
-    }
-}
-
 /// Turns a Ruby Expr into a Wasm predicate.
 /// A ruby Expr evaluates to a ruby-value (True, False, Nil, ...)
-/// To use as a Wasm predicate, we need to test whether the result is truthy or not.
-/// TODO -- right now we pretend that "truthy" is "not-false"
+/// To use as a Wasm predicate, we need to test whether the result is truthy:
+///     anything but `nil` and `false`.
 fn compile_expr_to_wasm_predicate(ctx: &mut CompileCtx, expr: &Expr) -> Vec<Instr> {
     let expr = compile_expr(ctx, expr);
     wat! {
-        (call $from_bool ,(expr))
+        (call $truthy ,(expr))
     }
 }
 
+/// Add a local to the current function, unless the variable is already defined.
 fn add_method_local(ctx: &mut CompileCtx, name: &String) {
-    if !ctx.method_locals.contains(name) {
-        ctx.method_locals.push(name.to_string())
+    if !ctx.scope.is_defined(name) {
+        ctx.scope.locals.push(name.to_string())
     }
 }
 
+/// Add a local, with a fresh name, for the compiler's own use. Returns its identifier.
+fn new_local(ctx: &mut CompileCtx) -> String {
+    let name = Uuid::new_v4().to_string();
+    add_method_local(ctx, &name);
+    local_identifier(&name)
+}
+
+/// Record where in the source we are, for runtime errors to report.
+/// Stores the byte index of the start of `span` in `$source_position`.
+fn source_position(span: Span) -> Instr {
+    wat![ (global_set $source_position (const_i32 ,(span.start.0 as i64))) ].remove(0)
+}
+
 fn i31_const(bits: i32) -> Instr {
     wat![(ref_i31(const_i32, (bits.into())))].remove(0)
 }
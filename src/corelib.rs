@@ -11,17 +11,27 @@ pub mod global;
 pub mod helpers;
 mod imports;
 pub mod method;
+pub mod tag;
 pub mod type_def;
 
 use crate::CompileCtx;
 
 pub fn add_core_items(ctx: &mut CompileCtx) {
-    ctx.methods.append(&mut method::corelib_methods());
-    ctx.classes.append(&mut class::corelib_classes());
+    // User definitions override corelib methods of the same name.
+    let mut corelib_methods = method::corelib_methods()
+        .into_iter()
+        .filter(|m| {
+            !ctx.methods
+                .iter()
+                .any(|user_method| user_method.identifier() == m.identifier())
+        })
+        .collect();
+    ctx.methods.append(&mut corelib_methods);
     type_def::add_type_defs(ctx);
     imports::add_imports(ctx);
     global::add_globals(ctx);
     class::add_class_defs(ctx);
     method::add_method_defs(ctx);
     function::add_functions(ctx);
+    tag::add_tags(ctx);
 }
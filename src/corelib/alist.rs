@@ -114,15 +114,16 @@ pub fn alist_str_unitype() -> AListTypeDef {
     }
 }
 
-pub fn alist_str_method() -> AListTypeDef {
+/// Alist of pairs `(Symbol, Method)`
+pub fn alist_sym_method() -> AListTypeDef {
     AListTypeDef {
-        key_type_identifier: "str".to_string(),
+        key_type_identifier: "sym".to_string(),
         val_type: wat![ (ref $method) ].into_storage_type(),
     }
 }
 
 pub fn alist_type_defs() -> Vec<TypeDef> {
-    [alist_str_unitype(), alist_str_method()]
+    [alist_str_unitype(), alist_sym_method()]
         .into_iter()
         .flat_map(AListTypeDef::into_type_defs)
         .collect()
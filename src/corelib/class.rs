@@ -1,15 +1,16 @@
 use crate::CompileCtx;
-use crate::corelib::global::string_identifier;
-use crate::corelib::method;
+use crate::corelib::global::{string_identifier, symbol_identifier};
 use crate::corelib::method::Method;
 use wat_defs::global::Global;
 use wat_defs::instr::Instr;
 use wat_macro::wat;
 
-/// A Ruby class. Compiles to:
+/// A Ruby class or module. Compiles to:
 /// - Definition of global string `$<CLASS_NAME>`
 /// - Global definition of class data
 /// - Definition of class methods
+///
+/// A module is a `$class` whose parent is `Module`, with no superclass.
 pub struct Class {
     pub name: String,
     /// The name of this class's class.
@@ -17,19 +18,47 @@ pub struct Class {
     pub parent_name: String,
     pub superclass_name: Option<String>,
     pub instance_methods: Vec<Method>,
+    /// Names of the modules passed to `include`, in the order they were included.
+    pub includes: Vec<String>,
+    /// Names of the modules passed to `prepend`, in the order they were prepended.
+    pub prepends: Vec<String>,
+    /// Names of the modules passed to `extend`, in the order they extended this class.
+    pub extends: Vec<String>,
 }
 
 impl Class {
+    pub fn new(name: &str, parent_name: &str, superclass_name: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            parent_name: parent_name.to_string(),
+            superclass_name: superclass_name.map(str::to_string),
+            instance_methods: vec![],
+            includes: vec![],
+            prepends: vec![],
+            extends: vec![],
+        }
+    }
+
     pub fn name_to_identifier(name: &str) -> String {
         format!("class_{}", name)
     }
 
+    /// The name of the module holding the `def self.` methods of the class `name`,
+    ///     like the name of its singleton class in Ruby.
+    pub fn singleton_name(name: &str) -> String {
+        format!("#<Class:{}>", name)
+    }
+
     pub fn identifier(&self) -> String {
         Self::name_to_identifier(&self.name)
     }
 
-    pub fn def(self) -> Global {
-        // Parent and superclass get ref.null for now.
+    pub fn is_module(&self) -> bool {
+        self.parent_name == module().name
+    }
+
+    pub fn def(&self) -> Global {
+        // Parent, superclass and ancestors get placeholders for now.
         // We build the cyclic references in the _start function
 
         wat! {
@@ -37,20 +66,103 @@ impl Class {
                     (ref $class)
                     (struct_new $class
                                 (ref_null $class)               // .parent
+                                (array_new_fixed $arr_unitype 0)  // .ivars
                                 (ref_null $class)               // .superclass
                                 (global_get ,(string_identifier(&self.name)))       // .name
-                                ,(self.methods_arr()))) // .instance-methods
+                                ,(self.methods_arr()) // .instance-methods
+                                (array_new_fixed $arr_unitype 0)  // .own-ancestors
+                                (array_new_fixed $arr_unitype 0))) // .extended
+        }
+    }
+
+    /// Names of the classes and modules searched for this class's instance methods,
+    ///     before its superclass: prepended modules, itself, then included modules.
+    /// The most recently prepended or included module comes first.
+    /// Each module brings its own ancestors with it.
+    /// As in Ruby, including a module that's already an ancestor of the superclass does nothing.
+    pub fn own_ancestors(&self, classes: &[Class]) -> Vec<String> {
+        self.own_ancestors_after(classes, &self.inherited_ancestors(classes))
+    }
+
+    /// `own_ancestors`, given the ancestors of the superclass.
+    fn own_ancestors_after(&self, classes: &[Class], inherited: &[String]) -> Vec<String> {
+        let prepended = self
+            .prepends
+            .iter()
+            .rev()
+            .flat_map(|name| find(classes, name).own_ancestors(classes));
+        let included = self
+            .includes
+            .iter()
+            .rev()
+            .flat_map(|name| find(classes, name).own_ancestors(classes))
+            .filter(|name| !inherited.contains(name));
+        let ancestors = prepended
+            .chain(std::iter::once(self.name.clone()))
+            .chain(included)
+            .collect();
+        without_duplicates(ancestors)
+    }
+
+    /// Whether this is the module `name`, or includes or prepends it, however indirectly.
+    /// `compile_mixin` checks this before each `include` and `prepend`,
+    ///     so that no module is ever its own ancestor, and walking the ancestors ends.
+    pub fn has_mixin(&self, classes: &[Class], name: &str) -> bool {
+        let mut unvisited = vec![self];
+        let mut visited: Vec<&str> = vec![];
+        while let Some(class) = unvisited.pop() {
+            if class.name == name {
+                return true;
+            }
+            if !visited.contains(&class.name.as_str()) {
+                visited.push(&class.name);
+                let mixins = class.includes.iter().chain(&class.prepends);
+                unvisited.extend(mixins.map(|mixin| find(classes, mixin)));
+            }
+        }
+        false
+    }
+
+    /// Names of all the classes and modules searched for this class's instance methods, in order.
+    fn ancestors(&self, classes: &[Class]) -> Vec<String> {
+        let inherited = self.inherited_ancestors(classes);
+        [self.own_ancestors_after(classes, &inherited), inherited].concat()
+    }
+
+    /// The ancestors of the superclass, if there is one.
+    fn inherited_ancestors(&self, classes: &[Class]) -> Vec<String> {
+        match &self.superclass_name {
+            Some(superclass_name) => find(classes, superclass_name).ancestors(classes),
+            None => vec![],
         }
     }
 
+    /// Names of the modules searched for methods called on this class itself, before `Class`'s.
+    /// As if the modules were included in its singleton class,
+    ///     whose own `def self.` methods come first.
+    pub fn extended_ancestors(&self, classes: &[Class]) -> Vec<String> {
+        let singleton = Self::singleton_name(&self.name);
+        let singleton = classes
+            .iter()
+            .any(|class| class.name == singleton)
+            .then_some(singleton);
+        let extended = self
+            .extends
+            .iter()
+            .rev()
+            .flat_map(|name| find(classes, name).own_ancestors(classes));
+        let ancestors = singleton.into_iter().chain(extended).collect();
+        without_duplicates(ancestors)
+    }
+
     fn methods_arr(&self) -> Instr {
         let struct_defs: Vec<_> = self
             .instance_methods
             .iter()
             .map(|method| {
                 wat! {
-                    (struct_new $alist_str_method_pair
-                        (global_get ,(string_identifier(&method.name)))
+                    (struct_new $alist_sym_method_pair
+                        (global_get ,(symbol_identifier(&method.name)))
                         (ref_func ,(method.identifier())))
                 }
             })
@@ -58,65 +170,227 @@ impl Class {
             .collect();
         let len: i64 = struct_defs.len().try_into().unwrap();
         wat! {
-            (array_new_fixed $alist_str_method ,(len)
+            (array_new_fixed $alist_sym_method ,(len)
                              ,(struct_defs))
         }
         .remove(0)
     }
 }
 
-/// The `Module` class.
-fn module() -> Class {
-    Class {
-        name: "Module".to_string(),
-        parent_name: "Class".to_string(),
-        superclass_name: Some("Object".to_string()),
-        instance_methods: vec![],
+fn find<'a>(classes: &'a [Class], name: &str) -> &'a Class {
+    classes.iter().find(|class| class.name == name).unwrap()
+}
+
+/// Keep the first occurrence of each name.
+fn without_duplicates(names: Vec<String>) -> Vec<String> {
+    let mut res: Vec<String> = vec![];
+    for name in names {
+        if !res.contains(&name) {
+            res.push(name)
+        }
     }
+    res
+}
+
+/// The `Module` class.
+pub fn module() -> Class {
+    Class::new("Module", "Class", Some("Object"))
 }
 
 /// The `Class` class.
 fn class() -> Class {
-    Class {
-        name: "Class".to_string(),
-        parent_name: "Class".to_string(),
-        superclass_name: Some("Module".to_string()),
-        instance_methods: vec![],
-    }
+    Class::new("Class", "Class", Some("Module"))
 }
 
 /// The `BasicObject` class.
 fn basic_object() -> Class {
-    Class {
-        name: "BasicObject".to_string(),
-        parent_name: "Class".to_string(),
-        superclass_name: None,
-        instance_methods: vec![],
-    }
+    Class::new("BasicObject", "Class", None)
 }
 
-/// The `BasicObject` class.
+/// The `Object` class.
 pub fn object() -> Class {
-    Class {
-        name: "Object".to_string(),
-        parent_name: "Class".to_string(),
-        superclass_name: Some("BasicObject".to_string()),
-        instance_methods: vec![],
-    }
+    Class::new("Object", "Class", Some("BasicObject"))
+}
+
+/// The `Proc` class.
+pub fn proc() -> Class {
+    Class::new("Proc", "Class", Some("Object"))
+}
+
+/// The `Range` class.
+pub fn range() -> Class {
+    Class::new("Range", "Class", Some("Object"))
+}
+
+/// The `Hash` class.
+pub fn hash() -> Class {
+    Class::new("Hash", "Class", Some("Object"))
+}
+
+/// The `Exception` class. Its instances, and those of its subclasses, are `$exception`s.
+pub fn exception() -> Class {
+    Class::new("Exception", "Class", Some("Object"))
+}
+
+/// The `StandardError` class, which `rescue` without a class rescues.
+pub fn standard_error() -> Class {
+    Class::new("StandardError", "Class", Some("Exception"))
+}
+
+/// The `RuntimeError` class, which `raise` with just a message raises.
+pub fn runtime_error() -> Class {
+    Class::new("RuntimeError", "Class", Some("StandardError"))
+}
+
+/// The `ArgumentError` class, which a method raises if it's called with the wrong args.
+pub fn argument_error() -> Class {
+    Class::new("ArgumentError", "Class", Some("StandardError"))
+}
+
+pub fn type_error() -> Class {
+    Class::new("TypeError", "Class", Some("StandardError"))
+}
+
+/// The `NameError` class, which reading a constant that isn't defined raises.
+pub fn name_error() -> Class {
+    Class::new("NameError", "Class", Some("StandardError"))
+}
+
+/// The `NoMethodError` class, which calling a method that doesn't exist raises.
+pub fn no_method_error() -> Class {
+    Class::new("NoMethodError", "Class", Some("NameError"))
+}
+
+/// The `ArithmeticError` class, the superclass of errors from arithmetic, like `ZeroDivisionError`.
+pub fn arithmetic_error() -> Class {
+    Class::new("ArithmeticError", "Class", Some("StandardError"))
+}
+
+/// The `ZeroDivisionError` class, which Integer division and modulo by zero raise.
+pub fn zero_division_error() -> Class {
+    Class::new("ZeroDivisionError", "Class", Some("ArithmeticError"))
+}
+
+/// The `RangeError` class, which Integer operations raise if the result doesn't fit in 64 bits.
+pub fn range_error() -> Class {
+    Class::new("RangeError", "Class", Some("StandardError"))
+}
+
+/// The `FrozenError` class, which setting an instance variable of an Integer or String raises.
+pub fn frozen_error() -> Class {
+    Class::new("FrozenError", "Class", Some("RuntimeError"))
+}
+
+fn index_error() -> Class {
+    Class::new("IndexError", "Class", Some("StandardError"))
+}
+
+pub fn local_jump_error() -> Class {
+    Class::new("LocalJumpError", "Class", Some("StandardError"))
+}
+
+fn script_error() -> Class {
+    Class::new("ScriptError", "Class", Some("Exception"))
+}
+
+/// The `NotImplementedError` class, raised by what Ruby allows but the compiler doesn't support.
+pub fn not_implemented_error() -> Class {
+    Class::new("NotImplementedError", "Class", Some("ScriptError"))
+}
+
+// Classes of values which aren't `$obj`s. See `$class_of`.
+
+fn numeric() -> Class {
+    Class::new("Numeric", "Class", Some("Object"))
+}
+
+pub fn integer() -> Class {
+    Class::new("Integer", "Class", Some("Numeric"))
+}
+
+pub fn float() -> Class {
+    Class::new("Float", "Class", Some("Numeric"))
+}
+
+pub fn string() -> Class {
+    Class::new("String", "Class", Some("Object"))
+}
+
+pub fn symbol() -> Class {
+    Class::new("Symbol", "Class", Some("Object"))
+}
+
+pub fn array() -> Class {
+    Class::new("Array", "Class", Some("Object"))
+}
+
+pub fn nil_class() -> Class {
+    Class::new("NilClass", "Class", Some("Object"))
+}
+
+pub fn true_class() -> Class {
+    Class::new("TrueClass", "Class", Some("Object"))
+}
+
+pub fn false_class() -> Class {
+    Class::new("FalseClass", "Class", Some("Object"))
+}
+
+/// A class defined by the program, with `class <name> < <superclass_name>`.
+pub fn user_class(name: &str, superclass_name: &str) -> Class {
+    Class::new(name, &class().name, Some(superclass_name))
+}
+
+/// A module defined by the program, with `module <name>`.
+pub fn user_module(name: &str) -> Class {
+    Class::new(name, &module().name, None)
 }
 
 /// A Vec of all classes defined in `corelib`.
 pub fn corelib_classes() -> Vec<Class> {
-    vec![module(), class(), basic_object(), object()]
+    vec![
+        module(),
+        class(),
+        basic_object(),
+        object(),
+        proc(),
+        range(),
+        hash(),
+        exception(),
+        standard_error(),
+        runtime_error(),
+        argument_error(),
+        type_error(),
+        name_error(),
+        no_method_error(),
+        arithmetic_error(),
+        zero_division_error(),
+        range_error(),
+        frozen_error(),
+        index_error(),
+        local_jump_error(),
+        script_error(),
+        not_implemented_error(),
+        numeric(),
+        integer(),
+        float(),
+        string(),
+        symbol(),
+        array(),
+        nil_class(),
+        true_class(),
+        false_class(),
+    ]
 }
 
+/// Add a global for each class in `ctx`, holding its instance methods.
+/// The classes are kept, for `_start` to link them to their parents and superclasses.
 pub fn add_class_defs(ctx: &mut CompileCtx) {
     assert!(!ctx.classes.is_empty());
-    let mut classes: Vec<_> = ctx.classes.drain(..).collect();
     for method in &ctx.methods {
-        add_instance_method(&mut classes, method)
+        add_instance_method(&mut ctx.classes, method)
     }
-    for class in classes {
+    for class in &ctx.classes {
         ctx.module.globals.push(class.def())
     }
 }
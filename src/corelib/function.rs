@@ -1,6 +1,8 @@
 use crate::corelib::alist::AListTypeDef;
 use crate::corelib::class::Class;
-use crate::corelib::helpers::i64_neg;
+use crate::corelib::global::{GET_SOURCE_POSITION, INSPECT, string_identifier, symbol_identifier};
+use crate::corelib::helpers;
+use crate::corelib::tag::DESCRIBE_EXCEPTION;
 use crate::corelib::{class, method};
 use crate::unitype::Unitype;
 use crate::{CompileCtx, corelib};
@@ -19,11 +21,32 @@ pub fn add_functions(ctx: &mut CompileCtx) {
 fn funcs() -> Vec<Func> {
     vec![
         str_eq(),
-        alist_str_method_get(),
+        str_cmp(),
+        intern(),
+        alist_sym_method_get(),
+        find_in_modules(),
+        find_method(),
         call(),
+        no_method_error(),
+        call_super(),
+        call_block(),
+        block_args(),
+        block_to_unitype(),
+        unitype_to_block(),
+        index(),
+        class_of(),
+        is_a(),
+        has_ancestor(),
+        case_eq(),
+        case_index(),
+        ancestors(),
         is_nil(),
         is_fixnum(),
         is_boxnum(),
+        is_integer(),
+        is_float(),
+        is_numeric(),
+        number_to_f64(),
         sign_extend(),
         sign_extend_fixnum(),
         fixnum_to_i64(),
@@ -34,35 +57,700 @@ fn funcs() -> Vec<Func> {
         i64_to_fixnum(),
         i64_to_boxnum(),
         i64_to_integer(),
+        operand_description(),
+        coerce_error(),
+        comparison_error(),
         add(),
         sub(),
+        mul(),
+        i64_add_checked(),
+        i64_sub_checked(),
+        i64_mul_checked(),
+        div(),
+        i64_floor_div(),
+        modulo(),
+        i64_floor_mod(),
+        check_divisor(),
+        pow(),
+        i64_pow(),
+        f64_pow(),
+        f64_pow_f64(),
+        f64_pow_positive(),
         to_bool(),
         from_bool(),
+        truthy(),
         negate(),
-        and(),
-        or(),
+        unary_plus(),
         lt(),
         gt(),
+        le(),
+        ge(),
         eq_eq(),
+        not_eq(),
+        cmp(),
+        not(),
+        bit_and(),
+        bit_or(),
+        bit_xor(),
+        shl(),
+        shr(),
+        i64_shift_left(),
+        bit_not(),
         arr_to_js(),
         unitype_to_js(),
         push(),
+        str_concat(),
+        i64_to_str(),
+        str_slice(),
+        f64_to_str(),
+        to_s(),
+        inspect(),
+        arr_inspect(),
+        get_source_position(),
+        new_exception(),
+        exception_to_raise(),
+        describe_exception(),
+        jump_value(),
+        local_jump_error(),
+        check_arity(),
+        positional_args(),
+        keyword_args(),
+        has_keyword_args(),
+        required_keyword_arg(),
+        check_keywords(),
+        arr_slice(),
+        arr_concat(),
+        push_keyword_args(),
+        arr_get_or_nil(),
+        to_ary(),
+        splat_end(),
+        arr_index(),
+        hash_index(),
+        hash_get(),
+        hash_except(),
+        hash_inspect(),
+        ivar_index(),
+        ivar_get(),
+        ivar_set(),
+        constant_get(),
     ]
 }
 
+/// Exports `$source_position`, so the host can report where a trap happened.
+fn get_source_position() -> Func {
+    wat! {
+        (func $get_source_position
+            (export ,(GET_SOURCE_POSITION.to_string()))
+            (result i32)
+            (global_get $source_position))
+    }
+}
+
+/// An instance of `$class`, which must be `Exception` or a subclass, with `$message`.
+fn new_exception() -> Func {
+    wat! {
+        (func $new_exception
+            (param $class (ref $class))
+            (param $message (ref eq))
+            (result (ref $exception))
+
+            (struct_new $exception
+                (local_get $class)
+                (array_new_fixed $arr_unitype 0)
+                (local_get $message)))
+    }
+}
+
+/// The exception that `raise` with `$args` raises:
+/// - `raise`: the exception being rescued, or a `RuntimeError`
+/// - `raise(message)`: a `RuntimeError`
+/// - `raise(exception_class, message?)`: `exception_class.new(message?)`
+/// - `raise(exception, message?)`: `exception`, with `message` if given
+///
+/// Anything else is a `TypeError`.
+fn exception_to_raise() -> Func {
+    let nil = Unitype::NIL_BIT_PATTERN as i64;
+    wat! {
+        (func $exception_to_raise
+            (param $args (ref $arr_unitype))
+            (result (ref $exception))
+            (local $x (ref eq))
+            (local $message (ref eq))
+
+            (if (i32_eqz (array_len (local_get $args)))
+                (then
+                    (if (ref_test (ref $exception) (global_get $current_exception))
+                        (then (return (ref_cast (ref $exception) (global_get $current_exception)))))
+                    (return
+                        (call $new_exception
+                            (global_get ,(class::runtime_error().identifier()))
+                            (global_get ,(string_identifier("unhandled exception")))))))
+            (local_set $x (array_get $arr_unitype (local_get $args) (const_i32 0)))
+            (local_set $message (ref_i31 (const_i32 ,(nil))))
+            (if (i32_gt_u (array_len (local_get $args)) (const_i32 1))
+                (then (local_set $message
+                          (array_get $arr_unitype (local_get $args) (const_i32 1)))))
+
+            (if (ref_test (ref $str) (local_get $x))
+                (then
+                    (return
+                        (call $new_exception
+                            (global_get ,(class::runtime_error().identifier()))
+                            (local_get $x)))))
+            (if (ref_test (ref $class) (local_get $x))
+                (then
+                    (local_set $x
+                        (call $call
+                            (local_get $x)
+                            (global_get ,(symbol_identifier("new")))
+                            (if (result (ref $arr_unitype))
+                                (call $is_nil (local_get $message))
+                                (then (global_get $empty_args))
+                                (else (array_new_fixed $arr_unitype 1 (local_get $message))))
+                            (ref_null $proc)))))
+            (if (ref_test (ref $exception) (local_get $x))
+                (then
+                    (if (i32_eqz (call $is_nil (local_get $message)))
+                        (then
+                            (struct_set $exception $message
+                                (ref_cast (ref $exception) (local_get $x))
+                                (local_get $message))))
+                    (return (ref_cast (ref $exception) (local_get $x)))))
+            (call $new_exception
+                (global_get ,(class::type_error().identifier()))
+                (global_get ,(string_identifier("exception class/object expected")))))
+    }
+}
+
+/// Exported, so the host can report an uncaught exception: `<message> (<class name>)`.
+/// Takes `(ref any)`, which is what the host gets out of the wasm exception.
+fn describe_exception() -> Func {
+    wat! {
+        (func $describe_exception
+            (export ,(DESCRIBE_EXCEPTION.to_string()))
+            (param $exception (ref any))
+            (result (ref eq))
+            (local $x (ref $exception))
+
+            (local_set $x (ref_cast (ref $exception) (local_get $exception)))
+            (call $str_concat
+                (call $str_concat
+                    (call $str_concat
+                        (call $to_s
+                            (call $call
+                                (local_get $x)
+                                (global_get ,(symbol_identifier("message")))
+                                (global_get $empty_args)
+                                (ref_null $proc)))
+                        (global_get ,(string_identifier(" ("))))
+                    (struct_get $class $name
+                        (ref_as_non_null (struct_get $obj $parent (local_get $x)))))
+                (global_get ,(string_identifier(")")))))
+    }
+}
+
+/// The value of `$jump`, if `$target` is what it jumps to. Otherwise it's thrown again.
+fn jump_value() -> Func {
+    wat! {
+        (func $jump_value
+            (param $jump (ref $jump))
+            (param $target (ref eq))
+            (result (ref eq))
+
+            (if (ref_eq (struct_get $jump $target (local_get $jump)) (local_get $target))
+                (then (return (struct_get $jump $val (local_get $jump)))))
+            (throw $ruby_jump (local_get $jump)))
+    }
+}
+
+/// The `LocalJumpError` for a `$jump` that nothing caught,
+///     because the method or call it jumps to has already returned.
+fn local_jump_error() -> Func {
+    wat! {
+        (func $local_jump_error
+            (param $jump (ref $jump))
+            (result (ref $exception))
+
+            (call $new_exception
+                (global_get ,(class::local_jump_error().identifier()))
+                (if (result (ref eq))
+                    (ref_test (ref $proc) (struct_get $jump $target (local_get $jump)))
+                    (then (global_get ,(string_identifier("break from proc-closure"))))
+                    (else (global_get ,(string_identifier("unexpected return")))))))
+    }
+}
+
+/// Throw an `ArgumentError`, unless `$given` is between `$min` and `$max` args.
+/// `$max` is -1 if there's no limit.
+/// "wrong number of arguments (given 1, expected 2..3)"
+fn check_arity() -> Func {
+    wat! {
+        (func $check_arity
+            (param $given i32)
+            (param $min i32)
+            (param $max i32)
+            (local $expected (ref $str))
+
+            (if (i32_and (i32_eqz (i32_lt_u (local_get $given) (local_get $min)))
+                         (i32_or (i32_eq (local_get $max) (const_i32 ,(-1)))
+                                 (i32_eqz (i32_gt_u (local_get $given) (local_get $max)))))
+                (then (return)))
+            (local_set $expected (call $i64_to_str (i64_extend_i32_u (local_get $min))))
+            (if (i32_eq (local_get $max) (const_i32 ,(-1)))
+                (then
+                    (local_set $expected
+                        (call $str_concat
+                            (local_get $expected)
+                            (global_get ,(string_identifier("+")))))))
+            (if (i32_gt_s (local_get $max) (local_get $min))
+                (then
+                    (local_set $expected
+                        (call $str_concat
+                            (call $str_concat
+                                (local_get $expected)
+                                (global_get ,(string_identifier(".."))))
+                            (call $i64_to_str (i64_extend_i32_u (local_get $max)))))))
+            (throw $ruby_exception
+                (call $new_exception
+                    (global_get ,(class::argument_error().identifier()))
+                    (call $str_concat
+                        (call $str_concat
+                            (call $str_concat
+                                (call $str_concat
+                                    (global_get
+                                        ,(string_identifier("wrong number of arguments (given ")))
+                                    (call $i64_to_str (i64_extend_i32_u (local_get $given))))
+                                (global_get ,(string_identifier(", expected "))))
+                            (local_get $expected))
+                        (global_get ,(string_identifier(")")))))))
+    }
+}
+
+/// `$args` without the keyword args, if the method `$takes_keywords`.
+/// Keyword args are passed as a `Hash` after the positional args.
+fn positional_args() -> Func {
+    wat! {
+        (func $positional_args
+            (param $args (ref $arr_unitype))
+            (param $takes_keywords i32)
+            (result (ref $arr_unitype))
+
+            (if (i32_eqz (call $has_keyword_args (local_get $args) (local_get $takes_keywords)))
+                (then (return (local_get $args))))
+            (call $arr_slice
+                (local_get $args)
+                (const_i32 0)
+                (i32_sub (array_len (local_get $args)) (const_i32 1))))
+    }
+}
+
+/// The keyword args in `$args`, or an empty `Hash` if there aren't any.
+fn keyword_args() -> Func {
+    wat! {
+        (func $keyword_args
+            (param $args (ref $arr_unitype))
+            (param $takes_keywords i32)
+            (result (ref $hash))
+
+            (if (call $has_keyword_args (local_get $args) (local_get $takes_keywords))
+                (then
+                    (return
+                        (ref_cast (ref $hash)
+                            (array_get $arr_unitype
+                                (local_get $args)
+                                (i32_sub (array_len (local_get $args)) (const_i32 1)))))))
+            (struct_new $hash
+                (global_get ,(class::hash().identifier()))
+                (array_new_fixed $arr_unitype 0)
+                (array_new_fixed $arr_unitype 0)
+                (array_new_fixed $arr_unitype 0)))
+    }
+}
+
+/// Is the last of `$args` a `Hash` of keyword args?
+/// For a method that doesn't take keywords, it's a positional arg.
+fn has_keyword_args() -> Func {
+    wat! {
+        (func $has_keyword_args
+            (param $args (ref $arr_unitype))
+            (param $takes_keywords i32)
+            (result i32)
+
+            (if (i32_or (i32_eqz (local_get $takes_keywords))
+                        (i32_eqz (array_len (local_get $args))))
+                (then (return (const_i32 0))))
+            (ref_test (ref $hash)
+                (array_get $arr_unitype
+                    (local_get $args)
+                    (i32_sub (array_len (local_get $args)) (const_i32 1)))))
+    }
+}
+
+/// The value of the keyword param `$key`, which must be given.
+/// TODO: Ruby reports all the missing keywords at once, "missing keywords: :a, :b".
+fn required_keyword_arg() -> Func {
+    wat! {
+        (func $required_keyword_arg
+            (param $hash (ref $hash))
+            (param $key (ref eq))
+            (result (ref eq))
+            (local $idx i32)
+
+            (local_set $idx (call $hash_index (local_get $hash) (local_get $key)))
+            (if (i32_lt_s (local_get $idx) (const_i32 0))
+                (then
+                    (throw $ruby_exception
+                        (call $new_exception
+                            (global_get ,(class::argument_error().identifier()))
+                            (call $str_concat
+                                (global_get ,(string_identifier("missing keyword: ")))
+                                (call $inspect (local_get $key)))))))
+            (array_get $arr_unitype
+                (struct_get $hash $vals (local_get $hash))
+                (local_get $idx)))
+    }
+}
+
+/// Throw an `ArgumentError` if `$hash` has a key that isn't in `$known`,
+///     for a method without a `**` param.
+/// TODO: Ruby reports all the unknown keywords at once, "unknown keywords: :a, :b".
+fn check_keywords() -> Func {
+    wat! {
+        (func $check_keywords
+            (param $hash (ref $hash))
+            (param $known (ref $arr_unitype))
+            (local $key (ref eq))
+            (local $idx i32)
+
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (struct_get $hash $keys (local_get $hash)))))
+                    (local_set $key
+                        (array_get $arr_unitype
+                            (struct_get $hash $keys (local_get $hash))
+                            (local_get $idx)))
+                    (if (i32_lt_s (call $arr_index (local_get $known) (local_get $key))
+                                  (const_i32 0))
+                        (then
+                            (throw $ruby_exception
+                                (call $new_exception
+                                    (global_get ,(class::argument_error().identifier()))
+                                    (call $str_concat
+                                        (global_get ,(string_identifier("unknown keyword: ")))
+                                        (call $inspect (local_get $key)))))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for))))
+    }
+}
+
+/// The elements of `$arr` from `$from`, up to but not including `$to`.
+fn arr_slice() -> Func {
+    wat! {
+        (func $arr_slice
+            (param $arr (ref $arr_unitype))
+            (param $from i32)
+            (param $to i32)
+            (result (ref $arr_unitype))
+            (local $res (ref $arr_unitype))
+
+            (if (i32_eqz (i32_gt_s (local_get $to) (local_get $from)))
+                (then (return (array_new_fixed $arr_unitype 0))))
+            (local_set $res
+                (array_new $arr_unitype
+                    (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+                    (i32_sub (local_get $to) (local_get $from))))
+            (array_copy $arr_unitype $arr_unitype
+                (local_get $res)
+                (const_i32 0)
+                (local_get $arr)
+                (local_get $from)
+                (i32_sub (local_get $to) (local_get $from)))
+            (local_get $res))
+    }
+}
+
+/// A new array of the elements of `$a`, then those of `$b`.
+fn arr_concat() -> Func {
+    wat! {
+        (func $arr_concat
+            (param $a (ref $arr_unitype))
+            (param $b (ref $arr_unitype))
+            (result (ref $arr_unitype))
+            (local $res (ref $arr_unitype))
+
+            (local_set $res
+                (array_new $arr_unitype
+                    (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+                    (i32_add (array_len (local_get $a)) (array_len (local_get $b)))))
+            (array_copy $arr_unitype $arr_unitype
+                (local_get $res)
+                (const_i32 0)
+                (local_get $a)
+                (const_i32 0)
+                (array_len (local_get $a)))
+            (array_copy $arr_unitype $arr_unitype
+                (local_get $res)
+                (array_len (local_get $a))
+                (local_get $b)
+                (const_i32 0)
+                (array_len (local_get $b)))
+            (local_get $res))
+    }
+}
+
+/// `$args` with the keyword args' `$hash` after them.
+/// Like Ruby, no keyword args pass no `Hash`, rather than an empty one.
+fn push_keyword_args() -> Func {
+    wat! {
+        (func $push_keyword_args
+            (param $args (ref $arr_unitype))
+            (param $hash (ref $hash))
+            (result (ref $arr_unitype))
+
+            (if (result (ref $arr_unitype))
+                (array_len (struct_get $hash $keys (local_get $hash)))
+                (then
+                    (ref_cast (ref $arr_unitype)
+                        (call $push (local_get $args) (local_get $hash))))
+                (else (local_get $args))))
+    }
+}
+
+/// `$arr[$idx]`, or `nil` past the end of `$arr`.
+fn arr_get_or_nil() -> Func {
+    wat! {
+        (func $arr_get_or_nil
+            (param $arr (ref $arr_unitype))
+            (param $idx i32)
+            (result (ref eq))
+
+            (if (i32_lt_s (local_get $idx) (array_len (local_get $arr)))
+                (then (return (array_get $arr_unitype (local_get $arr) (local_get $idx)))))
+            (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+    }
+}
+
+/// `$val` as an array, for a multiple assignment to destructure:
+///     itself if it's an array, or else an array of just it.
+fn to_ary() -> Func {
+    wat! {
+        (func $to_ary
+            (param $val (ref eq))
+            (result (ref $arr_unitype))
+
+            (if (ref_test (ref $arr_unitype) (local_get $val))
+                (then (return (ref_cast (ref $arr_unitype) (local_get $val)))))
+            (array_new_fixed $arr_unitype 1 (local_get $val)))
+    }
+}
+
+/// Where a splat's elements end in `$arr`, with `$lefts` targets before it and `$rights` after.
+/// The targets before it take their elements first, so it never ends before `$lefts`.
+fn splat_end() -> Func {
+    wat! {
+        (func $splat_end
+            (param $arr (ref $arr_unitype))
+            (param $lefts i32)
+            (param $rights i32)
+            (result i32)
+            (local $end i32)
+
+            (local_set $end (i32_sub (array_len (local_get $arr)) (local_get $rights)))
+            (if (result i32) (i32_lt_s (local_get $end) (local_get $lefts))
+                (then (local_get $lefts))
+                (else (local_get $end))))
+    }
+}
+
+/// The index of the first element of `$arr` that's `==` to `$x`, or -1.
+fn arr_index() -> Func {
+    wat! {
+        (func $arr_index
+            (param $arr (ref $arr_unitype))
+            (param $x (ref eq))
+            (result i32)
+            (local $idx i32)
+
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (local_get $arr))))
+                    (if (call $from_bool
+                            (call $eq_eq
+                                (array_get $arr_unitype (local_get $arr) (local_get $idx))
+                                (local_get $x)))
+                        (then (return (local_get $idx))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for)))
+            (const_i32 ,(-1)))
+    }
+}
+
+/// The index of `$key` in `$hash`'s keys, or -1.
+fn hash_index() -> Func {
+    wat! {
+        (func $hash_index
+            (param $hash (ref $hash))
+            (param $key (ref eq))
+            (result i32)
+
+            (call $arr_index (struct_get $hash $keys (local_get $hash)) (local_get $key)))
+    }
+}
+
+/// `hash[key]`, or `nil` if `$hash` doesn't have `$key`.
+fn hash_get() -> Func {
+    wat! {
+        (func $hash_get
+            (param $hash (ref $hash))
+            (param $key (ref eq))
+            (result (ref eq))
+            (local $idx i32)
+
+            (local_set $idx (call $hash_index (local_get $hash) (local_get $key)))
+            (if (i32_lt_s (local_get $idx) (const_i32 0))
+                (then (return (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))))
+            (array_get $arr_unitype
+                (struct_get $hash $vals (local_get $hash))
+                (local_get $idx)))
+    }
+}
+
+/// A new `Hash` of the pairs of `$hash` whose keys aren't in `$keys`.
+fn hash_except() -> Func {
+    wat! {
+        (func $hash_except
+            (param $hash (ref $hash))
+            (param $except (ref $arr_unitype))
+            (result (ref $hash))
+            (local $keys (ref $arr_unitype))
+            (local $vals (ref $arr_unitype))
+            (local $key (ref eq))
+            (local $idx i32)
+
+            (local_set $keys (array_new_fixed $arr_unitype 0))
+            (local_set $vals (array_new_fixed $arr_unitype 0))
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (struct_get $hash $keys (local_get $hash)))))
+                    (local_set $key
+                        (array_get $arr_unitype
+                            (struct_get $hash $keys (local_get $hash))
+                            (local_get $idx)))
+                    (if (i32_lt_s (call $arr_index (local_get $except) (local_get $key))
+                                  (const_i32 0))
+                        (then
+                            (local_set $keys
+                                (ref_cast (ref $arr_unitype)
+                                    (call $push (local_get $keys) (local_get $key))))
+                            (local_set $vals
+                                (ref_cast (ref $arr_unitype)
+                                    (call $push
+                                        (local_get $vals)
+                                        (array_get $arr_unitype
+                                            (struct_get $hash $vals (local_get $hash))
+                                            (local_get $idx)))))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for)))
+            (struct_new $hash
+                (global_get ,(class::hash().identifier()))
+                (array_new_fixed $arr_unitype 0)
+                (local_get $keys)
+                (local_get $vals)))
+    }
+}
+
+/// `{a: 1, "b" => 2}`, with each key and value `inspect`ed.
+/// TODO: Quote symbol keys which aren't valid labels, `"foo bar": 1`
+fn hash_inspect() -> Func {
+    wat! {
+        (func $hash_inspect
+            (param $hash (ref $hash))
+            (result (ref $str))
+            (local $res (ref $str))
+            (local $key (ref eq))
+            (local $idx i32)
+
+            (local_set $res (global_get ,(string_identifier("{"))))
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (struct_get $hash $keys (local_get $hash)))))
+                    (if (local_get $idx)
+                        (then
+                            (local_set $res
+                                (call $str_concat
+                                    (local_get $res)
+                                    (global_get ,(string_identifier(", ")))))))
+                    (local_set $key
+                        (array_get $arr_unitype
+                            (struct_get $hash $keys (local_get $hash))
+                            (local_get $idx)))
+                    (local_set $res
+                        (call $str_concat
+                            (local_get $res)
+                            (if (result (ref $str))
+                                (ref_test (ref $sym) (local_get $key))
+                                (then
+                                    (call $str_concat
+                                        (struct_get $sym $name
+                                            (ref_cast (ref $sym) (local_get $key)))
+                                        (global_get ,(string_identifier(": ")))))
+                                (else
+                                    (call $str_concat
+                                        (call $inspect (local_get $key))
+                                        (global_get ,(string_identifier(" => "))))))))
+                    (local_set $res
+                        (call $str_concat
+                            (local_get $res)
+                            (call $inspect
+                                (array_get $arr_unitype
+                                    (struct_get $hash $vals (local_get $hash))
+                                    (local_get $idx)))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for)))
+            (call $str_concat
+                (local_get $res)
+                (global_get ,(string_identifier("}")))))
+    }
+}
+
 /// The `start` function runs when the module is loaded.
-/// We use it to set up cyclic object references:
+/// We use it to set up cyclic object references, for corelib and user classes:
 /// - `Class.parent`
 /// - `Class.superclass`
+/// - `Class.own_ancestors`, which includes the class itself
+/// - `Class.extended`
 fn add_start(ctx: &mut CompileCtx) {
     let mut instrs = vec![];
-    let classes = class::corelib_classes();
-    for class in classes {
+    for class in &ctx.classes {
         let class_identifier = class.identifier();
+        let own_ancestors = class_array(class.own_ancestors(&ctx.classes));
+        let extended = class_array(class.extended_ancestors(&ctx.classes));
+        instrs.append(&mut wat! {
+            (struct_set $class $own_ancestors
+                (global_get ,(class_identifier.clone()))
+                ,(own_ancestors))
+            (struct_set $class $extended
+                (global_get ,(class_identifier.clone()))
+                ,(extended))
+        });
+
         let parent_identifier = Class::name_to_identifier(&class.parent_name);
         let superclass_identifier = class
             .superclass_name
-            .map(|name| Class::name_to_identifier(&name));
+            .as_ref()
+            .map(|name| Class::name_to_identifier(name));
 
         instrs.append(&mut wat! {
             (struct_set $class $parent
@@ -84,9 +772,10 @@ fn add_start(ctx: &mut CompileCtx) {
     instrs.append(&mut wat! {
         (global_set $main
             (ref_cast (ref $obj)
-                (call ,(method::class_new().identifier()) // ($self, $args) -> ...
+                (call ,(method::class_new().identifier()) // ($self, $args, $block) -> ...
                       (global_get ,(class::object().identifier()))
-                      (global_get $empty_args))))
+                      (global_get $empty_args)
+                      (ref_null $proc))))
     });
 
     let start_fn = wat! {
@@ -98,6 +787,19 @@ fn add_start(ctx: &mut CompileCtx) {
     ctx.module.start_fn = Some("_start".to_string());
 }
 
+/// An `$arr_unitype` of the globals of the classes named `names`.
+fn class_array(names: Vec<String>) -> Instr {
+    let classes: Vec<_> = names
+        .iter()
+        .flat_map(|name| wat! { (global_get ,(Class::name_to_identifier(name))) })
+        .collect();
+    wat! {
+        (array_new_fixed $arr_unitype ,(classes.len() as i64)
+            ,(classes))
+    }
+    .remove(0)
+}
+
 /// `str-eq : (ref $str) (ref $str) -> Bool`
 fn str_eq() -> Func {
     wat! {
@@ -131,118 +833,675 @@ fn str_eq() -> Func {
     }
 }
 
-const ALIST_STR_METHOD_GET_IDENTIFIER: &str = "alist-str-method-get";
+/// `str_cmp : (ref $str) (ref $str) -> i32`
+/// Compares the strings' bytes in order, giving `-1`, `0` or `1`.
+/// If one is a prefix of the other, the shorter one is less.
+fn str_cmp() -> Func {
+    wat! {
+        (func $str_cmp
+            (param $a (ref $str))
+            (param $b (ref $str))
+            (result i32)
+            (local $idx i32) (local $a_ch i32) (local $b_ch i32)
 
-/// TODO: This should be genericized for any type of alist we have.
-fn alist_str_method_get() -> Func {
+            (local_set $idx (const_i32 0))
+            (loop $for
+                (if (i32_eq (local_get $idx) (array_len (local_get $a)))
+                    (then
+                        (return (i32_sub (const_i32 0)
+                                         (i32_lt_u (local_get $idx)
+                                                   (array_len (local_get $b)))))))
+                (if (i32_eq (local_get $idx) (array_len (local_get $b)))
+                    (then (return (const_i32 1))))
+                (local_set $a_ch (array_get_u $str (local_get $a) (local_get $idx)))
+                (local_set $b_ch (array_get_u $str (local_get $b) (local_get $idx)))
+                (if (i32_eqz (i32_eq (local_get $a_ch) (local_get $b_ch)))
+                    (then
+                        (return (i32_sub (i32_gt_u (local_get $a_ch) (local_get $b_ch))
+                                         (i32_lt_u (local_get $a_ch) (local_get $b_ch))))))
+                (local_set $idx (i32_add (local_get $idx)
+                                         (const_i32 1)))
+                (br $for))
+            (unreachable))
+    }
+}
+
+/// `intern : (ref $str) -> (ref $sym)`
+/// The symbol named `$name`, added to `$symbols` if there isn't one yet.
+fn intern() -> Func {
     wat! {
-        (func $alist_str_method_get
-            (param $alist (ref $alist_str_method))
+        (func $intern
             (param $name (ref $str))
-            (result (ref $method))
+            (result (ref $sym))
+            (local $idx i32) (local $sym (ref $sym))
+            (local_set $idx (const_i32 0))
+            (loop $for
+                (if (i32_lt_u (local_get $idx)
+                              (array_len (global_get $symbols)))
+                    (then
+                        (local_set $sym
+                            (ref_cast (ref $sym)
+                                (array_get $arr_unitype
+                                    (global_get $symbols)
+                                    (local_get $idx))))
+                        (if (call $str_eq (struct_get $sym $name (local_get $sym))
+                                          (local_get $name))
+                            (then (return (local_get $sym))))
+                        (local_set $idx (i32_add (local_get $idx)
+                                                 (const_i32 1)))
+                        (br $for))))
+            (local_set $sym (struct_new $sym (local_get $name)))
+            (global_set $symbols
+                (ref_cast (ref $arr_unitype)
+                    (call $push (global_get $symbols) (local_get $sym))))
+            (local_get $sym))
+    }
+}
+
+/// The method stored under `$key`, or null if there isn't one.
+/// TODO: This should be genericized for any type of alist we have.
+fn alist_sym_method_get() -> Func {
+    wat! {
+        (func $alist_sym_method_get
+            (param $alist (ref $alist_sym_method))
+            (param $key (ref $sym))
+            (result (ref null $method))
             (local $idx i32)
-            (local $pair (ref $alist_str_method_pair))
-            (local $key (ref $str))
-            (local $val (ref $method))
+            (local $pair (ref $alist_sym_method_pair))
 
-            (local.set $idx (const_i32 0))
-            (loop $for (result (ref eq))
+            (local_set $idx (const_i32 0))
+            (loop $for
                 (if (i32_eq (local_get $idx)
                             (array_len (local_get $alist)))
-                    (then (unreachable)))
+                    (then (return (ref_null $method))))
                 (local_set $pair
-                    (array_get $alist_str_method
-                        (local_get $alist)
-                        (local_get $idx)))
-                (local_set $key
-                    (struct_get $alist_str_method_pair $key
-                        (local_get $pair)))
-                (local_set $val
-                    (struct_get $alist_str_method_pair $val
-                        (local_get $pair)))
-                (if (call $str_eq
-                        (local_get $key)
-                        (local_get $name))
-                    (then (return (local_get $val))))
-                (local_set $idx
-                    (i32_add (local_get $idx) (const_i32 1)))
+                    (array_get $alist_sym_method (local_get $alist) (local_get $idx)))
+                (if (ref_eq (struct_get $alist_sym_method_pair $key (local_get $pair))
+                            (local_get $key))
+                    (then (return (struct_get $alist_sym_method_pair $val (local_get $pair)))))
+                (local_set $idx (i32_add (local_get $idx)
+                                         (const_i32 1)))
+                (br $for))
+            (unreachable))
+    }
+}
+
+/// The first instance method named `$name` of any of `$modules`, an array of `$class`,
+///     or null if none of them has one.
+fn find_in_modules() -> Func {
+    wat! {
+        (func $find_in_modules
+            (param $modules (ref $arr_unitype))
+            (param $name (ref $sym))
+            (result (ref null $method))
+            (local $idx i32)
+            (local $method (ref null $method))
+
+            (local_set $idx (const_i32 0))
+            (loop $for
+                (if (i32_eq (local_get $idx)
+                            (array_len (local_get $modules)))
+                    (then (return (ref_null $method))))
+                (local_set $method
+                    (call $alist_sym_method_get
+                        (struct_get $class $instance_methods
+                            (ref_cast (ref $class)
+                                (array_get $arr_unitype (local_get $modules) (local_get $idx))))
+                        (local_get $name)))
+                (if (ref_test (ref $method) (local_get $method))
+                    (then (return (local_get $method))))
+                (local_set $idx (i32_add (local_get $idx)
+                                         (const_i32 1)))
                 (br $for))
             (unreachable))
     }
 }
 
+/// Search `$class`, then each of its superclasses, for the method named `$name`.
+/// For each class, searches `$extended` if `$singleton` is set,
+///     for methods called on the class itself, otherwise `$own_ancestors`.
+/// Returns null if there's no such method.
+fn find_method() -> Func {
+    wat! {
+        (func $find_method
+            (param $class (ref null $class))
+            (param $name (ref $sym))
+            (param $singleton i32)
+            (result (ref null $method))
+            (local $method (ref null $method))
+
+            (loop $superclasses
+                (if (i32_eqz (ref_test (ref $class) (local_get $class)))
+                    (then (return (ref_null $method))))
+                (local_set $method
+                    (call $find_in_modules
+                        (if (result (ref $arr_unitype))
+                            (local_get $singleton)
+                            (then (struct_get $class $extended (local_get $class)))
+                            (else (struct_get $class $own_ancestors (local_get $class))))
+                        (local_get $name)))
+                (if (ref_test (ref $method) (local_get $method))
+                    (then (return (local_get $method))))
+                (local_set $class (struct_get $class $superclass (local_get $class)))
+                (br $superclasses))
+            (unreachable))
+    }
+}
+
+/// Call the method named `$message` on `$receiver`.
+/// Methods are looked up in Ruby's order: see `find_method`.
+/// If the receiver is a class, modules extending it come before the methods of `Class`.
+/// Values which aren't `$obj`s, like Integers, use the methods of their class: see `class_of`.
+/// Raises a `NoMethodError` if there's no such method.
 fn call() -> Func {
     wat! {
         (func $call
             (param $receiver (ref eq))
-            (param $message (ref $str))
+            (param $message (ref $sym))
             (param $args (ref $arr_unitype))
+            (param $block (ref null $proc))
             (result (ref eq))
-            (local $receiver_obj (ref $obj))
-            (local $parent (ref $class))
-            (local $method (ref $method))
-
-            (local_set $receiver_obj (ref_cast (ref $obj) (local_get $receiver)))
-            (local_set $parent
-                (ref_as_non_null
-                    (struct_get $obj $parent
-                        (local_get $receiver_obj))))
-            (local_set $method
-                (ref_cast (ref $method)
-                    (call $alist_str_method_get
-                        (struct_get $class $instance_methods
-                            (local_get $parent))
-                        (local_get $message))))
+            (local $method (ref null $method))
+
+            (if (ref_test (ref $class) (local_get $receiver))
+                (then
+                    (local_set $method
+                        (call $find_method
+                            (ref_cast (ref $class) (local_get $receiver))
+                            (local_get $message)
+                            (const_i32 1)))))
+            (if (i32_eqz (ref_test (ref $method) (local_get $method)))
+                (then
+                    (local_set $method
+                        (call $find_method
+                            (call $class_of (local_get $receiver))
+                            (local_get $message)
+                            (const_i32 0)))))
+            (if (i32_eqz (ref_test (ref $method) (local_get $method)))
+                (then
+                    (throw $ruby_exception
+                        (call $no_method_error
+                            (global_get ,(string_identifier("undefined method '")))
+                            (local_get $receiver)
+                            (local_get $message)))))
             (call_ref $method
-                (local_get $receiver_obj)
+                (local_get $receiver)
                 (local_get $args)
+                (local_get $block)
                 (local_get $method))
         )
     }
 }
 
-fn is_nil() -> Func {
-    // Cast to `i31`, then test for the Unitype::FIXNUM_MARKER
+/// The `NoMethodError` for calling `$message` on `$receiver`, like Ruby's:
+///     `<prefix><message>' for an instance of <class>`,
+///     or `for nil`, `for main` or `for class <name>`.
+fn no_method_error() -> Func {
+    wat! {
+        (func $no_method_error
+            (param $prefix (ref $str))
+            (param $receiver (ref eq))
+            (param $message (ref $sym))
+            (result (ref $exception))
+            (local $description (ref $str))
+
+            (local_set $description
+                (call $str_concat
+                    (global_get ,(string_identifier("an instance of ")))
+                    (struct_get $class $name (call $class_of (local_get $receiver)))))
+            (if (call $is_nil (local_get $receiver))
+                (then (local_set $description (global_get ,(string_identifier("nil"))))))
+            (if (ref_eq (local_get $receiver) (global_get $main))
+                (then (local_set $description (global_get ,(string_identifier("main"))))))
+            (if (ref_test (ref $class) (local_get $receiver))
+                (then
+                    (local_set $description
+                        (call $str_concat
+                            (if (result (ref $str))
+                                (ref_eq
+                                    (struct_get $class $parent
+                                        (ref_cast (ref $class) (local_get $receiver)))
+                                    (global_get ,(class::module().identifier())))
+                                (then (global_get ,(string_identifier("module "))))
+                                (else (global_get ,(string_identifier("class ")))))
+                            (struct_get $class $name
+                                (ref_cast (ref $class) (local_get $receiver)))))))
+            (call $new_exception
+                (global_get ,(class::no_method_error().identifier()))
+                (call $str_concat
+                    (call $str_concat
+                        (call $str_concat
+                            (local_get $prefix)
+                            (struct_get $sym $name (local_get $message)))
+                        (global_get ,(string_identifier("' for "))))
+                    (local_get $description))))
+    }
+}
+
+/// `super` in a method of `$owner`: call the next method named `$message`
+///     in the ancestors of `$self`'s class after `$owner`.
+/// Raises a `NoMethodError` if there's no such method,
+///     or if `$owner` isn't one of the ancestors, as for a method called through `extend`.
+/// TODO: `super` in methods called on a class through `extend`.
+fn call_super() -> Func {
+    let no_superclass_method = wat! {
+        (throw $ruby_exception
+            (call $no_method_error
+                (global_get ,(string_identifier("super: no superclass method '")))
+                (local_get $self)
+                (local_get $message)))
+    };
+    wat! {
+        (func $call_super
+            (param $self (ref eq))
+            (param $owner (ref $class))
+            (param $message (ref $sym))
+            (param $args (ref $arr_unitype))
+            (param $block (ref null $proc))
+            (result (ref eq))
+            (local $ancestors (ref $arr_unitype))
+            (local $idx i32)
+            (local $method (ref null $method))
+
+            (local_set $ancestors
+                (call $ancestors (call $class_of (local_get $self))))
+            (local_set $idx (const_i32 0))
+            (block $found
+                (loop $for
+                    (if (i32_eq (local_get $idx)
+                                (array_len (local_get $ancestors)))
+                        (then ,(no_superclass_method.clone())))
+                    (br_if $found
+                        (ref_eq (array_get $arr_unitype (local_get $ancestors) (local_get $idx))
+                                (local_get $owner)))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for)))
+            (loop $for
+                (local_set $idx (i32_add (local_get $idx)
+                                         (const_i32 1)))
+                (if (i32_eq (local_get $idx)
+                            (array_len (local_get $ancestors)))
+                    (then ,(no_superclass_method)))
+                (local_set $method
+                    (call $alist_sym_method_get
+                        (struct_get $class $instance_methods
+                            (ref_cast (ref $class)
+                                (array_get $arr_unitype (local_get $ancestors) (local_get $idx))))
+                        (local_get $message)))
+                (br_if $for (i32_eqz (ref_test (ref $method) (local_get $method)))))
+            (call_ref $method
+                (local_get $self)
+                (local_get $args)
+                (local_get $block)
+                (local_get $method)))
+    }
+}
+
+/// Call `$block` with `$args`, and `$block_arg` as its own block, for `yield` and `Proc#call`.
+/// The block binds its own params, so a lambda checks its args there.
+fn call_block() -> Func {
+    wat! {
+        (func $call_block
+            (param $block (ref null $proc))
+            (param $args (ref $arr_unitype))
+            (param $block_arg (ref null $proc))
+            (result (ref eq))
+            (local $proc (ref $proc))
+
+            (if (i32_eqz (ref_test (ref $proc) (local_get $block)))
+                (then
+                    (throw $ruby_exception
+                        (call $new_exception
+                            (global_get ,(class::local_jump_error().identifier()))
+                            (global_get ,(string_identifier("no block given (yield)")))))))
+            (local_set $proc (ref_as_non_null (local_get $block)))
+            (call_ref $block_fn
+                (local_get $proc)
+                (local_get $args)
+                (local_get $block_arg)
+                (struct_get $proc $func (local_get $proc))))
+    }
+}
+
+/// The args for a proc with `$n` required params, given `$args`.
+/// As in Ruby, if `$splat`, a lone array arg is spread over the params,
+///     and missing args are nil.
+fn block_args() -> Func {
+    wat! {
+        (func $block_args
+            (param $args (ref $arr_unitype))
+            (param $splat i32)
+            (param $n i32)
+            (result (ref $arr_unitype))
+            (local $res (ref $arr_unitype))
+
+            (if (i32_and (local_get $splat)
+                         (i32_eq (array_len (local_get $args)) (const_i32 1)))
+                (then
+                    (if (ref_test (ref $arr_unitype)
+                            (array_get $arr_unitype (local_get $args) (const_i32 0)))
+                        (then
+                            (local_set $args
+                                (ref_cast (ref $arr_unitype)
+                                    (array_get $arr_unitype (local_get $args) (const_i32 0))))))))
+            (if (i32_eqz (i32_lt_u (array_len (local_get $args)) (local_get $n)))
+                (then (return (local_get $args))))
+            (local_set $res
+                (array_new $arr_unitype
+                    (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+                    (local_get $n)))
+            (array_copy $arr_unitype $arr_unitype
+                (local_get $res)
+                (const_i32 0)
+                (local_get $args)
+                (const_i32 0)
+                (array_len (local_get $args)))
+            (local_get $res))
+    }
+}
+
+/// `receiver[idx]`: indexes arrays directly, and calls `[]` on anything else.
+fn index() -> Func {
+    wat! {
+        (func $index
+            (param $receiver (ref eq))
+            (param $idx (ref eq))
+            (result (ref eq))
+
+            (if (ref_test (ref $arr_unitype) (local_get $receiver))
+                (then
+                    (return
+                        (array_get $arr_unitype
+                            (ref_cast (ref $arr_unitype) (local_get $receiver))
+                            (i32_wrap_i64 (call $integer_to_i64 (local_get $idx)))))))
+            (call $call
+                (local_get $receiver)
+                (global_get ,(symbol_identifier("[]")))
+                (array_new_fixed $arr_unitype 1 (local_get $idx))
+                (ref_null $proc)))
+    }
+}
+
+/// The class of any value, including those that aren't `$obj`s.
+fn class_of() -> Func {
+    wat! {
+        (func $class_of
+            (param $x (ref eq))
+            (result (ref $class))
+
+            (if (call $is_integer (local_get $x))
+                (then (return (global_get ,(class::integer().identifier())))))
+            (if (call $is_float (local_get $x))
+                (then (return (global_get ,(class::float().identifier())))))
+            (if (ref_test (ref $str) (local_get $x))
+                (then (return (global_get ,(class::string().identifier())))))
+            (if (ref_test (ref $sym) (local_get $x))
+                (then (return (global_get ,(class::symbol().identifier())))))
+            (if (ref_test (ref $arr_unitype) (local_get $x))
+                (then (return (global_get ,(class::array().identifier())))))
+            (if (call $is_nil (local_get $x))
+                (then (return (global_get ,(class::nil_class().identifier())))))
+            (if (ref_eq (local_get $x) (ref_i31 (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64))))
+                (then (return (global_get ,(class::true_class().identifier())))))
+            (if (ref_eq (local_get $x) (ref_i31 (const_i32 ,(Unitype::FALSE_BIT_PATTERN as i64))))
+                (then (return (global_get ,(class::false_class().identifier())))))
+            (ref_cast (ref $class)
+                (struct_get $obj $parent (ref_cast (ref $obj) (local_get $x)))))
+    }
+}
+
+/// Is `$class` one of the ancestors of `$x`'s class?
+fn is_a() -> Func {
+    wat! {
+        (func $is_a
+            (param $x (ref eq))
+            (param $class (ref $class))
+            (result i32)
+
+            (call $has_ancestor (call $class_of (local_get $x)) (local_get $class)))
+    }
+}
+
+/// Is `$ancestor` one of `$class`'s ancestors, including `$class` itself?
+fn has_ancestor() -> Func {
+    wat! {
+        (func $has_ancestor
+            (param $class (ref $class))
+            (param $ancestor (ref $class))
+            (result i32)
+            (local $ancestors (ref $arr_unitype))
+            (local $idx i32)
+
+            (local_set $ancestors (call $ancestors (local_get $class)))
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (local_get $ancestors))))
+                    (if (ref_eq (array_get $arr_unitype (local_get $ancestors) (local_get $idx))
+                                (local_get $ancestor))
+                        (then (return (const_i32 1))))
+                    (local_set $idx (i32_add (local_get $idx) (const_i32 1)))
+                    (br $for)))
+            (const_i32 0))
+    }
+}
+
+/// `pattern === x`, as in a `when`.
+/// Objects, including classes and ranges, have their `===` method called.
+/// Other values have no methods, and `===` is `==`.
+fn case_eq() -> Func {
+    wat! {
+        (func $case_eq
+            (param $pattern (ref eq))
+            (param $x (ref eq))
+            (result (ref eq))
+
+            (if (ref_test (ref $obj) (local_get $pattern))
+                (then
+                    (return
+                        (call $call
+                            (local_get $pattern)
+                            (global_get ,(symbol_identifier("===")))
+                            (array_new_fixed $arr_unitype 1 (local_get $x))
+                            (ref_null $proc)))))
+            (call $eq_eq (local_get $pattern) (local_get $x)))
+    }
+}
+
+/// The index in a `case`'s jump table of the `when` matching `$x`,
+///     whose integer patterns are all in `$min ..< $min + $len`.
+/// Values `==` to none of them get `$len`, the `else`.
+fn case_index() -> Func {
+    wat! {
+        (func $case_index
+            (param $x (ref eq))
+            (param $min i64)
+            (param $len i32)
+            (result i32)
+            (local $offset i64)
+            (local $f_offset f64)
+
+            (if (call $is_integer (local_get $x))
+                (then
+                    (local_set $offset (i64_sub (call $integer_to_i64 (local_get $x))
+                                                (local_get $min)))
+                    (if (i64_lt_u (local_get $offset) (i64_extend_i32_u (local_get $len)))
+                        (then (return (i32_wrap_i64 (local_get $offset)))))))
+            // Floats equal to an integer pattern match it too
+            (if (call $is_float (local_get $x))
+                (then
+                    (local_set $f_offset (f64_sub (call $number_to_f64 (local_get $x))
+                                                  (f64_convert_i64_s (local_get $min))))
+                    (if (i32_and (f64_eq (f64_floor (local_get $f_offset)) (local_get $f_offset))
+                                 (i32_and (f64_ge (local_get $f_offset) (const_f64 0.0))
+                                          (f64_lt (local_get $f_offset)
+                                                  (f64_convert_i64_s
+                                                      (i64_extend_i32_u (local_get $len))))))
+                        (then (return (i32_wrap_i64 (i64_trunc_f64_s (local_get $f_offset))))))))
+            (local_get $len))
+    }
+}
+
+/// A method's `$block` as a Ruby value, for a `&block` param: the `Proc`, or nil.
+fn block_to_unitype() -> Func {
+    wat! {
+        (func $block_to_unitype
+            (param $block (ref null $proc))
+            (result (ref eq))
+
+            (if (result (ref eq))
+                (ref_test (ref $proc) (local_get $block))
+                (then (ref_as_non_null (local_get $block)))
+                (else (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))))
+    }
+}
+
+/// A `&arg` block argument as a `$block`: null for nil, otherwise a `Proc`.
+fn unitype_to_block() -> Func {
+    wat! {
+        (func $unitype_to_block
+            (param $arg (ref eq))
+            (result (ref null $proc))
+
+            (if (call $is_nil (local_get $arg))
+                (then (return (ref_null $proc))))
+            // TODO: TypeError, "wrong argument type (expected Proc)"
+            (ref_cast (ref $proc) (local_get $arg)))
+    }
+}
+
+/// `Module#ancestors`: the `$own_ancestors` of `$class` and each of its superclasses.
+fn ancestors() -> Func {
+    wat! {
+        (func $ancestors
+            (param $class (ref null $class))
+            (result (ref $arr_unitype))
+            (local $res (ref $arr_unitype))
+            (local $own (ref $arr_unitype))
+            (local $next (ref $arr_unitype))
+
+            (local_set $res (array_new_fixed $arr_unitype 0))
+            (block $exit
+                (loop $superclasses
+                    (br_if $exit (i32_eqz (ref_test (ref $class) (local_get $class))))
+                    (local_set $own (struct_get $class $own_ancestors (local_get $class)))
+                    (local_set $next
+                        (array_new $arr_unitype
+                            (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+                            (i32_add (array_len (local_get $res))
+                                     (array_len (local_get $own)))))
+                    (array_copy $arr_unitype $arr_unitype
+                        (local_get $next)
+                        (const_i32 0)
+                        (local_get $res)
+                        (const_i32 0)
+                        (array_len (local_get $res)))
+                    (array_copy $arr_unitype $arr_unitype
+                        (local_get $next)
+                        (array_len (local_get $res))
+                        (local_get $own)
+                        (const_i32 0)
+                        (array_len (local_get $own)))
+                    (local_set $res (local_get $next))
+                    (local_set $class (struct_get $class $superclass (local_get $class)))
+                    (br $superclasses)))
+            (local_get $res))
+    }
+}
+
+fn is_nil() -> Func {
+    // Cast to `i31`, then test for the Unitype::FIXNUM_MARKER
+    wat! {
+        (func $is_nil
+            (param $n (ref eq))
+            (result i32)
+
+            // Wasm has no short-circuiting booleans.
+            (if (result i32)
+                (ref_test (ref i31) (local_get $n))
+                (then (i32_eq (i31_get_u (ref_cast (ref i31) (local_get $n)))
+                              (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+                (else (const_i32 0))))
+    }
+}
+
+fn is_fixnum() -> Func {
+    // Cast to `i31`, then test for the Unitype::FIXNUM_MARKER
+    wat! {
+        (func $is_fixnum
+            (param $n (ref eq))
+            (result i32)
+
+            // Wasm has no short-circuiting booleans.
+            (if (result i32)
+                (ref_test (ref i31) (local_get $n))
+                // The marker is the top bit of the i31, so this is 0 or 1.
+                (then (i32_shr_u (i31_get_u (ref_cast (ref i31) (local_get $n)))
+                                 (const_i32 ,(Unitype::FIXNUM_MARKER.trailing_zeros() as i64))))
+                (else (const_i32 0))))
+    }
+}
+
+fn is_boxnum() -> Func {
+    wat! {
+        (func $is_boxnum
+            (param $n (ref eq))
+            (result i32)
+
+            (if (result i32)
+                (ref_test (ref $boxnum) (local_get $n))
+                (then (const_i32 1))
+                (else (const_i32 0))))
+    }
+}
+
+fn is_integer() -> Func {
+    wat! {
+        (func $is_integer
+            (param $n (ref eq))
+            (result i32)
+
+            (i32_or (call $is_fixnum (local_get $n))
+                    (call $is_boxnum (local_get $n))))
+    }
+}
+
+fn is_float() -> Func {
     wat! {
-        (func $is_nil
+        (func $is_float
             (param $n (ref eq))
             (result i32)
 
-            // Wasm has no short-circuiting booleans.
-            (if (result i32)
-                (ref_test (ref i31) (local_get $n))
-                (then (i32_eq (i31_get_u (ref_cast (ref i31) (local_get $n)))
-                              (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
-                (else (const_i32 0))))
+            (ref_test (ref $boxfloat) (local_get $n)))
     }
 }
 
-fn is_fixnum() -> Func {
-    // Cast to `i31`, then test for the Unitype::FIXNUM_MARKER
+/// Is `n` an Integer or a Float?
+fn is_numeric() -> Func {
     wat! {
-        (func $is_fixnum
+        (func $is_numeric
             (param $n (ref eq))
             (result i32)
 
-            // Wasm has no short-circuiting booleans.
-            (if (result i32)
-                (ref_test (ref i31) (local_get $n))
-                (then (i32_and (const_i32 ,(Unitype::FIXNUM_MARKER as i64))
-                               (i31_get_u (ref_cast (ref i31) (local_get $n)))))
-                (else (const_i32 0))))
+            (i32_or (call $is_integer (local_get $n))
+                    (call $is_float (local_get $n))))
     }
 }
 
-fn is_boxnum() -> Func {
+/// Convert an Integer or Float to `f64`.
+/// Assumes that `n` is Numeric.
+fn number_to_f64() -> Func {
     wat! {
-        (func $is_boxnum
+        (func $number_to_f64
             (param $n (ref eq))
-            (result i32)
+            (result f64)
 
-            (if (result i32)
-                (ref_test (ref $boxnum) (local_get $n))
-                (then (const_i32 1))
-                (else (const_i32 0))))
+            (if (result f64)
+                (call $is_float (local_get $n))
+                (then (struct_get $boxfloat $val (ref_cast (ref $boxfloat) (local_get $n))))
+                (else (f64_convert_i64_s (call $integer_to_i64 (local_get $n))))))
     }
 }
 
@@ -336,12 +1595,10 @@ fn in_fixnum_range() -> Func {
         (func $in_fixnum_range
             (param $n i64)
             (result i32)
-            (local $n_i32 i32)
-            (local_set $n_i32 (i32_wrap_i64 (local_get $n)))
-            (i32_and (i32_lt_s (const_i32 ,(min))
-                               (local_get $n_i32))
-                     (i32_lt_s (local_get $n_i32)
-                               (const_i32 ,(max)))))
+            (i32_and (i64_lt_s (const_i64 ,(min - 1))
+                               (local_get $n))
+                     (i64_lt_s (local_get $n)
+                               (const_i64 ,(max + 1)))))
     }
 }
 
@@ -387,142 +1644,1128 @@ fn i64_to_integer() -> Func {
     }
 }
 
-fn add() -> Func {
-    // TODO: Should do checked add at least.
+fn to_bool() -> Func {
+    wat! {
+        (func $to_bool
+            (param $b i32)
+            (result (ref i31))
+            (ref_i31
+                (if (result i32)
+                    (local_get $b)
+                    (then (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64)))
+                    (else (const_i32 ,(Unitype::FALSE_BIT_PATTERN as i64))))))
+    }
+}
+
+fn from_bool() -> Func {
+    wat! {
+        (func $from_bool
+            (param $b (ref eq))
+            (result i32)
+            (ref_eq (ref_cast (ref i31) (local_get $b))
+                    (ref_i31 (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64)))))
+    }
+}
+
+/// Whether `$val` is truthy, as in Ruby: anything but `nil` and `false`.
+fn truthy() -> Func {
+    wat! {
+        (func $truthy
+            (param $val (ref eq))
+            (result i32)
+            (i32_eqz
+                (i32_or
+                    (ref_eq (local_get $val)
+                            (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+                    (ref_eq (local_get $val)
+                            (ref_i31 (const_i32 ,(Unitype::FALSE_BIT_PATTERN as i64)))))))
+    }
+}
+
+/// If `$a` is an `$obj`, return what its method `operator` gives for `args`.
+/// The operator functions do the operations of builtin values themselves,
+///     but objects can define their own.
+fn call_operator_method(operator: &str, args: Vec<Instr>) -> Vec<Instr> {
+    let len = args.len() as i64;
+    wat! {
+        (if (ref_test (ref $obj) (local_get $a))
+            (then
+                (return
+                    (call $call
+                        (local_get $a)
+                        (global_get ,(symbol_identifier(operator)))
+                        (array_new_fixed $arr_unitype ,(len) ,(args))
+                        (ref_null $proc)))))
+    }
+}
+
+/// Raise a `NoMethodError` for `operator` if `$a` isn't a builtin value `is_operand` accepts,
+///     like `"a" - 1`, then the exception `arg_error` makes from `$a` and `$b`
+///     if `$b` isn't accepted either, like `1 - "a"`.
+/// `is_operand` is `is_numeric` or `is_integer`.
+fn check_operands(operator: &str, is_operand: &str, arg_error: &str) -> Vec<Instr> {
+    [
+        check_receiver(operator, is_operand),
+        wat! {
+            (if (i32_eqz (call ,(is_operand.to_string()) (local_get $b)))
+                (then
+                    (throw $ruby_exception
+                        (call ,(arg_error.to_string()) (local_get $a) (local_get $b)))))
+        },
+    ]
+    .concat()
+}
+
+/// Raise a `NoMethodError` for `operator` if `$a` isn't a builtin value `is_operand` accepts.
+fn check_receiver(operator: &str, is_operand: &str) -> Vec<Instr> {
+    wat! {
+        (if (i32_eqz (call ,(is_operand.to_string()) (local_get $a)))
+            (then
+                (throw $ruby_exception
+                    (call $no_method_error
+                        (global_get ,(string_identifier("undefined method '")))
+                        (local_get $a)
+                        (global_get ,(symbol_identifier(operator)))))))
+    }
+}
+
+/// How Ruby's operand errors describe `$x`: `nil`, `true` or `false`, or else its class's name.
+fn operand_description() -> Func {
+    wat! {
+        (func $operand_description
+            (param $x (ref eq))
+            (result (ref $str))
+
+            (if (i32_and (ref_test (ref i31) (local_get $x))
+                         (i32_eqz (call $is_fixnum (local_get $x))))
+                (then (return (call $inspect (local_get $x)))))
+            (struct_get $class $name (call $class_of (local_get $x))))
+    }
+}
+
+/// The `TypeError` for arithmetic on a Numeric `$a` and a non-Numeric `$b`:
+///     `<b> can't be coerced into <class of a>`.
+fn coerce_error() -> Func {
+    wat! {
+        (func $coerce_error
+            (param $a (ref eq))
+            (param $b (ref eq))
+            (result (ref $exception))
+
+            (call $new_exception
+                (global_get ,(class::type_error().identifier()))
+                (call $str_concat
+                    (call $str_concat
+                        (call $operand_description (local_get $b))
+                        (global_get ,(string_identifier(" can't be coerced into "))))
+                    (struct_get $class $name (call $class_of (local_get $a))))))
+    }
+}
+
+/// The `ArgumentError` for comparing a Numeric `$a` with a non-Numeric `$b`:
+///     `comparison of <class of a> with <b> failed`.
+fn comparison_error() -> Func {
+    wat! {
+        (func $comparison_error
+            (param $a (ref eq))
+            (param $b (ref eq))
+            (result (ref $exception))
+
+            (call $new_exception
+                (global_get ,(class::argument_error().identifier()))
+                (call $str_concat
+                    (call $str_concat
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("comparison of ")))
+                                (struct_get $class $name (call $class_of (local_get $a))))
+                            (global_get ,(string_identifier(" with "))))
+                        (call $operand_description (local_get $b)))
+                    (global_get ,(string_identifier(" failed"))))))
+    }
+}
+
+/// Arithmetic or comparison on two Numerics, or the `$obj` `$a`'s method `operator`.
+/// If both are Integers, `integer_res` is the result.
+/// Otherwise, both are converted to `f64` and `float_res` is the result.
+/// In both bodies, `$a` and `$b` hold the operands.
+/// A non-Numeric `$b` raises the exception `arg_error` makes: see `check_operands`.
+fn numeric_binop(
+    name: &str,
+    operator: &str,
+    arg_error: &str,
+    integer_res: Vec<Instr>,
+    float_res: Vec<Instr>,
+) -> Func {
+    binop_func(
+        name,
+        [
+            call_operator_method(operator, wat! { (local_get $b) }),
+            numeric_binop_body(operator, arg_error, integer_res, float_res),
+        ]
+        .concat(),
+    )
+}
+
+/// What `numeric_binop` does once `$a` isn't an `$obj`.
+fn numeric_binop_body(
+    operator: &str,
+    arg_error: &str,
+    integer_res: Vec<Instr>,
+    float_res: Vec<Instr>,
+) -> Vec<Instr> {
+    [
+        check_operands(operator, "is_numeric", arg_error),
+        wat! {
+            (if (result (ref eq))
+                (i32_and (call $is_integer (local_get $a))
+                         (call $is_integer (local_get $b)))
+                (then ,(integer_res))
+                (else ,(float_res)))
+        },
+    ]
+    .concat()
+}
+
+/// The function `name` of the operands `$a` and `$b`.
+fn binop_func(name: &str, body: Vec<Instr>) -> Func {
+    wat! {
+        (func ,(name.to_string())
+            (param $a (ref eq))
+            (param $b (ref eq))
+            (result (ref eq))
+            ,(body))
+    }
+}
+
+/// `+` adds Numerics, or concatenates Strings into a new one.
+fn add() -> Func {
+    let concat = wat! {
+        (if (ref_test (ref $str) (local_get $a))
+            (then
+                (if (i32_eqz (ref_test (ref $str) (local_get $b)))
+                    (then
+                        (throw $ruby_exception
+                            (call $new_exception
+                                (global_get ,(class::type_error().identifier()))
+                                (call $str_concat
+                                    (call $str_concat
+                                        (global_get ,(string_identifier("no implicit conversion of ")))
+                                        (call $operand_description (local_get $b)))
+                                    (global_get ,(string_identifier(" into String"))))))))
+                (return
+                    (call $str_concat
+                        (ref_cast (ref $str) (local_get $a))
+                        (ref_cast (ref $str) (local_get $b))))))
+    };
+    let integer_res = wat! {
+        (call $i64_to_integer
+            (call $i64_add_checked (call $integer_to_i64 (local_get $a))
+                                 (call $integer_to_i64 (local_get $b))))
+    };
+    let float_res = wat! {
+        (struct_new $boxfloat
+            (f64_add (call $number_to_f64 (local_get $a))
+                     (call $number_to_f64 (local_get $b))))
+    };
+    binop_func(
+        "add",
+        [
+            call_operator_method("+", wat! { (local_get $b) }),
+            concat,
+            numeric_binop_body("+", "coerce_error", integer_res, float_res),
+        ]
+        .concat(),
+    )
+}
+
+fn sub() -> Func {
+    numeric_binop(
+        "sub",
+        "-",
+        "coerce_error",
+        wat! {
+            (call $i64_to_integer
+                (call $i64_sub_checked (call $integer_to_i64 (local_get $a))
+                                     (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (struct_new $boxfloat
+                (f64_sub (call $number_to_f64 (local_get $a))
+                         (call $number_to_f64 (local_get $b))))
+        },
+    )
+}
+
+fn mul() -> Func {
+    numeric_binop(
+        "mul",
+        "*",
+        "coerce_error",
+        wat! {
+            (call $i64_to_integer
+                (call $i64_mul_checked (call $integer_to_i64 (local_get $a))
+                                     (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (struct_new $boxfloat
+                (f64_mul (call $number_to_f64 (local_get $a))
+                         (call $number_to_f64 (local_get $b))))
+        },
+    )
+}
+
+/// `$a + $b`, raising a `RangeError` if it overflows.
+/// It overflows if the operands have the same sign and the result has the other one.
+fn i64_add_checked() -> Func {
+    wat! {
+        (func $i64_add_checked
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $res i64)
+
+            (local_set $res (i64_add (local_get $a) (local_get $b)))
+            (if (i64_lt_s (i64_and (i64_xor (local_get $a) (local_get $res))
+                                   (i64_xor (local_get $b) (local_get $res)))
+                          (const_i64 0))
+                (then ,(throw_integer_overflow())))
+            (local_get $res))
+    }
+}
+
+/// `$a - $b`, raising a `RangeError` if it overflows.
+/// It overflows if the operands have different signs and the result has `$b`'s.
+fn i64_sub_checked() -> Func {
+    wat! {
+        (func $i64_sub_checked
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $res i64)
+
+            (local_set $res (i64_sub (local_get $a) (local_get $b)))
+            (if (i64_lt_s (i64_and (i64_xor (local_get $a) (local_get $b))
+                                   (i64_xor (local_get $a) (local_get $res)))
+                          (const_i64 0))
+                (then ,(throw_integer_overflow())))
+            (local_get $res))
+    }
+}
+
+/// `$a * $b`, raising a `RangeError` if it overflows.
+/// It overflows if dividing the wrapped result by `$a` doesn't give back `$b`.
+/// `-1 * i64::MIN` is checked first, since both it and the division back overflow,
+///     and `$a` of 0 never overflows, and would trap dividing by it.
+fn i64_mul_checked() -> Func {
+    wat! {
+        (func $i64_mul_checked
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $res i64)
+
+            (if (i32_and (i64_eq (local_get $a) (const_i64 ,(-1)))
+                         (i64_eq (local_get $b) (const_i64 ,(i64::MIN))))
+                (then ,(throw_integer_overflow())))
+            (local_set $res (i64_mul (local_get $a) (local_get $b)))
+            (if (i32_eqz (i64_eqz (local_get $a)))
+                (then
+                    (if (i32_eqz (i64_eq (i64_div_s (local_get $res) (local_get $a))
+                                         (local_get $b)))
+                        (then ,(throw_integer_overflow())))))
+            (local_get $res))
+    }
+}
+
+/// Integer division rounds towards negative infinity, like Ruby.
+fn div() -> Func {
+    numeric_binop(
+        "div",
+        "/",
+        "coerce_error",
+        wat! {
+            (call $i64_to_integer
+                (call $i64_floor_div (call $integer_to_i64 (local_get $a))
+                                     (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (struct_new $boxfloat
+                (f64_div (call $number_to_f64 (local_get $a))
+                         (call $number_to_f64 (local_get $b))))
+        },
+    )
+}
+
+/// `i64.div_s` truncates towards zero.
+/// If the remainder is nonzero and has the opposite sign to the divisor,
+///     the floored quotient is one less.
+fn i64_floor_div() -> Func {
+    wat! {
+        (func $i64_floor_div
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $quot i64)
+            (local $rem i64)
+
+            (call $check_divisor (local_get $b))
+            // The only quotient too big for an `i64`, which `i64.div_s` traps on.
+            (if (i32_and (i64_eq (local_get $a) (const_i64 ,(i64::MIN)))
+                         (i64_eq (local_get $b) (const_i64 ,(-1))))
+                (then ,(throw_integer_overflow())))
+            (local_set $quot (i64_div_s (local_get $a) (local_get $b)))
+            (local_set $rem (i64_rem_s (local_get $a) (local_get $b)))
+            (if (i32_and (i32_eqz (i64_eqz (local_get $rem)))
+                         (i64_lt_s (i64_xor (local_get $rem) (local_get $b))
+                                   (const_i64 0)))
+                (then (local_set $quot (i64_sub (local_get $quot) (const_i64 1)))))
+            (local_get $quot))
+    }
+}
+
+/// The modulo has the sign of the divisor, like Ruby, so it matches `div`:
+///     `a == b * (a / b) + a % b`.
+fn modulo() -> Func {
+    numeric_binop(
+        "modulo",
+        "%",
+        "coerce_error",
+        wat! {
+            (call $i64_to_integer
+                (call $i64_floor_mod (call $integer_to_i64 (local_get $a))
+                                     (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (struct_new $boxfloat
+                (f64_sub (call $number_to_f64 (local_get $a))
+                         (f64_mul (call $number_to_f64 (local_get $b))
+                                  (f64_floor (f64_div (call $number_to_f64 (local_get $a))
+                                                      (call $number_to_f64 (local_get $b)))))))
+        },
+    )
+}
+
+/// `i64.rem_s` has the sign of the dividend.
+/// If it's nonzero and has the opposite sign to the divisor, adding the divisor fixes it.
+fn i64_floor_mod() -> Func {
+    wat! {
+        (func $i64_floor_mod
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $rem i64)
+
+            (call $check_divisor (local_get $b))
+            (local_set $rem (i64_rem_s (local_get $a) (local_get $b)))
+            (if (i32_and (i32_eqz (i64_eqz (local_get $rem)))
+                         (i64_lt_s (i64_xor (local_get $rem) (local_get $b))
+                                   (const_i64 0)))
+                (then (local_set $rem (i64_add (local_get $rem) (local_get $b)))))
+            (local_get $rem))
+    }
+}
+
+/// Raises `ZeroDivisionError` if an Integer divisor is zero.
+/// Float division by zero gives Infinity or NaN instead.
+fn check_divisor() -> Func {
+    wat! {
+        (func $check_divisor
+            (param $b i64)
+
+            (if (i64_eqz (local_get $b))
+                (then
+                    (throw $ruby_exception
+                        (call $new_exception
+                            (global_get ,(class::zero_division_error().identifier()))
+                            (global_get ,(string_identifier("divided by 0"))))))))
+    }
+}
+
+/// `**`. An Integer to a negative Integer power is a Float, where Ruby gives a Rational,
+///     except that `0` to one raises a `ZeroDivisionError` like Ruby's.
+fn pow() -> Func {
+    let body = [
+        call_operator_method("**", wat! { (local_get $b) }),
+        check_operands("**", "is_numeric", "coerce_error"),
+        wat! {
+            (if (i32_and (call $is_integer (local_get $a)) (call $is_integer (local_get $b)))
+                (then
+                    (if (i32_eqz (i64_lt_s (call $integer_to_i64 (local_get $b))
+                                           (const_i64 0)))
+                        (then
+                            (return
+                                (call $i64_to_integer
+                                    (call $i64_pow (call $integer_to_i64 (local_get $a))
+                                                   (call $integer_to_i64 (local_get $b)))))))
+                    (call $check_divisor (call $integer_to_i64 (local_get $a)))))
+            (struct_new $boxfloat
+                (call $f64_pow_f64 (call $number_to_f64 (local_get $a))
+                                   (call $number_to_f64 (local_get $b))))
+        },
+    ]
+    .concat();
+    wat! {
+        (func $pow
+            (param $a (ref eq))
+            (param $b (ref eq))
+            (result (ref eq))
+            ,(body))
+    }
+}
+
+/// Exponentiation by squaring, raising a `RangeError` if a step overflows.
+/// The base is only squared while there are bits of `$exp` left,
+///     so the last squaring can't overflow when its result isn't needed.
+/// Pre: `$exp` isn't negative.
+fn i64_pow() -> Func {
+    wat! {
+        (func $i64_pow
+            (param $base i64)
+            (param $exp i64)
+            (result i64)
+            (local $res i64)
+
+            (local_set $res (const_i64 1))
+            (loop $square
+                (if (i32_wrap_i64 (i64_and (local_get $exp) (const_i64 1)))
+                    (then
+                        (local_set $res (call $i64_mul_checked (local_get $res) (local_get $base)))))
+                (local_set $exp (i64_shr_s (local_get $exp) (const_i64 1)))
+                (if (i64_eqz (local_get $exp))
+                    (then (return (local_get $res))))
+                (local_set $base (call $i64_mul_checked (local_get $base) (local_get $base)))
+                (br $square))
+            (unreachable))
+    }
+}
+
+/// `$base` to a Float power, or NaN for a negative `$base` and a fractional `$exp`,
+///     where Ruby gives a Complex.
+/// A negative `$base` with a whole `$exp` is its absolute value's power, negated for an odd `$exp`.
+fn f64_pow_f64() -> Func {
+    wat! {
+        (func $f64_pow_f64
+            (param $base f64)
+            (param $exp f64)
+            (result f64)
+            (local $sign f64)
+
+            (if (f64_eq (local_get $exp) (const_f64 0.0))
+                (then (return (const_f64 1.0))))
+            (if (f64_eq (local_get $base) (const_f64 1.0))
+                (then (return (const_f64 1.0))))
+            // NaN to any other power is NaN, as is anything but 1 to the power NaN.
+            (if (i32_eqz (f64_eq (local_get $base) (local_get $base)))
+                (then (return (local_get $base))))
+            (if (i32_eqz (f64_eq (local_get $exp) (local_get $exp)))
+                (then (return (local_get $exp))))
+            (local_set $sign (const_f64 1.0))
+            // Negative, including `-0.0`.
+            (if (i64_lt_s (i64_reinterpret_f64 (local_get $base)) (const_i64 0))
+                (then
+                    (if (i32_eqz (f64_eq (f64_floor (local_get $exp)) (local_get $exp)))
+                        (then (return (f64_div (const_f64 0.0) (const_f64 0.0)))))
+                    (if (i32_eqz (f64_eq (f64_mul (f64_floor (f64_mul (local_get $exp) (const_f64 0.5)))
+                                                  (const_f64 2.0))
+                                         (local_get $exp)))
+                        (then (local_set $sign (const_f64 ,(-1.0)))))
+                    (local_set $base (f64_neg (local_get $base)))))
+            (if (f64_eq (local_get $base) (const_f64 1.0))
+                (then (return (local_get $sign))))
+            (if (i32_or (f64_eq (local_get $base) (const_f64 0.0))
+                        (f64_eq (local_get $base) (const_f64 ,(f64::INFINITY))))
+                (then
+                    (return
+                        (f64_mul (local_get $sign)
+                            (if (result f64)
+                                (i32_eq (f64_lt (local_get $exp) (const_f64 0.0))
+                                        (f64_eq (local_get $base) (const_f64 0.0)))
+                                (then (const_f64 ,(f64::INFINITY)))
+                                (else (const_f64 0.0)))))))
+            (f64_mul (local_get $sign)
+                     (call $f64_pow_positive (local_get $base) (local_get $exp))))
+    }
+}
+
+/// `e ** ($exp * ln($base))`, for a positive, finite `$base` other than 1 and a non-NaN `$exp`.
+/// The log and exponential are worked out in double-double arithmetic,
+///     so their rounding errors don't get scaled up by `$exp` and show in the result.
+fn f64_pow_positive() -> Func {
+    let body = [
+        f64_ln_dd(),
+        wat! {
+            // `z = $exp * ln($base)`.
+            (local_set $z_hi (f64_mul (local_get $exp) (local_get $l_hi)))
+            // Past these, the result overflows to Infinity or underflows to 0.
+            (if (f64_gt (local_get $z_hi) (const_f64 709.782712893384))
+                (then (return (const_f64 ,(f64::INFINITY)))))
+            (if (f64_lt (local_get $z_hi) (const_f64 ,(-745.1332191019412)))
+                (then (return (const_f64 0.0))))
+        },
+        // `|$exp|` is now below `2^63`, since `|l_hi| >= ln(1 + 2^-52)`.
+        helpers::f64_two_prod("exp", "l_hi", "z_hi", "z_lo"),
+        wat! {
+            (local_set $z_lo (f64_add (local_get $z_lo)
+                                      (f64_mul (local_get $exp) (local_get $l_lo))))
+        },
+        f64_exp_dd(),
+    ]
+    .concat();
+    wat! {
+        (func $f64_pow_positive
+            (param $base f64)
+            (param $exp f64)
+            (result f64)
+            (local $dd_tmp f64) (local $a_hi f64) (local $a_lo f64) (local $b_hi f64) (local $b_lo f64)
+            (local $bits i64) (local $k f64) (local $m f64) (local $f f64)
+            (local $d_hi f64) (local $d_lo f64) (local $s_hi f64) (local $s_lo f64)
+            (local $p f64) (local $p_lo f64) (local $s2 f64) (local $t f64) (local $n f64)
+            (local $lm_hi f64) (local $lm_lo f64) (local $k_ln2 f64) (local $l_hi f64) (local $l_lo f64)
+            (local $z_hi f64) (local $z_lo f64) (local $r_hi f64) (local $r_lo f64)
+            (local $term f64) (local $q f64) (local $h f64) (local $h_lo f64) (local $res f64)
+            ,(body))
+    }
+}
+
+/// `ln(2)` split in two, the first with its low 32 bits clear,
+///     so multiplying it by a whole number below `2^20` is exact.
+const LN2_HI: f64 = f64::from_bits(0x3FE6_2E42_FEE0_0000);
+const LN2_LO: f64 = f64::from_bits(0x3DEA_39EF_3579_3C76);
+
+/// Sets `$l_hi + $l_lo` to the natural log of a positive, finite `$base`.
+/// With `$base = m * 2^k` and `m` within a factor of `√2` of 1,
+///     `ln($base) = k * ln(2) + ln(m)`, and `ln(m) = 2 * atanh(s)` with `s = (m - 1) / (m + 1)`.
+/// `s` is small, so only its leading term `2s` needs to be in double-double.
+fn f64_ln_dd() -> Vec<Instr> {
+    [
+        wat! {
+            (local_set $k (const_f64 0.0))
+            // Scale subnormals up, so the exponent bits give `k`.
+            (if (f64_lt (local_get $base) (const_f64 2.2250738585072014e-308))
+                (then
+                    (local_set $base (f64_mul (local_get $base) (const_f64 18014398509481984.0)))
+                    (local_set $k (const_f64 ,(-54.0)))))
+            (local_set $bits (i64_reinterpret_f64 (local_get $base)))
+            (local_set $k
+                (f64_add (local_get $k)
+                         (f64_convert_i64_s (i64_sub (i64_shr_u (local_get $bits) (const_i64 52))
+                                                     (const_i64 1023)))))
+            (local_set $m
+                (f64_reinterpret_i64
+                    (i64_or (i64_and (local_get $bits) (const_i64 ,(0x000F_FFFF_FFFF_FFFF)))
+                            (const_i64 ,(0x3FF0_0000_0000_0000)))))
+            (if (f64_gt (local_get $m) (const_f64 ,(std::f64::consts::SQRT_2)))
+                (then
+                    (local_set $m (f64_mul (local_get $m) (const_f64 0.5)))
+                    (local_set $k (f64_add (local_get $k) (const_f64 1.0)))))
+            // Exact, as `m` is within a factor of 2 of 1.
+            (local_set $f (f64_sub (local_get $m) (const_f64 1.0)))
+            (local_set $m (const_f64 2.0))
+        },
+        helpers::f64_fast_two_sum("m", "f", "d_hi", "d_lo"),
+        wat! {
+            (local_set $s_hi (f64_div (local_get $f) (local_get $d_hi)))
+        },
+        helpers::f64_two_prod("s_hi", "d_hi", "p", "p_lo"),
+        wat! {
+            // `s_lo` is what's left of `f / d` after `s_hi`.
+            (local_set $s_lo
+                (f64_div (f64_sub (f64_sub (f64_sub (local_get $f) (local_get $p))
+                                           (local_get $p_lo))
+                                  (f64_mul (local_get $s_hi) (local_get $d_lo)))
+                         (local_get $d_hi)))
+            // `t = 2s^3 * (1/3 + s^2/5 + s^4/7 + ...)`, by Horner's method.
+            // `|s| < 0.18`, so the terms up to `s^24 / 25` are plenty.
+            (local_set $s2 (f64_mul (local_get $s_hi) (local_get $s_hi)))
+            (local_set $n (const_f64 25.0))
+            (local_set $t (f64_div (const_f64 1.0) (local_get $n)))
+            (loop $series
+                (local_set $n (f64_sub (local_get $n) (const_f64 2.0)))
+                (local_set $t (f64_add (f64_mul (local_get $t) (local_get $s2))
+                                       (f64_div (const_f64 1.0) (local_get $n))))
+                (br_if $series (f64_gt (local_get $n) (const_f64 3.0))))
+            (local_set $t (f64_mul (f64_mul (const_f64 2.0) (local_get $s_hi))
+                                   (f64_mul (local_get $s2) (local_get $t))))
+            (local_set $s_hi (f64_mul (const_f64 2.0) (local_get $s_hi)))
+            (local_set $s_lo (f64_add (f64_mul (const_f64 2.0) (local_get $s_lo)) (local_get $t)))
+        },
+        helpers::f64_fast_two_sum("s_hi", "s_lo", "lm_hi", "lm_lo"),
+        wat! {
+            (local_set $k_ln2 (f64_mul (local_get $k) (const_f64 ,(LN2_HI))))
+        },
+        helpers::f64_two_sum("k_ln2", "lm_hi", "l_hi", "l_lo"),
+        wat! {
+            (local_set $l_lo
+                (f64_add (local_get $l_lo)
+                         (f64_add (local_get $lm_lo)
+                                  (f64_mul (local_get $k) (const_f64 ,(LN2_LO))))))
+            (local_set $p (local_get $l_hi))
+            (local_set $p_lo (local_get $l_lo))
+        },
+        helpers::f64_fast_two_sum("p", "p_lo", "l_hi", "l_lo"),
+    ]
+    .concat()
+}
+
+/// Gives `e ** ($z_hi + $z_lo)`, for `$z_hi` within the range that neither overflows nor underflows.
+/// With `z = k * ln(2) + r` and `|r| <= ln(2) / 2`,
+///     it's `2^k * e^r`, and `e^r`'s Taylor series converges quickly.
+/// Only the `1 + r` at its start needs to be in double-double.
+fn f64_exp_dd() -> Vec<Instr> {
+    [
+        wat! {
+            (local_set $k (f64_floor (f64_add (f64_div (local_get $z_hi)
+                                                       (const_f64 ,(std::f64::consts::LN_2)))
+                                              (const_f64 0.5))))
+            (local_set $p (f64_sub (local_get $z_hi)
+                                   (f64_mul (local_get $k) (const_f64 ,(LN2_HI)))))
+            (local_set $p_lo (f64_sub (local_get $z_lo)
+                                      (f64_mul (local_get $k) (const_f64 ,(LN2_LO)))))
+        },
+        helpers::f64_fast_two_sum("p", "p_lo", "r_hi", "r_lo"),
+        wat! {
+            // `q = r^2/2! + r^3/3! + ...`, leaving out the `r_lo`s but their first product with `r_hi`.
+            // `|r| < 0.35`, so the terms up to `r^20 / 20!` are plenty.
+            (local_set $term (local_get $r_hi))
+            (local_set $q (f64_mul (local_get $r_hi) (local_get $r_lo)))
+            (local_set $n (const_f64 2.0))
+            (loop $series
+                (local_set $term (f64_div (f64_mul (local_get $term) (local_get $r_hi))
+                                          (local_get $n)))
+                (local_set $q (f64_add (local_get $q) (local_get $term)))
+                (local_set $n (f64_add (local_get $n) (const_f64 1.0)))
+                (br_if $series (f64_le (local_get $n) (const_f64 20.0))))
+            (local_set $m (const_f64 1.0))
+        },
+        helpers::f64_fast_two_sum("m", "r_hi", "h", "h_lo"),
+        wat! {
+            (local_set $res
+                (f64_add (local_get $h)
+                         (f64_add (local_get $h_lo)
+                                  (f64_add (local_get $r_lo) (local_get $q)))))
+            // `2^k` itself can overflow or underflow when the result doesn't, so it's applied in halves.
+            (local_set $bits (i64_trunc_f64_s (local_get $k)))
+            (f64_mul (f64_mul (local_get $res)
+                              (call $f64_pow (const_f64 2.0)
+                                             (i64_div_s (local_get $bits) (const_i64 2))))
+                     (call $f64_pow (const_f64 2.0)
+                                    (i64_sub (local_get $bits)
+                                             (i64_div_s (local_get $bits) (const_i64 2)))))
+        },
+    ]
+    .concat()
+}
+
+/// Exponentiation by squaring, of the reciprocal if `$exp` is negative.
+fn f64_pow() -> Func {
+    wat! {
+        (func $f64_pow
+            (param $base f64)
+            (param $exp i64)
+            (result f64)
+            (local $res f64)
+
+            (if (i64_lt_s (local_get $exp) (const_i64 0))
+                (then
+                    (return
+                        (f64_div (const_f64 1.0)
+                                 (call $f64_pow (local_get $base)
+                                                (i64_sub (const_i64 0) (local_get $exp)))))))
+            (local_set $res (const_f64 1.0))
+            (loop $square
+                (if (i64_eqz (local_get $exp))
+                    (then (return (local_get $res))))
+                (if (i32_wrap_i64 (i64_and (local_get $exp) (const_i64 1)))
+                    (then (local_set $res (f64_mul (local_get $res) (local_get $base)))))
+                (local_set $base (f64_mul (local_get $base) (local_get $base)))
+                (local_set $exp (i64_shr_s (local_get $exp) (const_i64 1)))
+                (br $square))
+            (unreachable))
+    }
+}
+
+/// `-@` negates a Numeric, or calls an `$obj`'s own `-@`.
+fn negate() -> Func {
+    let body = [
+        call_operator_method("-@", vec![]),
+        check_receiver("-@", "is_numeric"),
+        wat! {
+            (if (call $is_integer (local_get $a))
+                (then
+                    (return
+                        (call $i64_to_integer
+                            (call $i64_sub_checked (const_i64 0)
+                                                   (call $integer_to_i64 (local_get $a)))))))
+            (struct_new $boxfloat
+                (f64_neg (call $number_to_f64 (local_get $a))))
+        },
+    ]
+    .concat();
+    wat! {
+        (func $negate
+            (param $a (ref eq))
+            (result (ref eq))
+            ,(body))
+    }
+}
+
+/// `+x` is just `x`, unless it's an `$obj` with its own `+@`.
+fn unary_plus() -> Func {
+    let body = [call_operator_method("+@", vec![]), wat! { (local_get $a) }].concat();
     wat! {
-        (func $add
-            (param $lhs (ref eq))
-            (param $rhs (ref eq))
+        (func $unary_plus
+            (param $a (ref eq))
             (result (ref eq))
-            (local $lhs_val i64)
-            (local $rhs_val i64)
-            (local $res i64)
-
-            (local_set $lhs_val (call $integer_to_i64 (local_get $lhs)))
-            (local_set $rhs_val (call $integer_to_i64 (local_get $rhs)))
-            (local_set $res (i64_add (local_get $lhs_val)
-                                     (local_get $rhs_val)))
-            (call $i64_to_integer (local_get $res))
-        )
+            ,(body))
     }
 }
 
-fn sub() -> Func {
-    // TODO: Should do checked add at least.
-    wat! {
-        (func $sub
-            (param $lhs (ref eq))
-            (param $rhs (ref eq))
-            (result (ref eq))
-            (local $lhs_val i64)
-            (local $rhs_val i64)
-            (local $res i64)
+fn lt() -> Func {
+    numeric_binop(
+        "lt",
+        "<",
+        "comparison_error",
+        wat! {
+            (call $to_bool
+                (i64_lt_s (call $integer_to_i64 (local_get $a))
+                          (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (call $to_bool
+                (f64_lt (call $number_to_f64 (local_get $a))
+                        (call $number_to_f64 (local_get $b))))
+        },
+    )
+}
 
-            (local_set $lhs_val (call $integer_to_i64 (local_get $lhs)))
-            (local_set $rhs_val (call $integer_to_i64 (local_get $rhs)))
-            (local_set $res (i64_sub (local_get $lhs_val)
-                                     (local_get $rhs_val)))
-            (call $i64_to_integer (local_get $res))
-        )
-    }
+fn gt() -> Func {
+    numeric_binop(
+        "gt",
+        ">",
+        "comparison_error",
+        wat! {
+            (call $to_bool
+                (i64_gt_s (call $integer_to_i64 (local_get $a))
+                          (call $integer_to_i64 (local_get $b))))
+        },
+        wat! {
+            (call $to_bool
+                (f64_gt (call $number_to_f64 (local_get $a))
+                        (call $number_to_f64 (local_get $b))))
+        },
+    )
 }
 
-fn to_bool() -> Func {
-    wat! {
-        (func $to_bool
-            (param $b i32)
-            (result (ref i31))
-            (ref_i31
-                (if (result i32)
-                    (local_get $b)
-                    (then (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64)))
-                    (else (const_i32 ,(Unitype::FALSE_BIT_PATTERN as i64))))))
-    }
+fn le() -> Func {
+    numeric_binop(
+        "le",
+        "<=",
+        "comparison_error",
+        wat! {
+            (call $to_bool
+                (i32_eqz (i64_gt_s (call $integer_to_i64 (local_get $a))
+                                   (call $integer_to_i64 (local_get $b)))))
+        },
+        wat! {
+            (call $to_bool
+                (f64_le (call $number_to_f64 (local_get $a))
+                        (call $number_to_f64 (local_get $b))))
+        },
+    )
 }
 
-fn from_bool() -> Func {
-    wat! {
-        (func $from_bool
-            (param $b (ref eq))
-            (result i32)
-            (ref_eq (ref_cast (ref i31) (local_get $b))
-                    (ref_i31 (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64)))))
-    }
+fn ge() -> Func {
+    numeric_binop(
+        "ge",
+        ">=",
+        "comparison_error",
+        wat! {
+            (call $to_bool
+                (i32_eqz (i64_lt_s (call $integer_to_i64 (local_get $a))
+                                   (call $integer_to_i64 (local_get $b)))))
+        },
+        wat! {
+            (call $to_bool
+                (f64_ge (call $number_to_f64 (local_get $a))
+                        (call $number_to_f64 (local_get $b))))
+        },
+    )
 }
 
-fn negate() -> Func {
+/// `==` compares Numerics by value and Strings by content.
+/// An `$obj`'s own `==` is called. Anything else, including Symbols, is compared by identity.
+fn eq_eq() -> Func {
+    let body = [
+        call_operator_method("==", wat! { (local_get $b) }),
+        wat! {
+            (if (i32_and (call $is_integer (local_get $a))
+                         (call $is_integer (local_get $b)))
+                (then
+                    (return
+                        (call $to_bool
+                            (i64_eq (call $integer_to_i64 (local_get $a))
+                                    (call $integer_to_i64 (local_get $b)))))))
+            (if (i32_and (call $is_numeric (local_get $a))
+                         (call $is_numeric (local_get $b)))
+                (then
+                    (return
+                        (call $to_bool
+                            (f64_eq (call $number_to_f64 (local_get $a))
+                                    (call $number_to_f64 (local_get $b)))))))
+            (if (i32_and (ref_test (ref $str) (local_get $a))
+                         (ref_test (ref $str) (local_get $b)))
+                (then
+                    (return
+                        (call $to_bool
+                            (call $str_eq (ref_cast (ref $str) (local_get $a))
+                                          (ref_cast (ref $str) (local_get $b)))))))
+            (call $to_bool (ref_eq (local_get $a) (local_get $b)))
+        },
+    ]
+    .concat();
     wat! {
-        (func $negate
-            (param $n (ref eq))
+        (func $eq_eq
+            (param $a (ref eq))
+            (param $b (ref eq))
             (result (ref eq))
-            (call $i64_to_integer ,(vec![i64_neg(wat![ (call $integer_to_i64 (local_get $n)) ])])))
+            ,(body))
     }
 }
 
-fn and() -> Func {
+/// `!=` is the opposite of `==`.
+fn not_eq() -> Func {
     wat! {
-        (func $and
+        (func $not_eq
             (param $a (ref eq))
             (param $b (ref eq))
             (result (ref eq))
 
-            (ref_i31
-                (i32_and (i31_get_u (ref_cast (ref i31) (local_get $a)))
-                         (i31_get_u (ref_cast (ref i31) (local_get $b))))))
+            (call $to_bool
+                (i32_eqz (call $from_bool (call $eq_eq (local_get $a) (local_get $b))))))
     }
 }
 
-fn or() -> Func {
+/// `<=>` gives `-1`, `0` or `1` for Numerics, or `nil` if one is NaN, and for Strings.
+/// An `$obj`'s own `<=>` is called.
+/// Anything else is `0` if it's `==`, or else `nil`, like `Object#<=>`.
+fn cmp() -> Func {
+    let body = [
+        call_operator_method("<=>", wat! { (local_get $b) }),
+        wat! {
+            (if (i32_and (ref_test (ref $str) (local_get $a))
+                         (ref_test (ref $str) (local_get $b)))
+                (then
+                    (return
+                        (call $i64_to_integer
+                            (i64_extend_i32_s
+                                (call $str_cmp (ref_cast (ref $str) (local_get $a))
+                                               (ref_cast (ref $str) (local_get $b))))))))
+            (if (i32_and (call $is_integer (local_get $a))
+                         (call $is_integer (local_get $b)))
+                (then
+                    (return
+                        (call $i64_to_integer
+                            (i64_extend_i32_s
+                                (i32_sub
+                                    (i64_gt_s (call $integer_to_i64 (local_get $a))
+                                              (call $integer_to_i64 (local_get $b)))
+                                    (i64_lt_s (call $integer_to_i64 (local_get $a))
+                                              (call $integer_to_i64 (local_get $b)))))))))
+            (if (i32_and (call $is_numeric (local_get $a))
+                         (call $is_numeric (local_get $b)))
+                (then
+                    (local_set $x (call $number_to_f64 (local_get $a)))
+                    (local_set $y (call $number_to_f64 (local_get $b)))
+                    (if (i32_or (i32_or (f64_lt (local_get $x) (local_get $y))
+                                        (f64_gt (local_get $x) (local_get $y)))
+                                (f64_eq (local_get $x) (local_get $y)))
+                        (then
+                            (return
+                                (call $i64_to_integer
+                                    (i64_extend_i32_s
+                                        (i32_sub (f64_gt (local_get $x) (local_get $y))
+                                                 (f64_lt (local_get $x) (local_get $y))))))))
+                    (return (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))))
+            (if (result (ref eq))
+                (call $from_bool (call $eq_eq (local_get $a) (local_get $b)))
+                (then (call $i64_to_integer (const_i64 0)))
+                (else (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))))
+        },
+    ]
+    .concat();
     wat! {
-        (func $or
+        (func $cmp
             (param $a (ref eq))
             (param $b (ref eq))
             (result (ref eq))
+            (local $x f64)
+            (local $y f64)
+            ,(body))
+    }
+}
 
-            (ref_i31
-                (i32_or (i31_get_u (ref_cast (ref i31) (local_get $a)))
-                        (i31_get_u (ref_cast (ref i31) (local_get $b))))))
+/// `!` is `true` for `nil` and `false`, and `false` for any other builtin value.
+/// It's the method `!` of an `$obj`, which is `Object#!` unless it defines its own.
+fn not() -> Func {
+    let body = [
+        call_operator_method("!", vec![]),
+        wat! {
+            (call $to_bool (i32_eqz (call $truthy (local_get $a))))
+        },
+    ]
+    .concat();
+    wat! {
+        (func $not
+            (param $a (ref eq))
+            (result (ref eq))
+            ,(body))
     }
 }
 
-fn lt() -> Func {
+/// A bitwise operation on two Integers, or the `$obj` `$a`'s method `operator`.
+/// `res` computes an `i64` from `$x` and `$y`, the operands as `i64`s.
+/// A non-Integer `$b` raises a `TypeError`, like `1 & 1.5`.
+fn integer_binop(name: &str, operator: &str, res: Vec<Instr>) -> Func {
+    let body = [
+        call_operator_method(operator, wat! { (local_get $b) }),
+        check_operands(operator, "is_integer", "coerce_error"),
+        wat! {
+            (local_set $x (call $integer_to_i64 (local_get $a)))
+            (local_set $y (call $integer_to_i64 (local_get $b)))
+            (call $i64_to_integer ,(res))
+        },
+    ]
+    .concat();
     wat! {
-        (func $lt
+        (func ,(name.to_string())
             (param $a (ref eq))
             (param $b (ref eq))
             (result (ref eq))
-
-            (call $to_bool
-                (i64_lt_s (call $integer_to_i64 (local_get $a))
-                          (call $integer_to_i64 (local_get $b)))))
+            (local $x i64)
+            (local $y i64)
+            ,(body))
     }
 }
 
-fn gt() -> Func {
+fn bit_and() -> Func {
+    integer_binop(
+        "bit_and",
+        "&",
+        wat! { (i64_and (local_get $x) (local_get $y)) },
+    )
+}
+
+fn bit_or() -> Func {
+    integer_binop(
+        "bit_or",
+        "|",
+        wat! { (i64_or (local_get $x) (local_get $y)) },
+    )
+}
+
+fn bit_xor() -> Func {
+    integer_binop(
+        "bit_xor",
+        "^",
+        wat! { (i64_xor (local_get $x) (local_get $y)) },
+    )
+}
+
+/// `<<` shifts an Integer left, or appends to an Array.
+/// Like `push`, appending gives a new Array.
+fn shl() -> Func {
+    let body = [
+        call_operator_method("<<", wat! { (local_get $b) }),
+        wat! {
+            (if (ref_test (ref $arr_unitype) (local_get $a))
+                (then
+                    (return
+                        (call $push (ref_cast (ref $arr_unitype) (local_get $a))
+                                    (local_get $b)))))
+        },
+        check_operands("<<", "is_integer", "coerce_error"),
+        wat! {
+            (call $i64_to_integer
+                (call $i64_shift_left (call $integer_to_i64 (local_get $a))
+                                      (call $integer_to_i64 (local_get $b))))
+        },
+    ]
+    .concat();
     wat! {
-        (func $gt
+        (func $shl
             (param $a (ref eq))
             (param $b (ref eq))
             (result (ref eq))
+            ,(body))
+    }
+}
 
-            (call $to_bool
-                (i64_gt_s (call $integer_to_i64 (local_get $a))
-                          (call $integer_to_i64 (local_get $b)))))
+/// `a >> b` is `a << -b`.
+fn shr() -> Func {
+    integer_binop(
+        "shr",
+        ">>",
+        wat! { (call $i64_shift_left (local_get $x) (i64_sub (const_i64 0) (local_get $y))) },
+    )
+}
+
+/// Shifts `$a` left by `$b`, or right by `-$b` if it's negative.
+/// Unlike Wasm's shifts, which take the amount modulo 64, shifting right by 64 or more
+///     shifts every bit out: `0`, or `-1` for a negative `$a`.
+/// Raises a `RangeError` if shifting left loses bits, which would need a Bignum.
+fn i64_shift_left() -> Func {
+    wat! {
+        (func $i64_shift_left
+            (param $a i64)
+            (param $b i64)
+            (result i64)
+            (local $res i64)
+
+            (if (i64_lt_s (local_get $b) (const_i64 0))
+                (then
+                    (if (i64_lt_s (local_get $b) (const_i64 ,(-63)))
+                        (then (local_set $b (const_i64 ,(-63)))))
+                    (return (i64_shr_s (local_get $a) (i64_sub (const_i64 0) (local_get $b))))))
+            (if (i64_eqz (local_get $a))
+                (then (return (const_i64 0))))
+            (if (i64_gt_s (local_get $b) (const_i64 63))
+                (then ,(throw_integer_overflow())))
+            (local_set $res (i64_shl (local_get $a) (local_get $b)))
+            (if (i32_eqz (i64_eq (i64_shr_s (local_get $res) (local_get $b))
+                                 (local_get $a)))
+                (then ,(throw_integer_overflow())))
+            (local_get $res))
     }
 }
 
-/// `==` for Integers only.
-/// Assumes that all inputs are Integers.
-fn eq_eq() -> Func {
+/// Raise a `RangeError` for an Integer result that doesn't fit in an `i64`.
+/// TODO: Bignums.
+fn throw_integer_overflow() -> Vec<Instr> {
     wat! {
-        (func $eq_eq
+        (throw $ruby_exception
+            (call $new_exception
+                (global_get ,(class::range_error().identifier()))
+                (global_get ,(string_identifier("integer too big; Integers are 64-bit")))))
+    }
+}
+
+/// `~n` flips every bit, so it's `-n - 1`. An `$obj`'s own `~` is called.
+fn bit_not() -> Func {
+    let body = [
+        call_operator_method("~", vec![]),
+        check_receiver("~", "is_integer"),
+        wat! {
+            (call $i64_to_integer
+                (i64_xor (call $integer_to_i64 (local_get $a)) (const_i64 ,(-1))))
+        },
+    ]
+    .concat();
+    wat! {
+        (func $bit_not
             (param $a (ref eq))
-            (param $b (ref eq))
             (result (ref eq))
-
-            (call $to_bool
-                (i64_eq (call $integer_to_i64 (local_get $a))
-                        (call $integer_to_i64 (local_get $b)))))
+            ,(body))
     }
 }
 
@@ -617,3 +2860,612 @@ fn push() -> Func {
             (local_get $new_arr))
     }
 }
+
+/// `str_concat : (ref $str) (ref $str) -> (ref $str)`
+/// Returns a new string; neither argument is modified.
+fn str_concat() -> Func {
+    wat! {
+        (func $str_concat
+            (param $a (ref $str))
+            (param $b (ref $str))
+            (result (ref $str))
+            (local $res (ref $str))
+
+            (local_set $res
+                (array_new $str
+                    (const_i32 0)
+                    (i32_add (array_len (local_get $a))
+                             (array_len (local_get $b)))))
+            (array_copy $str $str
+                (local_get $res)
+                (const_i32 0)
+                (local_get $a)
+                (const_i32 0)
+                (array_len (local_get $a)))
+            (array_copy $str $str
+                (local_get $res)
+                (array_len (local_get $a))
+                (local_get $b)
+                (const_i32 0)
+                (array_len (local_get $b)))
+            (local_get $res))
+    }
+}
+
+/// `i64_to_str : i64 -> (ref $str)`
+/// Decimal representation of `n`.
+fn i64_to_str() -> Func {
+    // The longest i64 is `-9223372036854775808`, 20 chars.
+    // Digits are written right-to-left into a buffer,
+    //  then copied out.
+    // We work with `n <= 0` so that `i64::MIN` doesn't overflow.
+    wat! {
+        (func $i64_to_str
+            (param $n i64)
+            (result (ref $str))
+            (local $is_neg i32)
+            (local $buf (ref $str))
+            (local $idx i32)
+            (local $len i32)
+            (local $res (ref $str))
+
+            (local_set $is_neg (i64_lt_s (local_get $n) (const_i64 0)))
+            (if (i32_eqz (local_get $is_neg))
+                (then (local_set $n (i64_sub (const_i64 0) (local_get $n)))))
+            (local_set $buf (array_new $str (const_i32 0) (const_i32 20)))
+            (local_set $idx (const_i32 20))
+            (loop $digits
+                (local_set $idx (i32_sub (local_get $idx) (const_i32 1)))
+                // '0' - (n % 10)
+                (array_set $str
+                    (local_get $buf)
+                    (local_get $idx)
+                    (i32_sub (const_i32 48)
+                             (i32_wrap_i64 (i64_rem_s (local_get $n) (const_i64 10)))))
+                (local_set $n (i64_div_s (local_get $n) (const_i64 10)))
+                (br_if $digits (i32_eqz (i64_eqz (local_get $n)))))
+            (if (local_get $is_neg)
+                (then
+                    (local_set $idx (i32_sub (local_get $idx) (const_i32 1)))
+                    // '-'
+                    (array_set $str (local_get $buf) (local_get $idx) (const_i32 45))))
+            (local_set $len (i32_sub (const_i32 20) (local_get $idx)))
+            (local_set $res (array_new $str (const_i32 0) (local_get $len)))
+            (array_copy $str $str
+                (local_get $res)
+                (const_i32 0)
+                (local_get $buf)
+                (local_get $idx)
+                (local_get $len))
+            (local_get $res))
+    }
+}
+
+/// `to_s : Unitype -> (ref $str)`
+/// Strings are returned as-is, objects are sent `to_s`,
+///  and everything else uses its `inspect` representation.
+fn to_s() -> Func {
+    wat! {
+        (func $to_s
+            (param $x (ref eq))
+            (result (ref $str))
+
+            (if (call $is_nil (local_get $x))
+                (then (return (global_get ,(string_identifier(""))))))
+            (if (ref_test (ref $str) (local_get $x))
+                (then (return (ref_cast (ref $str) (local_get $x)))))
+            (if (ref_test (ref $sym) (local_get $x))
+                (then (return (struct_get $sym $name (ref_cast (ref $sym) (local_get $x))))))
+            (if (ref_test (ref $obj) (local_get $x))
+                (then
+                    (return
+                        (ref_cast (ref $str)
+                            (call $call
+                                (local_get $x)
+                                (global_get ,(symbol_identifier("to_s")))
+                                (global_get $empty_args)
+                                (ref_null $proc))))))
+            (call $inspect (local_get $x)))
+    }
+}
+
+/// `str_slice : (ref $str) i32 i32 -> (ref $str)`
+/// A copy of the `$len` bytes of `$s` from `$start`.
+fn str_slice() -> Func {
+    wat! {
+        (func $str_slice
+            (param $s (ref $str))
+            (param $start i32)
+            (param $len i32)
+            (result (ref $str))
+            (local $res (ref $str))
+
+            (local_set $res (array_new $str (const_i32 0) (local_get $len)))
+            (array_copy $str $str
+                (local_get $res)
+                (const_i32 0)
+                (local_get $s)
+                (local_get $start)
+                (local_get $len))
+            (local_get $res))
+    }
+}
+
+/// How close, relative to the half gap between `f64`s, digits have to be to halfway to be taken as halfway.
+/// The double-double error is around `2^-47` of it.
+const TIE_TOLERANCE: f64 = 1.0 / (1u64 << 44) as f64;
+
+/// `0.1` as a double-double: the `f64` nearest it, and the `f64` nearest the difference.
+const TENTH_LO: f64 = -5.551115123125783e-18;
+
+/// `f64_to_str : f64 -> (ref $str)`
+/// Formats `$x` like Ruby's `Float#to_s`: the fewest digits that read back as `$x`,
+///     in fixed-point notation if the decimal point is within 16 digits of them,
+///     else in scientific notation with a signed exponent of at least 2 digits.
+/// `$x` is scaled to 17 digits before the point in double-double arithmetic.
+/// Then the digits are cut to each precision in turn, until they're closer to `$x`
+///     than half the gap to the next `f64` on that side.
+/// That's exact but for digits within about `2^-44` of a gap of halfway between two `f64`s,
+///     which are taken to be exactly halfway.
+fn f64_to_str() -> Func {
+    let body = [
+        wat! {
+            (if (i32_eqz (f64_eq (local_get $x) (local_get $x)))
+                (then (return (global_get ,(string_identifier("NaN"))))))
+            // Negative, including `-0.0`.
+            (if (i64_lt_s (i64_reinterpret_f64 (local_get $x)) (const_i64 0))
+                (then
+                    (return
+                        (call $str_concat
+                            (global_get ,(string_identifier("-")))
+                            (call $f64_to_str (f64_neg (local_get $x)))))))
+            (if (f64_eq (local_get $x) (const_f64 ,(f64::INFINITY)))
+                (then (return (global_get ,(string_identifier("Infinity"))))))
+            (if (f64_eq (local_get $x) (const_f64 0.0))
+                (then (return (global_get ,(string_identifier("0.0"))))))
+
+            (local_set $bits (i64_reinterpret_f64 (local_get $x)))
+            // The binary exponent, scaling subnormals up so the exponent bits give it.
+            (local_set $n (local_get $bits))
+            (local_set $k (const_i64 1023))
+            (if (i64_eqz (i64_shr_u (local_get $bits) (const_i64 52)))
+                (then
+                    (local_set $n (i64_reinterpret_f64 (f64_mul (local_get $x)
+                                                                (const_f64 18014398509481984.0))))
+                    (local_set $k (const_i64 ,(1023 + 54)))))
+            // A guess at the decimal exponent, which the scaling below corrects.
+            (local_set $exp
+                (i64_trunc_f64_s
+                    (f64_floor
+                        (f64_mul (f64_convert_i64_s (i64_sub (i64_shr_u (local_get $n) (const_i64 52))
+                                                             (local_get $k)))
+                                 (const_f64 ,(std::f64::consts::LOG10_2))))))
+            // The gaps to the neighbouring `f64`s, which are exact.
+            // The largest `f64` has no next one, but the gap to it would be the same as the gap below.
+            (local_set $gap_down (f64_sub (local_get $x)
+                                          (f64_reinterpret_i64 (i64_sub (local_get $bits) (const_i64 1)))))
+            (local_set $gap_up (f64_sub (f64_reinterpret_i64 (i64_add (local_get $bits) (const_i64 1)))
+                                        (local_get $x)))
+            (if (f64_eq (local_get $gap_up) (const_f64 ,(f64::INFINITY)))
+                (then (local_set $gap_up (local_get $gap_down))))
+
+            // `s = $x * 10^(16 - $exp)`, so `$exp` is right when `10^16 <= s < 10^17`.
+            // The power of 10 can overflow, or be subnormal, when `s` isn't, so it's applied in halves.
+            // Exact powers of 2 keep the double-double products away from overflow and underflow.
+            (loop $scale
+                (local_set $s_hi (local_get $x))
+                (local_set $post (const_f64 1.0))
+                (if (f64_gt (local_get $x) (const_f64 ,(2f64.powi(900))))
+                    (then
+                        (local_set $s_hi (f64_mul (local_get $x) (const_f64 ,(2f64.powi(-100)))))
+                        (local_set $post (const_f64 ,(2f64.powi(100))))))
+                (if (f64_lt (local_get $x) (const_f64 ,(2f64.powi(-900))))
+                    (then
+                        (local_set $s_hi (f64_mul (local_get $x) (const_f64 ,(2f64.powi(100)))))
+                        (local_set $post (const_f64 ,(2f64.powi(-100))))))
+                (local_set $s_lo (const_f64 0.0))
+                (local_set $k (i64_div_s (i64_sub (const_i64 16) (local_get $exp)) (const_i64 2)))
+                (block $scale_low ,(mul_pow10_dd()))
+                (local_set $k (i64_sub (i64_sub (const_i64 16) (local_get $exp)) (local_get $k)))
+                (block $scale_high ,(mul_pow10_dd()))
+                (local_set $s_hi (f64_mul (local_get $s_hi) (local_get $post)))
+                (local_set $s_lo (f64_mul (local_get $s_lo) (local_get $post)))
+                (if (f64_ge (local_get $s_hi) (const_f64 ,(1e17)))
+                    (then
+                        (local_set $exp (i64_add (local_get $exp) (const_i64 1)))
+                        (br $scale)))
+                (if (f64_lt (local_get $s_hi) (const_f64 ,(1e16)))
+                    (then
+                        (local_set $exp (i64_sub (local_get $exp) (const_i64 1)))
+                        (br $scale))))
+            // Half the gaps, scaled like `s`.
+            (local_set $gap_down (f64_mul (f64_mul (local_get $s_hi) (const_f64 0.5))
+                                          (f64_div (local_get $gap_down) (local_get $x))))
+            (local_set $gap_up (f64_mul (f64_mul (local_get $s_hi) (const_f64 0.5))
+                                        (f64_div (local_get $gap_up) (local_get $x))))
+
+            (local_set $prec (const_i64 1))
+            (block $found
+                (loop $shorten
+                    // `q = s / d` has `$prec` digits before the point.
+                    (local_set $d (call $f64_pow (const_f64 10.0)
+                                                 (i64_sub (const_i64 17) (local_get $prec))))
+                    (local_set $q_hi (f64_div (local_get $s_hi) (local_get $d)))
+                    (block $split ,(helpers::f64_two_prod("q_hi", "d", "t_hi", "t_lo")))
+                    (local_set $q_lo
+                        (f64_div (f64_add (f64_sub (f64_sub (local_get $s_hi) (local_get $t_hi))
+                                                   (local_get $t_lo))
+                                          (local_get $s_lo))
+                                 (local_get $d)))
+                    // `$n` is `q` rounded, and `$diff` is `q - $n`.
+                    // `$q_hi` is rounded first, and then `$q_lo`, which is only past `0.5` at 17 digits.
+                    (local_set $digits (f64_floor (f64_add (local_get $q_hi) (const_f64 0.5))))
+                    (local_set $diff (f64_add (f64_sub (local_get $q_hi) (local_get $digits))
+                                              (local_get $q_lo)))
+                    (local_set $t_hi (f64_floor (f64_add (local_get $diff) (const_f64 0.5))))
+                    (local_set $diff (f64_sub (local_get $diff) (local_get $t_hi)))
+                    (local_set $n (i64_add (i64_trunc_f64_s (local_get $digits))
+                                           (i64_trunc_f64_s (local_get $t_hi))))
+                    // 17 digits always read back.
+                    (br_if $found (i64_eq (local_get $prec) (const_i64 17)))
+                    // `$t_hi` is how far the digits are from `s`, and `$t_lo` is the half gap on that side.
+                    (local_set $t_hi (f64_mul (local_get $diff) (local_get $d)))
+                    (local_set $t_lo (local_get $gap_down))
+                    (if (f64_lt (local_get $t_hi) (const_f64 0.0))
+                        (then
+                            (local_set $t_hi (f64_neg (local_get $t_hi)))
+                            (local_set $t_lo (local_get $gap_up))))
+                    (br_if $found ,(within_half_gap()))
+                    // Below a power of 2, the gap is half the one above,
+                    //     so the digits one further away, on the other side, can still be closer than its half.
+                    (if (f64_lt (local_get $diff) (const_f64 0.0))
+                        (then
+                            (local_set $n (i64_sub (local_get $n) (const_i64 1)))
+                            (local_set $t_hi (f64_mul (f64_add (const_f64 1.0) (local_get $diff))
+                                                      (local_get $d)))
+                            (local_set $t_lo (local_get $gap_down)))
+                        (else
+                            (local_set $n (i64_add (local_get $n) (const_i64 1)))
+                            (local_set $t_hi (f64_mul (f64_sub (const_f64 1.0) (local_get $diff))
+                                                      (local_get $d)))
+                            (local_set $t_lo (local_get $gap_up))))
+                    (br_if $found ,(within_half_gap()))
+                    (local_set $prec (i64_add (local_get $prec) (const_i64 1)))
+                    (br $shorten)))
+
+            // Rounding up to `10^$prec` carries into the next decimal place.
+            (if (i64_eq (local_get $n) (call $i64_pow (const_i64 10) (local_get $prec)))
+                (then (local_set $exp (i64_add (local_get $exp) (const_i64 1)))))
+            (block $stripped
+                (loop $strip
+                    (br_if $stripped (i32_eqz (i64_eqz (i64_rem_s (local_get $n) (const_i64 10)))))
+                    (local_set $n (i64_div_s (local_get $n) (const_i64 10)))
+                    (br $strip)))
+            (local_set $str (call $i64_to_str (local_get $n)))
+            (local_set $len (array_len (local_get $str)))
+            // Where the decimal point goes, counting from the start of the digits.
+            (local_set $point (i32_add (i32_wrap_i64 (local_get $exp)) (const_i32 1)))
+
+            (if (i32_and (i32_gt_s (local_get $point) (const_i32 0))
+                         (i32_lt_s (local_get $point) (const_i32 17)))
+                (then
+                    (if (i32_eqz (i32_gt_s (local_get $len) (local_get $point)))
+                        (then
+                            (return
+                                (call $str_concat
+                                    (call $str_concat
+                                        (local_get $str)
+                                        (array_new $str (const_i32 48)
+                                                        (i32_sub (local_get $point) (local_get $len))))
+                                    (global_get ,(string_identifier(".0")))))))
+                    (return
+                        (call $str_concat
+                            (call $str_concat
+                                (call $str_slice (local_get $str) (const_i32 0) (local_get $point))
+                                (global_get ,(string_identifier("."))))
+                            (call $str_slice (local_get $str)
+                                             (local_get $point)
+                                             (i32_sub (local_get $len) (local_get $point)))))))
+            (if (i32_and (i32_gt_s (local_get $point) (const_i32 ,(-4)))
+                         (i32_lt_s (local_get $point) (const_i32 1)))
+                (then
+                    (return
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("0.")))
+                                (array_new $str (const_i32 48)
+                                                (i32_sub (const_i32 0) (local_get $point))))
+                            (local_get $str)))))
+
+            (local_set $mantissa
+                (call $str_concat
+                    (call $str_concat
+                        (call $str_slice (local_get $str) (const_i32 0) (const_i32 1))
+                        (global_get ,(string_identifier("."))))
+                    (if (result (ref $str))
+                        (i32_gt_s (local_get $len) (const_i32 1))
+                        (then (call $str_slice (local_get $str)
+                                               (const_i32 1)
+                                               (i32_sub (local_get $len) (const_i32 1))))
+                        (else (global_get ,(string_identifier("0")))))))
+            (local_set $exp_sign (global_get ,(string_identifier("e+"))))
+            (if (i64_lt_s (local_get $exp) (const_i64 0))
+                (then
+                    (local_set $exp_sign (global_get ,(string_identifier("e-"))))
+                    (local_set $exp (i64_sub (const_i64 0) (local_get $exp)))))
+            (local_set $str (call $i64_to_str (local_get $exp)))
+            (if (i64_lt_s (local_get $exp) (const_i64 10))
+                (then
+                    (local_set $str (call $str_concat (global_get ,(string_identifier("0")))
+                                                      (local_get $str)))))
+            (call $str_concat
+                (call $str_concat (local_get $mantissa) (local_get $exp_sign))
+                (local_get $str))
+        },
+    ]
+    .concat();
+    wat! {
+        (func $f64_to_str
+            (param $x f64)
+            (result (ref $str))
+            (local $bits i64) (local $n i64) (local $k i64) (local $exp i64) (local $prec i64)
+            (local $gap_down f64) (local $gap_up f64) (local $s_hi f64) (local $s_lo f64)
+            (local $p_hi f64) (local $p_lo f64) (local $sq_hi f64) (local $sq_lo f64)
+            (local $t_hi f64) (local $t_lo f64) (local $q_hi f64) (local $q_lo f64)
+            (local $d f64) (local $digits f64) (local $diff f64) (local $post f64)
+            (local $dd_tmp f64) (local $a_hi f64) (local $a_lo f64) (local $b_hi f64) (local $b_lo f64)
+            (local $str (ref $str)) (local $len i32) (local $point i32)
+            (local $mantissa (ref $str)) (local $exp_sign (ref $str))
+            ,(body))
+    }
+}
+
+/// Are digits `$t_hi` from `s` close enough to read back as `$x`, with the half gap `$t_lo` on their side?
+/// Halfway reads back as `$x` if its last bit is 0, as Wasm rounds ties to even.
+/// Anything within `TIE_TOLERANCE` of halfway is taken to be exactly halfway.
+fn within_half_gap() -> Vec<Instr> {
+    wat! {
+        (i32_or
+            (f64_lt (local_get $t_hi)
+                    (f64_mul (local_get $t_lo) (const_f64 ,(1.0 - TIE_TOLERANCE))))
+            (i32_and (i64_eqz (i64_and (local_get $bits) (const_i64 1)))
+                     (f64_lt (local_get $t_hi)
+                             (f64_mul (local_get $t_lo) (const_f64 ,(1.0 + TIE_TOLERANCE))))))
+    }
+}
+
+/// Multiplies `$s_hi + $s_lo` by `10^$k`, for `|$k| <= 200`,
+///     with the power of 10 worked out in double-double by squaring `10` or `0.1`.
+fn mul_pow10_dd() -> Vec<Instr> {
+    [
+        wat! {
+            (if (i64_lt_s (local_get $k) (const_i64 0))
+                (then
+                    (local_set $sq_hi (const_f64 0.1))
+                    (local_set $sq_lo (const_f64 ,(TENTH_LO)))
+                    (local_set $n (i64_sub (const_i64 0) (local_get $k))))
+                (else
+                    (local_set $sq_hi (const_f64 10.0))
+                    (local_set $sq_lo (const_f64 0.0))
+                    (local_set $n (local_get $k))))
+            (local_set $p_hi (const_f64 1.0))
+            (local_set $p_lo (const_f64 0.0))
+            (block $powered
+                (loop $square
+                    (br_if $powered (i64_eqz (local_get $n)))
+                    (if (i32_wrap_i64 (i64_and (local_get $n) (const_i64 1)))
+                        (then
+                            (block $mul ,(helpers::f64_dd_mul("p_hi", "p_lo", "sq_hi", "sq_lo", "t_hi", "t_lo")))
+                            (local_set $p_hi (local_get $t_hi))
+                            (local_set $p_lo (local_get $t_lo))))
+                    (local_set $n (i64_shr_s (local_get $n) (const_i64 1)))
+                    (br_if $powered (i64_eqz (local_get $n)))
+                    (block $mul ,(helpers::f64_dd_mul("sq_hi", "sq_lo", "sq_hi", "sq_lo", "t_hi", "t_lo")))
+                    (local_set $sq_hi (local_get $t_hi))
+                    (local_set $sq_lo (local_get $t_lo))
+                    (br $square)))
+        },
+        helpers::f64_dd_mul("s_hi", "s_lo", "p_hi", "p_lo", "t_hi", "t_lo"),
+        wat! {
+            (local_set $s_hi (local_get $t_hi))
+            (local_set $s_lo (local_get $t_lo))
+        },
+    ]
+    .concat()
+}
+
+/// `inspect : Unitype -> (ref $str)`
+/// Objects are described by their `inspect` method.
+fn inspect() -> Func {
+    wat! {
+        (func $inspect
+            (export ,(INSPECT.to_string()))
+            (param $x (ref eq))
+            (result (ref $str))
+
+            (if (call $is_nil (local_get $x))
+                (then (return (global_get ,(string_identifier("nil"))))))
+            (if (ref_eq (local_get $x) (ref_i31 (const_i32 ,(Unitype::TRUE_BIT_PATTERN as i64))))
+                (then (return (global_get ,(string_identifier("true"))))))
+            (if (ref_eq (local_get $x) (ref_i31 (const_i32 ,(Unitype::FALSE_BIT_PATTERN as i64))))
+                (then (return (global_get ,(string_identifier("false"))))))
+            (if (call $is_integer (local_get $x))
+                (then (return (call $i64_to_str (call $integer_to_i64 (local_get $x))))))
+            (if (ref_test (ref $str) (local_get $x))
+                (then
+                    (return
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("\"")))
+                                (ref_cast (ref $str) (local_get $x)))
+                            (global_get ,(string_identifier("\"")))))))
+            // TODO: Quote symbols which aren't valid identifiers, `:"foo bar"`
+            (if (ref_test (ref $sym) (local_get $x))
+                (then
+                    (return
+                        (call $str_concat
+                            (global_get ,(string_identifier(":")))
+                            (struct_get $sym $name (ref_cast (ref $sym) (local_get $x)))))))
+            (if (ref_test (ref $arr_unitype) (local_get $x))
+                (then
+                    (return
+                        (call $arr_inspect (ref_cast (ref $arr_unitype) (local_get $x))))))
+            (if (call $is_float (local_get $x))
+                (then (return (call $f64_to_str (call $number_to_f64 (local_get $x))))))
+            (ref_cast (ref $str)
+                (call $call
+                    (local_get $x)
+                    (global_get ,(symbol_identifier("inspect")))
+                    (global_get $empty_args)
+                    (ref_null $proc))))
+    }
+}
+
+/// `[a, b, ...]`, with each element `inspect`ed.
+fn arr_inspect() -> Func {
+    wat! {
+        (func $arr_inspect
+            (param $arr (ref $arr_unitype))
+            (result (ref $str))
+            (local $res (ref $str))
+            (local $idx i32)
+
+            (local_set $res (global_get ,(string_identifier("["))))
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (local_get $arr))))
+                    (if (local_get $idx)
+                        (then
+                            (local_set $res
+                                (call $str_concat
+                                    (local_get $res)
+                                    (global_get ,(string_identifier(", ")))))))
+                    (local_set $res
+                        (call $str_concat
+                            (local_get $res)
+                            (call $inspect
+                                (array_get $arr_unitype (local_get $arr) (local_get $idx)))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 1)))
+                    (br $for)))
+            (call $str_concat
+                (local_get $res)
+                (global_get ,(string_identifier("]")))))
+    }
+}
+
+/// The index in `$ivars` of the instance variable named `$name`, or -1.
+/// See `$obj`.
+fn ivar_index() -> Func {
+    wat! {
+        (func $ivar_index
+            (param $ivars (ref $arr_unitype))
+            (param $name (ref $sym))
+            (result i32)
+            (local $idx i32)
+
+            (local_set $idx (const_i32 0))
+            (block $exit
+                (loop $for
+                    (br_if $exit (i32_eq (local_get $idx)
+                                         (array_len (local_get $ivars))))
+                    (if (ref_eq (array_get $arr_unitype (local_get $ivars) (local_get $idx))
+                                (local_get $name))
+                        (then (return (local_get $idx))))
+                    (local_set $idx (i32_add (local_get $idx)
+                                             (const_i32 2)))
+                    (br $for)))
+            (const_i32 ,(-1)))
+    }
+}
+
+/// `$val`, the value of the constant named `$name`,
+///     raising a `NameError` if it's null, since the constant hasn't been assigned.
+fn constant_get() -> Func {
+    wat! {
+        (func $constant_get
+            (param $val (ref null eq))
+            (param $name (ref $str))
+            (result (ref eq))
+
+            (if (i32_eqz (ref_test (ref eq) (local_get $val)))
+                (then
+                    (throw $ruby_exception
+                        (call $new_exception
+                            (global_get ,(class::name_error().identifier()))
+                            (call $str_concat
+                                (global_get ,(string_identifier("uninitialized constant ")))
+                                (local_get $name))))))
+            (ref_as_non_null (local_get $val)))
+    }
+}
+
+/// `@name` of `$self`, or `nil` if it hasn't been set.
+/// Values which aren't `$obj`s have no instance variables.
+fn ivar_get() -> Func {
+    wat! {
+        (func $ivar_get
+            (param $self (ref eq))
+            (param $name (ref $sym))
+            (result (ref eq))
+            (local $ivars (ref $arr_unitype))
+            (local $idx i32)
+
+            (if (i32_eqz (ref_test (ref $obj) (local_get $self)))
+                (then (return (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))))
+            (local_set $ivars (struct_get $obj $ivars (ref_cast (ref $obj) (local_get $self))))
+            (local_set $idx (call $ivar_index (local_get $ivars) (local_get $name)))
+            (if (result (ref eq))
+                (i32_lt_s (local_get $idx) (const_i32 0))
+                (then (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+                (else
+                    (array_get $arr_unitype
+                        (local_get $ivars)
+                        (i32_add (local_get $idx) (const_i32 1))))))
+    }
+}
+
+/// `@name = $val` on `$self`, adding the instance variable if it hasn't been set.
+/// Evaluates to `$val`, like the assignment.
+/// Values which aren't `$obj`s are frozen, so raise a `FrozenError`.
+fn ivar_set() -> Func {
+    wat! {
+        (func $ivar_set
+            (param $self (ref eq))
+            (param $name (ref $sym))
+            (param $val (ref eq))
+            (result (ref eq))
+            (local $obj (ref $obj))
+            (local $ivars (ref $arr_unitype))
+            (local $idx i32)
+
+            (if (i32_eqz (ref_test (ref $obj) (local_get $self)))
+                (then
+                    (throw $ruby_exception
+                        (call $new_exception
+                            (global_get ,(class::frozen_error().identifier()))
+                            (call $str_concat
+                                (global_get ,(string_identifier("can't modify frozen ")))
+                                (struct_get $class $name (call $class_of (local_get $self))))))))
+            (local_set $obj (ref_cast (ref $obj) (local_get $self)))
+            (local_set $ivars (struct_get $obj $ivars (local_get $obj)))
+            (local_set $idx (call $ivar_index (local_get $ivars) (local_get $name)))
+            (if (i32_lt_s (local_get $idx) (const_i32 0))
+                (then
+                    (struct_set $obj $ivars
+                        (local_get $obj)
+                        (ref_cast (ref $arr_unitype)
+                            (call $push
+                                (ref_cast (ref $arr_unitype)
+                                    (call $push (local_get $ivars) (local_get $name)))
+                                (local_get $val)))))
+                (else
+                    (array_set $arr_unitype
+                        (local_get $ivars)
+                        (i32_add (local_get $idx) (const_i32 1))
+                        (local_get $val))))
+            (local_get $val))
+    }
+}
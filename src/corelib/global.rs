@@ -1,12 +1,13 @@
 use crate::CompileCtx;
 use crate::corelib::{class, method};
+use crate::lexer::OPERATOR_METHOD_NAMES;
 use crate::unitype::Unitype;
 use wat_defs::global::Global;
 use wat_defs::instr::Instr;
 use wat_macro::wat;
 
 pub fn add_globals(ctx: &mut CompileCtx) {
-    let mut globals = vec![main(), empty_args()];
+    let mut globals = vec![main(), empty_args(), source_position(), current_exception()];
     ctx.module.globals.append(&mut globals);
     add_string_defs(ctx);
 }
@@ -25,16 +26,109 @@ fn main() -> Global {
     wat! {
         (global $main
                 (mut (ref $obj))
-                (struct_new $obj (ref_null $class)))
+                (struct_new $obj (ref_null $class) (array_new_fixed $arr_unitype 0)))
     }
 }
 
+/// Name of the exported function returning `$source_position`.
+pub const GET_SOURCE_POSITION: &str = "get_source_position";
+
+/// Name of the exported `$inspect`, which the host uses to show results.
+pub const INSPECT: &str = "inspect";
+
+/// Byte index in the Ruby source of the call being made, or -1 before any call.
+/// Set by compiled code before each call, so a runtime error can say where it happened.
+fn source_position() -> Global {
+    wat! {
+        (global $source_position
+                (mut i32)
+                (const_i32 ,(-1)))
+    }
+}
+
+/// The exception being rescued, which `raise` without args raises again.
+/// `nil` outside of `rescue` clauses.
+fn current_exception() -> Global {
+    wat! {
+        (global $current_exception
+                (mut (ref eq))
+                (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+    }
+}
+
+/// Strings used directly by corelib functions.
+const CORELIB_STRINGS: [&str; 58] = [
+    "",
+    "nil",
+    "true",
+    "false",
+    "\"",
+    ":",
+    "[",
+    "]",
+    ", ",
+    "#<",
+    ">",
+    "#<Proc:...>",
+    "#<Proc:... (lambda)>",
+    "..",
+    "...",
+    " (",
+    ")",
+    "unhandled exception",
+    "exception class/object expected",
+    "unexpected return",
+    "break from proc-closure",
+    "no block given (yield)",
+    "undefined method '",
+    "super: no superclass method '",
+    "' for ",
+    "an instance of ",
+    "class ",
+    "module ",
+    "main",
+    "can't modify frozen ",
+    "{",
+    "}",
+    ": ",
+    " => ",
+    "+",
+    "wrong number of arguments (given ",
+    ", expected ",
+    "missing keyword: ",
+    "unknown keyword: ",
+    "divided by 0",
+    "integer too big; Integers are 64-bit",
+    " can't be coerced into ",
+    "comparison of ",
+    " with ",
+    " failed",
+    "no implicit conversion of ",
+    " into String",
+    "NaN",
+    "Infinity",
+    "-",
+    "0",
+    "0.",
+    "0.0",
+    ".",
+    ".0",
+    "e+",
+    "e-",
+    "uninitialized constant ",
+];
+
 /// Add string definitions from:
 /// - Class names
-/// - Method names
+/// - Corelib functions
+///
+/// Then add symbol definitions, which refer to their name strings.
 fn add_string_defs(ctx: &mut CompileCtx) {
     add_class_string_defs(ctx);
-    add_method_string_defs(ctx);
+    for string in CORELIB_STRINGS {
+        add_string_def(ctx, string.to_string());
+    }
+    add_symbol_defs(ctx);
 }
 
 fn add_class_string_defs(ctx: &mut CompileCtx) {
@@ -44,15 +138,73 @@ fn add_class_string_defs(ctx: &mut CompileCtx) {
     }
 }
 
-fn add_method_string_defs(ctx: &mut CompileCtx) {
-    let method_names: Vec<_> = ctx.methods.iter().map(|m| m.name.clone()).collect();
-    for name in method_names {
-        add_string_def(ctx, name);
+/// Add symbol definitions from:
+/// - Method names
+/// - Symbols used by the program
+fn add_symbol_defs(ctx: &mut CompileCtx) {
+    let method_names = ctx.methods.iter().map(|m| m.name.clone());
+    // The operator functions call an `$obj`'s operator methods.
+    let operator_names = OPERATOR_METHOD_NAMES.iter().map(|name| name.to_string());
+    let names: Vec<_> = method_names
+        .chain(operator_names)
+        .chain(ctx.symbols.iter().cloned())
+        .collect();
+    for name in names {
+        add_symbol_def(ctx, name);
     }
+    add_symbol_table(ctx);
+}
+
+/// Every symbol, so `$intern` can find a symbol built at runtime, like `:"a#{1}"`.
+/// Must be added after all the symbol definitions.
+fn add_symbol_table(ctx: &mut CompileCtx) {
+    let symbol_prefix = format!("{}_", Unitype::SYMBOL_TYPE_IDENTIFIER);
+    let symbols: Vec<Instr> = ctx
+        .module
+        .globals
+        .iter()
+        .filter(|glob| glob.name.starts_with(&symbol_prefix))
+        .flat_map(|glob| wat![(global_get, (glob.name.clone()))])
+        .collect();
+    let len = symbols.len();
+
+    let res = wat! {
+        (global $symbols
+            (mut (ref $arr_unitype))
+            (array_new_fixed $arr_unitype ,(len.try_into().unwrap())
+                ,(symbols)))
+    };
+
+    ctx.module.globals.push(res);
+}
+
+pub fn add_symbol_def(ctx: &mut CompileCtx, name: String) {
+    let identifier = symbol_identifier(&name);
+    if ctx
+        .module
+        .globals
+        .iter()
+        .any(|glob| glob.name == identifier)
+    {
+        return;
+    }
+    let name_identifier = string_identifier(&name);
+    add_string_def(ctx, name);
+
+    let res = wat! {
+        (global ,(identifier)
+            (ref $sym)
+            (struct_new $sym (global_get ,(name_identifier))))
+    };
+
+    ctx.module.globals.push(res);
 }
 
 pub fn add_string_def(ctx: &mut CompileCtx, string: String) {
     let name = string_identifier(&string);
+    if ctx.module.globals.iter().any(|glob| glob.name == name) {
+        return;
+    }
     let bytes: Vec<Instr> = string
         .as_bytes()
         .iter()
@@ -72,5 +224,26 @@ pub fn add_string_def(ctx: &mut CompileCtx, string: String) {
 }
 
 pub fn string_identifier(string: &str) -> String {
-    format!("{}_{}", Unitype::STRING_TYPE_IDENTIFIER, &string)
+    format!("{}_{}", Unitype::STRING_TYPE_IDENTIFIER, mangle(string))
+}
+
+pub fn symbol_identifier(name: &str) -> String {
+    format!("{}_{}", Unitype::SYMBOL_TYPE_IDENTIFIER, mangle(name))
+}
+
+/// Bytes that can't appear in a Wasm identifier are escaped as `%XX`.
+pub fn mangle(string: &str) -> String {
+    string
+        .bytes()
+        .map(|b| match b {
+            b'%' => "%25".to_string(),
+            b if is_id_char(b) => (b as char).to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// See https://webassembly.github.io/spec/core/text/values.html#text-id
+fn is_id_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-./:<=>?@\\^_`|~".contains(&b)
 }
@@ -35,6 +35,7 @@ pub fn for_in_arr(
     let block = Instr {
         unfolded_instr: UnfoldedInstr::Block {
             label: "exit_for".to_string(),
+            block_type: None,
         },
         folded_instrs: vec![Instr {
             unfolded_instr: UnfoldedInstr::Loop {
@@ -122,3 +123,95 @@ pub fn i64_neg(body: Vec<Instr>) -> Instr {
     }
     .remove(0)
 }
+
+// Double-double arithmetic: a value held exactly as the sum of two `f64` locals,
+//     the rounded value and its rounding error.
+// The helpers take the names of `f64` locals, which must all be different.
+
+/// Sets `$sum + $err` to exactly `$a + $b`. Knuth's two-sum.
+/// Requires local `$dd_tmp`.
+pub fn f64_two_sum(a: &str, b: &str, sum: &str, err: &str) -> Vec<Instr> {
+    wat! {
+        (local_set ,(sum.to_string()) (f64_add (local_get ,(a.to_string())) (local_get ,(b.to_string()))))
+        (local_set $dd_tmp (f64_sub (local_get ,(sum.to_string())) (local_get ,(a.to_string()))))
+        (local_set ,(err.to_string())
+            (f64_add (f64_sub (local_get ,(a.to_string()))
+                              (f64_sub (local_get ,(sum.to_string())) (local_get $dd_tmp)))
+                     (f64_sub (local_get ,(b.to_string())) (local_get $dd_tmp))))
+    }
+}
+
+/// Sets `$sum + $err` to exactly `$a + $b`, given `|$a| >= |$b|`. Dekker's fast two-sum.
+pub fn f64_fast_two_sum(a: &str, b: &str, sum: &str, err: &str) -> Vec<Instr> {
+    wat! {
+        (local_set ,(sum.to_string()) (f64_add (local_get ,(a.to_string())) (local_get ,(b.to_string()))))
+        (local_set ,(err.to_string())
+            (f64_sub (local_get ,(b.to_string()))
+                     (f64_sub (local_get ,(sum.to_string())) (local_get ,(a.to_string())))))
+    }
+}
+
+/// Sets `$prod + $err` to exactly `$a * $b`, without a fused multiply-add.
+/// Dekker's two-product: each operand is split into halves of 26 bits,
+///     whose products are exact.
+/// Requires locals `$dd_tmp`, `$a_hi`, `$a_lo`, `$b_hi` and `$b_lo`.
+/// Pre: `|$a|` and `|$b|` are below `2^996`, so splitting them doesn't overflow.
+pub fn f64_two_prod(a: &str, b: &str, prod: &str, err: &str) -> Vec<Instr> {
+    [
+        f64_split(a, "a_hi", "a_lo"),
+        f64_split(b, "b_hi", "b_lo"),
+        wat! {
+            (local_set ,(prod.to_string()) (f64_mul (local_get ,(a.to_string())) (local_get ,(b.to_string()))))
+            (local_set ,(err.to_string())
+                (f64_add
+                    (f64_add
+                        (f64_add (f64_sub (f64_mul (local_get $a_hi) (local_get $b_hi))
+                                          (local_get ,(prod.to_string())))
+                                 (f64_mul (local_get $a_hi) (local_get $b_lo)))
+                        (f64_mul (local_get $a_lo) (local_get $b_hi)))
+                    (f64_mul (local_get $a_lo) (local_get $b_lo))))
+        },
+    ]
+    .concat()
+}
+
+/// Sets `$hi + $lo` to exactly `$x`, each with at most 26 significant bits.
+fn f64_split(x: &str, hi: &str, lo: &str) -> Vec<Instr> {
+    wat! {
+        (local_set $dd_tmp (f64_mul (const_f64 134217729.0) (local_get ,(x.to_string()))))
+        (local_set ,(hi.to_string())
+            (f64_sub (local_get $dd_tmp)
+                     (f64_sub (local_get $dd_tmp) (local_get ,(x.to_string())))))
+        (local_set ,(lo.to_string())
+            (f64_sub (local_get ,(x.to_string())) (local_get ,(hi.to_string()))))
+    }
+}
+
+/// Sets `$hi + $lo` to `($a_hi + $a_lo) * ($b_hi + $b_lo)`, to within about `2^-104` of it.
+/// Requires the locals of `f64_two_prod`, which `$hi` and `$lo` must differ from, as must the operands.
+/// Pre: as for `f64_two_prod`, on `$a_hi` and `$b_hi`.
+pub fn f64_dd_mul(
+    a_hi: &str,
+    a_lo: &str,
+    b_hi: &str,
+    b_lo: &str,
+    hi: &str,
+    lo: &str,
+) -> Vec<Instr> {
+    [
+        f64_two_prod(a_hi, b_hi, hi, lo),
+        wat! {
+            (local_set ,(lo.to_string())
+                (f64_add (local_get ,(lo.to_string()))
+                         (f64_add (f64_mul (local_get ,(a_hi.to_string())) (local_get ,(b_lo.to_string())))
+                                  (f64_mul (local_get ,(a_lo.to_string())) (local_get ,(b_hi.to_string()))))))
+            // Renormalize, so `$lo` is within half an ulp of `$hi`.
+            (local_set $dd_tmp (f64_add (local_get ,(hi.to_string())) (local_get ,(lo.to_string()))))
+            (local_set ,(lo.to_string())
+                (f64_sub (local_get ,(lo.to_string()))
+                         (f64_sub (local_get $dd_tmp) (local_get ,(hi.to_string())))))
+            (local_set ,(hi.to_string()) (local_get $dd_tmp))
+        },
+    ]
+    .concat()
+}
@@ -1,4 +1,6 @@
 use crate::CompileCtx;
+use crate::corelib::class;
+use crate::corelib::global::{mangle, string_identifier, symbol_identifier};
 use crate::corelib::type_def::METHOD_TYPE_IDENTIFIER;
 use crate::node::RequiredParam;
 use crate::unitype::Unitype;
@@ -8,8 +10,8 @@ use wat_macro::wat;
 
 /// A Ruby method. Compiles to:
 /// - Definition of function type `$<METHOD_FUNC_NAME>`
-///     with signature `(self: Object, args: Array Unitype) -> Unitype`
-/// - Definition of global string `$<METHOD_NAME>`
+///   with signature `(self: Object, args: Array Unitype, block: Proc?) -> Unitype`
+/// - Definition of global symbol `$<METHOD_NAME>`
 #[derive(Debug, Clone)]
 pub struct Method {
     pub class: String,
@@ -24,7 +26,13 @@ impl Method {
 }
 
 pub fn method_identifier(class: &str, name: &str) -> String {
-    format!("{}_{}_{}", METHOD_TYPE_IDENTIFIER, class, name)
+    format!("{}_{}_{}", METHOD_TYPE_IDENTIFIER, class, mangle(name))
+}
+
+/// The wasm local holding the Ruby variable `name`.
+/// Prefixed so they can't collide with the `$self`, `$args`, `$block` and `$proc` params.
+pub fn local_identifier(name: &str) -> String {
+    format!("local_{}", name)
 }
 
 const NEW_NAME: &str = "new";
@@ -37,35 +45,116 @@ pub fn class_new() -> Method {
     }
 }
 
-fn new_method_def(class: &str) -> Func {
+/// A new instance of the class, which `initialize` is called on with the args and block.
+/// Exceptions are `$exception`s, whose `initialize` sets their message.
+fn new_method_def(class_name: &str) -> Func {
     make_method_def(
-        class,
+        class_name,
         "new",
-        &vec![],
-        vec![],
+        &[],
+        vec!["obj".to_string()],
         wat! {
-            (struct_new $obj
-              // .class
-              (ref_cast (ref $class) (local_get $self)))
+            (local_set $local_obj
+                (if (result (ref eq))
+                    (call $has_ancestor
+                        (ref_cast (ref $class) (local_get $self))
+                        (global_get ,(class::exception().identifier())))
+                    (then
+                        (struct_new $exception
+                            (ref_cast (ref $class) (local_get $self))
+                            (array_new_fixed $arr_unitype 0)
+                            (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))))
+                    (else
+                        (struct_new $obj
+                            // .class
+                            (ref_cast (ref $class) (local_get $self))
+                            (array_new_fixed $arr_unitype 0)))))
+            (drop
+                (call $call
+                    (local_get $local_obj)
+                    (global_get ,(symbol_identifier(INITIALIZE_NAME)))
+                    (local_get $args)
+                    (local_get $block)))
+            (local_get $local_obj)
         },
     )
 }
 
+const INITIALIZE_NAME: &str = "initialize";
+
+/// `Object#initialize` takes no args.
+pub fn object_initialize() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: INITIALIZE_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            INITIALIZE_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $check_arity (array_len (local_get $args)) (const_i32 0) (const_i32 0))
+                (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+            },
+        ),
+    }
+}
+
+/// `Exception#initialize` sets the message, if one is given.
+pub fn exception_initialize() -> Method {
+    Method {
+        class: "Exception".to_string(),
+        name: INITIALIZE_NAME.to_string(),
+        method_def: make_method_def(
+            "Exception",
+            INITIALIZE_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $check_arity (array_len (local_get $args)) (const_i32 0) (const_i32 1))
+                (if (array_len (local_get $args))
+                    (then
+                        (struct_set $exception $message
+                            (ref_cast (ref $exception) (local_get $self))
+                            (array_get $arr_unitype (local_get $args) (const_i32 0)))))
+                (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))
+            },
+        ),
+    }
+}
+
 const NAME_NAME: &str = "name";
 
-pub fn class_name() -> Method {
+pub fn module_name() -> Method {
     Method {
-        class: "Class".to_string(),
+        class: "Module".to_string(),
         name: NAME_NAME.to_string(),
-        method_def: name_method_def("Class"),
+        method_def: name_method_def("Module", NAME_NAME),
+    }
+}
+
+/// `Module#to_s` is the module's name, like `Module#name`.
+pub fn module_to_s() -> Method {
+    Method {
+        class: "Module".to_string(),
+        name: TO_S_NAME.to_string(),
+        method_def: name_method_def("Module", TO_S_NAME),
     }
 }
 
-fn name_method_def(class: &str) -> Func {
+pub fn module_inspect() -> Method {
+    Method {
+        class: "Module".to_string(),
+        name: INSPECT_NAME.to_string(),
+        method_def: name_method_def("Module", INSPECT_NAME),
+    }
+}
+
+fn name_method_def(class: &str, name: &str) -> Func {
     make_method_def(
         class,
-        "name",
-        &vec![],
+        name,
+        &[],
         vec![],
         wat! {
             (struct_get $class $name
@@ -74,6 +163,513 @@ fn name_method_def(class: &str) -> Func {
     )
 }
 
+const ANCESTORS_NAME: &str = "ancestors";
+
+pub fn module_ancestors() -> Method {
+    Method {
+        class: "Module".to_string(),
+        name: ANCESTORS_NAME.to_string(),
+        method_def: ancestors_method_def("Module"),
+    }
+}
+
+/// The modules searched for instance methods, in order.
+fn ancestors_method_def(class: &str) -> Func {
+    make_method_def(
+        class,
+        ANCESTORS_NAME,
+        &[],
+        vec![],
+        wat! {
+            (call $ancestors
+              (ref_cast (ref $class) (local_get $self)))
+        },
+    )
+}
+
+const CASE_EQ_NAME: &str = "===";
+
+/// `Module#===`: is the arg an instance of this module, or of a class including it?
+pub fn module_case_eq() -> Method {
+    Method {
+        class: "Module".to_string(),
+        name: CASE_EQ_NAME.to_string(),
+        method_def: make_method_def(
+            "Module",
+            CASE_EQ_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $to_bool
+                    (call $is_a
+                        (array_get $arr_unitype (local_get $args) (const_i32 0))
+                        (ref_cast (ref $class) (local_get $self))))
+            },
+        ),
+    }
+}
+
+/// `Object#===` is `==`.
+pub fn object_case_eq() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: CASE_EQ_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            CASE_EQ_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $eq_eq
+                    (local_get $self)
+                    (array_get $arr_unitype (local_get $args) (const_i32 0)))
+            },
+        ),
+    }
+}
+
+const EQ_EQ_NAME: &str = "==";
+
+/// `Object#==`: is the arg this very object?
+pub fn object_eq_eq() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: EQ_EQ_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            EQ_EQ_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $to_bool
+                    (ref_eq
+                        (local_get $self)
+                        (array_get $arr_unitype (local_get $args) (const_i32 0))))
+            },
+        ),
+    }
+}
+
+const NOT_NAME: &str = "!";
+
+/// `Object#!` is `false`, since an object is truthy. Objects can define their own `!`.
+pub fn object_not() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: NOT_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            NOT_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $to_bool (i32_eqz (call $truthy (local_get $self))))
+            },
+        ),
+    }
+}
+
+const CMP_NAME: &str = "<=>";
+
+/// `Object#<=>` is `0` if the arg is `==` to this object, or else `nil`.
+pub fn object_cmp() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: CMP_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            CMP_NAME,
+            &[],
+            vec![],
+            wat! {
+                (if (result (ref eq))
+                    (call $from_bool
+                        (call $eq_eq
+                            (local_get $self)
+                            (array_get $arr_unitype (local_get $args) (const_i32 0))))
+                    (then (call $i64_to_integer (const_i64 0)))
+                    (else (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))))
+            },
+        ),
+    }
+}
+
+/// `Range#===`: is the arg between the range's ends?
+/// TODO: Only numeric ranges. Ranges of other values compare their ends with `<=>`.
+pub fn range_case_eq() -> Method {
+    let mut method = range_field_method(
+        CASE_EQ_NAME,
+        wat! {
+                (local_set $x (array_get $arr_unitype (local_get $args) (const_i32 0)))
+                (if (i32_eqz
+                        (i32_and (call $is_numeric (local_get $x))
+                                 (i32_and (call $is_numeric
+                                              (struct_get $range $begin (local_get $range)))
+                                          (call $is_numeric
+                                              (struct_get $range $end (local_get $range))))))
+                    (then (return (call $to_bool (const_i32 0)))))
+                (if (call $from_bool
+                        (call $lt (local_get $x) (struct_get $range $begin (local_get $range))))
+                    (then (return (call $to_bool (const_i32 0)))))
+                (if (struct_get $range $exclusive (local_get $range))
+                    (then
+                        (return
+                            (call $lt (local_get $x) (struct_get $range $end (local_get $range))))))
+                (call $to_bool
+                    (i32_eqz
+                        (call $from_bool
+                            (call $lt (struct_get $range $end (local_get $range))
+                                      (local_get $x)))))
+        },
+    );
+    method.method_def.locals.push(wat! { (local $x (ref eq)) });
+    method
+}
+
+pub fn range_begin() -> Method {
+    range_field_method(
+        "begin",
+        wat! { (struct_get $range $begin (local_get $range)) },
+    )
+}
+
+pub fn range_end() -> Method {
+    range_field_method("end", wat! { (struct_get $range $end (local_get $range)) })
+}
+
+pub fn range_exclude_end() -> Method {
+    range_field_method(
+        "exclude_end?",
+        wat! { (call $to_bool (struct_get $range $exclusive (local_get $range))) },
+    )
+}
+
+/// `1..2`, or `1...2` if it excludes its end, with each end `to_s`ed.
+pub fn range_to_s() -> Method {
+    range_description_method(TO_S_NAME, "to_s")
+}
+
+/// `1..2`, or `1...2` if it excludes its end, with each end `inspect`ed.
+pub fn range_inspect() -> Method {
+    range_description_method(INSPECT_NAME, "inspect")
+}
+
+/// `<begin>..<end>`, with each end converted to a string by the corelib function `$<convert>`.
+fn range_description_method(name: &str, convert: &str) -> Method {
+    let convert = convert.to_string();
+    range_field_method(
+        name,
+        wat! {
+            (call $str_concat
+                (call $str_concat
+                    (call ,(convert.clone()) (struct_get $range $begin (local_get $range)))
+                    (if (result (ref $str))
+                        (struct_get $range $exclusive (local_get $range))
+                        (then (global_get ,(string_identifier("..."))))
+                        (else (global_get ,(string_identifier(".."))))))
+                (call ,(convert) (struct_get $range $end (local_get $range))))
+        },
+    )
+}
+
+/// A method of `Range`, computing its result from `$range`, the receiver.
+fn range_field_method(name: &str, body: Vec<Instr>) -> Method {
+    let mut method_def = make_method_def(
+        "Range",
+        name,
+        &[],
+        vec![],
+        [
+            wat! { (local_set $range (ref_cast (ref $range) (local_get $self))) },
+            body,
+        ]
+        .concat(),
+    );
+    method_def.locals.push(wat! { (local $range (ref $range)) });
+    Method {
+        class: "Range".to_string(),
+        name: name.to_string(),
+        method_def,
+    }
+}
+
+pub fn exception_message() -> Method {
+    exception_message_method("message")
+}
+
+pub fn exception_to_s() -> Method {
+    exception_message_method(TO_S_NAME)
+}
+
+/// `#<ClassName: message>`
+pub fn exception_inspect() -> Method {
+    Method {
+        class: "Exception".to_string(),
+        name: INSPECT_NAME.to_string(),
+        method_def: make_method_def(
+            "Exception",
+            INSPECT_NAME,
+            &[],
+            vec![],
+            wat! {
+                (call $str_concat
+                    (call $str_concat
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("#<")))
+                                (struct_get $class $name (call $class_of (local_get $self))))
+                            (global_get ,(string_identifier(": "))))
+                        (call $to_s (local_get $self)))
+                    (global_get ,(string_identifier(">"))))
+            },
+        ),
+    }
+}
+
+/// The message the exception was made with, or the name of its class if none was.
+fn exception_message_method(name: &str) -> Method {
+    Method {
+        class: "Exception".to_string(),
+        name: name.to_string(),
+        method_def: make_method_def(
+            "Exception",
+            name,
+            &[],
+            vec![],
+            wat! {
+                (if (result (ref eq))
+                    (call $is_nil
+                        (struct_get $exception $message
+                            (ref_cast (ref $exception) (local_get $self))))
+                    (then (struct_get $class $name
+                        (ref_as_non_null (struct_get $exception $parent
+                            (ref_cast (ref $exception) (local_get $self))))))
+                    (else (struct_get $exception $message
+                        (ref_cast (ref $exception) (local_get $self)))))
+            },
+        ),
+    }
+}
+
+/// `raise`: throw the exception described by the args. See `$exception_to_raise`.
+pub fn object_raise() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: "raise".to_string(),
+        method_def: make_method_def(
+            "Object",
+            "raise",
+            &[],
+            vec![],
+            wat! {
+                (throw $ruby_exception (call $exception_to_raise (local_get $args)))
+            },
+        ),
+    }
+}
+
+/// `Hash#[]`, or `nil` if the hash doesn't have the key.
+pub fn hash_index() -> Method {
+    hash_method(
+        INDEX_NAME,
+        wat! {
+            (call $hash_get
+                (local_get $hash)
+                (array_get $arr_unitype (local_get $args) (const_i32 0)))
+        },
+    )
+}
+
+pub fn hash_has_key() -> Method {
+    hash_method(
+        "key?",
+        wat! {
+            (call $to_bool
+                (i32_eqz
+                    (i32_lt_s
+                        (call $hash_index
+                            (local_get $hash)
+                            (array_get $arr_unitype (local_get $args) (const_i32 0)))
+                        (const_i32 0))))
+        },
+    )
+}
+
+pub fn hash_size() -> Method {
+    hash_method(
+        "size",
+        wat! {
+            (call $i32_to_fixnum (array_len (struct_get $hash $keys (local_get $hash))))
+        },
+    )
+}
+
+pub fn hash_keys() -> Method {
+    hash_method("keys", wat! { (struct_get $hash $keys (local_get $hash)) })
+}
+
+pub fn hash_values() -> Method {
+    hash_method(
+        "values",
+        wat! { (struct_get $hash $vals (local_get $hash)) },
+    )
+}
+
+/// `{a: 1, "b" => 2}`
+pub fn hash_to_s() -> Method {
+    hash_method(TO_S_NAME, wat! { (call $hash_inspect (local_get $hash)) })
+}
+
+pub fn hash_inspect() -> Method {
+    hash_method(
+        INSPECT_NAME,
+        wat! { (call $hash_inspect (local_get $hash)) },
+    )
+}
+
+/// A method of `Hash`, computing its result from `$hash`, the receiver.
+fn hash_method(name: &str, body: Vec<Instr>) -> Method {
+    let mut method_def = make_method_def(
+        "Hash",
+        name,
+        &[],
+        vec![],
+        [
+            wat! { (local_set $hash (ref_cast (ref $hash) (local_get $self))) },
+            body,
+        ]
+        .concat(),
+    );
+    method_def.locals.push(wat! { (local $hash (ref $hash)) });
+    Method {
+        class: "Hash".to_string(),
+        name: name.to_string(),
+        method_def,
+    }
+}
+
+const SUPERCLASS_NAME: &str = "superclass";
+
+pub fn class_superclass() -> Method {
+    Method {
+        class: "Class".to_string(),
+        name: SUPERCLASS_NAME.to_string(),
+        method_def: superclass_method_def("Class"),
+    }
+}
+
+/// The superclass, or `nil` for `BasicObject`.
+fn superclass_method_def(class: &str) -> Func {
+    make_method_def(
+        class,
+        "superclass",
+        &[],
+        vec![],
+        wat! {
+            // `ref.test` against a non-null type fails for null.
+            (if (result (ref eq))
+                (ref_test (ref $class)
+                    (struct_get $class $superclass
+                        (ref_cast (ref $class) (local_get $self))))
+                (then (ref_as_non_null
+                    (struct_get $class $superclass
+                        (ref_cast (ref $class) (local_get $self)))))
+                (else (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64)))))
+        },
+    )
+}
+
+const CALL_NAME: &str = "call";
+const INDEX_NAME: &str = "[]";
+
+pub fn proc_call() -> Method {
+    Method {
+        class: "Proc".to_string(),
+        name: CALL_NAME.to_string(),
+        method_def: call_method_def("Proc", CALL_NAME),
+    }
+}
+
+/// `Proc#[]`, the same as `Proc#call`.
+pub fn proc_index() -> Method {
+    Method {
+        class: "Proc".to_string(),
+        name: INDEX_NAME.to_string(),
+        method_def: call_method_def("Proc", INDEX_NAME),
+    }
+}
+
+/// Call the proc's block with the args, and the block passed to the call.
+fn call_method_def(class: &str, name: &str) -> Func {
+    make_method_def(
+        class,
+        name,
+        &[],
+        vec![],
+        wat! {
+            (call $call_block
+                (ref_cast (ref $proc) (local_get $self))
+                (local_get $args)
+                (local_get $block))
+        },
+    )
+}
+
+pub fn proc_is_lambda() -> Method {
+    Method {
+        class: "Proc".to_string(),
+        name: "lambda?".to_string(),
+        method_def: make_method_def(
+            "Proc",
+            "lambda?",
+            &[],
+            vec![],
+            wat! {
+                (call $to_bool
+                    (struct_get $proc $lambda (ref_cast (ref $proc) (local_get $self))))
+            },
+        ),
+    }
+}
+
+pub fn proc_arity() -> Method {
+    Method {
+        class: "Proc".to_string(),
+        name: "arity".to_string(),
+        method_def: make_method_def(
+            "Proc",
+            "arity",
+            &[],
+            vec![],
+            wat! {
+                (call $i32_to_fixnum
+                    (struct_get $proc $arity (ref_cast (ref $proc) (local_get $self))))
+            },
+        ),
+    }
+}
+
+/// `#<Proc:...>`, or `#<Proc:... (lambda)>` for a lambda.
+/// Ruby also shows the proc's address and where it was defined, which we don't know.
+pub fn proc_inspect() -> Method {
+    Method {
+        class: "Proc".to_string(),
+        name: INSPECT_NAME.to_string(),
+        method_def: make_method_def(
+            "Proc",
+            INSPECT_NAME,
+            &[],
+            vec![],
+            wat! {
+                (if (result (ref eq))
+                    (struct_get $proc $lambda (ref_cast (ref $proc) (local_get $self)))
+                    (then (global_get ,(string_identifier("#<Proc:... (lambda)>"))))
+                    (else (global_get ,(string_identifier("#<Proc:...>")))))
+            },
+        ),
+    }
+}
+
 const CLASS_NAME: &str = "class";
 
 pub fn object_class() -> Method {
@@ -88,65 +684,94 @@ fn class_method_def(class: &str) -> Func {
     make_method_def(
         class,
         "class",
-        &vec![],
+        &[],
         vec![],
         wat! {
-            (ref_cast (ref eq)
-                (struct_get $obj $parent (local_get $self)))
+            (call $class_of (local_get $self))
         },
     )
 }
 
+const TO_S_NAME: &str = "to_s";
+
+/// Called by `$inspect` on objects, so the host shows results this way.
+const INSPECT_NAME: &str = "inspect";
+
+/// `#<ClassName>` for an `$obj`, or what `$to_s` gives for any other value, like `"1"` for `1`.
+/// `main` is `main`, as in Ruby.
+pub fn object_to_s() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: TO_S_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            TO_S_NAME,
+            &[],
+            vec![],
+            wat! {
+                (if (ref_eq (local_get $self) (global_get $main))
+                    (then (return (global_get ,(string_identifier("main"))))))
+                (if (result (ref eq))
+                    (ref_test (ref $obj) (local_get $self))
+                    (then
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("#<")))
+                                (struct_get $class $name (call $class_of (local_get $self))))
+                            (global_get ,(string_identifier(">")))))
+                    (else (call $to_s (local_get $self))))
+            },
+        ),
+    }
+}
+
+/// `#<ClassName>` for an `$obj`, or what `$inspect` gives for any other value, like `[1, "a"]`.
+/// `main` is `main`, as in Ruby.
+pub fn object_inspect() -> Method {
+    Method {
+        class: "Object".to_string(),
+        name: INSPECT_NAME.to_string(),
+        method_def: make_method_def(
+            "Object",
+            INSPECT_NAME,
+            &[],
+            vec![],
+            wat! {
+                (if (ref_eq (local_get $self) (global_get $main))
+                    (then (return (global_get ,(string_identifier("main"))))))
+                (if (result (ref eq))
+                    (ref_test (ref $obj) (local_get $self))
+                    (then
+                        (call $str_concat
+                            (call $str_concat
+                                (global_get ,(string_identifier("#<")))
+                                (struct_get $class $name (call $class_of (local_get $self))))
+                            (global_get ,(string_identifier(">")))))
+                    (else (call $inspect (local_get $self))))
+            },
+        ),
+    }
+}
+
 pub fn make_method_def(
     class: &str,
     name: &str,
-    params: &Vec<RequiredParam>,
+    params: &[RequiredParam],
     locals: Vec<String>,
     body: Vec<Instr>,
 ) -> Func {
-    let param_local_defs: Vec<Local> = params
-        .iter()
-        .map(|p| {
-            wat! { (local ,(p.name.to_string()) (ref eq)) }
-        })
-        .collect();
-    let local_defs = locals
-        .iter()
-        .map(|l| {
-            wat! { (local ,(l.to_string()) (ref eq)) }
-        })
-        .collect();
-    let local_defs = [param_local_defs, local_defs].concat();
-    let param_local_setters: Vec<Instr> = params
-        .iter()
-        .enumerate()
-        .map(|(idx, p)| {
-            wat! {
-                (local_set ,(p.name.clone())
-                    (array_get $arr_unitype (local_get $args) (const_i32 ,(idx as i64))))
-            }
-        })
-        .flatten()
-        .collect();
-    let local_setters = locals
-        .iter()
-        .map(|l| {
-            wat! {
-                (local_set ,(l.to_string())
-                    (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
-            }
-        })
-        .flatten()
-        .collect();
-    let instrs = [param_local_setters, local_setters, body].concat();
+    let params: Vec<_> = params.iter().map(|p| p.name.clone()).collect();
+    let (local_defs, setters) = variable_locals(&params, &locals);
+    let instrs = [setters, body].concat();
 
     // TODO: Ughhh quasiquoting is broken.
 
     let no_locals = wat! {
         (func ,(method_identifier(class, name))
             (type $method)
-            (param $self (ref $obj))
+            (param $self (ref eq))
             (param $args (ref $arr_unitype))
+            (param $block (ref null $proc))
             (result (ref eq))
             ,(instrs))
     };
@@ -172,8 +797,72 @@ pub fn make_method_def(
     }
 }
 
+/// Locals for a function's Ruby params and variables,
+///     and the instrs binding the params from `$args` and setting the variables to nil.
+pub fn variable_locals(params: &[String], locals: &[String]) -> (Vec<Local>, Vec<Instr>) {
+    let local_defs = params
+        .iter()
+        .chain(locals)
+        .map(|name| {
+            wat! { (local ,(local_identifier(name)) (ref eq)) }
+        })
+        .collect();
+    let param_setters = params.iter().enumerate().flat_map(|(idx, name)| {
+        wat! {
+            (local_set ,(local_identifier(name))
+                (array_get $arr_unitype (local_get $args) (const_i32 ,(idx as i64))))
+        }
+    });
+    let local_setters = locals.iter().flat_map(|name| {
+        wat! {
+            (local_set ,(local_identifier(name))
+                (ref_i31 (const_i32 ,(Unitype::NIL_BIT_PATTERN as i64))))
+        }
+    });
+    (local_defs, param_setters.chain(local_setters).collect())
+}
+
 pub fn corelib_methods() -> Vec<Method> {
-    vec![class_new(), object_class(), class_name()]
+    vec![
+        class_new(),
+        object_initialize(),
+        exception_initialize(),
+        object_class(),
+        object_to_s(),
+        object_inspect(),
+        class_superclass(),
+        module_name(),
+        module_to_s(),
+        module_inspect(),
+        module_ancestors(),
+        proc_call(),
+        proc_index(),
+        proc_is_lambda(),
+        proc_arity(),
+        proc_inspect(),
+        module_case_eq(),
+        object_case_eq(),
+        object_eq_eq(),
+        object_not(),
+        object_cmp(),
+        range_case_eq(),
+        range_begin(),
+        range_end(),
+        range_exclude_end(),
+        range_to_s(),
+        range_inspect(),
+        hash_index(),
+        hash_has_key(),
+        hash_size(),
+        hash_keys(),
+        hash_values(),
+        hash_to_s(),
+        hash_inspect(),
+        exception_message(),
+        exception_to_s(),
+        exception_inspect(),
+        object_raise(),
+    ]
 }
 
 pub fn add_method_defs(compile_ctx: &mut CompileCtx) {
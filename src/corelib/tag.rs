@@ -0,0 +1,28 @@
+use crate::CompileCtx;
+use wat_defs::module::Tag;
+use wat_macro::wat;
+
+/// Ruby exceptions are thrown as wasm exceptions with this tag, carrying the `$exception`.
+pub const EXCEPTION_TAG_IDENTIFIER: &str = "ruby_exception";
+
+/// Name of the exported function describing an exception, for the host to report it if uncaught.
+pub const DESCRIBE_EXCEPTION: &str = "describe_exception";
+
+pub fn add_tags(ctx: &mut CompileCtx) {
+    ctx.module.tags.push(exception());
+    ctx.module.tags.push(jump());
+}
+
+fn exception() -> Tag {
+    wat! {
+        (tag $ruby_exception (param (ref $exception)))
+    }
+}
+
+/// `break` and `return` inside a block leave a function further up the stack,
+///     by throwing a `$jump` with this tag.
+fn jump() -> Tag {
+    wat! {
+        (tag $ruby_jump (param (ref $jump)))
+    }
+}
@@ -8,7 +8,22 @@ pub const CLASS_TYPE_IDENTIFIER: &str = "class";
 pub const METHOD_TYPE_IDENTIFIER: &str = "method";
 
 pub fn add_type_defs(ctx: &mut CompileCtx) {
-    let mut type_defs = vec![string(), boxnum(), obj(), method(), class()];
+    let mut type_defs = vec![
+        string(),
+        symbol(),
+        boxnum(),
+        boxfloat(),
+        obj(),
+        method(),
+        class(),
+        block_fn(),
+        proc(),
+        var(),
+        range(),
+        hash(),
+        exception(),
+        jump(),
+    ];
     type_defs.append(&mut array::array_type_defs());
     type_defs.append(&mut alist::alist_type_defs());
 
@@ -25,31 +40,50 @@ pub fn add_type_defs(ctx: &mut CompileCtx) {
 // }
 
 fn string() -> TypeDef {
-    wat![ (type $str (array i8)) ]
+    wat![ (type $str (array (mut i8))) ]
+}
+
+/// Symbols are interned: two symbols are the same symbol iff they're `ref.eq`.
+fn symbol() -> TypeDef {
+    wat![ (type $sym (struct (field $name (ref $str)))) ]
 }
 
 fn boxnum() -> TypeDef {
     wat![ (type $boxnum (struct (field $val i64))) ]
 }
 
+/// Floats don't fit in an `i31`, so they're always boxed.
+fn boxfloat() -> TypeDef {
+    wat![ (type $boxfloat (struct (field $val f64))) ]
+}
+
 /// The wasm type-definition of a Ruby object.
+/// `$ivars` holds its instance variables as `[name, val, name, val, ...]`, names being symbols.
 fn obj() -> TypeDef {
     wat! {
-        (type $obj (sub (struct (field $parent (mut (ref null $class))))))
+        (type $obj
+            (sub
+                (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype))))))
     }
 }
 
 /// The wasm type-definition of a Ruby class.
 /// Each defined class (`BasicObject`, `Class`, ...)
 ///     is a global of type $class
+/// `$own_ancestors` and `$extended` are arrays of `$class`,
+///     see `Class::own_ancestors` and `Class::extended_ancestors`.
 fn class() -> TypeDef {
     wat! {
         (type $class
             (sub final $obj
                 (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype)))
                         (field $superclass (mut (ref null $class)))
                         (field $name (ref $str))
-                        (field $instance_methods (ref $alist_str_method)))))
+                        (field $instance_methods (ref $alist_sym_method))
+                        (field $own_ancestors (mut (ref $arr_unitype)))
+                        (field $extended (mut (ref $arr_unitype))))))
     }
 }
 
@@ -59,8 +93,96 @@ fn method() -> TypeDef {
     wat! {
         (type $method
             (sub final
-                (func (param $self (ref $obj))
+                (func (param $self (ref eq))
+                      (param $args (ref $arr_unitype))
+                      (param $block (ref null $proc))
+                      (result (ref eq)))))
+    }
+}
+
+/// The wasm type-definition of a block.
+/// Each block is a function subtyping $block_fn, called with the `Proc` made from it,
+///     its args, and the block passed to `Proc#call`, if any.
+fn block_fn() -> TypeDef {
+    wat! {
+        (type $block_fn
+            (sub final
+                (func (param $proc (ref $proc))
                       (param $args (ref $arr_unitype))
+                      (param $block_arg (ref null $proc))
                       (result (ref eq)))))
     }
 }
+
+/// The wasm type-definition of a Ruby `Proc`, made from a block or lambda.
+/// Holds what the block captured from where it was written:
+///     `self`, the method's block, and the boxes of the variables it uses.
+/// `$lambda` procs must be called with exactly `$arity` args.
+fn proc() -> TypeDef {
+    wat! {
+        (type $proc
+            (sub final $obj
+                (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype)))
+                        (field $func (ref $block_fn))
+                        (field $self (ref eq))
+                        (field $block (ref null $proc))
+                        (field $env (ref $arr_unitype))
+                        (field $lambda i32)
+                        (field $arity i32))))
+    }
+}
+
+/// The wasm type-definition of a Ruby `Range`.
+fn range() -> TypeDef {
+    wat! {
+        (type $range
+            (sub final $obj
+                (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype)))
+                        (field $begin (ref eq))
+                        (field $end (ref eq))
+                        (field $exclusive i32))))
+    }
+}
+
+/// The wasm type-definition of a Ruby `Hash`.
+/// `$keys` and `$vals` are parallel arrays, in insertion order.
+/// TODO: Keys are found by linear search.
+fn hash() -> TypeDef {
+    wat! {
+        (type $hash
+            (sub final $obj
+                (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype)))
+                        (field $keys (ref $arr_unitype))
+                        (field $vals (ref $arr_unitype)))))
+    }
+}
+
+/// The wasm type-definition of an instance of `Exception`, or of one of its subclasses.
+/// `$message` is `nil` if none was given, and the message is then the class's name.
+fn exception() -> TypeDef {
+    wat! {
+        (type $exception
+            (sub final $obj
+                (struct (field $parent (mut (ref null $class)))
+                        (field $ivars (mut (ref $arr_unitype)))
+                        (field $message (mut (ref eq))))))
+    }
+}
+
+/// A `break` or `return` from a block, to the function catching it whose `$target` this is.
+/// For `break`, the target is the block's `$proc`. For `return`, the method's `$return_target`.
+fn jump() -> TypeDef {
+    wat! {
+        (type $jump (struct (field $target (ref eq)) (field $val (ref eq))))
+    }
+}
+
+/// A box holding a local variable that blocks share with the function defining it.
+fn var() -> TypeDef {
+    wat! {
+        (type $var (struct (field $val (mut (ref eq)))))
+    }
+}
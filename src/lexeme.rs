@@ -14,27 +14,133 @@ pub struct Line(pub u32);
 pub struct Col(pub u32);
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize)]
-pub struct CharIdx(pub usize);
+pub struct ByteIdx(pub usize);
 
-impl Add<CharDifference> for CharIdx {
+impl Add<ByteDifference> for ByteIdx {
     type Output = Self;
 
-    fn add(self, rhs: CharDifference) -> Self::Output {
+    fn add(self, rhs: ByteDifference) -> Self::Output {
         Self(self.0 + rhs.0)
     }
 }
 
-impl AddAssign<CharDifference> for CharIdx {
-    fn add_assign(&mut self, rhs: CharDifference) {
+impl AddAssign<ByteDifference> for ByteIdx {
+    fn add_assign(&mut self, rhs: ByteDifference) {
         *self = *self + rhs;
     }
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize)]
-pub struct CharDifference(pub usize);
+pub struct ByteDifference(pub usize);
 
-pub fn text_in_range(text: &str, start_idx: CharIdx, len: CharDifference) -> String {
-    text.chars().skip(start_idx.0).take(len.0).collect()
+/// A range of source text.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize)]
+pub struct Span {
+    pub start: ByteIdx,
+    pub len: ByteDifference,
+}
+
+impl Span {
+    pub fn new(start: ByteIdx, len: ByteDifference) -> Self {
+        Self { start, len }
+    }
+
+    /// Index one past the end of the span.
+    pub fn end(self) -> ByteIdx {
+        self.start + self.len
+    }
+}
+
+/// Maps each `ByteIdx` of a text to its `Line` and `Col`.
+/// Lines are found by binary search over the line starts,
+/// then columns are counted in chars from the start of the line.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'text> {
+    text: &'text str,
+    /// Index of the first byte of each line.
+    /// `line_starts[0]` is line 1.
+    line_starts: Vec<ByteIdx>,
+}
+
+impl<'text> LineIndex<'text> {
+    pub fn new(text: &'text str) -> Self {
+        let newlines = text
+            .bytes()
+            .enumerate()
+            .filter(|&(_, b)| b == b'\n')
+            .map(|(idx, _)| ByteIdx(idx + 1));
+        let line_starts = std::iter::once(ByteIdx(0)).chain(newlines).collect();
+        Self { text, line_starts }
+    }
+
+    pub fn line_col(&self, idx: ByteIdx) -> (Line, Col) {
+        let line = self
+            .line_starts
+            .partition_point(|start| start.0 <= idx.0)
+            .max(1);
+        let line_start = self.line_starts[line - 1].0;
+        let col = self.text[line_start..idx.0].chars().count();
+        (Line(line as u32), Col(col as u32))
+    }
+
+    /// Index of the first byte of `line`.
+    pub fn line_start(&self, line: Line) -> ByteIdx {
+        self.line_starts[line.0 as usize - 1]
+    }
+
+    /// The text of `line`, without its newline.
+    pub fn line_text(&self, line: Line) -> &'text str {
+        let rest = &self.text[self.line_start(line).0..];
+        rest.split('\n').next().unwrap_or_default()
+    }
+
+    /// Formats `idx` as `file_name:line:col`, with columns starting from 1.
+    pub fn location(&self, file_name: &str, idx: ByteIdx) -> String {
+        let (Line(line), Col(col)) = self.line_col(idx);
+        format!("{}:{}:{}", file_name, line, col + 1)
+    }
+
+    /// Formats an error message with its line and column, underlining `span`:
+    /// ```text
+    /// error: unterminated string meets end of file
+    ///  --> 1:5
+    ///   |
+    /// 1 | x = "abc
+    ///   |     ^
+    /// ```
+    /// Columns are counted in chars, starting from 1.
+    pub fn render_error(&self, span: Span, message: &str) -> String {
+        let (Line(line_number), Col(col)) = self.line_col(span.start);
+        let col = col as usize;
+        let line = self.line_text(Line(line_number));
+        // Underline the chars of the error, up to the end of its first line.
+        let rest_of_line =
+            &self.text[span.start.0..self.line_start(Line(line_number)).0 + line.len()];
+        let underline_len = rest_of_line
+            .char_indices()
+            .take_while(|&(idx, _)| idx < span.len.0)
+            .count()
+            .max(1);
+
+        let gutter = " ".repeat(line_number.to_string().len());
+        format!(
+            "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}\n",
+            message,
+            gutter,
+            line_number,
+            col + 1,
+            gutter,
+            line_number,
+            line,
+            gutter,
+            " ".repeat(col),
+            "^".repeat(underline_len),
+        )
+    }
+}
+
+pub fn text_in_range(text: &str, start_idx: ByteIdx, len: ByteDifference) -> String {
+    text[start_idx.0..start_idx.0 + len.0].to_string()
 }
 
 /// A lexeme lexed from a text file.
@@ -42,34 +148,35 @@ pub fn text_in_range(text: &str, start_idx: CharIdx, len: CharDifference) -> Str
 #[derive(Debug, Eq, PartialEq, Clone, Serialize)]
 pub struct Lexeme {
     pub kind: LexemeKind,
-    pub start: CharIdx,
-    pub len: CharDifference,
+    pub start: ByteIdx,
+    pub len: ByteDifference,
 }
 
 impl Lexeme {
-    pub fn new(kind: LexemeKind, start: CharIdx, len: CharDifference) -> Self {
+    pub fn new(kind: LexemeKind, start: ByteIdx, len: ByteDifference) -> Self {
         Self { kind, start, len }
     }
 
-    /// Get the text spanned by this lexeme. O(n).
+    /// Get the text spanned by this lexeme.
     pub fn to_source(self, program_text: &str) -> String {
-        program_text
-            .chars()
-            .skip(self.start.0)
-            .take(self.len.0)
-            .collect()
+        text_in_range(program_text, self.start, self.len)
     }
 
     pub fn is_operator(&self) -> bool {
         use LexemeKind::*;
         match self.kind {
-            In | Equal | PipePipe | AmpersandAmpersand | EqualEqual | Greater | GreaterEqual
-            | Less | LessEqual | Minus | Plus | Slash | Star | BracketLeft | Dot => true,
-            _ => false,
+            Equal | DotDot | DotDotDot | PipePipe | AmpersandAmpersand | EqualEqual
+            | EqualEqualEqual | BangEqual | LessEqualGreater | Greater | GreaterEqual | Less
+            | LessEqual | Pipe | Caret | Ampersand | LessLess | GreaterGreater | Minus | Plus
+            | Slash | Star | Percent | StarStar | BracketLeft | BracketLeftRight | Dot
+            | Question | If | Unless | While | Until => true,
+            _ => self.kind.is_operator_write(),
         }
     }
 
     pub const UNARY_MINUS_BINDING_POWER: u8 = 42;
+    /// For `!`, `~` and unary `+`, which bind tighter than `**`, unlike unary `-`.
+    pub const UNARY_BINDING_POWER: u8 = 46;
     /// Gets `(lhs_binding_power, rhs_binding_power)`
     pub fn binding_power(&self) -> (u8, u8) {
         self.kind.binding_power()
@@ -94,21 +201,58 @@ pub enum LexemeKind {
     Newline,
     /// Single-line comment of the form `#[^\n]*`
     InlineComment,
+    /// Block comment, from a `=begin` line up to and including an `=end` line.
+    /// Prism splits this into `EMBDOC_BEGIN`, `EMBDOC_LINE`s and `EMBDOC_END`.
+    EmbDoc,
     /// Character literal of the form `?<CHAR>`
     CharacterLiteral,
-    /// Integer of the form `\d[\d_]+\d`
-    /// TODO: Recognize 0x, 0b, ...
+    /// Integer of the form `\d[\d_]*`, or with a radix prefix:
+    /// `0x` (hex), `0b` (binary), `0o` or `0` (octal), `0d` (decimal).
+    /// The text is kept verbatim; see `lexer::integer_value`.
     IntegerLiteral {
         text: String,
     },
-    /// Float of the form `\d[\d_]+\d(\.\d[\d_]+\d)?`
-    /// TODO: Recognize float exponents
-    FloatLiteral,
+    /// Float of the form `\d[\d_]*(\.\d[\d_]*)?([eE][+-]?\d[\d_]*)?`,
+    /// with at least one of the fractional part or exponent.
+    FloatLiteral {
+        text: String,
+    },
     /// String literal surrounded by single quotes.
     /// Allows only the escape characters `\'` and `\\`
     SingleQuoteStringLiteral {
         text: String,
     },
+    /// `"`, `%q(`, `%Q(` or `%(` opening a string literal.
+    /// Followed by any number of `StringContent` and `EmbExprBegin ... EmbExprEnd`,
+    /// then `StringEnd`.
+    StringBegin,
+    /// Literal text within a string, with its escape sequences already applied.
+    StringContent {
+        text: String,
+    },
+    /// Closes a double-quoted string, quoted symbol, or `%i` list.
+    StringEnd,
+    /// `<<ID`, `<<-ID` or `<<~ID`, opening a heredoc.
+    /// Followed by the lexemes of the heredoc's body, as for a double-quoted string,
+    /// then `HeredocEnd`, then the rest of the line the heredoc was opened on.
+    HeredocStart,
+    /// The line closing a heredoc's body.
+    HeredocEnd,
+    /// Whitespace between the elements of a `%i` list.
+    WordsSep,
+    /// Symbol literal of the form `:<IDENTIFIER>`, `:@<IDENTIFIER>`, `:$<IDENTIFIER>`,
+    /// or `:<OPERATOR>`.
+    /// `text` doesn't include the leading `:`.
+    SymbolLiteral {
+        text: String,
+    },
+    /// `:"` or `%s(` opening a quoted symbol.
+    /// Followed by the same lexemes as a double-quoted string.
+    SymbolBegin,
+    /// `#{` within a double-quoted string.
+    EmbExprBegin,
+    /// `}` closing an `EmbExprBegin`.
+    EmbExprEnd,
 
     /// Instance variable of the form `@<IDENTIFIER>`
     InstanceVariable,
@@ -215,15 +359,17 @@ pub enum LexemeKind {
     Percent,
     /// '%='
     PercentEqual,
-    /// '%i'
+    /// '%i', including the opening delimiter.
+    /// Followed by `StringContent` words separated by `WordsSep`, then `StringEnd`.
     PercentLowerI,
-    /// '%w'
+    /// '%w', including the opening delimiter. Followed by words, as for `%i`.
     PercentLowerW,
     /// '%x'
     PercentLowerX,
-    /// '%I'
+    /// '%I', including the opening delimiter.
+    /// Followed by words, as for `%i`, which may contain `EmbExprBegin ... EmbExprEnd`.
     PercentUpperI,
-    /// '%W'
+    /// '%W', including the opening delimiter. Followed by words, as for `%I`.
     PercentUpperW,
     /// '|'
     Pipe,
@@ -278,6 +424,8 @@ pub enum LexemeKind {
     For,
     In,
     If,
+    Module,
+    Next,
     Nil,
     Not,
     Or,
@@ -301,36 +449,278 @@ pub enum LexemeKind {
     UnderscoreFile,
     /// "__LINE__"
     UnderscoreLine,
+    /// "__END__", alone on a line.
+    /// It ends the program, and is followed by `Eof`.
+    /// The rest of the file is the data section; see `Lexer::data_section`.
+    UnderscoreEnd,
 }
 
 impl LexemeKind {
+    /// Can this lexeme be the end of an operand?
+    /// If so, a following `<<` is a binary operator.
+    pub fn ends_operand(&self) -> bool {
+        use LexemeKind::*;
+        matches!(
+            self,
+            IntegerLiteral { .. }
+                | FloatLiteral { .. }
+                | CharacterLiteral
+                | SingleQuoteStringLiteral { .. }
+                | StringEnd
+                | HeredocEnd
+                | SymbolLiteral { .. }
+                | InstanceVariable
+                | ClassVariable
+                | GlobalVariable { .. }
+                | Constant { .. }
+                | Identifier { .. }
+                | RightParen
+                | BracketRight
+                | BraceRight
+                | End
+                | False
+                | Nil
+                | SelfKeyword
+                | True
+                | UnderscoreEncoding
+                | UnderscoreFile
+                | UnderscoreLine
+        )
+    }
+
+    /// How the lexeme is named in syntax errors, eg. `` `end` `` or `integer`.
+    pub fn describe(&self) -> String {
+        use LexemeKind::*;
+        if let Some(spelling) = self.spelling() {
+            return format!("`{}`", spelling);
+        }
+        match self {
+            Eof => "end-of-input",
+            Newline => "newline",
+            InlineComment | EmbDoc => "comment",
+            CharacterLiteral => "character literal",
+            IntegerLiteral { .. } => "integer",
+            FloatLiteral { .. } => "float",
+            SingleQuoteStringLiteral { .. } | StringBegin | HeredocStart => "string literal",
+            StringContent { .. } => "string content",
+            StringEnd => "string end",
+            HeredocEnd => "heredoc end",
+            WordsSep => "word separator",
+            SymbolLiteral { .. } | SymbolBegin => "symbol literal",
+            InstanceVariable => "instance variable",
+            ClassVariable => "class variable",
+            GlobalVariable { .. } => "global variable",
+            Constant { .. } => "constant",
+            Identifier { .. } => "local variable or method",
+            PercentLowerI | PercentLowerW | PercentUpperI | PercentUpperW => "word list",
+            _ => unreachable!("{:?} has a fixed spelling", self),
+        }
+        .to_string()
+    }
+
+    /// Is this a keyword, like `class` or `__FILE__`?
+    pub fn is_keyword(&self) -> bool {
+        self.spelling().is_some_and(|spelling| {
+            spelling.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        })
+    }
+
+    /// The text of a lexeme which is always spelled the same way, like `end` or `+=`.
+    pub fn spelling(&self) -> Option<&'static str> {
+        use LexemeKind::*;
+        let spelling = match self {
+            EmbExprBegin => "#{",
+            EmbExprEnd => "}",
+            Ampersand => "&",
+            AmpersandAmpersand => "&&",
+            AmpersandAmpersandEqual => "&&=",
+            AmpersandEqual => "&=",
+            AmpersandPeriod => "&.",
+            Backtick => "`",
+            Bang => "!",
+            BangEqual => "!=",
+            BangTilde => "!~",
+            BraceLeft => "{",
+            BraceRight => "}",
+            BracketLeft => "[",
+            BracketLeftRight => "[]",
+            BracketLeftRightEqual => "[]=",
+            BracketRight => "]",
+            Caret => "^",
+            CaretEqual => "^=",
+            Colon => ":",
+            ColonColon => "::",
+            Comma => ",",
+            Dot => ".",
+            DotDot => "..",
+            DotDotDot => "...",
+            Equal => "=",
+            EqualEqual => "==",
+            EqualEqualEqual => "===",
+            EqualGreater => "=>",
+            EqualTilde => "=~",
+            Greater => ">",
+            GreaterEqual => ">=",
+            GreaterGreater => ">>",
+            GreaterGreaterEqual => ">>=",
+            Less => "<",
+            LessEqual => "<=",
+            LessEqualGreater => "<=>",
+            LessLess => "<<",
+            LessLessEqual => "<<=",
+            Minus => "-",
+            MinusAt => "-@",
+            MinusEqual => "-=",
+            MinusGreater => "->",
+            LeftParen => "(",
+            RightParen => ")",
+            Percent => "%",
+            PercentEqual => "%=",
+            PercentLowerX => "%x",
+            Pipe => "|",
+            PipeEqual => "|=",
+            PipePipe => "||",
+            PipePipeEqual => "||=",
+            Plus => "+",
+            PlusAt => "+@",
+            PlusEqual => "+=",
+            Question => "?",
+            Slash => "/",
+            SlashEqual => "/=",
+            Star => "*",
+            StarEqual => "*=",
+            StarStar => "**",
+            StarStarEqual => "**=",
+            TildeOrTildeAt => "~",
+            Semicolon => ";",
+            Alias => "alias",
+            And => "and",
+            Begin => "begin",
+            BeginUpperCase => "BEGIN",
+            Break => "break",
+            Case => "case",
+            Class => "class",
+            Do => "do",
+            Def => "def",
+            Else => "else",
+            Elsif => "elsif",
+            End => "end",
+            Ensure => "ensure",
+            False => "false",
+            For => "for",
+            In => "in",
+            If => "if",
+            Module => "module",
+            Next => "next",
+            Nil => "nil",
+            Not => "not",
+            Or => "or",
+            Redo => "redo",
+            Retry => "retry",
+            Rescue => "rescue",
+            Return => "return",
+            SelfKeyword => "self",
+            Super => "super",
+            True => "true",
+            Then => "then",
+            Undef => "undef",
+            Unless => "unless",
+            Until => "until",
+            When => "when",
+            While => "while",
+            Yield => "yield",
+            UnderscoreEncoding => "__ENCODING__",
+            UnderscoreFile => "__FILE__",
+            UnderscoreLine => "__LINE__",
+            UnderscoreEnd => "__END__",
+            _ => return None,
+        };
+        Some(spelling)
+    }
+
+    /// Is this a compound assignment operator, like `+=` or `||=`?
+    pub fn is_operator_write(&self) -> bool {
+        use LexemeKind::*;
+        matches!(
+            self,
+            AmpersandAmpersandEqual
+                | AmpersandEqual
+                | CaretEqual
+                | GreaterGreaterEqual
+                | LessLessEqual
+                | MinusEqual
+                | PercentEqual
+                | PipeEqual
+                | PipePipeEqual
+                | PlusEqual
+                | SlashEqual
+                | StarEqual
+                | StarStarEqual
+        )
+    }
+
     pub fn binding_power(&self) -> (u8, u8) {
         use LexemeKind::*;
         // Copied from Prism `prism.c` `pm_binding_power_t`
         // Higher rhs binding power makes it left-associative.
-        const BP_MATCH: (u8, u8) = (12, 13);
+        const BP_MODIFIER: (u8, u8) = (2, 3);
         const BP_ASSIGNMENT: (u8, u8) = (18, 18);
+        const BP_TERNARY: (u8, u8) = (20, 20);
+        const BP_RANGE: (u8, u8) = (22, 23);
         const BP_LOGICAL_OR: (u8, u8) = (24, 25);
         const BP_LOGICAL_AND: (u8, u8) = (26, 27);
         const BP_EQUALITY: (u8, u8) = (28, 29);
         const BP_COMPARISON: (u8, u8) = (30, 31);
+        const BP_BITWISE_OR: (u8, u8) = (32, 33);
+        const BP_BITWISE_AND: (u8, u8) = (34, 35);
+        const BP_SHIFT: (u8, u8) = (36, 37);
 
         const BP_TERM: (u8, u8) = (38, 39);
         const BP_FACTOR: (u8, u8) = (40, 41);
+        const BP_EXPONENT: (u8, u8) = (44, 44);
         const BP_INDEX: (u8, u8) = (48, 49);
         const BP_CALL: (u8, u8) = (50, 50);
         match self {
-            In => BP_MATCH,
+            If | Unless | While | Until => BP_MODIFIER,
             Equal => BP_ASSIGNMENT,
+            kind if kind.is_operator_write() => BP_ASSIGNMENT,
+            Question => BP_TERNARY,
+            DotDot | DotDotDot => BP_RANGE,
             PipePipe => BP_LOGICAL_OR,
             AmpersandAmpersand => BP_LOGICAL_AND,
-            EqualEqual => BP_EQUALITY,
+            EqualEqual | EqualEqualEqual | BangEqual | LessEqualGreater => BP_EQUALITY,
             Greater | GreaterEqual | Less | LessEqual => BP_COMPARISON,
+            Pipe | Caret => BP_BITWISE_OR,
+            Ampersand => BP_BITWISE_AND,
+            LessLess | GreaterGreater => BP_SHIFT,
             Minus | Plus => BP_TERM,
-            Slash | Star => BP_FACTOR,
-            BracketLeft => BP_INDEX,
+            Slash | Star | Percent => BP_FACTOR,
+            StarStar => BP_EXPONENT,
+            BracketLeft | BracketLeftRight => BP_INDEX,
             Dot => BP_CALL,
             other => panic!("Lexeme of kind {:?} has no binding power", other),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index() {
+        let line_index = LineIndex::new("ab\n\nc€d\n");
+        assert_eq!((Line(1), Col(0)), line_index.line_col(ByteIdx(0)));
+        assert_eq!((Line(1), Col(2)), line_index.line_col(ByteIdx(2)));
+        assert_eq!((Line(2), Col(0)), line_index.line_col(ByteIdx(3)));
+        assert_eq!((Line(3), Col(0)), line_index.line_col(ByteIdx(4)));
+        // Columns count chars, not bytes.
+        assert_eq!((Line(3), Col(1)), line_index.line_col(ByteIdx(5)));
+        assert_eq!((Line(3), Col(2)), line_index.line_col(ByteIdx(8)));
+        // EOF, after the final newline.
+        assert_eq!((Line(4), Col(0)), line_index.line_col(ByteIdx(10)));
+        assert_eq!(ByteIdx(4), line_index.line_start(Line(3)));
+        assert_eq!("c€d", line_index.line_text(Line(3)));
+        assert_eq!("foo.rb:3:3", line_index.location("foo.rb", ByteIdx(8)));
+    }
+}
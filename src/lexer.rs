@@ -1,5 +1,5 @@
-use crate::lexeme::{CharDifference, CharIdx, Lexeme, LexemeKind};
-use std::str::Chars;
+use crate::lexeme::{ByteDifference, ByteIdx, Lexeme, LexemeKind, LineIndex, Span};
+use std::str::CharIndices;
 
 use crate::lexeme;
 use LexemeKind::*;
@@ -8,26 +8,138 @@ use LexemeKind::*;
 /// Attempts to produce identical output to the Prism lexer.
 /// NOTE: If we find ourselves adding state to this struct, consider whether
 /// it would be better added as parser-driven input to the lex method
+/// Cloning it lets the parser look ahead, then carry on from where it was.
+#[derive(Clone)]
 pub struct Lexer<'text> {
     text: &'text str,
-    /// Peekable iterator of `(char_idx, char)`
+    /// Peekable iterator of `(byte_idx, char)`
     iter: LexerIter<'text>,
     /// Have we lexed Eof?
     lexed_eof: bool,
     /// Remember a peeked value, even if it was None.
     peeked: Option<Lexeme>,
+    /// Stack of modes. The last element is the current mode.
+    modes: Vec<LexMode>,
+    /// Problems found so far. Lexing carries on past them.
+    errors: Vec<LexError>,
+    /// The kind of the last lexeme returned, ignoring comments.
+    /// Decides whether `<<` starts a heredoc or is an operator.
+    prev_kind: Option<LexemeKind>,
+    /// Where lexing continues after the current line ends,
+    /// skipping the bodies of heredocs started on it.
+    heredoc_resume: Option<ByteIdx>,
+    /// Index of the text after an `__END__` line, if we've lexed one.
+    data_start: Option<ByteIdx>,
+}
+
+/// A malformed piece of input, found while lexing.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LexError {
+    pub start: ByteIdx,
+    pub len: ByteDifference,
+    pub message: String,
+}
+
+impl LexError {
+    /// Formats the error with its line and column, underlining it in `text`.
+    /// See `LineIndex::render_error`.
+    pub fn render(&self, text: &str) -> String {
+        LineIndex::new(text).render_error(Span::new(self.start, self.len), &self.message)
+    }
+}
+
+/// What kind of text the lexer is currently inside.
+/// Modes are kept on a stack, so that strings can nest within interpolations within strings.
+/// See `notes/Lexing/Modal Lexing.md`
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum LexMode {
+    /// Ordinary Ruby code.
+    /// Counts unclosed `{`, so we can tell which `}` closes an interpolation.
+    Code { brace_depth: usize },
+    /// The inside of a string which began at `start`:
+    /// `"..."`, `:"..."`, or a percent literal like `%q(...)`.
+    /// Counts unclosed `open` delimiters, which may nest within the string.
+    /// Escapes and `#{}` only apply if `interpolate`.
+    Quoted {
+        start: ByteIdx,
+        open: char,
+        close: char,
+        depth: usize,
+        interpolate: bool,
+    },
+    /// The inside of a `%w` or `%i` list which began at `start`.
+    /// Counts unclosed `open` delimiters, which may nest within the list.
+    /// Escapes and `#{}` only apply if `interpolate`, as in `%W` and `%I`.
+    Words {
+        start: ByteIdx,
+        open: char,
+        close: char,
+        depth: usize,
+        interpolate: bool,
+    },
+    /// The body of a heredoc, which runs up to the line starting at `body_end`.
+    /// Once the body is lexed, lexing returns to `resume`, just after the heredoc's opener.
+    /// `dedent` is the width of indentation stripped from each line of a `<<~` heredoc.
+    Heredoc {
+        interpolate: bool,
+        dedent: usize,
+        at_line_start: bool,
+        body_end: ByteIdx,
+        resume: ByteIdx,
+    },
+}
+
+impl LexMode {
+    /// The inside of a `"..."` string which began at `start`.
+    fn double_quote(start: ByteIdx) -> Self {
+        LexMode::Quoted {
+            start,
+            open: '"',
+            close: '"',
+            depth: 0,
+            interpolate: true,
+        }
+    }
 }
 
 impl<'text> Lexer<'text> {
     pub fn new(text: &'text str) -> Self {
         Self {
             text,
-            iter: LexerIter::from(text.chars()),
+            iter: LexerIter::from(text),
             lexed_eof: false,
             peeked: None,
+            modes: vec![LexMode::Code { brace_depth: 0 }],
+            errors: vec![],
+            prev_kind: None,
+            heredoc_resume: None,
+            data_start: None,
         }
     }
 
+    /// The whole text being lexed.
+    pub fn text(&self) -> &'text str {
+        self.text
+    }
+
+    /// Errors found in the lexemes returned so far.
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// The text after `__END__`, once `UnderscoreEnd` has been lexed.
+    pub fn data_section(&self) -> Option<&'text str> {
+        self.data_start.map(|start| &self.text[start.0..])
+    }
+
+    fn error(&mut self, start: ByteIdx, len: ByteDifference, message: impl Into<String>) {
+        self.errors.push(LexError {
+            start,
+            len,
+            message: message.into(),
+        });
+    }
+
     pub fn next(&mut self) -> Lexeme {
         match self.peeked.take() {
             Some(v) => v,
@@ -46,12 +158,50 @@ impl<'text> Lexer<'text> {
         }
     }
 
-    /// Consume and return a single lexeme
+    /// Consume and return a single lexeme.
+    /// Once `Eof` has been lexed, keeps returning `Eof`.
     fn lex(&mut self) -> Lexeme {
+        if self.lexed_eof {
+            return Lexeme::new(Eof, self.iter.eof_idx(), ByteDifference(0));
+        }
+        loop {
+            if let Some(lexeme) = self.try_lex() {
+                if !matches!(lexeme.kind, InlineComment | EmbDoc) {
+                    self.prev_kind = Some(lexeme.kind.clone());
+                }
+                return lexeme;
+            }
+        }
+    }
+
+    /// Consume and return a single lexeme,
+    /// or `None` if we skipped over malformed input, reporting an error.
+    fn try_lex(&mut self) -> Option<Lexeme> {
         // Strategy: For punctuation, simple matching.
 
-        if self.lexed_eof {
-            panic!();
+        match self.modes.last() {
+            Some(&LexMode::Quoted {
+                open,
+                close,
+                depth,
+                interpolate,
+                ..
+            }) => {
+                return Some(self.quoted_content(open, close, depth, interpolate));
+            }
+            Some(&LexMode::Words {
+                open,
+                close,
+                depth,
+                interpolate,
+                ..
+            }) => {
+                return Some(self.words_content(open, close, depth, interpolate));
+            }
+            Some(LexMode::Heredoc { .. }) => {
+                return Some(self.heredoc_content());
+            }
+            _ => {}
         }
 
         // Prism doesn't include whitespace in token width.
@@ -60,59 +210,96 @@ impl<'text> Lexer<'text> {
         let lexeme = match self.iter.next() {
             // Prism EOF is 0-width.
             None => {
+                self.unterminated_modes();
                 self.lexed_eof = true;
-                Lexeme::new(Eof, self.iter.eof_idx(), CharDifference(0))
+                Lexeme::new(Eof, self.iter.eof_idx(), ByteDifference(0))
             }
             Some((start_idx, c)) => match c {
                 // TODO -- Newlines are only meaningful in some locations
-                '\n' => Lexeme::new(Newline, start_idx, CharDifference(1)),
+                '\n' => {
+                    self.skip_heredoc_bodies();
+                    Lexeme::new(Newline, start_idx, ByteDifference(1))
+                }
 
                 '#' => {
-                    let final_idx = self.skip_to_next_line(start_idx);
+                    self.skip_to_line_end();
                     Lexeme::new(
                         InlineComment,
                         start_idx,
-                        len_inclusive(start_idx, final_idx),
+                        len_exclusive(start_idx, self.peek_idx()),
                     )
                 }
 
+                '=' if self.is_line_start(start_idx)
+                    && starts_with_marker(&self.text[start_idx.0 + 1..], "begin") =>
+                {
+                    self.embedded_document(start_idx)
+                }
+
+                '_' if self.is_line_start(start_idx)
+                    && is_end_marker_line(&self.text[start_idx.0..]) =>
+                {
+                    self.end_marker(start_idx)
+                }
+
                 // '?' can be the start of a character literal, or a ternary operator
                 '?' => match self.iter.peek() {
-                    None => Lexeme::new(Question, start_idx, CharDifference(1)),
+                    None => Lexeme::new(Question, start_idx, ByteDifference(1)),
                     Some((_, c)) if c.is_whitespace() => {
-                        Lexeme::new(Question, start_idx, CharDifference(1))
+                        Lexeme::new(Question, start_idx, ByteDifference(1))
                     }
                     _ => self.character_literal(start_idx),
                 },
 
-                c if c.is_ascii_digit() => self.integer_or_float(start_idx),
+                c if c.is_ascii_digit() => self.integer_or_float(start_idx, c),
 
-                '\'' => {
-                    // TODO: I think this `next()` is wrong
-                    self.iter.next();
-                    self.single_quote_string(start_idx)
+                '"' => {
+                    self.modes.push(LexMode::double_quote(start_idx));
+                    Lexeme::new(StringBegin, start_idx, ByteDifference(1))
                 }
 
-                '@' => {
-                    // TODO: I think this `next()` is wrong
-                    self.iter.next();
-                    match self.iter.peek() {
-                        Some((_, '@')) => {
-                            self.iter.next();
-                            self.class_variable(start_idx)
-                        }
-                        Some((_, c)) if !c.is_whitespace() => self.instance_variable(start_idx),
-                        Some(_) => panic!("Bare `@`"),
-                        None => panic!("Bare `@`"),
+                '\'' => self.single_quote_string(start_idx),
+
+                '@' => match (self.iter.peek(), self.iter.peek2()) {
+                    (Some((_, '@')), Some((_, c))) if is_variable_name_start(c) => {
+                        self.iter.next();
+                        self.class_variable(start_idx)
                     }
-                }
+                    (Some((_, '@')), _) => {
+                        self.iter.next();
+                        let message =
+                            "'@@' without identifiers is not allowed as a class variable name";
+                        self.error(start_idx, ByteDifference(2), message);
+                        return None;
+                    }
+                    (Some((_, c)), _) if is_variable_name_start(c) => {
+                        self.instance_variable(start_idx)
+                    }
+                    _ => {
+                        let message =
+                            "'@' without identifiers is not allowed as an instance variable name";
+                        self.error(start_idx, ByteDifference(1), message);
+                        return None;
+                    }
+                },
 
                 '$' => match self.iter.peek() {
                     Some((_, c)) if is_identifier_start(c) => self.global_variable(start_idx),
-                    Some(_) => panic!("Bare `$`"),
-                    None => panic!("Bare `$`"),
+                    _ => {
+                        let message =
+                            "'$' without identifiers is not allowed as a global variable name";
+                        self.error(start_idx, ByteDifference(1), message);
+                        return None;
+                    }
                 },
 
+                // A `\` at the end of a line continues the line.
+                '\\' if matches!(self.iter.peek(), Some((_, '\n'))) => {
+                    self.iter.next();
+                    self.skip_heredoc_bodies();
+                    return None;
+                }
+
                 // Punctuation
                 // TODO: This can definitely be replaced by a wasm_instr that generates the match chain.
                 //  Only problem would be losing error reporting.
@@ -124,76 +311,104 @@ impl<'text> Lexer<'text> {
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(AmpersandAmpersandEqual, start_idx, CharDifference(3))
+                                Lexeme::new(AmpersandAmpersandEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(AmpersandAmpersand, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(AmpersandAmpersand, start_idx, ByteDifference(2)),
                         }
                     }
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(AmpersandEqual, start_idx, CharDifference(2))
+                        Lexeme::new(AmpersandEqual, start_idx, ByteDifference(2))
                     }
                     Some((_, '.')) => {
                         self.iter.next();
-                        Lexeme::new(AmpersandPeriod, start_idx, CharDifference(2))
+                        Lexeme::new(AmpersandPeriod, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Ampersand, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Ampersand, start_idx, ByteDifference(1)),
                 },
-                '`' => Lexeme::new(Backtick, start_idx, CharDifference(1)),
+                '`' => Lexeme::new(Backtick, start_idx, ByteDifference(1)),
                 '!' => match self.iter.peek() {
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(BangEqual, start_idx, CharDifference(2))
+                        Lexeme::new(BangEqual, start_idx, ByteDifference(2))
                     }
                     Some((_, '~')) => {
                         self.iter.next();
-                        Lexeme::new(BangTilde, start_idx, CharDifference(2))
+                        Lexeme::new(BangTilde, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Bang, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Bang, start_idx, ByteDifference(1)),
                 },
-                '{' => Lexeme::new(BraceLeft, start_idx, CharDifference(1)),
-                '}' => Lexeme::new(BraceRight, start_idx, CharDifference(1)),
+                '{' => {
+                    if let Some(LexMode::Code { brace_depth }) = self.modes.last_mut() {
+                        *brace_depth += 1;
+                    }
+                    Lexeme::new(BraceLeft, start_idx, ByteDifference(1))
+                }
+                '}' => {
+                    // The bottom-most mode is top-level code, which is never popped.
+                    let is_top_level = self.is_top_level();
+                    match self.modes.last_mut() {
+                        // An unmatched `}` within an interpolation closes the interpolation.
+                        Some(LexMode::Code { brace_depth: 0 }) if !is_top_level => {
+                            self.modes.pop();
+                            Lexeme::new(EmbExprEnd, start_idx, ByteDifference(1))
+                        }
+                        Some(LexMode::Code { brace_depth }) => {
+                            *brace_depth = brace_depth.saturating_sub(1);
+                            Lexeme::new(BraceRight, start_idx, ByteDifference(1))
+                        }
+                        _ => Lexeme::new(BraceRight, start_idx, ByteDifference(1)),
+                    }
+                }
                 '[' => match self.iter.peek() {
                     Some((_, ']')) => {
                         self.iter.next();
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(BracketLeftRightEqual, start_idx, CharDifference(3))
+                                Lexeme::new(BracketLeftRightEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(BracketLeftRight, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(BracketLeftRight, start_idx, ByteDifference(2)),
                         }
                     }
-                    _ => Lexeme::new(BracketLeft, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(BracketLeft, start_idx, ByteDifference(1)),
                 },
-                ']' => Lexeme::new(BracketRight, start_idx, CharDifference(1)),
+                ']' => Lexeme::new(BracketRight, start_idx, ByteDifference(1)),
                 '^' => match self.iter.peek() {
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(CaretEqual, start_idx, CharDifference(2))
+                        Lexeme::new(CaretEqual, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Caret, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Caret, start_idx, ByteDifference(1)),
                 },
                 ':' => match self.iter.peek() {
                     Some((_, ':')) => {
                         self.iter.next();
-                        Lexeme::new(ColonColon, start_idx, CharDifference(2))
+                        Lexeme::new(ColonColon, start_idx, ByteDifference(2))
+                    }
+                    Some((_, '"')) => {
+                        self.iter.next();
+                        self.modes.push(LexMode::double_quote(start_idx));
+                        Lexeme::new(SymbolBegin, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Colon, start_idx, CharDifference(1)),
+                    _ => match self.symbol_literal(start_idx) {
+                        Some(lexeme) => lexeme,
+                        None => Lexeme::new(Colon, start_idx, ByteDifference(1)),
+                    },
                 },
-                ',' => Lexeme::new(Comma, start_idx, CharDifference(1)),
+                ',' => Lexeme::new(Comma, start_idx, ByteDifference(1)),
                 '.' => match self.iter.peek() {
                     Some((_, '.')) => {
                         self.iter.next();
                         match self.iter.peek() {
                             Some((_, '.')) => {
                                 self.iter.next();
-                                Lexeme::new(DotDotDot, start_idx, CharDifference(3))
+                                Lexeme::new(DotDotDot, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(DotDot, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(DotDot, start_idx, ByteDifference(2)),
                         }
                     }
-                    _ => Lexeme::new(Dot, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Dot, start_idx, ByteDifference(1)),
                 },
                 '=' => match self.iter.peek() {
                     Some((_, '=')) => {
@@ -201,14 +416,20 @@ impl<'text> Lexer<'text> {
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(EqualEqualEqual, start_idx, CharDifference(3))
+                                Lexeme::new(EqualEqualEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(EqualEqual, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(EqualEqual, start_idx, ByteDifference(2)),
                         }
                     }
-                    Some((_, '>')) => Lexeme::new(EqualGreater, start_idx, CharDifference(2)),
-                    Some((_, '~')) => Lexeme::new(EqualTilde, start_idx, CharDifference(2)),
-                    _ => Lexeme::new(Equal, start_idx, CharDifference(1)),
+                    Some((_, '>')) => {
+                        self.iter.next();
+                        Lexeme::new(EqualGreater, start_idx, ByteDifference(2))
+                    }
+                    Some((_, '~')) => {
+                        self.iter.next();
+                        Lexeme::new(EqualTilde, start_idx, ByteDifference(2))
+                    }
+                    _ => Lexeme::new(Equal, start_idx, ByteDifference(1)),
                 },
                 '>' => match self.iter.peek() {
                     Some((_, '>')) => {
@@ -216,13 +437,16 @@ impl<'text> Lexer<'text> {
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(GreaterGreaterEqual, start_idx, CharDifference(3))
+                                Lexeme::new(GreaterGreaterEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(GreaterGreater, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(GreaterGreater, start_idx, ByteDifference(2)),
                         }
                     }
-                    Some((_, '=')) => Lexeme::new(GreaterEqual, start_idx, CharDifference(2)),
-                    _ => Lexeme::new(Greater, start_idx, CharDifference(1)),
+                    Some((_, '=')) => {
+                        self.iter.next();
+                        Lexeme::new(GreaterEqual, start_idx, ByteDifference(2))
+                    }
+                    _ => Lexeme::new(Greater, start_idx, ByteDifference(1)),
                 },
                 '<' => match self.iter.peek() {
                     Some((_, '=')) => {
@@ -230,196 +454,293 @@ impl<'text> Lexer<'text> {
                         match self.iter.peek() {
                             Some((_, '>')) => {
                                 self.iter.next();
-                                Lexeme::new(LessEqualGreater, start_idx, CharDifference(3))
+                                Lexeme::new(LessEqualGreater, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(LessEqualGreater, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(LessEqual, start_idx, ByteDifference(2)),
                         }
                     }
-                    Some((_, '<')) => Lexeme::new(LessLess, start_idx, CharDifference(2)),
-                    _ => Lexeme::new(Less, start_idx, CharDifference(1)),
+                    Some((_, '<')) => match self.heredoc_start(start_idx) {
+                        Some(lexeme) => lexeme,
+                        None => {
+                            self.iter.next();
+                            match self.iter.peek() {
+                                Some((_, '=')) => {
+                                    self.iter.next();
+                                    Lexeme::new(LessLessEqual, start_idx, ByteDifference(3))
+                                }
+                                _ => Lexeme::new(LessLess, start_idx, ByteDifference(2)),
+                            }
+                        }
+                    },
+                    _ => Lexeme::new(Less, start_idx, ByteDifference(1)),
                 },
                 '-' => match self.iter.peek() {
-                    Some((_, '@')) => {
+                    Some((_, '@')) if !self.instance_variable_follows() => {
                         self.iter.next();
-                        Lexeme::new(MinusAt, start_idx, CharDifference(2))
+                        Lexeme::new(MinusAt, start_idx, ByteDifference(2))
                     }
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(MinusEqual, start_idx, CharDifference(2))
+                        Lexeme::new(MinusEqual, start_idx, ByteDifference(2))
                     }
                     Some((_, '>')) => {
                         self.iter.next();
-                        Lexeme::new(MinusGreater, start_idx, CharDifference(2))
+                        Lexeme::new(MinusGreater, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Minus, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Minus, start_idx, ByteDifference(1)),
                 },
-                '(' => Lexeme::new(LeftParen, start_idx, CharDifference(1)),
-                ')' => Lexeme::new(RightParen, start_idx, CharDifference(1)),
+                '(' => Lexeme::new(LeftParen, start_idx, ByteDifference(1)),
+                ')' => Lexeme::new(RightParen, start_idx, ByteDifference(1)),
                 '%' => match self.iter.peek() {
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(PercentEqual, start_idx, CharDifference(2))
-                    }
-                    Some((_, 'i')) => {
-                        self.iter.next();
-                        Lexeme::new(PercentLowerI, start_idx, CharDifference(2))
-                    }
-                    Some((_, 'w')) => {
-                        self.iter.next();
-                        Lexeme::new(PercentLowerW, start_idx, CharDifference(2))
-                    }
-                    Some((_, 'x')) => {
-                        self.iter.next();
-                        Lexeme::new(PercentLowerX, start_idx, CharDifference(2))
-                    }
-                    Some((_, 'I')) => {
-                        self.iter.next();
-                        Lexeme::new(PercentUpperI, start_idx, CharDifference(2))
-                    }
-                    Some((_, 'W')) => {
-                        self.iter.next();
-                        Lexeme::new(PercentUpperW, start_idx, CharDifference(2))
+                        Lexeme::new(PercentEqual, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Percent, start_idx, CharDifference(1)),
+                    _ if self.can_start_literal(start_idx) => self.percent_literal(start_idx),
+                    _ => Lexeme::new(Percent, start_idx, ByteDifference(1)),
                 },
                 '|' => match self.iter.peek() {
-                    Some((_, '=')) => Lexeme::new(PipeEqual, start_idx, CharDifference(2)),
+                    Some((_, '=')) => {
+                        self.iter.next();
+                        Lexeme::new(PipeEqual, start_idx, ByteDifference(2))
+                    }
                     Some((_, '|')) => {
                         self.iter.next();
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(PipePipeEqual, start_idx, CharDifference(3))
+                                Lexeme::new(PipePipeEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(PipePipe, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(PipePipe, start_idx, ByteDifference(2)),
                         }
                     }
-                    _ => Lexeme::new(Pipe, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Pipe, start_idx, ByteDifference(1)),
                 },
                 '+' => match self.iter.peek() {
-                    Some((_, '@')) => {
+                    Some((_, '@')) if !self.instance_variable_follows() => {
                         self.iter.next();
-                        Lexeme::new(PlusAt, start_idx, CharDifference(2))
+                        Lexeme::new(PlusAt, start_idx, ByteDifference(2))
                     }
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(PlusEqual, start_idx, CharDifference(2))
+                        Lexeme::new(PlusEqual, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Plus, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Plus, start_idx, ByteDifference(1)),
                 },
                 '/' => match self.iter.peek() {
                     Some((_, '=')) => {
                         self.iter.next();
-                        Lexeme::new(SlashEqual, start_idx, CharDifference(2))
+                        Lexeme::new(SlashEqual, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(Slash, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Slash, start_idx, ByteDifference(1)),
                 },
                 '*' => match self.iter.peek() {
-                    Some((_, '=')) => Lexeme::new(StarEqual, start_idx, CharDifference(2)),
+                    Some((_, '=')) => {
+                        self.iter.next();
+                        Lexeme::new(StarEqual, start_idx, ByteDifference(2))
+                    }
                     Some((_, '*')) => {
                         self.iter.next();
                         match self.iter.peek() {
                             Some((_, '=')) => {
                                 self.iter.next();
-                                Lexeme::new(StarStarEqual, start_idx, CharDifference(3))
+                                Lexeme::new(StarStarEqual, start_idx, ByteDifference(3))
                             }
-                            _ => Lexeme::new(StarStar, start_idx, CharDifference(2)),
+                            _ => Lexeme::new(StarStar, start_idx, ByteDifference(2)),
                         }
                     }
-                    _ => Lexeme::new(Star, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(Star, start_idx, ByteDifference(1)),
                 },
                 '~' => match self.iter.peek() {
                     Some((_, '@')) => {
                         self.iter.next();
-                        Lexeme::new(TildeOrTildeAt, start_idx, CharDifference(2))
+                        Lexeme::new(TildeOrTildeAt, start_idx, ByteDifference(2))
                     }
-                    _ => Lexeme::new(TildeOrTildeAt, start_idx, CharDifference(1)),
+                    _ => Lexeme::new(TildeOrTildeAt, start_idx, ByteDifference(1)),
                 },
-                ';' => Lexeme::new(Semicolon, start_idx, CharDifference(1)),
+                ';' => Lexeme::new(Semicolon, start_idx, ByteDifference(1)),
                 c if c.is_ascii_uppercase() => self.constant(start_idx),
                 c if is_identifier_start(c) => self.identifier_or_keyword(start_idx, c),
-                c => panic!("Unexpected char {}", c),
+                c => {
+                    self.error(
+                        start_idx,
+                        ByteDifference(c.len_utf8()),
+                        format!("unexpected character {:?}", c),
+                    );
+                    return None;
+                }
             },
         };
 
-        lexeme
+        Some(lexeme)
+    }
+
+    /// Reports strings and lists left open at EOF, and returns to the top-level mode.
+    fn unterminated_modes(&mut self) {
+        while let Some(mode) = self.modes.pop() {
+            match mode {
+                LexMode::Code { .. } => {}
+                LexMode::Quoted { start, .. } => self.error(
+                    start,
+                    ByteDifference(1),
+                    "unterminated string meets end of file",
+                ),
+                LexMode::Words { start, .. } => self.error(
+                    start,
+                    ByteDifference(3),
+                    "unterminated list meets end of file",
+                ),
+                // Reported when the heredoc was opened.
+                LexMode::Heredoc { .. } => {}
+            }
+        }
+        self.modes.push(LexMode::Code { brace_depth: 0 });
     }
 
     /// Lexes a character literal of the form `?<CHAR>`
     /// TODO: Allow remaining escape sequences. See Prism `lex_question_mark`
     /// Pre: `?` has been consumed.
     ///     `self.chars.peek()` is some non-whitespace character.
-    fn character_literal(&mut self, start_idx: CharIdx) -> Lexeme {
+    fn character_literal(&mut self, start_idx: ByteIdx) -> Lexeme {
         match self.iter.peek() {
-            Some((_, '\\')) => match self.iter.peek() {
-                None => panic!(),
-                Some((_, '\\')) | Some((_, 'n')) | Some((_, 't')) => {
-                    Lexeme::new(CharacterLiteral, start_idx, CharDifference(3))
+            Some((_, '\\')) => {
+                self.iter.next();
+                match self.iter.next() {
+                    Some(_) => {
+                        let len = len_exclusive(start_idx, self.peek_idx());
+                        Lexeme::new(CharacterLiteral, start_idx, len)
+                    }
+                    None => {
+                        self.error(start_idx, ByteDifference(2), "incomplete character syntax");
+                        Lexeme::new(CharacterLiteral, start_idx, ByteDifference(2))
+                    }
                 }
-                _ => panic!(),
-            },
+            }
             Some((_, c)) if !c.is_whitespace() => {
                 self.iter.next();
-                Lexeme::new(CharacterLiteral, start_idx, CharDifference(2))
+                let len = len_exclusive(start_idx, self.peek_idx());
+                Lexeme::new(CharacterLiteral, start_idx, len)
             }
             _ => unreachable!(),
         }
     }
 
-    fn integer_or_float(&mut self, start_idx: CharIdx) -> Lexeme {
-        let mut len = 1;
-        loop {
-            match self.iter.peek() {
-                // Integers and floats can contain `_`, but can't end on it.
-                // Floats also can't have an underscore directly before the period.
-                Some((_, '_')) => {
-                    len += 1;
-                    self.iter.next();
-                    match self.iter.peek() {
-                        Some((_, c)) if c.is_ascii_digit() => continue,
-                        _ => panic!(),
-                    }
-                }
-                Some((_, '.')) => {
-                    len += 1;
-                    break;
-                }
-                Some((_, c)) if c.is_ascii_digit() => {
-                    len += 1;
-                    self.iter.next();
-                }
-                Some((_, c)) if c.is_whitespace() => {
-                    return self.integer_literal(start_idx, CharDifference(len));
-                }
-                _ => {
-                    return self.integer_literal(start_idx, CharDifference(len));
-                }
+    /// Lexes an integer or float literal.
+    /// Floats have a fractional part `.\d+`, an exponent `[eE][+-]?\d+`, or both.
+    /// Integers may instead have a radix prefix, in which case they can't be floats.
+    /// Pre: The first digit has been consumed.
+    fn integer_or_float(&mut self, start_idx: ByteIdx, first: char) -> Lexeme {
+        if first == '0'
+            && let Some(radix) = self.radix_prefix()
+        {
+            return self.prefixed_integer(start_idx, radix);
+        }
+
+        self.digits(10, true);
+        let mut is_float = false;
+
+        // `1.foo` and `1..2` are method calls and ranges, so we need to see a digit after the `.`
+        if let (Some((_, '.')), Some((_, c))) = (self.iter.peek(), self.iter.peek2())
+            && c.is_ascii_digit()
+        {
+            self.iter.next();
+            self.digits(10, false);
+            is_float = true;
+        }
+
+        if let (Some((_, 'e' | 'E')), Some((_, c))) = (self.iter.peek(), self.iter.peek2())
+            && (c.is_ascii_digit() || c == '+' || c == '-')
+        {
+            self.iter.next();
+            if let Some((_, '+' | '-')) = self.iter.peek() {
+                self.iter.next();
+            }
+            if !self.digits(10, false) {
+                let idx = self.peek_idx();
+                self.error(idx, ByteDifference(0), "expected digits in float exponent");
             }
+            is_float = true;
+        }
+
+        let len = len_exclusive(start_idx, self.peek_idx());
+        if is_float {
+            let text = lexeme::text_in_range(self.text, start_idx, len);
+            Lexeme::new(FloatLiteral { text }, start_idx, len)
+        } else {
+            self.integer_literal(start_idx, len)
+        }
+    }
+
+    /// Consumes the radix prefix following a leading `0`, if there is one.
+    /// A `0` directly followed by a digit or `_` is octal, with no prefix to consume.
+    fn radix_prefix(&mut self) -> Option<Radix> {
+        let radix = match self.iter.peek() {
+            Some((_, 'x' | 'X')) => Radix::Hexadecimal,
+            Some((_, 'b' | 'B')) => Radix::Binary,
+            Some((_, 'o' | 'O')) => Radix::Octal,
+            Some((_, 'd' | 'D')) => Radix::Decimal,
+            Some((_, c)) if c.is_ascii_digit() || c == '_' => return Some(Radix::LeadingZeroOctal),
+            _ => return None,
+        };
+        self.iter.next();
+        Some(radix)
+    }
+
+    /// Lexes the digits of an integer with a radix prefix.
+    /// Pre: The leading `0` and the prefix have been consumed.
+    fn prefixed_integer(&mut self, start_idx: ByteIdx, radix: Radix) -> Lexeme {
+        let has_digits = self.digits(radix.base(), radix == Radix::LeadingZeroOctal);
+        if !has_digits {
+            let len = len_exclusive(start_idx, self.peek_idx());
+            let message = format!(
+                "invalid {} number; numeric literal without digits",
+                radix.name()
+            );
+            self.error(start_idx, len, message);
+        }
+
+        // `09` and `0b12` look like numbers, so treat them as malformed ones
+        //     rather than `0` followed by `9`, or `0b1` followed by `2`.
+        if matches!(
+            radix,
+            Radix::Octal | Radix::LeadingZeroOctal | Radix::Binary
+        ) && let Some((idx, c)) = self.iter.peek()
+            && c.is_ascii_digit()
+        {
+            self.digits(10, true);
+            let len = len_exclusive(idx, self.peek_idx());
+            self.error(idx, len, format!("invalid {} digit", radix.name()));
         }
 
-        // TODO: This is a float, or a property access/method call
-        //  Determining which one requires >1 char lookahead...
+        let len = len_exclusive(start_idx, self.peek_idx());
+        self.integer_literal(start_idx, len)
+    }
 
-        // Assume this is a float
+    /// Consume digits in base `radix`, which may be separated by single `_`s.
+    /// `seen_digit` says whether a digit has already been consumed, so an `_` may come next.
+    /// Returns whether the number has any digits.
+    fn digits(&mut self, radix: u32, mut seen_digit: bool) -> bool {
         loop {
             match self.iter.peek() {
-                // Floats can contain `_` in the decimal part, but can't end on it.
-                Some((_, '_')) => {
-                    len += 1;
+                Some((idx, '_')) => {
                     self.iter.next();
                     match self.iter.peek() {
-                        Some((_, c)) if c.is_ascii_digit() => continue,
-                        _ => panic!(),
+                        Some((_, c)) if c.is_digit(radix) && seen_digit => {}
+                        Some((_, c)) if c.is_digit(radix) || c == '_' => {
+                            while let Some((_, '_')) = self.iter.peek() {
+                                self.iter.next();
+                            }
+                            let len = len_exclusive(idx, self.peek_idx());
+                            self.error(idx, len, "invalid underscore placement in number");
+                        }
+                        _ => self.error(idx, ByteDifference(1), "trailing '_' in number"),
                     }
                 }
-                Some((_, c)) if c.is_ascii_digit() => {
-                    len += 1;
+                Some((_, c)) if c.is_digit(radix) => {
                     self.iter.next();
+                    seen_digit = true;
                 }
-                Some((_, c)) if c.is_whitespace() => {
-                    return Lexeme::new(FloatLiteral, start_idx, CharDifference(len));
-                }
-                _ => panic!(),
+                _ => return seen_digit,
             }
         }
     }
@@ -427,120 +748,677 @@ impl<'text> Lexer<'text> {
     /// Lexes a string surrounded by single quotes.
     /// Allows only the escape characters `\'` and `\\`
     /// Pre: `'` has been consumed.
-    fn single_quote_string(&mut self, start_idx: CharIdx) -> Lexeme {
-        loop {
+    fn single_quote_string(&mut self, start_idx: ByteIdx) -> Lexeme {
+        let len = loop {
             match self.iter.next() {
-                None => panic!("Unterminated single-quote string literal"),
                 // Consume `\'` without returning.
-                Some((_, '\\')) => match self.iter.next() {
-                    None => panic!("Unterminated single-quote string literal"),
-                    Some(_) => (),
-                },
-                Some((idx, '\'')) => {
-                    let len = len_inclusive(start_idx, idx);
-                    let lexeme_text = lexeme::text_in_range(self.text, start_idx, len);
-                    return Lexeme::new(
-                        SingleQuoteStringLiteral { text: lexeme_text },
-                        start_idx,
-                        len,
-                    );
+                Some((_, '\\')) => {
+                    self.iter.next();
                 }
+                Some((_, '\'')) => break len_exclusive(start_idx, self.peek_idx()),
                 Some(_) => (),
-            };
-        }
+                None => {
+                    let message = "unterminated string meets end of file";
+                    self.error(start_idx, ByteDifference(1), message);
+                    break len_exclusive(start_idx, self.iter.eof_idx());
+                }
+            }
+        };
+        let lexeme_text = lexeme::text_in_range(self.text, start_idx, len);
+        Lexeme::new(
+            SingleQuoteStringLiteral { text: lexeme_text },
+            start_idx,
+            len,
+        )
     }
 
-    /// Lexes an instance variable of the form `@<IDENTIFIER>`
-    /// Pre: `@` has been consumed.
-    /// TODO: This identifier-recognition code is duplicated like 4 times.
-    ///     But I'm afraid it might eventually be different between identifier types?
-    ///     Ruby _would_ do something obnoxious like that.
-    fn instance_variable(&mut self, start_idx: CharIdx) -> Lexeme {
-        let mut len = 1;
+    /// Lexes the next piece of a string:
+    /// one of `StringContent`, `EmbExprBegin`, or `StringEnd`.
+    /// Without `interpolate`, the only escapes are `\\` and escaped delimiters,
+    ///     as in a single-quoted string.
+    /// Pre: `self.modes.last()` is `Some(LexMode::Quoted { .. })`
+    fn quoted_content(
+        &mut self,
+        open: char,
+        close: char,
+        depth: usize,
+        interpolate: bool,
+    ) -> Lexeme {
+        let start_idx = match self.iter.peek() {
+            None => return self.unterminated_string_end(),
+            Some((idx, c)) if c == close && depth == 0 => {
+                self.iter.next();
+                self.modes.pop();
+                return Lexeme::new(StringEnd, idx, ByteDifference(1));
+            }
+            Some((idx, '#')) if interpolate && self.iter.starts_with("#{") => {
+                self.iter.next();
+                self.iter.next();
+                self.modes.push(LexMode::Code { brace_depth: 0 });
+                return Lexeme::new(EmbExprBegin, idx, ByteDifference(2));
+            }
+            Some((idx, _)) => idx,
+        };
 
+        let mut depth = depth;
+        let mut text = String::new();
         loop {
             match self.iter.peek() {
-                Some((_, c)) if is_identifier_char(c) => {
-                    len += 1;
+                None => break,
+                Some((_, c)) if c == close && depth == 0 => break,
+                Some((_, '#')) if interpolate && self.iter.starts_with("#{") => break,
+                Some((idx, '\\')) if interpolate => {
+                    self.iter.next();
+                    self.escape_sequence(idx, &mut text);
+                }
+                Some((_, '\\')) => {
+                    self.iter.next();
+                    match self.iter.next() {
+                        Some((_, c)) if c == '\\' || c == open || c == close => text.push(c),
+                        Some((_, c)) => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                        None => text.push('\\'),
+                    }
+                }
+                Some((_, c)) => {
                     self.iter.next();
+                    depth = nesting_depth(depth, c, open, close);
+                    text.push(c);
                 }
-                _ => return Lexeme::new(InstanceVariable, start_idx, CharDifference(len)),
             }
         }
-    }
+        self.set_delimiter_depth(depth);
 
-    /// Lexes a class variable of the form `@@<IDENTIFIER>`
-    /// Pre: `@@` has been consumed.
-    fn class_variable(&mut self, start_idx: CharIdx) -> Lexeme {
-        let mut len = 1;
+        let len = len_exclusive(start_idx, self.peek_idx());
+        Lexeme::new(StringContent { text }, start_idx, len)
+    }
 
-        loop {
-            match self.iter.peek() {
-                Some((_, c)) if is_identifier_char(c) => {
-                    len += 1;
-                    self.iter.next();
-                }
-                _ => return Lexeme::new(ClassVariable, start_idx, CharDifference(len)),
+    /// Lexes a percent literal: `%w[...]`, `%i[...]`, `%q(...)`, `%Q(...)`, `%(...)` or `%s(...)`.
+    /// Any punctuation may be the delimiter. Brackets are closed by their pair, and nest.
+    /// Returns `Percent` if there's no delimiter, as in `a %b`.
+    /// Pre: `%` has been consumed.
+    fn percent_literal(&mut self, start_idx: ByteIdx) -> Lexeme {
+        let letter = match (self.iter.peek(), self.iter.peek2()) {
+            (Some((_, c)), _) if closing_delimiter(c).is_some() => None,
+            (Some((_, c)), Some((_, open)))
+                if c.is_ascii_alphabetic() && closing_delimiter(open).is_some() =>
+            {
+                self.iter.next();
+                Some(c)
             }
-        }
+            _ => return Lexeme::new(Percent, start_idx, ByteDifference(1)),
+        };
+        let (_, open) = self.iter.next().unwrap();
+        let close = closing_delimiter(open).unwrap();
+        let quoted = |interpolate| LexMode::Quoted {
+            start: start_idx,
+            open,
+            close,
+            depth: 0,
+            interpolate,
+        };
+        let words = |interpolate| LexMode::Words {
+            start: start_idx,
+            open,
+            close,
+            depth: 0,
+            interpolate,
+        };
+        let (kind, mode) = match letter {
+            None | Some('Q') => (StringBegin, quoted(true)),
+            Some('q') => (StringBegin, quoted(false)),
+            Some('s') => (SymbolBegin, quoted(false)),
+            Some('w') => (PercentLowerW, words(false)),
+            Some('W') => (PercentUpperW, words(true)),
+            Some('i') => (PercentLowerI, words(false)),
+            Some('I') => (PercentUpperI, words(true)),
+            Some(_) => {
+                // Recover by lexing it as a `%q` string.
+                self.error(start_idx, ByteDifference(2), "unknown type of %string");
+                (StringBegin, quoted(false))
+            }
+        };
+        self.modes.push(mode);
+        Lexeme::new(kind, start_idx, len_exclusive(start_idx, self.peek_idx()))
     }
 
-    /// Lexes a global variable of the form `$<IDENTIFIER>`
-    /// Pre: `$` has been consumed
-    fn global_variable(&mut self, start_idx: CharIdx) -> Lexeme {
-        let mut len = 1;
+    /// Record the nesting depth of delimiters in the current `Quoted` or `Words` mode.
+    fn set_delimiter_depth(&mut self, new_depth: usize) {
+        if let Some(LexMode::Quoted { depth, .. } | LexMode::Words { depth, .. }) =
+            self.modes.last_mut()
+        {
+            *depth = new_depth;
+        }
+    }
 
-        loop {
-            let c = self.iter.peek();
-            match c {
-                Some((_, c)) if is_identifier_char(c) => {
-                    len += 1;
-                    self.iter.next();
-                }
-                _ => {
-                    let lexeme_text =
-                        lexeme::text_in_range(self.text, start_idx, CharDifference(len));
-                    return Lexeme::new(
-                        GlobalVariable { text: lexeme_text },
-                        start_idx,
-                        CharDifference(len),
-                    );
+    /// Lexes the opener of a heredoc, `<<ID`, `<<-ID` or `<<~ID`,
+    /// if one follows and `<<` can't be an operator here.
+    /// `ID` may be quoted: `<<~'ID'` disables interpolation and escapes.
+    /// The heredoc's body starts on the line after the opener,
+    /// or after the body of the previous heredoc opened on the same line.
+    /// Lexing jumps to the body, and returns to just after the opener once the body is lexed.
+    /// Pre: The first `<` has been consumed, and `self.iter.peek()` is the second.
+    fn heredoc_start(&mut self, start_idx: ByteIdx) -> Option<Lexeme> {
+        if !self.can_start_literal(start_idx) {
+            return None;
+        }
+        let opener = &self.text[start_idx.0 + 2..];
+        let (squiggly, dash, rest) = match opener.chars().next() {
+            Some('~') => (true, false, &opener[1..]),
+            Some('-') => (false, true, &opener[1..]),
+            _ => (false, false, opener),
+        };
+        let (id, interpolate, id_len) = match rest.chars().next()? {
+            quote @ ('\'' | '"') => {
+                let id = rest[1..].split(['\n', quote]).next()?;
+                if !rest[1 + id.len()..].starts_with(quote) {
+                    return None;
                 }
+                (id, quote == '"', id.len() + 2)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let len = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..len], true, len)
+            }
+            _ => return None,
+        };
+        let len = ByteDifference(opener.len() - rest.len() + id_len + 2);
+        let resume = start_idx + len;
+
+        let body_start = match self.heredoc_resume.take() {
+            Some(idx) => idx,
+            None => match self.text[resume.0..].find('\n') {
+                Some(newline) => ByteIdx(resume.0 + newline + 1),
+                None => self.iter.eof_idx_of_text(),
+            },
+        };
+        let body_end = match heredoc_terminator(self.text, body_start, id, squiggly || dash) {
+            Some(idx) => idx,
+            None => {
+                let message = format!(
+                    "unterminated heredoc; can't find string {:?} anywhere before EOF",
+                    id
+                );
+                self.error(start_idx, len, message);
+                self.iter.eof_idx_of_text()
             }
+        };
+        let dedent = if squiggly {
+            heredoc_dedent(&self.text[body_start.0..body_end.0])
+        } else {
+            0
+        };
+
+        self.modes.push(LexMode::Heredoc {
+            interpolate,
+            dedent,
+            at_line_start: true,
+            body_end,
+            resume,
+        });
+        self.iter.jump_to(body_start);
+        Some(Lexeme::new(HeredocStart, start_idx, len))
+    }
+
+    /// Is the next `@` the start of an instance or class variable, as in `-@x`, rather than part of `-@`?
+    fn instance_variable_follows(&mut self) -> bool {
+        self.iter
+            .peek2()
+            .is_some_and(|(_, c)| is_variable_name_start(c) || c == '@')
+    }
+
+    /// Can a `<<` or `%` at `start_idx` open a heredoc or percent literal,
+    /// rather than being an operator?
+    /// It can't follow an operand, as in `x << y` or `x % y`.
+    /// But `puts <<~EOS` is a method call with a heredoc argument,
+    ///     so an identifier followed by a space and `<<` without one starts a heredoc.
+    ///     Ruby decides this by whether the identifier is a local variable.
+    fn can_start_literal(&self, start_idx: ByteIdx) -> bool {
+        match &self.prev_kind {
+            Some(Identifier { .. }) => self.text[..start_idx.0].ends_with([' ', '\t']),
+            Some(kind) => !kind.ends_operand(),
+            None => true,
         }
     }
 
-    /// Lexes a constant of the form `[A-Z]<IDENTIFIER>`
-    /// Pre: Initial uppercase character has been consumed.
-    fn constant(&mut self, start_idx: CharIdx) -> Lexeme {
-        let mut len = 1;
+    /// Lexes the next piece of a heredoc body:
+    /// one of `StringContent`, `EmbExprBegin`, or `HeredocEnd`.
+    /// Each line of the body is a separate `StringContent`,
+    ///     with its indentation stripped in a `<<~` heredoc.
+    /// Pre: `self.modes.last()` is `Some(LexMode::Heredoc { .. })`
+    fn heredoc_content(&mut self) -> Lexeme {
+        let Some(&LexMode::Heredoc {
+            interpolate,
+            dedent,
+            at_line_start,
+            body_end,
+            resume,
+        }) = self.modes.last()
+        else {
+            unreachable!()
+        };
 
-        loop {
+        if self.peek_idx() == body_end {
+            self.modes.pop();
+            let terminator_line = &self.text[body_end.0..];
+            let len = terminator_line
+                .find('\n')
+                .map_or(terminator_line.len(), |newline| newline + 1);
+            self.heredoc_resume = Some(body_end + ByteDifference(len));
+            self.iter.jump_to(resume);
+            return Lexeme::new(HeredocEnd, body_end, ByteDifference(len));
+        }
+
+        if at_line_start {
+            self.skip_indentation(dedent);
+            self.set_heredoc_line_start(false);
+        }
+        let start_idx = self.peek_idx();
+        if interpolate && self.iter.starts_with("#{") {
+            self.iter.next();
+            self.iter.next();
+            self.modes.push(LexMode::Code { brace_depth: 0 });
+            return Lexeme::new(EmbExprBegin, start_idx, ByteDifference(2));
+        }
+
+        let mut text = String::new();
+        while self.peek_idx() != body_end {
             match self.iter.peek() {
-                Some((_, c)) if is_identifier_char(c) => {
-                    len += 1;
+                None => break,
+                Some((_, '\n')) => {
+                    self.iter.next();
+                    text.push('\n');
+                    self.set_heredoc_line_start(true);
+                    break;
+                }
+                Some((_, '#')) if interpolate && self.iter.starts_with("#{") => break,
+                Some((idx, '\\')) if interpolate => {
                     self.iter.next();
+                    self.escape_sequence(idx, &mut text);
                 }
-                _ => {
-                    let lexeme_text =
-                        lexeme::text_in_range(self.text, start_idx, CharDifference(len));
-                    return Lexeme::new(
-                        Constant { text: lexeme_text },
-                        start_idx,
-                        CharDifference(len),
-                    );
+                Some((_, c)) => {
+                    self.iter.next();
+                    text.push(c);
                 }
             }
         }
+
+        let len = ByteDifference(self.peek_idx().0 - start_idx.0);
+        Lexeme::new(StringContent { text }, start_idx, len)
     }
 
-    /// Lexes an identifier or a keyword starting with one of:
-    /// - A lowercase letter
-    /// - `_`
-    /// Pre: `first_character` has been consumed.
-    fn identifier_or_keyword(&mut self, start_idx: CharIdx, first_character: char) -> Lexeme {
-        // Strategy taken from Robert Nystrom -- Crafting Interpreters chapter 16
-        // We use nested cases until we reach non-ambiguity between keywords.
+    /// Skip up to `width` columns of indentation. Tabs advance to the next multiple of 8.
+    fn skip_indentation(&mut self, width: usize) {
+        let mut skipped = 0;
+        while let Some((_, c @ (' ' | '\t'))) = self.iter.peek() {
+            let next = if c == '\t' {
+                skipped / 8 * 8 + 8
+            } else {
+                skipped + 1
+            };
+            if next > width {
+                break;
+            }
+            skipped = next;
+            self.iter.next();
+        }
+    }
+
+    fn set_heredoc_line_start(&mut self, value: bool) {
+        if let Some(LexMode::Heredoc { at_line_start, .. }) = self.modes.last_mut() {
+            *at_line_start = value;
+        }
+    }
+
+    /// At the end of a line which opened heredocs, jump past their bodies.
+    fn skip_heredoc_bodies(&mut self) {
+        if let Some(idx) = self.heredoc_resume.take() {
+            self.iter.jump_to(idx);
+        }
+    }
+
+    /// Reports the string or list we're inside as unterminated,
+    /// and ends it with a zero-width `StringEnd`.
+    /// Pre: EOF has been reached inside a `Quoted` or `Words` mode.
+    fn unterminated_string_end(&mut self) -> Lexeme {
+        let (start, len, message) = match self.modes.pop() {
+            Some(LexMode::Quoted { start, .. }) => {
+                (start, 1, "unterminated string meets end of file")
+            }
+            Some(LexMode::Words { start, .. }) => (start, 3, "unterminated list meets end of file"),
+            _ => unreachable!(),
+        };
+        self.error(start, ByteDifference(len), message);
+        Lexeme::new(StringEnd, self.iter.eof_idx(), ByteDifference(0))
+    }
+
+    /// Lexes an escape sequence within a double-quoted string,
+    /// pushing the escaped character(s) onto `text`.
+    /// Malformed escapes are reported, and push nothing.
+    /// TODO: `\c`, `\C-` and `\M-` control and meta escapes.
+    /// TODO: `\x` and octal escapes should produce raw bytes, not Latin-1 chars.
+    /// Pre: `\` at `start_idx` has been consumed.
+    fn escape_sequence(&mut self, start_idx: ByteIdx, text: &mut String) {
+        // The string will be reported as unterminated.
+        let Some((_, c)) = self.iter.next() else {
+            return;
+        };
+        match c {
+            'n' => text.push('\n'),
+            't' => text.push('\t'),
+            's' => text.push(' '),
+            'r' => text.push('\r'),
+            'a' => text.push('\x07'),
+            'b' => text.push('\x08'),
+            'e' => text.push('\x1b'),
+            'f' => text.push('\x0c'),
+            'v' => text.push('\x0b'),
+            // Escaped newline continues the string on the next line.
+            '\n' => {}
+            // `\nnn`: Up to 3 octal digits
+            '0'..='7' => {
+                let mut val = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match self.iter.peek() {
+                        Some((_, c)) if c.is_digit(8) => {
+                            self.iter.next();
+                            val = val * 8 + c.to_digit(8).unwrap();
+                        }
+                        _ => break,
+                    }
+                }
+                text.push(char::from((val & 0xFF) as u8));
+            }
+            // `\xHH`: Up to 2 hex digits
+            'x' => match self.hex_digits(2) {
+                (_, 0) => self.escape_error(start_idx, "invalid hex escape"),
+                (val, _) => text.push(char::from(val as u8)),
+            },
+            // `\uHHHH` or `\u{H+ H+ ...}`
+            'u' => match self.iter.peek() {
+                Some((_, '{')) => {
+                    self.iter.next();
+                    loop {
+                        while let Some((_, ' ' | '\t')) = self.iter.peek() {
+                            self.iter.next();
+                        }
+                        if let Some((_, '}')) = self.iter.peek() {
+                            self.iter.next();
+                            break;
+                        }
+                        match self.hex_digits(6) {
+                            (_, 0) => {
+                                self.escape_error(start_idx, "invalid Unicode escape");
+                                break;
+                            }
+                            (val, _) => self.push_codepoint(start_idx, val, text),
+                        }
+                    }
+                }
+                _ => match self.hex_digits(4) {
+                    (val, 4) => self.push_codepoint(start_idx, val, text),
+                    _ => self.escape_error(start_idx, "invalid Unicode escape"),
+                },
+            },
+            // Any other escaped character is itself.
+            c => text.push(c),
+        }
+    }
+
+    fn push_codepoint(&mut self, start_idx: ByteIdx, val: u32, text: &mut String) {
+        match char::from_u32(val) {
+            Some(c) => text.push(c),
+            None => self.escape_error(start_idx, "invalid Unicode codepoint"),
+        }
+    }
+
+    /// Reports the escape sequence from `start_idx` up to the next char.
+    fn escape_error(&mut self, start_idx: ByteIdx, message: &str) {
+        let len = len_exclusive(start_idx, self.peek_idx());
+        self.error(start_idx, len, message);
+    }
+
+    /// Consume up to `max_len` hex digits.
+    /// Returns `(value, number_of_digits_consumed)`.
+    fn hex_digits(&mut self, max_len: usize) -> (u32, usize) {
+        let mut val = 0;
+        let mut len = 0;
+        while len < max_len {
+            match self.iter.peek() {
+                Some((_, c)) if c.is_ascii_hexdigit() => {
+                    self.iter.next();
+                    val = val * 16 + c.to_digit(16).unwrap();
+                    len += 1;
+                }
+                _ => break,
+            }
+        }
+        (val, len)
+    }
+
+    /// Lexes the next element of a `%w` or `%i` list.
+    /// A `close` delimiter only ends the list if it isn't matching a nested `open`.
+    /// Escaped whitespace and delimiters are included in the element.
+    fn words_content(
+        &mut self,
+        open: char,
+        close: char,
+        depth: usize,
+        interpolate: bool,
+    ) -> Lexeme {
+        let start_idx = match self.iter.peek() {
+            Some((idx, _)) => idx,
+            None => return self.unterminated_string_end(),
+        };
+
+        if self.iter.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+            while self.iter.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+                self.iter.next();
+            }
+            let len = len_exclusive(start_idx, self.peek_idx());
+            return Lexeme::new(WordsSep, start_idx, len);
+        }
+
+        if let Some((_, c)) = self.iter.peek()
+            && c == close
+            && depth == 0
+        {
+            self.iter.next();
+            self.modes.pop();
+            return Lexeme::new(StringEnd, start_idx, ByteDifference(1));
+        }
+        if interpolate && self.iter.starts_with("#{") {
+            self.iter.next();
+            self.iter.next();
+            self.modes.push(LexMode::Code { brace_depth: 0 });
+            return Lexeme::new(EmbExprBegin, start_idx, ByteDifference(2));
+        }
+
+        let mut depth = depth;
+        let mut text = String::new();
+        loop {
+            match self.iter.peek() {
+                None => break,
+                Some((_, c)) if c.is_whitespace() => break,
+                Some((_, c)) if c == close && depth == 0 => break,
+                Some((_, '#')) if interpolate && self.iter.starts_with("#{") => break,
+                Some((idx, '\\')) if interpolate => {
+                    self.iter.next();
+                    self.escape_sequence(idx, &mut text);
+                }
+                Some((_, '\\')) => {
+                    self.iter.next();
+                    match self.iter.next() {
+                        Some((_, c)) if c.is_whitespace() || c == open || c == close => {
+                            text.push(c)
+                        }
+                        Some((_, c)) => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                        None => text.push('\\'),
+                    }
+                }
+                Some((_, c)) => {
+                    self.iter.next();
+                    depth = nesting_depth(depth, c, open, close);
+                    text.push(c);
+                }
+            }
+        }
+        self.set_delimiter_depth(depth);
+
+        let len = len_exclusive(start_idx, self.peek_idx());
+        Lexeme::new(StringContent { text }, start_idx, len)
+    }
+
+    /// Lexes a symbol literal of the form `:<IDENTIFIER>` or `:<OPERATOR>`,
+    ///     if one follows.
+    /// Pre: `:` has been consumed.
+    fn symbol_literal(&mut self, start_idx: ByteIdx) -> Option<Lexeme> {
+        let mut text = String::new();
+        match self.iter.peek() {
+            Some((_, c)) if is_identifier_start(c) || c.is_uppercase() => {
+                self.identifier_chars(&mut text);
+                // Setter names, `:foo=`. But not `:foo==`, or the `=>` of a hash.
+                match (self.iter.peek(), self.iter.peek2()) {
+                    (Some((_, '!')), _) => {
+                        self.iter.next();
+                        text.push('!');
+                    }
+                    (Some((_, '=')), Some((_, '=' | '~' | '>'))) => {}
+                    (Some((_, '=')), _) => {
+                        self.iter.next();
+                        text.push('=');
+                    }
+                    _ => {}
+                }
+            }
+            Some((_, '@')) => {
+                self.iter.next();
+                text.push('@');
+                if let Some((_, '@')) = self.iter.peek() {
+                    self.iter.next();
+                    text.push('@');
+                }
+                self.identifier_chars(&mut text);
+            }
+            Some((_, '$')) => {
+                self.iter.next();
+                text.push('$');
+                self.identifier_chars(&mut text);
+            }
+            _ => {
+                let op = OPERATOR_METHOD_NAMES
+                    .iter()
+                    .find(|op| self.iter.starts_with(op))?;
+                for _ in op.chars() {
+                    self.iter.next();
+                }
+                text.push_str(op);
+            }
+        }
+
+        let len = len_exclusive(start_idx, self.peek_idx());
+        Some(Lexeme::new(SymbolLiteral { text }, start_idx, len))
+    }
+
+    /// Consume identifier characters, pushing them onto `text`.
+    fn identifier_chars(&mut self, text: &mut String) {
+        while let Some((_, c)) = self.iter.peek()
+            && is_identifier_char(c)
+        {
+            self.iter.next();
+            text.push(c);
+        }
+    }
+
+    fn is_top_level(&self) -> bool {
+        self.modes.len() == 1
+    }
+
+    /// Index of the next char, or of EOF if there are no chars left.
+    fn peek_idx(&mut self) -> ByteIdx {
+        match self.iter.peek() {
+            Some((idx, _)) => idx,
+            None => self.iter.eof_idx(),
+        }
+    }
+
+    /// Lexes an instance variable of the form `@<IDENTIFIER>`
+    /// Pre: `@` has been consumed.
+    /// TODO: This identifier-recognition code is duplicated like 4 times.
+    ///     But I'm afraid it might eventually be different between identifier types?
+    ///     Ruby _would_ do something obnoxious like that.
+    fn instance_variable(&mut self, start_idx: ByteIdx) -> Lexeme {
+        while let Some((_, c)) = self.iter.peek() {
+            if !is_identifier_char(c) {
+                break;
+            }
+            self.iter.next();
+        }
+        Lexeme::new(
+            InstanceVariable,
+            start_idx,
+            len_exclusive(start_idx, self.peek_idx()),
+        )
+    }
+
+    /// Lexes a class variable of the form `@@<IDENTIFIER>`
+    /// Pre: `@@` has been consumed.
+    fn class_variable(&mut self, start_idx: ByteIdx) -> Lexeme {
+        while let Some((_, c)) = self.iter.peek() {
+            if !is_identifier_char(c) {
+                break;
+            }
+            self.iter.next();
+        }
+        Lexeme::new(
+            ClassVariable,
+            start_idx,
+            len_exclusive(start_idx, self.peek_idx()),
+        )
+    }
+
+    /// Lexes a global variable of the form `$<IDENTIFIER>`
+    /// Pre: `$` has been consumed
+    fn global_variable(&mut self, start_idx: ByteIdx) -> Lexeme {
+        while let Some((_, c)) = self.iter.peek() {
+            if !is_identifier_char(c) {
+                break;
+            }
+            self.iter.next();
+        }
+        let len = len_exclusive(start_idx, self.peek_idx());
+        let lexeme_text = lexeme::text_in_range(self.text, start_idx, len);
+        Lexeme::new(GlobalVariable { text: lexeme_text }, start_idx, len)
+    }
+
+    /// Lexes a constant of the form `[A-Z]<IDENTIFIER>`
+    /// Pre: Initial uppercase character has been consumed.
+    fn constant(&mut self, start_idx: ByteIdx) -> Lexeme {
+        while let Some((_, c)) = self.iter.peek() {
+            if !is_identifier_char(c) {
+                break;
+            }
+            self.iter.next();
+        }
+        let len = len_exclusive(start_idx, self.peek_idx());
+        let lexeme_text = lexeme::text_in_range(self.text, start_idx, len);
+        Lexeme::new(Constant { text: lexeme_text }, start_idx, len)
+    }
+
+    /// Lexes an identifier or a keyword starting with one of:
+    /// - A lowercase letter
+    /// - `_`
+    /// Pre: `first_character` has been consumed.
+    fn identifier_or_keyword(&mut self, start_idx: ByteIdx, first_character: char) -> Lexeme {
+        // Strategy taken from Robert Nystrom -- Crafting Interpreters chapter 16
+        // We use nested cases until we reach non-ambiguity between keywords.
         // Then we check against that keyword in particular.
 
         // len includes 1 for `first_character`
@@ -568,7 +1446,7 @@ impl<'text> Lexer<'text> {
                         Some((_, c)) if is_identifier_char(c) => None,
                         _ => {
                             self.iter.next();
-                            Some(Lexeme::new(Do, start_idx, CharDifference(2)))
+                            Some(Lexeme::new(Do, start_idx, ByteDifference(2)))
                         }
                     }
                 }
@@ -581,18 +1459,9 @@ impl<'text> Lexer<'text> {
                         Some((_, 's')) => {
                             self.iter.next();
                             match self.iter.peek() {
-                                Some((_, 'e')) => {
-                                    self.iter.next();
-                                    match self.iter.peek() {
-                                        Some((_, 'i')) => {
-                                            self.check_rest_of_keyword("if", Elsif, start_idx)
-                                        }
-                                        Some((_, c)) if is_identifier_char(c) => None,
-                                        _ => {
-                                            self.iter.next();
-                                            Some(Lexeme::new(Else, start_idx, CharDifference(4)))
-                                        }
-                                    }
+                                Some((_, 'e')) => self.check_rest_of_keyword("e", Else, start_idx),
+                                Some((_, 'i')) => {
+                                    self.check_rest_of_keyword("if", Elsif, start_idx)
                                 }
                                 _ => None,
                             }
@@ -621,6 +1490,7 @@ impl<'text> Lexer<'text> {
                 _ => None,
             },
             'n' => match self.iter.peek() {
+                Some((_, 'e')) => self.check_rest_of_keyword("ext", Next, start_idx),
                 Some((_, 'i')) => self.check_rest_of_keyword("il", Nil, start_idx),
                 Some((_, 'o')) => self.check_rest_of_keyword("ot", Not, start_idx),
                 _ => None,
@@ -649,6 +1519,7 @@ impl<'text> Lexer<'text> {
                 }
                 _ => None,
             },
+            'm' => self.check_rest_of_keyword("odule", Module, start_idx),
             's' => match self.iter.peek() {
                 Some((_, 'e')) => self.check_rest_of_keyword("elf", SelfKeyword, start_idx),
                 Some((_, 'u')) => self.check_rest_of_keyword("uper", Super, start_idx),
@@ -733,7 +1604,7 @@ impl<'text> Lexer<'text> {
         &mut self,
         expected: &str,
         kind: LexemeKind,
-        start_idx: CharIdx,
+        start_idx: ByteIdx,
     ) -> Option<Lexeme> {
         for expected_char in expected.chars() {
             match self.iter.peek() {
@@ -756,24 +1627,55 @@ impl<'text> Lexer<'text> {
         }
     }
 
-    /// Skip until the start (column 0) of the next line.
-    /// Returns the index of the last skipped character
+    /// Skip until the newline ending this line, leaving it unconsumed.
     /// Pre: '#' was just consumed.
-    fn skip_to_next_line(&mut self, start_idx: CharIdx) -> CharIdx {
-        let mut final_idx = start_idx;
-        loop {
-            match self.iter.next() {
-                None => return final_idx,
-                Some((idx, '\n')) => {
-                    return idx;
-                }
-                Some((idx, _)) => {
-                    final_idx = idx;
-                }
-            }
+    fn skip_to_line_end(&mut self) {
+        while self.iter.peek().is_some_and(|(_, c)| c != '\n') {
+            self.iter.next();
         }
     }
 
+    /// Is `idx` at the start (column 0) of a line?
+    fn is_line_start(&self, idx: ByteIdx) -> bool {
+        idx.0 == 0 || self.text[..idx.0].ends_with('\n')
+    }
+
+    /// Lexes a block comment, from a `=begin` line up to and including the next `=end` line.
+    /// Both must start at column 0.
+    /// Pre: `=` at the start of a line has been consumed, and `begin` follows.
+    fn embedded_document(&mut self, start_idx: ByteIdx) -> Lexeme {
+        let first_line_len = self.text[start_idx.0..]
+            .find('\n')
+            .map_or(self.text.len() - start_idx.0, |newline| newline + 1);
+        let mut line_start = start_idx.0 + first_line_len;
+        let end = loop {
+            let rest = &self.text[line_start..];
+            if rest.is_empty() {
+                let message = "embedded document meets end of file";
+                self.error(start_idx, ByteDifference(6), message);
+                break self.text.len();
+            }
+            let line_len = rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+            if starts_with_marker(rest, "=end") {
+                break line_start + line_len;
+            }
+            line_start += line_len;
+        };
+        self.iter.jump_to(ByteIdx(end));
+        Lexeme::new(EmbDoc, start_idx, ByteDifference(end - start_idx.0))
+    }
+
+    /// Lexes `__END__`, and skips to EOF.
+    /// The rest of the text is the program's data section, available as `DATA`.
+    /// Pre: `_` at the start of a line has been consumed, and the line is `__END__`.
+    fn end_marker(&mut self, start_idx: ByteIdx) -> Lexeme {
+        let rest = &self.text[start_idx.0..];
+        let line_len = rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+        self.data_start = Some(start_idx + ByteDifference(line_len));
+        self.iter.jump_to(self.iter.eof_idx_of_text());
+        Lexeme::new(UnderscoreEnd, start_idx, ByteDifference("__END__".len()))
+    }
+
     /// Skip non-newline whitespace, incrementing `self.col`.
     fn skip_whitespace(&mut self) {
         while self
@@ -785,55 +1687,216 @@ impl<'text> Lexer<'text> {
         }
     }
 
-    fn integer_literal(&self, start_idx: CharIdx, len: CharDifference) -> Lexeme {
+    fn integer_literal(&mut self, start_idx: ByteIdx, len: ByteDifference) -> Lexeme {
         let lexeme_text = lexeme::text_in_range(self.text, start_idx, len);
+        // TODO: Bignum literals.
+        if let (base, Some(digits)) = integer_digits(&lexeme_text)
+            && i64::from_str_radix(&digits, base).is_err()
+        {
+            self.error(
+                start_idx,
+                len,
+                "integer literal too big; Integers are 64-bit",
+            );
+        }
         Lexeme::new(IntegerLiteral { text: lexeme_text }, start_idx, len)
     }
 }
 
-/// Gets the length of the doubly-inclusive range `[self, rhs]`.
-pub fn len_inclusive(lhs: CharIdx, rhs: CharIdx) -> CharDifference {
-    assert!(lhs.0 <= rhs.0);
-    CharDifference(rhs.0 - lhs.0 + 1)
+/// The radix of an integer literal, chosen by its prefix.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+enum Radix {
+    /// `0x`
+    Hexadecimal,
+    /// `0b`
+    Binary,
+    /// `0o`
+    Octal,
+    /// `0` followed directly by digits, as in `017`.
+    LeadingZeroOctal,
+    /// `0d`
+    Decimal,
+}
+
+impl Radix {
+    fn base(self) -> u32 {
+        match self {
+            Radix::Hexadecimal => 16,
+            Radix::Binary => 2,
+            Radix::Octal | Radix::LeadingZeroOctal => 8,
+            Radix::Decimal => 10,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Radix::Hexadecimal => "hexadecimal",
+            Radix::Binary => "binary",
+            Radix::Octal | Radix::LeadingZeroOctal => "octal",
+            Radix::Decimal => "decimal",
+        }
+    }
+}
+
+/// The value of the text of an `IntegerLiteral`.
+/// Malformed literals, including ones too big for an `i64`, have already been reported by the lexer,
+///     and take the value 0.
+pub fn integer_value(text: &str) -> i64 {
+    match integer_digits(text) {
+        (base, Some(digits)) => i64::from_str_radix(&digits, base).unwrap_or(0),
+        (_, None) => 0,
+    }
+}
+
+/// The base of an integer literal's text, and its digits without the prefix or `_`s,
+///     or `None` if it's malformed.
+fn integer_digits(text: &str) -> (u32, Option<String>) {
+    let text = text.replace('_', "");
+    let (base, digits) = match text.get(..2) {
+        Some("0x" | "0X") => (16, &text[2..]),
+        Some("0b" | "0B") => (2, &text[2..]),
+        Some("0o" | "0O") => (8, &text[2..]),
+        Some("0d" | "0D") => (10, &text[2..]),
+        Some(_) if text.starts_with('0') => (8, &text[1..]),
+        _ => (10, &text[..]),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(base)) {
+        return (base, None);
+    }
+    (base, Some(digits.to_string()))
 }
 
 /// Gets the length of the range `[self, rhs)`.
-pub fn len_exclusive(lhs: CharIdx, rhs: CharIdx) -> CharDifference {
+pub fn len_exclusive(lhs: ByteIdx, rhs: ByteIdx) -> ByteDifference {
     assert!(lhs.0 < rhs.0);
-    CharDifference(rhs.0 - lhs.0)
+    ByteDifference(rhs.0 - lhs.0)
+}
+
+/// Operators which can be defined as methods, and so named by symbols.
+/// Ordered so that each operator comes before its prefixes.
+pub const OPERATOR_METHOD_NAMES: [&str; 27] = [
+    "[]=", "[]", "===", "==", "=~", "!=", "!~", "!", "<=>", "<=", "<<", "<", ">=", ">>", ">", "+@",
+    "+", "-@", "-", "**", "*", "/", "%", "&", "|", "^", "~",
+];
+
+/// The delimiter closing a percent literal opened with `open`.
+/// Bracket-like delimiters close with their pair, and other punctuation closes with itself.
+fn closing_delimiter(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '<' => Some('>'),
+        c if c.is_ascii_punctuation() => Some(c),
+        _ => None,
+    }
+}
+
+/// The nesting depth of `open` delimiters after `c`, within a percent literal.
+/// For delimiters like `|`, `open == close`, and they can't nest.
+/// Pre: `c` isn't a `close` which ends the literal.
+fn nesting_depth(depth: usize, c: char, open: char, close: char) -> usize {
+    if c == close {
+        depth - 1
+    } else if c == open {
+        depth + 1
+    } else {
+        depth
+    }
 }
 
 /// Is `c` alphabetic or an underscore?
 /// TODO: I think Ruby allows certain other chars in UTF-8.
 ///     It also has many other encodings I'm going to ignore.
+/// Does `text` start with `marker`, followed by whitespace or the end of the text?
+/// As for `=begin` and `=end`.
+fn starts_with_marker(text: &str, marker: &str) -> bool {
+    text.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Is the line starting `text` exactly `__END__`?
+fn is_end_marker_line(text: &str) -> bool {
+    text.strip_prefix("__END__")
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n"))
+}
+
+/// Finds the line ending the heredoc body which starts at `body_start`:
+/// the first line which is exactly `id`, or `id` after indentation if `indented`.
+/// Returns the index of the start of that line, or `None` if there is no such line.
+fn heredoc_terminator(
+    text: &str,
+    body_start: ByteIdx,
+    id: &str,
+    indented: bool,
+) -> Option<ByteIdx> {
+    let mut line_start = body_start.0;
+    for line in text[body_start.0..].split_inclusive('\n') {
+        let content = line.trim_end_matches('\n').trim_end_matches('\r');
+        let content = if indented {
+            content.trim_start_matches([' ', '\t'])
+        } else {
+            content
+        };
+        if content == id {
+            return Some(ByteIdx(line_start));
+        }
+        line_start += line.len();
+    }
+    None
+}
+
+/// The indentation stripped from a `<<~` heredoc `body`:
+/// the width of the least-indented line, ignoring lines of only whitespace.
+fn heredoc_dedent(body: &str) -> usize {
+    body.lines()
+        .filter_map(|line| {
+            let mut width = 0;
+            for c in line.chars() {
+                match c {
+                    ' ' => width += 1,
+                    '\t' => width = width / 8 * 8 + 8,
+                    '\r' => {}
+                    _ => return Some(width),
+                }
+            }
+            None
+        })
+        .min()
+        .unwrap_or(0)
+}
+
 fn is_identifier_start(c: char) -> bool {
     c == '_' || (c.is_alphabetic() && c.is_lowercase())
 }
 
+/// Instance and class variable names may also start with an uppercase letter.
+fn is_variable_name_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
 fn is_identifier_char(c: char) -> bool {
     c.is_alphanumeric() || c == '?' || c == '_'
 }
 
-/// Peekable iterator over `(CharIdx, char)`
+/// Peekable iterator over `(ByteIdx, char)`
+#[derive(Clone)]
 struct LexerIter<'a> {
-    idx_state: IdxState,
-    iter: Chars<'a>,
+    text: &'a str,
+    /// Iterator of `(byte_idx - offset, char)`
+    iter: CharIndices<'a>,
+    /// Byte index of the start of `iter` in `text`.
+    offset: usize,
     /// Remember a peeked value, even if it was None.
-    peeked: Option<Option<(CharIdx, char)>>,
-}
-
-/// Tracks whether a char has been consumed.
-/// If a char has been consumed, tracks the index of the most-recently-consumed char.
-enum IdxState {
-    NoCharsConsumed,
-    CharConsumed { last_char_idx: CharIdx },
+    peeked: Option<Option<(ByteIdx, char)>>,
 }
 
-impl<'a> From<Chars<'a>> for LexerIter<'a> {
-    fn from(value: Chars<'a>) -> Self {
+impl<'a> From<&'a str> for LexerIter<'a> {
+    fn from(value: &'a str) -> Self {
         Self {
-            idx_state: IdxState::NoCharsConsumed,
-            iter: value,
+            text: value,
+            iter: value.char_indices(),
+            offset: 0,
             peeked: None,
         }
     }
@@ -842,19 +1905,45 @@ impl<'a> From<Chars<'a>> for LexerIter<'a> {
 impl<'a> LexerIter<'a> {
     /// Get the index of the EOF lexeme.
     /// Pre: `self.peek() == None`
-    pub fn eof_idx(&mut self) -> CharIdx {
-        // Strategy:
-        // Either:
-        // - We've consumed nothing -- EOF idx is 0
-        // - Our final char has idx `k` -- EOF idx is `k + 1`
+    pub fn eof_idx(&mut self) -> ByteIdx {
         assert_eq!(None, self.peek());
-        match self.idx_state {
-            IdxState::NoCharsConsumed => CharIdx(0),
-            IdxState::CharConsumed { last_char_idx } => last_char_idx + CharDifference(1),
+        self.eof_idx_of_text()
+    }
+
+    /// Get the index of the end of the text, wherever we are in it.
+    pub fn eof_idx_of_text(&self) -> ByteIdx {
+        ByteIdx(self.text.len())
+    }
+
+    /// Continue iterating from `idx`, which may be before or after the current position.
+    pub fn jump_to(&mut self, idx: ByteIdx) {
+        self.iter = self.text[idx.0..].char_indices();
+        self.offset = idx.0;
+        self.peeked = None;
+    }
+
+    /// Do the unconsumed chars start with `prefix`?
+    pub fn starts_with(&mut self, prefix: &str) -> bool {
+        match self.peek() {
+            None => prefix.is_empty(),
+            Some((_, c)) => match prefix.strip_prefix(c) {
+                Some(rest) => self.iter.as_str().starts_with(rest),
+                None => false,
+            },
         }
     }
 
-    pub fn peek(&mut self) -> Option<(CharIdx, char)> {
+    /// Peek the char after the next one.
+    pub fn peek2(&mut self) -> Option<(ByteIdx, char)> {
+        self.peek()?;
+        let offset = self.offset;
+        self.iter
+            .clone()
+            .next()
+            .map(|(idx, c)| (ByteIdx(offset + idx), c))
+    }
+
+    pub fn peek(&mut self) -> Option<(ByteIdx, char)> {
         match self.peeked {
             Some(v) => v,
             None => {
@@ -867,24 +1956,15 @@ impl<'a> LexerIter<'a> {
 }
 
 impl<'a> Iterator for LexerIter<'a> {
-    type Item = (CharIdx, char);
+    type Item = (ByteIdx, char);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.peeked.take() {
             Some(v) => v,
-            None => match self.iter.next() {
-                None => None,
-                Some(char) => {
-                    let idx = match self.idx_state {
-                        IdxState::NoCharsConsumed => CharIdx(0),
-                        IdxState::CharConsumed { last_char_idx } => {
-                            last_char_idx + CharDifference(1)
-                        }
-                    };
-                    self.idx_state = IdxState::CharConsumed { last_char_idx: idx };
-                    Some((idx, char))
-                }
-            },
+            None => self
+                .iter
+                .next()
+                .map(|(idx, c)| (ByteIdx(self.offset + idx), c)),
         }
     }
 }
@@ -916,6 +1996,20 @@ mod tests {
         format_sexpr(&sexpr)
     }
 
+    /// Lex the given text until EOF, and return just the lexeme kinds.
+    fn lex_kinds(text: &str) -> Vec<LexemeKind> {
+        let mut kinds = vec![];
+        let mut lexer = Lexer::new(text);
+        loop {
+            let kind = lexer.next().kind;
+            let eof = kind == Eof;
+            kinds.push(kind);
+            if eof {
+                return kinds;
+            }
+        }
+    }
+
     /// Pretty print given sexpr
     /// TODO -- Remove the emacs dependency...
     fn format_sexpr(sexpr: &str) -> String {
@@ -933,16 +2027,16 @@ mod tests {
     #[test]
     fn test_lexer_iter() {
         let text = "abc";
-        let mut iter = LexerIter::from(text.chars());
-        assert_eq!(Some((CharIdx(0), 'a')), iter.peek());
-        assert_eq!(Some((CharIdx(0), 'a')), iter.peek());
-        assert_eq!(Some((CharIdx(0), 'a')), iter.next());
-        assert_eq!(Some((CharIdx(1), 'b')), iter.next());
-        assert_eq!(Some((CharIdx(2), 'c')), iter.peek());
-        assert_eq!(Some((CharIdx(2), 'c')), iter.next());
-        assert_eq!(CharIdx(3), iter.eof_idx());
+        let mut iter = LexerIter::from(text);
+        assert_eq!(Some((ByteIdx(0), 'a')), iter.peek());
+        assert_eq!(Some((ByteIdx(0), 'a')), iter.peek());
+        assert_eq!(Some((ByteIdx(0), 'a')), iter.next());
+        assert_eq!(Some((ByteIdx(1), 'b')), iter.next());
+        assert_eq!(Some((ByteIdx(2), 'c')), iter.peek());
+        assert_eq!(Some((ByteIdx(2), 'c')), iter.next());
+        assert_eq!(ByteIdx(3), iter.eof_idx());
         assert_eq!(None, iter.next());
-        assert_eq!(CharIdx(3), iter.eof_idx());
+        assert_eq!(ByteIdx(3), iter.eof_idx());
     }
 
     #[test]
@@ -1106,4 +2200,1063 @@ mod tests {
         let actual = lex_to_sexpr(text);
         expected.assert_eq(&actual);
     }
+
+    #[test]
+    fn operator_writes() {
+        assert_eq!(
+            vec![
+                PlusEqual,
+                MinusEqual,
+                StarEqual,
+                SlashEqual,
+                PercentEqual,
+                StarStarEqual,
+                PipeEqual,
+                PipePipeEqual,
+                AmpersandEqual,
+                AmpersandAmpersandEqual,
+                CaretEqual,
+                LessLessEqual,
+                GreaterGreaterEqual,
+                Eof
+            ],
+            lex_kinds("+= -= *= /= %= **= |= ||= &= &&= ^= <<= >>=")
+        );
+    }
+
+    #[test]
+    fn else_and_elsif() {
+        let ident = |text: &str| Identifier {
+            text: text.to_string(),
+        };
+        assert_eq!(
+            vec![
+                Else,
+                Newline,
+                Elsif,
+                ident("elsewhere"),
+                ident("elsif_"),
+                ident("els"),
+                Else,
+                Eof
+            ],
+            lex_kinds("else\nelsif elsewhere elsif_ els else")
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let ident = |text: &str| Identifier {
+            text: text.to_string(),
+        };
+        let int = |text: &str| IntegerLiteral {
+            text: text.to_string(),
+        };
+        assert_eq!(
+            vec![
+                ident("a"),
+                LessEqual,
+                ident("b"),
+                GreaterEqual,
+                ident("c"),
+                LessEqualGreater,
+                ident("d"),
+                BangEqual,
+                ident("e"),
+                Percent,
+                ident("f"),
+                StarStar,
+                ident("g"),
+                Eof
+            ],
+            lex_kinds("a <= b >= c <=> d != e % f ** g")
+        );
+        assert_eq!(
+            vec![
+                Bang,
+                ident("a"),
+                Pipe,
+                TildeOrTildeAt,
+                ident("b"),
+                Ampersand,
+                ident("c"),
+                Caret,
+                ident("d"),
+                LessLess,
+                int("1"),
+                GreaterGreater,
+                int("2"),
+                Eof
+            ],
+            lex_kinds("!a | ~b & c ^ d << 1 >> 2")
+        );
+    }
+
+    mod double_quote_string {
+        use super::*;
+
+        fn content(text: &str) -> LexemeKind {
+            StringContent {
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn plain() {
+            assert_eq!(
+                vec![StringBegin, content("abc"), StringEnd, Eof],
+                lex_kinds("\"abc\"")
+            );
+        }
+
+        #[test]
+        fn empty() {
+            assert_eq!(vec![StringBegin, StringEnd, Eof], lex_kinds("\"\""));
+        }
+
+        #[test]
+        fn escapes() {
+            assert_eq!(
+                vec![StringBegin, content("a\n\t\"\\\u{1b}Aé😀"), StringEnd, Eof],
+                lex_kinds(r#""a\n\t\"\\\e\101\u00e9\u{1F600}""#)
+            );
+        }
+
+        #[test]
+        fn hex_escape_takes_at_most_two_digits() {
+            assert_eq!(
+                vec![StringBegin, content("A1"), StringEnd, Eof],
+                lex_kinds(r#""\x411""#)
+            );
+        }
+
+        #[test]
+        fn unknown_escape_is_the_char_itself() {
+            assert_eq!(
+                vec![StringBegin, content("q#"), StringEnd, Eof],
+                lex_kinds(r#""\q\#""#)
+            );
+        }
+
+        #[test]
+        fn interpolation() {
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    content("a"),
+                    EmbExprBegin,
+                    IntegerLiteral {
+                        text: "1".to_string()
+                    },
+                    Plus,
+                    IntegerLiteral {
+                        text: "2".to_string()
+                    },
+                    EmbExprEnd,
+                    content("b"),
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("\"a#{1 + 2}b\"")
+            );
+        }
+
+        #[test]
+        fn hash_without_brace_is_literal() {
+            assert_eq!(
+                vec![StringBegin, content("#a#"), StringEnd, Eof],
+                lex_kinds("\"#a#\"")
+            );
+        }
+
+        #[test]
+        fn nested_interpolation() {
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    EmbExprBegin,
+                    StringBegin,
+                    EmbExprBegin,
+                    Identifier {
+                        text: "x".to_string()
+                    },
+                    EmbExprEnd,
+                    StringEnd,
+                    EmbExprEnd,
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("\"#{\"#{x}\"}\"")
+            );
+        }
+
+        #[test]
+        fn braces_inside_interpolation() {
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    EmbExprBegin,
+                    BraceLeft,
+                    BraceRight,
+                    EmbExprEnd,
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("\"#{{}}\"")
+            );
+        }
+    }
+
+    mod symbol {
+        use super::*;
+
+        fn symbol(text: &str) -> LexemeKind {
+            SymbolLiteral {
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn identifiers() {
+            assert_eq!(
+                vec![
+                    symbol("foo"),
+                    symbol("Foo"),
+                    symbol("foo?"),
+                    symbol("foo!"),
+                    symbol("foo="),
+                    symbol("@foo"),
+                    symbol("@@foo"),
+                    symbol("$foo"),
+                    Eof
+                ],
+                lex_kinds(":foo :Foo :foo? :foo! :foo= :@foo :@@foo :$foo")
+            );
+        }
+
+        #[test]
+        fn operators() {
+            assert_eq!(
+                vec![
+                    symbol("+"),
+                    symbol("[]"),
+                    symbol("[]="),
+                    symbol("<=>"),
+                    symbol("=="),
+                    symbol("-@"),
+                    Eof
+                ],
+                lex_kinds(":+ :[] :[]= :<=> :== :-@")
+            );
+        }
+
+        #[test]
+        fn setter_not_confused_with_hash_rocket() {
+            assert_eq!(vec![symbol("foo"), EqualGreater, Eof], lex_kinds(":foo=>"));
+        }
+
+        #[test]
+        fn colon_colon_and_bare_colon() {
+            assert_eq!(
+                vec![
+                    Constant {
+                        text: "A".to_string()
+                    },
+                    ColonColon,
+                    Constant {
+                        text: "B".to_string()
+                    },
+                    Colon,
+                    Eof
+                ],
+                lex_kinds("A::B :")
+            );
+        }
+
+        #[test]
+        fn quoted() {
+            assert_eq!(
+                vec![
+                    SymbolBegin,
+                    StringContent {
+                        text: "foo bar".to_string()
+                    },
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds(":\"foo bar\"")
+            );
+        }
+
+        #[test]
+        fn percent_i() {
+            let word = |text: &str| StringContent {
+                text: text.to_string(),
+            };
+            assert_eq!(
+                vec![
+                    PercentLowerI,
+                    word("a"),
+                    WordsSep,
+                    word("b"),
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("%i[a b]")
+            );
+            assert_eq!(
+                vec![
+                    PercentLowerI,
+                    WordsSep,
+                    word("a(b)"),
+                    WordsSep,
+                    word("c d"),
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("%i( a(b)\nc\\ d)")
+            );
+        }
+    }
+
+    mod percent {
+        use super::*;
+
+        fn content(text: &str) -> LexemeKind {
+            StringContent {
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn words() {
+            assert_eq!(
+                vec![
+                    PercentLowerW,
+                    content("a"),
+                    WordsSep,
+                    content("#{b}\\n"),
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("%w[a #{b}\\n]")
+            );
+            assert_eq!(
+                vec![
+                    PercentUpperW,
+                    content("a"),
+                    EmbExprBegin,
+                    Identifier {
+                        text: "b".to_string()
+                    },
+                    EmbExprEnd,
+                    content("\n"),
+                    WordsSep,
+                    content("c"),
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("%W<a#{b}\\n c>")
+            );
+            assert_eq!(
+                vec![PercentUpperI, content("a"), StringEnd, Eof],
+                lex_kinds("%I|a|")
+            );
+        }
+
+        #[test]
+        fn strings() {
+            assert_eq!(
+                vec![StringBegin, content("a(b)\\n#{c})"), StringEnd, Eof],
+                lex_kinds("%q(a(b)\\n#{c}\\))")
+            );
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    content("{\n}"),
+                    EmbExprBegin,
+                    EmbExprEnd,
+                    StringEnd,
+                    Eof
+                ],
+                lex_kinds("%Q{{\\n}#{}}")
+            );
+            assert_eq!(
+                vec![StringBegin, content("a"), StringEnd, Eof],
+                lex_kinds("%[a]")
+            );
+            assert_eq!(
+                vec![SymbolBegin, content("a b"), StringEnd, Eof],
+                lex_kinds("%s!a b!")
+            );
+        }
+
+        #[test]
+        fn modulo() {
+            let integer = |text: &str| IntegerLiteral {
+                text: text.to_string(),
+            };
+            assert_eq!(
+                vec![
+                    integer("7"),
+                    Percent,
+                    LeftParen,
+                    integer("3"),
+                    RightParen,
+                    Eof
+                ],
+                lex_kinds("7 %(3)")
+            );
+            assert_eq!(
+                vec![
+                    Identifier {
+                        text: "x".to_string()
+                    },
+                    Percent,
+                    Identifier {
+                        text: "w".to_string()
+                    },
+                    Eof
+                ],
+                lex_kinds("x%w")
+            );
+        }
+
+        #[test]
+        fn unknown_type() {
+            let mut lexer = Lexer::new("%z(a)");
+            assert_eq!(
+                Lexeme::new(StringBegin, ByteIdx(0), ByteDifference(3)),
+                lexer.next()
+            );
+            while lexer.next().kind != Eof {}
+            assert_eq!(
+                &[LexError {
+                    start: ByteIdx(0),
+                    len: ByteDifference(2),
+                    message: "unknown type of %string".to_string(),
+                }],
+                lexer.errors()
+            );
+        }
+    }
+
+    mod comments {
+        use super::*;
+
+        #[test]
+        fn inline_comment_excludes_newline() {
+            let mut lexer = Lexer::new("1 # a\n");
+            lexer.next();
+            assert_eq!(
+                Lexeme::new(InlineComment, ByteIdx(2), ByteDifference(3)),
+                lexer.next()
+            );
+            assert_eq!(Newline, lexer.next().kind);
+        }
+
+        #[test]
+        fn embedded_document() {
+            let text = "1\n=begin x\n2\n=ending\n=end y\n3";
+            let mut lexer = Lexer::new(text);
+            lexer.next();
+            lexer.next();
+            assert_eq!(
+                Lexeme::new(EmbDoc, ByteIdx(2), ByteDifference(26)),
+                lexer.next()
+            );
+            assert_eq!(
+                IntegerLiteral {
+                    text: "3".to_string()
+                },
+                lexer.next().kind
+            );
+        }
+
+        #[test]
+        fn embedded_document_only_at_line_start() {
+            assert_eq!(vec![Equal, Begin, Eof], lex_kinds(" =begin"));
+            assert_eq!(
+                vec![
+                    Equal,
+                    Identifier {
+                        text: "beginning".to_string()
+                    },
+                    Eof
+                ],
+                lex_kinds("=beginning")
+            );
+        }
+
+        #[test]
+        fn unterminated_embedded_document() {
+            let mut lexer = Lexer::new("=begin\n=ending\n");
+            assert_eq!(EmbDoc, lexer.next().kind);
+            assert_eq!(Eof, lexer.next().kind);
+            assert_eq!(
+                &[LexError {
+                    start: ByteIdx(0),
+                    len: ByteDifference(6),
+                    message: "embedded document meets end of file".to_string(),
+                }],
+                lexer.errors()
+            );
+        }
+
+        #[test]
+        fn end_marker() {
+            let mut lexer = Lexer::new("1\n__END__\n2 \"\n");
+            assert_eq!(
+                vec![
+                    IntegerLiteral {
+                        text: "1".to_string()
+                    },
+                    Newline,
+                    UnderscoreEnd,
+                    Eof
+                ],
+                (0..4).map(|_| lexer.next().kind).collect::<Vec<_>>()
+            );
+            assert_eq!(Some("2 \"\n"), lexer.data_section());
+            assert!(lexer.errors().is_empty());
+        }
+
+        #[test]
+        fn end_marker_only_alone_on_line() {
+            let ident = |text: &str| Identifier {
+                text: text.to_string(),
+            };
+            assert_eq!(vec![ident("__END__x"), Eof], lex_kinds("__END__x"));
+            assert_eq!(
+                vec![ident("a"), ident("__END__"), Eof],
+                lex_kinds("a __END__")
+            );
+        }
+    }
+
+    mod heredoc {
+        use super::*;
+
+        fn content(text: &str) -> LexemeKind {
+            StringContent {
+                text: text.to_string(),
+            }
+        }
+
+        fn ident(text: &str) -> LexemeKind {
+            Identifier {
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn body_comes_before_rest_of_line() {
+            assert_eq!(
+                vec![
+                    ident("x"),
+                    Equal,
+                    HeredocStart,
+                    content("a\n"),
+                    content("b\n"),
+                    HeredocEnd,
+                    Dot,
+                    ident("y"),
+                    Newline,
+                    ident("z"),
+                    Eof
+                ],
+                lex_kinds("x = <<EOS.y\na\nb\nEOS\nz")
+            );
+        }
+
+        #[test]
+        fn terminator_indentation() {
+            // `<<EOS` must end with an unindented `EOS`.
+            assert_eq!(
+                vec![HeredocStart, content("  EOS\n"), HeredocEnd, Newline, Eof],
+                lex_kinds("<<EOS\n  EOS\nEOS\n")
+            );
+            assert_eq!(
+                vec![HeredocStart, content("  a\n"), HeredocEnd, Newline, Eof],
+                lex_kinds("<<-EOS\n  a\n  EOS\n")
+            );
+        }
+
+        #[test]
+        fn squiggly_strips_least_indentation() {
+            assert_eq!(
+                vec![
+                    HeredocStart,
+                    content("a\n"),
+                    content("  b\n"),
+                    content("\n"),
+                    content("\tc\n"),
+                    HeredocEnd,
+                    Newline,
+                    Eof
+                ],
+                lex_kinds("<<~EOS\n    a\n      b\n\n    \\tc\n  EOS\n")
+            );
+        }
+
+        #[test]
+        fn squiggly_tabs() {
+            assert_eq!(
+                vec![
+                    HeredocStart,
+                    content("a\n"),
+                    content(" b\n"),
+                    HeredocEnd,
+                    Newline,
+                    Eof
+                ],
+                lex_kinds("<<~EOS\n\ta\n        \x20b\nEOS\n")
+            );
+        }
+
+        #[test]
+        fn interpolation() {
+            assert_eq!(
+                vec![
+                    HeredocStart,
+                    content("a "),
+                    EmbExprBegin,
+                    ident("x"),
+                    EmbExprEnd,
+                    content("\n"),
+                    HeredocEnd,
+                    Newline,
+                    Eof
+                ],
+                lex_kinds("<<~\"EOS\"\n  a #{x}\nEOS\n")
+            );
+        }
+
+        #[test]
+        fn single_quoted_terminator_is_raw() {
+            assert_eq!(
+                vec![HeredocStart, content("#{x}\\n\n"), HeredocEnd, Newline, Eof],
+                lex_kinds("<<~'EOS'\n  #{x}\\n\nEOS\n")
+            );
+        }
+
+        #[test]
+        fn several_on_one_line() {
+            assert_eq!(
+                vec![
+                    ident("f"),
+                    LeftParen,
+                    HeredocStart,
+                    content("a\n"),
+                    HeredocEnd,
+                    Comma,
+                    HeredocStart,
+                    content("b\n"),
+                    HeredocEnd,
+                    RightParen,
+                    InlineComment,
+                    Newline,
+                    ident("c"),
+                    Eof
+                ],
+                lex_kinds("f(<<~A, <<~B) # !\n  a\nA\n  b\nB\nc")
+            );
+        }
+
+        #[test]
+        fn shift_operator() {
+            let integer = |text: &str| IntegerLiteral {
+                text: text.to_string(),
+            };
+            assert_eq!(
+                vec![ident("a"), LessLess, ident("b"), Eof],
+                lex_kinds("a << b")
+            );
+            assert_eq!(
+                vec![ident("a"), LessLess, ident("b"), Eof],
+                lex_kinds("a<<b")
+            );
+            assert_eq!(
+                vec![
+                    integer("1"),
+                    LessLess,
+                    Constant {
+                        text: "X".to_string()
+                    },
+                    Eof
+                ],
+                lex_kinds("1 <<X")
+            );
+        }
+
+        #[test]
+        fn unterminated() {
+            let mut lexer = Lexer::new("x = <<~EOS\n  a\n");
+            while lexer.next().kind != Eof {}
+            assert_eq!(
+                &[LexError {
+                    start: ByteIdx(4),
+                    len: ByteDifference(6),
+                    message: "unterminated heredoc; can't find string \"EOS\" anywhere before EOF"
+                        .to_string(),
+                }],
+                lexer.errors()
+            );
+        }
+    }
+
+    mod float {
+        use super::*;
+
+        fn float(text: &str) -> LexemeKind {
+            FloatLiteral {
+                text: text.to_string(),
+            }
+        }
+
+        fn integer(text: &str) -> LexemeKind {
+            IntegerLiteral {
+                text: text.to_string(),
+            }
+        }
+
+        #[test]
+        fn literals() {
+            assert_eq!(
+                vec![
+                    float("1.5"),
+                    float("1e-3"),
+                    float("2.0e10"),
+                    float("1E+2"),
+                    float("1_000.000_1"),
+                    Eof
+                ],
+                lex_kinds("1.5 1e-3 2.0e10 1E+2 1_000.000_1")
+            );
+        }
+
+        #[test]
+        fn dot_without_fraction_is_integer() {
+            assert_eq!(
+                vec![
+                    integer("1"),
+                    Dot,
+                    Identifier {
+                        text: "foo".to_string()
+                    },
+                    Eof
+                ],
+                lex_kinds("1.foo")
+            );
+            assert_eq!(
+                vec![integer("1"), DotDot, integer("2"), Eof],
+                lex_kinds("1..2")
+            );
+        }
+    }
+
+    mod integer {
+        use super::*;
+
+        /// Lexes `text` as a single integer literal, returning its text and value.
+        fn lex_integer(text: &str) -> (String, i64, Vec<String>) {
+            let mut lexer = Lexer::new(text);
+            let IntegerLiteral { text } = lexer.next().kind else {
+                panic!("Expected an integer literal in {:?}", text);
+            };
+            assert_eq!(Eof, lexer.next().kind);
+            let errors = lexer.errors().iter().map(|e| e.message.clone()).collect();
+            let value = integer_value(&text);
+            (text, value, errors)
+        }
+
+        /// Every radix, in each spelling Prism accepts.
+        #[test]
+        fn prism_parity() {
+            let table: [(&str, i64); 20] = [
+                ("0", 0),
+                ("7", 7),
+                ("1_000", 1000),
+                ("0d19", 19),
+                ("0D1_9", 19),
+                ("0xFF", 255),
+                ("0xff", 255),
+                ("0Xdead_BEEF", 0xdead_beef),
+                ("0x0", 0),
+                ("0b1010", 10),
+                ("0B1_0", 2),
+                ("0b0", 0),
+                ("0o17", 15),
+                ("0O7_7", 63),
+                ("017", 15),
+                ("0_17", 15),
+                ("00", 0),
+                ("0xFFFF_FFFF_FF", 0xFF_FFFF_FFFF),
+                ("0x7FFF_FFFF_FFFF_FFFF", i64::MAX),
+                ("0b1111_0000", 0xF0),
+            ];
+            for (text, value) in table {
+                assert_eq!(
+                    (text.to_string(), value, vec![]),
+                    lex_integer(text),
+                    "{}",
+                    text
+                );
+            }
+        }
+
+        #[test]
+        fn prefix_ends_literal() {
+            assert_eq!(
+                vec![
+                    IntegerLiteral {
+                        text: "0x1".to_string()
+                    },
+                    Dot,
+                    Identifier {
+                        text: "to_s".to_string()
+                    },
+                    Eof
+                ],
+                lex_kinds("0x1.to_s")
+            );
+        }
+
+        #[test]
+        fn malformed() {
+            let table = [
+                (
+                    "0x",
+                    "invalid hexadecimal number; numeric literal without digits",
+                ),
+                (
+                    "0b",
+                    "invalid binary number; numeric literal without digits",
+                ),
+                ("0o", "invalid octal number; numeric literal without digits"),
+                (
+                    "0d",
+                    "invalid decimal number; numeric literal without digits",
+                ),
+                ("1__0", "invalid underscore placement in number"),
+                ("0x_1", "invalid underscore placement in number"),
+                ("1_", "trailing '_' in number"),
+                ("09", "invalid octal digit"),
+                ("0o78", "invalid octal digit"),
+                ("0b102", "invalid binary digit"),
+                (
+                    "9999999999999999999999",
+                    "integer literal too big; Integers are 64-bit",
+                ),
+                (
+                    "0x1_0000_0000_0000_0000",
+                    "integer literal too big; Integers are 64-bit",
+                ),
+            ];
+            for (text, message) in table {
+                let (lexed, _, errors) = lex_integer(text);
+                assert_eq!(text, lexed);
+                assert_eq!(vec![message.to_string()], errors, "{}", text);
+            }
+        }
+
+        #[test]
+        fn malformed_span() {
+            let mut lexer = Lexer::new("x = 1__0");
+            while lexer.next().kind != Eof {}
+            assert_eq!(
+                &[LexError {
+                    start: ByteIdx(5),
+                    len: ByteDifference(2),
+                    message: "invalid underscore placement in number".to_string(),
+                }],
+                lexer.errors()
+            );
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        /// Lexes all of `text`, returning the kinds lexed and the messages of any errors.
+        fn lex_with_errors(text: &str) -> (Vec<LexemeKind>, Vec<String>) {
+            let mut kinds = vec![];
+            let mut lexer = Lexer::new(text);
+            loop {
+                let kind = lexer.next().kind;
+                let eof = kind == Eof;
+                kinds.push(kind);
+                if eof {
+                    let messages = lexer.errors().iter().map(|e| e.message.clone()).collect();
+                    return (kinds, messages);
+                }
+            }
+        }
+
+        #[test]
+        fn eof_repeats() {
+            let mut lexer = Lexer::new("x");
+            lexer.next();
+            assert_eq!(Eof, lexer.next().kind);
+            assert_eq!(Eof, lexer.next().kind);
+            assert_eq!(Eof, lexer.peek().kind);
+            assert!(lexer.errors().is_empty());
+        }
+
+        #[test]
+        fn recovers_after_each_error() {
+            let (kinds, messages) = lex_with_errors("@ + $ - € * @@");
+            assert_eq!(vec![Plus, Minus, Star, Eof], kinds);
+            assert_eq!(
+                vec![
+                    "'@' without identifiers is not allowed as an instance variable name",
+                    "'$' without identifiers is not allowed as a global variable name",
+                    "unexpected character '€'",
+                    "'@@' without identifiers is not allowed as a class variable name",
+                ],
+                messages
+            );
+        }
+
+        #[test]
+        fn error_span() {
+            let mut lexer = Lexer::new("a €");
+            while lexer.next().kind != Eof {}
+            assert_eq!(
+                &[LexError {
+                    start: ByteIdx(2),
+                    len: ByteDifference(3),
+                    message: "unexpected character '€'".to_string(),
+                }],
+                lexer.errors()
+            );
+        }
+
+        #[test]
+        fn unterminated_strings() {
+            let (kinds, messages) = lex_with_errors("'abc");
+            assert_eq!(
+                vec![
+                    SingleQuoteStringLiteral {
+                        text: "'abc".to_string()
+                    },
+                    Eof
+                ],
+                kinds
+            );
+            assert_eq!(vec!["unterminated string meets end of file"], messages);
+
+            let (kinds, messages) = lex_with_errors("\"abc");
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    StringContent {
+                        text: "abc".to_string()
+                    },
+                    StringEnd,
+                    Eof
+                ],
+                kinds
+            );
+            assert_eq!(vec!["unterminated string meets end of file"], messages);
+
+            let (_, messages) = lex_with_errors("%i[a b");
+            assert_eq!(vec!["unterminated list meets end of file"], messages);
+        }
+
+        #[test]
+        fn unterminated_interpolation() {
+            let (kinds, messages) = lex_with_errors("\"a#{b");
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    StringContent {
+                        text: "a".to_string()
+                    },
+                    EmbExprBegin,
+                    Identifier {
+                        text: "b".to_string()
+                    },
+                    Eof
+                ],
+                kinds
+            );
+            assert_eq!(vec!["unterminated string meets end of file"], messages);
+        }
+
+        #[test]
+        fn invalid_escapes() {
+            let (kinds, messages) = lex_with_errors("\"\\x \\u12 \\u{110000}\"");
+            assert_eq!(
+                vec![
+                    StringBegin,
+                    StringContent {
+                        text: "  ".to_string()
+                    },
+                    StringEnd,
+                    Eof
+                ],
+                kinds
+            );
+            assert_eq!(
+                vec![
+                    "invalid hex escape",
+                    "invalid Unicode escape",
+                    "invalid Unicode codepoint"
+                ],
+                messages
+            );
+        }
+
+        #[test]
+        fn variables() {
+            let mut lexer = Lexer::new("@foo @@bar @Baz");
+            assert_eq!(
+                Lexeme::new(InstanceVariable, ByteIdx(0), ByteDifference(4)),
+                lexer.next()
+            );
+            assert_eq!(
+                Lexeme::new(ClassVariable, ByteIdx(5), ByteDifference(5)),
+                lexer.next()
+            );
+            assert_eq!(
+                Lexeme::new(InstanceVariable, ByteIdx(11), ByteDifference(4)),
+                lexer.next()
+            );
+        }
+
+        #[test]
+        fn line_continuation() {
+            let (kinds, messages) = lex_with_errors("1 \\\n+ 2");
+            assert_eq!(
+                vec![
+                    IntegerLiteral {
+                        text: "1".to_string()
+                    },
+                    Plus,
+                    IntegerLiteral {
+                        text: "2".to_string()
+                    },
+                    Eof
+                ],
+                kinds
+            );
+            assert!(messages.is_empty());
+        }
+
+        #[test]
+        fn render() {
+            let text = "x = 1\ny = @ + 2\n";
+            let mut lexer = Lexer::new(text);
+            while lexer.next().kind != Eof {}
+            let expected = concat!(
+                "error: '@' without identifiers is not allowed as an instance variable name\n",
+                " --> 2:5\n",
+                "  |\n",
+                "2 | y = @ + 2\n",
+                "  |     ^\n",
+            );
+            assert_eq!(expected, lexer.errors()[0].render(text));
+        }
+    }
 }
@@ -4,7 +4,7 @@ use ruby_wasm::lexer::Lexer;
 use ruby_wasm::parser::Parser;
 use ruby_wasm::{CompileCtx, compiler};
 use ruby_wasm::{binary, html, run};
-use std::fs;
+use std::{fs, process};
 use wat_defs::module::Module;
 
 #[derive(clap::Parser)]
@@ -57,8 +57,8 @@ enum Command {
 
     /// Compiles and runs the given program.
     Run {
-        /// Text of program to compile
-        text: String,
+        #[clap(flatten)]
+        group: TextOrFile,
     },
 
     /// Compiles the given program, printing an `.html` file.
@@ -84,17 +84,24 @@ fn main() {
 
     match cli.command {
         Command::Lex { text } => {
-            println!("{:?}", run::lex(&text));
+            print!("{}", run::lex(&text));
         }
 
         Command::Parse { text } => {
             let parser = Parser::new(Lexer::new(&text));
-            println!("{:?}", parser.parse());
+            let (program, diagnostics) = parser.parse();
+            println!("{:?}", program);
+            for diagnostic in diagnostics {
+                print!("\n{}", diagnostic.render(&text));
+            }
         }
 
         Command::Compile { text } => {
             let parser = Parser::new(Lexer::new(&text));
-            let program = parser.parse();
+            let (program, diagnostics) = parser.parse();
+            if !diagnostics.is_empty() {
+                exit_with_diagnostics(run::render_diagnostics(&text, &diagnostics));
+            }
             let module = Module::new();
             let ctx = &mut CompileCtx::new(module);
             compiler::compile(ctx, &program);
@@ -105,13 +112,11 @@ fn main() {
             let TextOrFile { text, file } = group;
             match (text, file) {
                 (Some(text), None) => {
-                    let wat = run::compile_ctx_to_wat(&run::text_to_compile_ctx(text));
+                    let wat = run::compile_ctx_to_wat(&compile(text));
                     println!("{}", wat);
                 }
                 (None, Some(file)) => {
-                    let wat = run::compile_ctx_to_wat(&run::text_to_compile_ctx(
-                        fs::read_to_string(file).unwrap(),
-                    ));
+                    let wat = run::compile_ctx_to_wat(&compile(fs::read_to_string(file).unwrap()));
                     println!("{}", wat);
                 }
                 _ => unreachable!("Clap should prevent this."),
@@ -119,17 +124,22 @@ fn main() {
         }
 
         Command::Wasm { text } => {
-            let ctx = run::text_to_compile_ctx(text);
+            let ctx = compile(text);
             let bytes = binary::module_to_binary(&ctx.module);
             binary::print_bytes(&bytes);
         }
 
-        Command::Run { text } => {
-            println!("{}", run::run_text(text))
+        Command::Run { group } => {
+            let TextOrFile { text, file } = group;
+            match (text, file) {
+                (Some(text), None) => run("-e", text),
+                (None, Some(file)) => run(&file, fs::read_to_string(&file).unwrap()),
+                _ => unreachable!("Clap should prevent this."),
+            }
         }
 
         Command::Html { text } => {
-            let ctx = run::text_to_compile_ctx(text);
+            let ctx = compile(text);
             let bytes = binary::module_to_binary(&ctx.module);
             let html = html::make_html_wrapper(&bytes);
             println!("{}", html);
@@ -147,3 +157,21 @@ fn main() {
         }
     }
 }
+
+/// Compiles `text`, exiting if it has syntax errors.
+fn compile(text: String) -> CompileCtx {
+    run::text_to_compile_ctx(text).unwrap_or_else(|diagnostics| exit_with_diagnostics(diagnostics))
+}
+
+/// Runs `text`, the contents of `file_name`, exiting if it has syntax errors.
+fn run(file_name: &str, text: String) {
+    match run::try_run_source(file_name, text) {
+        Ok(res) => println!("{}", res),
+        Err(diagnostics) => exit_with_diagnostics(diagnostics),
+    }
+}
+
+fn exit_with_diagnostics(diagnostics: String) -> ! {
+    eprintln!("{}", diagnostics);
+    process::exit(1)
+}
@@ -1,39 +1,133 @@
+use crate::lexeme::Span;
 use serde::Serialize;
+use serde::ser::{SerializeStruct, Serializer};
 
 /// Root of the AST
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Program {
     pub statements: Statements,
+    /// The text after `__END__`, which the program reads as `DATA`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Statements {
     pub body: Vec<Expr>,
+    #[serde(skip)]
+    pub span: Span,
 }
 
+/// An expression, and the span of source text it was parsed from.
+/// Serializes as just its `kind`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
-pub enum Expr {
+#[serde(transparent)]
+pub struct Expr {
+    pub kind: ExprKind,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ExprKind {
     /// TODO -- Final representation will not be `i64`
     Integer(i64),
-    SingleQuoteString(String),
+    Float(Float),
+    String(String),
+    InterpolatedString(Box<InterpolatedString>),
+    /// Symbol literal. Holds the name without the leading `:`.
+    Symbol(String),
+    /// Symbol with interpolations, like `:"a#{b}"`, interned at runtime.
+    InterpolatedSymbol(Box<InterpolatedString>),
     True,
     False,
     Nil,
+    /// `self`: the method's receiver, the class in a class or module body, otherwise `main`.
+    SelfKeyword,
     GlobalVariableWrite(Box<GlobalVariableWrite>),
     GlobalVariableRead(Box<GlobalVariableRead>),
     ConstantWrite(Box<ConstantWrite>),
     ConstantRead(Box<ConstantRead>),
+    InstanceVariableWrite(Box<InstanceVariableWrite>),
+    InstanceVariableRead(Box<InstanceVariableRead>),
+    /// `<TARGET> <OP>= <VALUE>`, like `x += 1`, `a[i] ||= []` or `obj.x &&= y`.
+    OperatorWrite(Box<OperatorWrite>),
+    /// `<TARGET>, <TARGET> = <VALUE>`, like `a, b = b, a` or `first, *rest = list`.
+    MultiWrite(Box<MultiWrite>),
     If(Box<If>),
+    Case(Box<Case>),
+    Begin(Box<Begin>),
+    /// `retry`, in a `rescue` clause: run the `begin` body again.
+    Retry,
+    /// `return`: leave the method, or the lambda.
+    Return(Box<Jump>),
+    /// `break`: leave the loop, or the method the block was passed to.
+    Break(Box<Jump>),
+    /// `next`: go on to the loop's next iteration, or leave the block.
+    Next(Box<Jump>),
+    /// `redo`: run the loop's body or the block again, without checking the condition.
+    Redo,
     While(Box<While>),
     Until(Box<Until>),
     Call(Box<Call>),
     And(Box<And>),
     Or(Box<Or>),
     Array(Box<Array>),
+    /// `<KEY>: <VAL>, ...` at the end of a call's args, passed as a `Hash`.
+    Hash(Box<Hash>),
+    /// `*<EXPR>` among a call's args: passes each element of the array `<EXPR>` evaluates to.
+    SplatArg(Box<Expr>),
+    Range(Box<Range>),
     Def(Box<Def>),
+    Class(Box<Class>),
+    Module(Box<Module>),
+    Super(Box<Super>),
+    /// `super` without an argument list: passes on the current method's arguments.
+    ForwardingSuper,
+    Yield(Box<Yield>),
+    /// `-> (<PARAMS>) { <BODY> }`
+    Lambda(Box<Block>),
+    /// `( <STATEMENTS> )`: the value of the last statement, or `nil` if there are none.
+    Parentheses(Box<Statements>),
     LocalVariableRead(Box<LocalVariableRead>),
     LocalVariableWrite(Box<LocalVariableWrite>),
     For(Box<For>),
+    /// Stands in for an expression that couldn't be parsed.
+    /// The parser reports a `Diagnostic` for each one, so they're never compiled.
+    Missing,
+}
+
+/// `f64` with bitwise equality, so that `ExprKind` can be `Eq`.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(transparent)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+/// String literal containing `#{}` interpolations.
+/// Strings without interpolations are `ExprKind::String`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterpolatedString {
+    pub parts: Vec<StringPart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum StringPart {
+    Literal(String),
+    /// `#{ <STATEMENTS> }`
+    Interpolation(Statements),
 }
 
 /// Method definition.
@@ -58,16 +152,164 @@ pub struct LocalVariableRead {
 }
 
 /// Method definition.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/// `def (self.)?<NAME>(<PARAMS>)`
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Def {
+    /// `def self.<NAME>`, defining a method of the class itself, rather than of its instances.
+    pub singleton: bool,
+    pub name: String,
+    pub params: Params,
+    pub body: Statements,
+}
+
+impl Serialize for Def {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct(
+            "Def",
+            usize::from(self.singleton) + 2 + self.params.field_count(),
+        )?;
+        serialize_field_if(&mut state, "singleton", &self.singleton, self.singleton)?;
+        state.serialize_field("name", &self.name)?;
+        self.params.serialize_fields(&mut state)?;
+        state.serialize_field("body", &self.body)?;
+        state.end()
+    }
+}
+
+/// The params of a method or block, by kind, in the order they must be written:
+/// `<REQUIRED>, <OPTIONAL>, <REST>, <POST>, <KEYWORD>, <KEYWORD_REST>, <BLOCK>`,
+/// each kind of param being optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    pub required: Vec<RequiredParam>,
+    pub optional: Vec<OptionalParam>,
+    /// `*rest`, binding an `Array` of the positional args after the optional params.
+    pub rest: Option<RestParam>,
+    /// Required params after the optional or rest params, like `c` in `def f(a, b = 1, c)`,
+    ///     which take the last positional args.
+    pub post: Vec<RequiredParam>,
+    pub keyword: Vec<KeywordParam>,
+    /// `**opts`, binding a `Hash` of the keyword args that aren't keyword params.
+    pub keyword_rest: Option<RestParam>,
+    /// `&block`, binding the block passed to the method or block.
+    pub block: Option<BlockParam>,
+}
+
+impl Params {
+    /// The number of fields `serialize_fields` writes.
+    fn field_count(&self) -> usize {
+        1 + usize::from(!self.optional.is_empty())
+            + usize::from(self.rest.is_some())
+            + usize::from(!self.post.is_empty())
+            + usize::from(!self.keyword.is_empty())
+            + usize::from(self.keyword_rest.is_some())
+            + usize::from(self.block.is_some())
+    }
+
+    /// Serialize the params as fields of the `Def` or `Block` holding them,
+    ///     leaving out the kinds of param there are none of, other than the required params.
+    fn serialize_fields<S: SerializeStruct>(&self, state: &mut S) -> Result<(), S::Error> {
+        state.serialize_field("params", &self.required)?;
+        serialize_field_if(
+            state,
+            "optional_params",
+            &self.optional,
+            !self.optional.is_empty(),
+        )?;
+        serialize_field_if(state, "rest_param", &self.rest, self.rest.is_some())?;
+        serialize_field_if(state, "post_params", &self.post, !self.post.is_empty())?;
+        serialize_field_if(
+            state,
+            "keyword_params",
+            &self.keyword,
+            !self.keyword.is_empty(),
+        )?;
+        serialize_field_if(
+            state,
+            "keyword_rest_param",
+            &self.keyword_rest,
+            self.keyword_rest.is_some(),
+        )?;
+        serialize_field_if(state, "block_param", &self.block, self.block.is_some())
+    }
+}
+
+/// Serialize the field `key` if `present`, otherwise skip it,
+///     like `#[serde(skip_serializing_if)]`.
+fn serialize_field_if<S: SerializeStruct, T: Serialize>(
+    state: &mut S,
+    key: &'static str,
+    value: &T,
+    present: bool,
+) -> Result<(), S::Error> {
+    if present {
+        state.serialize_field(key, value)
+    } else {
+        state.skip_field(key)
+    }
+}
+
+/// Class definition, or reopening of an existing class.
+/// `class <NAME> ( < <SUPERCLASS> )? <BODY> end`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Expr>,
+    pub body: Statements,
+}
+
+/// Module definition, or reopening of an existing module.
+/// `module <NAME> <BODY> end`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Module {
     pub name: String,
-    pub params: Vec<RequiredParam>,
     pub body: Statements,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct RequiredParam {
     pub name: String,
+    /// `( <TARGETS> )`, which destructures the arg like a multiple assignment.
+    /// `name` is then a hidden variable holding the arg, starting with `%`, which Ruby variables can't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub targets: Option<MultiTarget>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// `<NAME> = <DEFAULT>`, evaluated in the method if the arg isn't given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OptionalParam {
+    pub name: String,
+    pub default: Expr,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// `*<NAME>` or `**<NAME>`, collecting the remaining positional or keyword args.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestParam {
+    pub name: String,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// `<NAME>:`, which must be given, or `<NAME>: <DEFAULT>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeywordParam {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Expr>,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+/// `&<NAME>`, the last of a method's or block's params.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockParam {
+    pub name: String,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -75,6 +317,26 @@ pub struct Array {
     pub vals: Vec<Expr>,
 }
 
+/// Keyword args, with symbol keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Hash {
+    pub pairs: Vec<Pair>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Pair {
+    pub key: String,
+    pub val: Expr,
+}
+
+/// `<LEFT>..<RIGHT>`, or `<LEFT>...<RIGHT>` which excludes `right`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Range {
+    pub left: Expr,
+    pub right: Expr,
+    pub exclusive: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct And {
     pub lhs: Expr,
@@ -109,6 +371,71 @@ pub struct ConstantRead {
     pub name: String,
 }
 
+/// Holds the name without the leading `@`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstanceVariableWrite {
+    pub name: String,
+    pub expr: Expr,
+}
+
+/// Holds the name without the leading `@`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstanceVariableRead {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OperatorWrite {
+    /// How the target is read: a variable or constant read,
+    ///     or an index or attribute `Call`, whose receiver and args are only evaluated once.
+    pub target: Expr,
+    pub operator: WriteOperator,
+    pub value: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MultiWrite {
+    pub targets: MultiTarget,
+    /// A single value, or an `Array` of the comma-separated values.
+    pub value: Expr,
+}
+
+/// The targets of a multiple assignment, or of a nested `( <TARGETS> )` within one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MultiTarget {
+    /// The targets before the splat, or all of them without one.
+    pub lefts: Vec<Target>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rest: Option<Splat>,
+    /// The targets after the splat.
+    pub rights: Vec<Target>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum Target {
+    /// How the target is read, as in `OperatorWrite`.
+    Single(Expr),
+    /// `( <TARGETS> )`, which destructures its value again.
+    Nested(Box<MultiTarget>),
+}
+
+/// `*<TARGET>`, which takes the values the other targets don't, as an array.
+/// A bare `*` ignores them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Splat {
+    pub target: Option<Target>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum WriteOperator {
+    /// `||=`: writes the value only if the target is falsy.
+    Or,
+    /// `&&=`: writes the value only if the target is truthy.
+    And,
+    /// Like `+=`: writes `<TARGET> + <VALUE>`. Holds the method name, like `+`.
+    Call(String),
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct If {
     pub predicate: Expr,
@@ -116,16 +443,66 @@ pub struct If {
     pub subsequent: Subsequent,
 }
 
+/// `case <SUBJECT>? <WHEN>+ (else <STATEMENTS>)? end`
+/// With a subject, a `when` matches if `pattern === subject` for one of its patterns.
+/// Without one, if one of its patterns is truthy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Case {
+    pub subject: Option<Expr>,
+    pub whens: Vec<When>,
+    pub else_clause: Option<Else>,
+}
+
+/// `when <PATTERN> (, <PATTERN>)* then <STATEMENTS>`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct When {
+    pub patterns: Vec<Expr>,
+    pub statements: Statements,
+}
+
+/// `begin <STATEMENTS> <RESCUE>* (else <STATEMENTS>)? (ensure <STATEMENTS>)? end`
+/// A `def` body with `rescue`, `else` or `ensure` clauses is one of these too.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Begin {
+    pub statements: Statements,
+    pub rescues: Vec<Rescue>,
+    /// Runs if `statements` raised no exception.
+    pub else_clause: Option<Else>,
+    /// Runs last, whether or not an exception was raised.
+    pub ensure_clause: Option<Statements>,
+}
+
+/// `rescue <CLASS> (, <CLASS>)* (=> <VARIABLE>)? then? <STATEMENTS>`
+/// Without any classes, rescues `StandardError`s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Rescue {
+    pub classes: Vec<Expr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variable: Option<String>,
+    pub statements: Statements,
+}
+
+/// The value that `return`, `break` or `next` leaves with.
+/// `nil` if there isn't one, and an array if there are several.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Jump {
+    pub value: Option<Expr>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct While {
     pub predicate: Expr,
     pub statements: Statements,
+    /// `begin ... end while predicate`, whose body runs before the predicate is first checked.
+    pub do_while: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Until {
     pub predicate: Expr,
     pub statements: Statements,
+    /// `begin ... end until predicate`, whose body runs before the predicate is first checked.
+    pub do_while: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
@@ -133,6 +510,45 @@ pub struct Call {
     pub receiver: Option<Expr>,
     pub name: String,
     pub args: Vec<Expr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<CallBlock>,
+}
+
+/// The block passed to a call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum CallBlock {
+    /// `{ |<PARAMS>| <BODY> }` or `do |<PARAMS>| <BODY> end`
+    Literal(Box<Block>),
+    /// `&<EXPR>`, passing a `Proc` as the block.
+    Argument(Expr),
+}
+
+/// A block or lambda, whose params are like a `def`'s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub params: Params,
+    pub body: Statements,
+}
+
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Block", 1 + self.params.field_count())?;
+        self.params.serialize_fields(&mut state)?;
+        state.serialize_field("body", &self.body)?;
+        state.end()
+    }
+}
+
+/// `yield(<ARGS>)`: call the current method's block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Yield {
+    pub args: Vec<Expr>,
+}
+
+/// `super(<ARGS>)`: call the method this one overrides, with these arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Super {
+    pub args: Vec<Expr>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
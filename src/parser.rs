@@ -1,6 +1,9 @@
 //! Ruby text -> AST parser
 
-use crate::lexeme::{Lexeme, LexemeKind as LK, LexemeKind};
+use crate::lexeme::{
+    ByteDifference, ByteIdx, Lexeme, LexemeKind as LK, LexemeKind, LineIndex, Span,
+};
+use crate::lexer;
 use crate::lexer::Lexer;
 use crate::node as N;
 use std::str::FromStr;
@@ -11,17 +14,161 @@ use std::vec;
 /// - Output AST
 /// - Recursive descent
 /// - Use call stack as implicit stack of half-finished nodes
+/// - On a syntax error, record a `Diagnostic`, put `ExprKind::Missing` in the tree,
+///   and skip to the end of the statement. So we find every error in the file at once.
 pub struct Parser<'text> {
     lexer: Lexer<'text>,
+    /// Index just past the last consumed lexeme, where the current node's span ends.
+    prev_end: ByteIdx,
+    /// Syntax errors found so far.
+    diagnostics: Vec<Diagnostic>,
+    /// In the header of a `while`, `until` or `for`, `do` ends the header instead of starting a block.
+    in_loop_header: bool,
+    /// In the args of a command call, `do` starts the command's block, not one for the last arg.
+    in_command_args: bool,
+    /// The local variables declared so far in each enclosing scope, innermost last.
+    /// As in Prism, an identifier that isn't one of them is a method call.
+    scopes: Vec<LocalScope>,
+}
+
+/// The local variables of the top level, a `def`, a class or module body, or a block.
+#[derive(Default)]
+struct LocalScope {
+    names: Vec<String>,
+    /// Blocks can also use the variables of the scope they're in.
+    is_block: bool,
+    /// In the body of a `while`, `until` or `for` loop.
+    /// There, and in blocks, `break`, `next` and `redo` have somewhere to go.
+    in_loop: bool,
+    /// In a `rescue` clause, where `retry` can go.
+    in_rescue: bool,
+    /// `break`, `next` and `redo` outside of a loop or block,
+    ///     which are errors unless a `while` or `until` modifier applies to them.
+    stray_jumps: Vec<(Span, LexemeKind)>,
+}
+
+/// A syntax error, found while parsing.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    /// Lexeme kinds that would have been accepted instead.
+    /// Empty if the error isn't about a single unexpected lexeme.
+    pub expected: Vec<LexemeKind>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Formats the diagnostic with its line and column, underlining it in `text`.
+    /// See `LineIndex::render_error`.
+    pub fn render(&self, text: &str) -> String {
+        LineIndex::new(text).render_error(self.span, &self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum DefParamKind {
+    Required,
+    Optional,
+    Rest,
+    /// A required param after optional or rest params.
+    Post,
+    Keyword,
+    KeywordRest,
+}
+
+impl DefParamKind {
+    fn describe(self) -> &'static str {
+        match self {
+            DefParamKind::Required | DefParamKind::Post => "required",
+            DefParamKind::Optional => "optional",
+            DefParamKind::Rest => "rest",
+            DefParamKind::Keyword => "keyword",
+            DefParamKind::KeywordRest => "keyword rest",
+        }
+    }
+}
+
+impl N::Params {
+    /// The latest kind of param so far.
+    fn last_kind(&self) -> Option<DefParamKind> {
+        if self.keyword_rest.is_some() {
+            Some(DefParamKind::KeywordRest)
+        } else if !self.keyword.is_empty() {
+            Some(DefParamKind::Keyword)
+        } else if !self.post.is_empty() {
+            Some(DefParamKind::Post)
+        } else if self.rest.is_some() {
+            Some(DefParamKind::Rest)
+        } else if !self.optional.is_empty() {
+            Some(DefParamKind::Optional)
+        } else if !self.required.is_empty() {
+            Some(DefParamKind::Required)
+        } else {
+            None
+        }
+    }
+
+    /// Add a required param, starting at `start`,
+    /// which is a post param if it comes after optional or rest params.
+    fn push_required(&mut self, parser: &mut Parser, param: N::RequiredParam, start: ByteIdx) {
+        match self.last_kind() {
+            Some(DefParamKind::Optional | DefParamKind::Rest | DefParamKind::Post) => {
+                self.post.push(param)
+            }
+            _ => {
+                self.check_order(parser, DefParamKind::Required, start);
+                self.required.push(param)
+            }
+        }
+    }
+
+    /// Report a param of `kind`, starting at `start`, that comes after a later kind,
+    /// or is a second `*` or `**` param.
+    fn check_order(&self, parser: &mut Parser, kind: DefParamKind, start: ByteIdx) {
+        let Some(last_kind) = self.last_kind() else {
+            return;
+        };
+        let repeated_rest =
+            last_kind == kind && matches!(kind, DefParamKind::Rest | DefParamKind::KeywordRest);
+        if last_kind > kind || repeated_rest {
+            parser.error(
+                parser.span_from(start),
+                vec![],
+                format!(
+                    "{} param after {} param",
+                    kind.describe(),
+                    last_kind.describe()
+                ),
+            );
+        }
+    }
 }
 
 impl<'text> Parser<'text> {
     pub fn new(lexer: Lexer<'text>) -> Self {
-        Self { lexer }
+        Self {
+            lexer,
+            prev_end: ByteIdx(0),
+            diagnostics: vec![],
+            in_loop_header: false,
+            in_command_args: false,
+            scopes: vec![LocalScope::default()],
+        }
     }
 
-    pub fn parse(mut self) -> N::Program {
-        self.program()
+    /// Parse the whole text.
+    /// The program is only fit to compile if there are no diagnostics.
+    /// Lex errors are included, in order of where they occur.
+    pub fn parse(mut self) -> (N::Program, Vec<Diagnostic>) {
+        let program = self.program();
+        let lex_errors = self.lexer.errors().iter().map(|error| Diagnostic {
+            span: Span::new(error.start, error.len),
+            expected: vec![],
+            message: error.message.clone(),
+        });
+        let mut diagnostics: Vec<_> = lex_errors.chain(self.diagnostics).collect();
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start.0);
+        (program, diagnostics)
     }
 
     ///
@@ -29,8 +176,17 @@ impl<'text> Parser<'text> {
     /// PROGRAM = STATEMENTS
     /// ```
     fn program(&mut self) -> N::Program {
-        let statements = self.statements();
-        N::Program { statements }
+        let statements = self.statements_until(|kind| matches!(kind, LK::Eof | LK::UnderscoreEnd));
+        let top_level = std::mem::take(&mut self.scopes[0]);
+        self.report_stray_jumps(top_level);
+        let data = match self.peek().kind {
+            LK::UnderscoreEnd => {
+                self.next();
+                self.lexer.data_section().map(str::to_string)
+            }
+            _ => None,
+        };
+        N::Program { statements, data }
     }
 
     ///
@@ -38,15 +194,233 @@ impl<'text> Parser<'text> {
     /// STATEMENTS = EXPR*
     /// ```
     fn statements(&mut self) -> N::Statements {
+        self.statements_until(ends_statements)
+    }
+
+    /// Parse statements up to a lexeme for which `stop` holds.
+    /// Anything else that can't start a statement is reported and skipped,
+    /// like a stray `end` at the top level.
+    fn statements_until(&mut self, stop: fn(&LexemeKind) -> bool) -> N::Statements {
         let mut body = vec![];
 
         self.skip_newlines();
-        while let Some(expr) = self.expr() {
-            body.push(expr);
+        let start = self.peek().start;
+        loop {
+            let errors_before = self.diagnostics.len();
+            match self.statement() {
+                Some(expr) => {
+                    body.push(expr);
+                    let lexeme = self.peek();
+                    let terminated = matches!(lexeme.kind, LK::Newline | LK::Semicolon);
+                    if !terminated && !stop(&lexeme.kind) && self.diagnostics.len() == errors_before
+                    {
+                        // Like `1 2`: statements on the same line need a `;` between them.
+                        self.unexpected(&lexeme, vec![LK::Newline, LK::Semicolon]);
+                    }
+                }
+                None => {
+                    let lexeme = self.peek();
+                    if stop(&lexeme.kind) {
+                        break;
+                    }
+                    self.unexpected(&lexeme, vec![]);
+                    self.next();
+                }
+            }
+            if self.diagnostics.len() > errors_before {
+                self.synchronize();
+            }
             self.skip_newlines();
         }
 
-        N::Statements { body }
+        let span = match (body.first(), body.last()) {
+            (Some(first), Some(last)) => Span::new(
+                first.span.start,
+                ByteDifference(last.span.end().0 - first.span.start.0),
+            ),
+            _ => Span::new(start, ByteDifference(0)),
+        };
+        N::Statements { body, span }
+    }
+
+    /// A statement, which unlike an expression can be a multiple assignment.
+    /// ```ebnf
+    /// STATEMENT = MULTI_WRITE MODIFIER* | EXPR
+    /// ```
+    fn statement(&mut self) -> Option<N::Expr> {
+        self.skip_newlines();
+        let start = self.peek().start;
+        let targets = match self.peek().kind {
+            // Only a multiple assignment can start with `*`,
+            // or with nested targets, like `(a, b), c = x`.
+            LK::Star => self.multi_target(None, LK::Equal),
+            LK::LeftParen if self.kind_after_parentheses() == LK::Comma => {
+                self.multi_target(None, LK::Equal)
+            }
+            // `(a, b) = x` is the same as `a, b = x`.
+            LK::LeftParen if self.kind_after_parentheses() == LK::Equal => {
+                self.next();
+                let targets = self.multi_target(None, LK::RightParen);
+                self.expect(&[LK::Equal]);
+                targets
+            }
+            _ => {
+                let first = self.expr()?;
+                if self.peek().kind != LK::Comma {
+                    return Some(first);
+                }
+                self.multi_target(Some(first), LK::Equal)
+            }
+        };
+        let mut statement = self.multi_write(start, targets);
+        while is_modifier(&self.peek().kind) {
+            let modifier = self.next().kind;
+            let kind = self.modifier(modifier, statement);
+            statement = N::Expr::new(kind, self.span_from(start));
+        }
+        Some(statement)
+    }
+
+    /// The kind of the lexeme after the parentheses about to be parsed.
+    /// If it's `,` or `=`, they're targets of a multiple assignment, rather than an expression.
+    /// Looks ahead with a copy of the lexer, so nothing is consumed.
+    fn kind_after_parentheses(&mut self) -> LexemeKind {
+        let mut lexer = self.lexer.clone();
+        let mut depth = 0;
+        loop {
+            match lexer.next().kind {
+                LK::LeftParen => depth += 1,
+                LK::RightParen if depth == 1 => break,
+                LK::RightParen => depth -= 1,
+                LK::Eof => return LK::Eof,
+                _ => (),
+            }
+        }
+        loop {
+            match lexer.next().kind {
+                LK::InlineComment | LK::EmbDoc => (),
+                kind => return kind,
+            }
+        }
+    }
+
+    /// Parse a multiple assignment's value,
+    ///     given its targets, which have been parsed up to and including `=`.
+    /// ```ebnf
+    /// MULTI_WRITE = MULTI_TARGET "=" EXPR ( "," EXPR )*
+    /// MULTI_TARGET = TARGET ( "," TARGET )* ","? | "(" MULTI_TARGET ")"
+    /// TARGET = EXPR | "*" EXPR? | "(" MULTI_TARGET ")"
+    /// ```
+    fn multi_write(&mut self, start: ByteIdx, targets: N::MultiTarget) -> N::Expr {
+        let value_start = self.peek().start;
+        let mut vals = vec![self.operand_before_modifier()];
+        while self.consume_if_found(LK::Comma).is_some() {
+            vals.push(self.operand_before_modifier());
+        }
+        let value = if vals.len() == 1 {
+            vals.remove(0)
+        } else {
+            N::Expr::new(
+                N::ExprKind::Array(Box::new(N::Array { vals })),
+                self.span_from(value_start),
+            )
+        };
+        let multi_write = N::MultiWrite { targets, value };
+        N::Expr::new(
+            N::ExprKind::MultiWrite(Box::new(multi_write)),
+            self.span_from(start),
+        )
+    }
+
+    /// Parse targets up to and including `close`: `=`, or the `)` of nested targets.
+    fn multi_target(&mut self, mut first: Option<N::Expr>, close: LexemeKind) -> N::MultiTarget {
+        let mut multi_target = N::MultiTarget {
+            lefts: vec![],
+            rest: None,
+            rights: vec![],
+        };
+        loop {
+            let lexeme = self.peek();
+            let target = match (first.take(), lexeme.kind) {
+                (Some(expr), _) => Some(self.target(expr)),
+                (None, LK::Star) => {
+                    self.next();
+                    let target = match self.peek().kind {
+                        LK::Comma | LK::Equal | LK::RightParen => None,
+                        _ => Some(self.target_operand()),
+                    };
+                    if multi_target.rest.is_some() {
+                        let span = self.span_from(lexeme.start);
+                        self.error(span, vec![], "multiple splats in one assignment");
+                    }
+                    multi_target.rest = Some(N::Splat { target });
+                    None
+                }
+                (None, LK::LeftParen) => {
+                    self.next();
+                    let nested = self.multi_target(None, LK::RightParen);
+                    Some(N::Target::Nested(Box::new(nested)))
+                }
+                (None, _) => Some(self.target_operand()),
+            };
+            match (target, &multi_target.rest) {
+                (Some(target), None) => multi_target.lefts.push(target),
+                (Some(target), Some(_)) => multi_target.rights.push(target),
+                (None, _) => (),
+            }
+
+            let lexeme = self.peek();
+            match lexeme.kind {
+                LK::Comma => {
+                    self.next();
+                    // A trailing comma, like `first, = list`
+                    if self.consume_if_found(close.clone()).is_some() {
+                        break;
+                    }
+                }
+                lk if lk == close => {
+                    self.next();
+                    break;
+                }
+                _ => {
+                    self.unexpected(&lexeme, vec![LK::Comma, close]);
+                    break;
+                }
+            }
+        }
+        multi_target
+    }
+
+    /// Parse a target of a multiple assignment, stopping before `=`.
+    fn target_operand(&mut self) -> N::Target {
+        let (l_bp, _) = LK::Equal.binding_power();
+        let expr = self.operand(l_bp + 1);
+        self.target(expr)
+    }
+
+    /// The target of a multiple assignment, from how it's read.
+    /// An identifier that isn't a local variable yet becomes one.
+    fn target(&mut self, expr: N::Expr) -> N::Target {
+        match &expr.kind {
+            N::ExprKind::Call(call) if is_vcall(call) => {
+                self.declare(&call.name);
+                let read = N::LocalVariableRead {
+                    name: call.name.clone(),
+                };
+                let read = N::ExprKind::LocalVariableRead(Box::new(read));
+                N::Target::Single(N::Expr::new(read, expr.span))
+            }
+            N::ExprKind::LocalVariableRead(read) => {
+                // Before `=`, a read even if it isn't a local variable yet.
+                self.declare(&read.name);
+                N::Target::Single(expr)
+            }
+            _ if is_writable(&expr) => N::Target::Single(expr),
+            _ => {
+                self.error(expr.span, vec![], "target cannot be written");
+                N::Target::Single(N::Expr::new(N::ExprKind::Missing, expr.span))
+            }
+        }
     }
 
     /// ```ebnf
@@ -57,10 +431,10 @@ impl<'text> Parser<'text> {
     }
 
     fn expr_bp(&mut self, min_bp: u8) -> Option<N::Expr> {
-        /// Expect the given keyword's lexem kind, then return its node
+        /// Consume the given keyword's lexeme, then return its node
         macro_rules! expect_simple_kw(
             ($lexeme_kind:path, $node_expr_variant:path) => ({
-                self.expect(&[$lexeme_kind]);
+                self.debug_expect(&[$lexeme_kind]);
                 Some($node_expr_variant)
             })
         );
@@ -74,46 +448,82 @@ impl<'text> Parser<'text> {
 
         self.skip_newlines();
 
-        let lexeme = self.lexer.peek();
+        let lexeme = self.peek();
+        let start = lexeme.start;
         let lhs = match lexeme.kind {
             // Literals
             LK::IntegerLiteral { .. } => self.integer_literal(),
+            LK::FloatLiteral { .. } => Some(self.float_literal()),
             LK::SingleQuoteStringLiteral { .. } => self.single_quote_string_literal(),
-            LK::BracketLeftRight { .. } => {
-                self.lexer.next();
-                Some(N::Expr::Array(Box::new(N::Array { vals: vec![] })))
+            LK::StringBegin => Some(self.double_quote_string_literal()),
+            LK::HeredocStart => Some(self.double_quote_string_literal()),
+            LK::SymbolLiteral { .. } => Some(self.symbol_literal()),
+            LK::SymbolBegin => Some(self.quoted_symbol_literal()),
+            LK::PercentLowerW | LK::PercentUpperW | LK::PercentLowerI | LK::PercentUpperI => {
+                Some(self.word_list_literal())
             }
-            LK::BracketLeft { .. } => box_expr_variant!(self.array_literal(), N::Expr::Array),
+            LK::BracketLeftRight => {
+                self.next();
+                Some(N::ExprKind::Array(Box::new(N::Array { vals: vec![] })))
+            }
+            LK::BracketLeft => box_expr_variant!(self.array_literal(), N::ExprKind::Array),
 
             // Keywords
-            LK::True => expect_simple_kw!(LK::True, N::Expr::True),
-            LK::False => expect_simple_kw!(LK::False, N::Expr::False),
-            LK::Nil => expect_simple_kw!(LK::Nil, N::Expr::Nil),
+            LK::True => expect_simple_kw!(LK::True, N::ExprKind::True),
+            LK::False => expect_simple_kw!(LK::False, N::ExprKind::False),
+            LK::Nil => expect_simple_kw!(LK::Nil, N::ExprKind::Nil),
+            LK::SelfKeyword => expect_simple_kw!(LK::SelfKeyword, N::ExprKind::SelfKeyword),
 
             LK::GlobalVariable { .. } => {
-                box_expr_variant!(self.global_variable(), N::Expr::GlobalVariableRead)
+                box_expr_variant!(self.global_variable(), N::ExprKind::GlobalVariableRead)
             }
             LK::Constant { .. } => Some(self.constant()),
+            LK::ColonColon => Some(self.top_level_constant()),
+            LK::InstanceVariable => {
+                box_expr_variant!(self.instance_variable(), N::ExprKind::InstanceVariableRead)
+            }
             LK::Identifier { .. } => Some(self.parse_ident(min_bp)),
 
             // Control flow
-            LK::If => box_expr_variant!(self.if_expr(), N::Expr::If),
-            LK::While => box_expr_variant!(self.while_expr(), N::Expr::While),
-            LK::Until => box_expr_variant!(self.until_expr(), N::Expr::Until),
-            LK::For => box_expr_variant!(self.for_in_expr(), N::Expr::For),
+            LK::If => box_expr_variant!(self.if_expr(), N::ExprKind::If),
+            LK::Unless => box_expr_variant!(self.unless_expr(), N::ExprKind::If),
+            LK::While => box_expr_variant!(self.while_expr(), N::ExprKind::While),
+            LK::Until => box_expr_variant!(self.until_expr(), N::ExprKind::Until),
+            LK::For => box_expr_variant!(self.for_in_expr(), N::ExprKind::For),
 
             LK::Minus => self.unary_minus(),
+            LK::Bang | LK::TildeOrTildeAt | LK::Plus => self.unary(),
+            LK::Not => Some(self.not_expr()),
 
-            LK::Def => box_expr_variant!(self.def_expr(), N::Expr::Def),
+            LK::Def => box_expr_variant!(self.def_expr(), N::ExprKind::Def),
+            LK::Class => box_expr_variant!(self.class_expr(), N::ExprKind::Class),
+            LK::Module => box_expr_variant!(self.module_expr(), N::ExprKind::Module),
+            LK::Super => Some(self.super_expr()),
+            LK::Yield => box_expr_variant!(self.yield_expr(), N::ExprKind::Yield),
+            LK::Case => box_expr_variant!(self.case_expr(), N::ExprKind::Case),
+            LK::Begin => box_expr_variant!(self.begin_expr(), N::ExprKind::Begin),
+            LK::Retry => {
+                self.check_jump(LK::Retry);
+                expect_simple_kw!(LK::Retry, N::ExprKind::Retry)
+            }
+            LK::Return => box_expr_variant!(self.jump(LK::Return), N::ExprKind::Return),
+            LK::Break => box_expr_variant!(self.jump(LK::Break), N::ExprKind::Break),
+            LK::Next => box_expr_variant!(self.jump(LK::Next), N::ExprKind::Next),
+            LK::Redo => {
+                self.check_jump(LK::Redo);
+                expect_simple_kw!(LK::Redo, N::ExprKind::Redo)
+            }
+            LK::MinusGreater => box_expr_variant!(self.lambda_expr(), N::ExprKind::Lambda),
+            LK::LeftParen => box_expr_variant!(self.parentheses(), N::ExprKind::Parentheses),
 
             _ => None,
         };
 
-        let Some(mut lhs) = lhs else { return None };
+        let mut lhs = N::Expr::new(lhs?, self.span_from(start));
 
         // Parse infix operators
         loop {
-            let op = match self.lexer.peek() {
+            let op = match self.peek() {
                 op if op.is_operator() => op,
                 _ => break,
             };
@@ -122,103 +532,197 @@ impl<'text> Parser<'text> {
                 break;
             }
 
-            self.lexer.next();
-            lhs = match op.kind {
+            self.next();
+            let kind = match op.kind {
                 LK::Dot => {
-                    let name = match self.lexer.next().kind {
-                        LK::Identifier { text: name } => name,
-                        LK::BracketLeftRight => "[]".to_string(),
-                        _ => panic!("Expected identifier or `[]`."),
+                    let name = match self.peek().kind {
+                        LK::Identifier { text: name } => {
+                            self.next();
+                            name
+                        }
+                        LK::BracketLeftRight => {
+                            self.next();
+                            "[]".to_string()
+                        }
+                        // `callable.(args)` calls `call`
+                        LK::LeftParen => "call".to_string(),
+                        // Like `object.class()`
+                        kw if kw.is_keyword() => {
+                            self.next();
+                            kw.spelling().unwrap().to_string()
+                        }
+                        _ => {
+                            let lexeme = self.peek();
+                            let expected = vec![
+                                LK::Identifier {
+                                    text: String::new(),
+                                },
+                                LK::BracketLeftRight,
+                            ];
+                            self.unexpected(&lexeme, expected);
+                            return Some(N::Expr::new(N::ExprKind::Missing, self.span_from(start)));
+                        }
                     };
 
-                    self.expect(&[LK::LeftParen]);
-                    let args = self.args(LK::RightParen);
+                    let (args, block) = match self.block() {
+                        Some(block) => (vec![], Some(N::CallBlock::Literal(Box::new(block)))),
+                        None => match self.consume_if_found(LK::LeftParen) {
+                            Some(_) => self.call_args(),
+                            None if self.starts_command_arg() => self.command_args(),
+                            None => (vec![], None),
+                        },
+                    };
 
-                    N::Expr::Call(Box::new(N::Call {
+                    N::ExprKind::Call(Box::new(N::Call {
                         receiver: Some(lhs),
                         name,
                         args,
+                        block,
                     }))
                 }
-                LK::Equal => match lhs {
-                    N::Expr::GlobalVariableRead(glob) => {
-                        let rhs = self.expr_bp(r_bp).unwrap();
+                LK::Equal => match lhs.kind {
+                    N::ExprKind::GlobalVariableRead(glob) => {
+                        let rhs = self.operand(r_bp);
                         let N::GlobalVariableRead { name } = *glob;
-                        N::Expr::GlobalVariableWrite(Box::new(N::GlobalVariableWrite {
+                        N::ExprKind::GlobalVariableWrite(Box::new(N::GlobalVariableWrite {
+                            name,
+                            expr: rhs,
+                        }))
+                    }
+                    N::ExprKind::InstanceVariableRead(read) => {
+                        let rhs = self.operand(r_bp);
+                        let N::InstanceVariableRead { name } = *read;
+                        N::ExprKind::InstanceVariableWrite(Box::new(N::InstanceVariableWrite {
                             name,
                             expr: rhs,
                         }))
                     }
-                    N::Expr::Call(call) if call.name == "[]" => {
-                        let rhs = self.expr_bp(r_bp).unwrap();
-                        N::Expr::Call(Box::new(N::Call {
+                    N::ExprKind::Call(call) if is_index(&call) || is_attribute(&call) => {
+                        let rhs = self.operand(r_bp);
+                        N::ExprKind::Call(Box::new(N::Call {
                             receiver: call.receiver,
-                            name: "[]=".to_string(),
+                            name: format!("{}=", call.name),
                             args: [call.args, vec![rhs]].concat(),
+                            block: None,
                         }))
                     }
-                    _ => todo!("Unknown assignment lhs: {:?}", lhs),
+                    _ => {
+                        self.error(lhs.span, vec![], "unexpected `=`; target cannot be written");
+                        self.operand(r_bp);
+                        N::ExprKind::Missing
+                    }
                 },
+                op if op.is_operator_write() => {
+                    let value = self.operand(r_bp);
+                    if !is_writable(&lhs) {
+                        let message = format!(
+                            "unexpected `{}`; target cannot be written",
+                            op.spelling().unwrap()
+                        );
+                        self.error(lhs.span, vec![], message);
+                        lhs = N::Expr::new(N::ExprKind::Missing, lhs.span);
+                        continue;
+                    }
+                    N::ExprKind::OperatorWrite(Box::new(N::OperatorWrite {
+                        target: lhs,
+                        operator: write_operator(&op),
+                        value,
+                    }))
+                }
+                op @ (LK::DotDot | LK::DotDotDot) => {
+                    let rhs = self.operand(r_bp);
+                    N::ExprKind::Range(Box::new(N::Range {
+                        left: lhs,
+                        right: rhs,
+                        exclusive: op == LK::DotDotDot,
+                    }))
+                }
                 LK::AmpersandAmpersand => {
-                    let rhs = self.expr_bp(r_bp).unwrap();
-                    N::Expr::And(Box::new(N::And { lhs, rhs }))
+                    let rhs = self.operand(r_bp);
+                    N::ExprKind::And(Box::new(N::And { lhs, rhs }))
                 }
                 LK::PipePipe => {
-                    let rhs = self.expr_bp(r_bp).unwrap();
+                    let rhs = self.operand(r_bp);
 
-                    N::Expr::Or(Box::new(N::Or { lhs, rhs }))
+                    N::ExprKind::Or(Box::new(N::Or { lhs, rhs }))
                 }
                 op @ (LK::EqualEqual
+                | LK::EqualEqualEqual
+                | LK::BangEqual
+                | LK::LessEqualGreater
                 | LK::Greater
+                | LK::GreaterEqual
                 | LK::Less
+                | LK::LessEqual
+                | LK::Pipe
+                | LK::Caret
+                | LK::Ampersand
+                | LK::LessLess
+                | LK::GreaterGreater
                 | LK::Minus
                 | LK::Plus
                 | LK::Slash
-                | LK::Star) => {
-                    let name = match op {
-                        LK::EqualEqual => "==".to_string(),
-                        LK::Greater => ">".to_string(),
-                        LK::Less => "<".to_string(),
-                        LK::Minus => "-".to_string(),
-                        LK::Plus => "+".to_string(),
-                        LK::Slash => "/".to_string(),
-                        LK::Star => "*".to_string(),
-                        _ => unreachable!(),
-                    };
-                    let rhs = self.expr_bp(r_bp).unwrap();
+                | LK::Star
+                | LK::Percent
+                | LK::StarStar) => {
+                    let name = op.spelling().unwrap().to_string();
+                    let rhs = self.operand(r_bp);
 
-                    N::Expr::Call(Box::new(N::Call {
+                    N::ExprKind::Call(Box::new(N::Call {
                         receiver: Some(lhs),
                         name,
                         args: vec![rhs],
+                        block: None,
+                    }))
+                }
+                LK::Question => {
+                    let then_expr = self.operand_before_modifier();
+                    self.skip_newlines();
+                    self.expect(&[LK::Colon]);
+                    let else_expr = self.operand(r_bp);
+                    N::ExprKind::If(Box::new(N::If {
+                        predicate: lhs,
+                        statements: single_statement(then_expr),
+                        subsequent: N::Subsequent::Else(N::Else {
+                            statements: single_statement(else_expr),
+                        }),
                     }))
                 }
+                modifier @ (LK::If | LK::Unless | LK::While | LK::Until) => {
+                    self.modifier(modifier, lhs)
+                }
                 LK::BracketLeft => {
                     let args = self.args(LK::BracketRight);
-                    assert_eq!(1, args.len());
 
-                    N::Expr::Call(Box::new(N::Call {
+                    N::ExprKind::Call(Box::new(N::Call {
                         receiver: Some(lhs),
                         name: "[]".to_string(),
                         args,
+                        block: None,
                     }))
                 }
+                // `[]` without a space between is one lexeme.
+                LK::BracketLeftRight => N::ExprKind::Call(Box::new(N::Call {
+                    receiver: Some(lhs),
+                    name: "[]".to_string(),
+                    args: vec![],
+                    block: None,
+                })),
                 other => unreachable!("Lexeme kind {:?} is not an operator.", other),
             };
+            lhs = N::Expr::new(kind, self.span_from(start));
         }
         Some(lhs)
     }
 
     fn for_in_expr(&mut self) -> N::For {
-        self.expect(&[LK::For]);
-        let N::Expr::LocalVariableRead(lvr) = self.parse_ident(0) else {
-            panic!()
-        };
-        let N::LocalVariableRead { name: idx } = *lvr;
+        self.debug_expect(&[LK::For]);
+        let idx = self.identifier().unwrap_or_default();
+        self.declare(&idx);
         self.expect(&[LK::In]);
-        let collection = self.expr().unwrap();
+        let collection = self.loop_header();
         self.expect(&[LK::Do]);
-        let stmts = self.statements();
+        let stmts = self.loop_body();
         self.expect(&[LK::End]);
 
         N::For {
@@ -228,814 +732,2104 @@ impl<'text> Parser<'text> {
         }
     }
 
-    /// Parse ident to LocalVariableRead, LocalVariableWrite, or Call
-    fn parse_ident(&mut self, min_bp: u8) -> N::Expr {
-        let LK::Identifier { text: name } = self.lexer.next().kind else {
+    /// Parse ident to LocalVariableRead, LocalVariableWrite, or Call.
+    /// An identifier that isn't a local variable is a call,
+    ///     with args in parens, as a command call, or without args.
+    fn parse_ident(&mut self, min_bp: u8) -> N::ExprKind {
+        let LK::Identifier { text: name } = self.next().kind else {
             unreachable!()
         };
-        match self.lexer.peek().kind {
+        let is_local = self.is_local(&name);
+        let call = |args, block| {
+            N::ExprKind::Call(Box::new(N::Call {
+                receiver: None,
+                name: name.clone(),
+                args,
+                block,
+            }))
+        };
+        let read = || {
+            N::ExprKind::LocalVariableRead(Box::new(N::LocalVariableRead { name: name.clone() }))
+        };
+        match self.peek().kind {
             LK::LeftParen => {
-                self.lexer.next();
-                let args = self.args(LK::RightParen);
-                N::Expr::Call(Box::new(N::Call {
-                    receiver: None,
-                    name,
-                    args,
-                }))
+                self.next();
+                let (args, block) = self.call_args();
+                call(args, block)
             }
+            LK::BraceLeft | LK::Do => match self.block() {
+                Some(block) => call(vec![], Some(N::CallBlock::Literal(Box::new(block)))),
+                // `while x do`
+                None if is_local => read(),
+                None => call(vec![], None),
+            },
             LK::Equal => {
                 let (l_bp, r_bp) = LK::Equal.binding_power();
                 if l_bp < min_bp {
-                    return N::Expr::LocalVariableRead(Box::new(N::LocalVariableRead { name }));
+                    return read();
                 }
-                self.lexer.next();
-                let rhs = self.expr_bp(r_bp).unwrap();
-                N::Expr::LocalVariableWrite(Box::new(N::LocalVariableWrite { name, val: rhs }))
+                self.next();
+                self.declare(&name);
+                let rhs = self.operand(r_bp);
+                N::ExprKind::LocalVariableWrite(Box::new(N::LocalVariableWrite { name, val: rhs }))
+            }
+            // `x += 1` reads `x` first, so it declares `x` too.
+            kind if kind.is_operator_write() => {
+                self.declare(&name);
+                read()
+            }
+            _ if is_local => read(),
+            _ if self.starts_command_arg() => {
+                let (args, block) = self.command_args();
+                call(args, block)
             }
-            _ => N::Expr::LocalVariableRead(Box::new(N::LocalVariableRead { name })),
+            _ => call(vec![], None),
+        }
+    }
+
+    /// Whether `name` is a local variable where it's used.
+    /// Blocks see the variables of their enclosing scopes, up to the nearest `def`, class or module.
+    fn is_local(&self, name: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if scope.names.iter().any(|local| local == name) {
+                return true;
+            }
+            if !scope.is_block {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Make `name` a local variable from here to the end of the current scope.
+    fn declare(&mut self, name: &str) {
+        if !self.is_local(name) {
+            let scope = self.scopes.last_mut().unwrap();
+            scope.names.push(name.to_string());
         }
     }
 
     fn def_expr(&mut self) -> N::Def {
-        self.lexer.next();
-        let LK::Identifier { text: name } = self.lexer.next().kind else {
-            panic!("Expected method name after `def`.")
+        self.debug_expect(&[LK::Def]);
+        let singleton = self.consume_if_found(LK::SelfKeyword).is_some();
+        if singleton {
+            self.expect(&[LK::Dot]);
+        }
+        let name = self.method_name().unwrap_or_default();
+        self.scopes.push(LocalScope::default());
+        let params = match self.expect(&[LK::LeftParen]) {
+            Some(_) => self.def_params(LK::RightParen),
+            None => N::Params::default(),
         };
-        self.expect(&[LK::LeftParen]);
-        let params = self.params();
         self.skip_newlines();
+        let start = self.peek().start;
         let body = self.statements();
+        let body = match self.peek().kind {
+            LK::Rescue | LK::Else | LK::Ensure => {
+                let begin = self.begin_clauses(body);
+                let begin =
+                    N::Expr::new(N::ExprKind::Begin(Box::new(begin)), self.span_from(start));
+                N::Statements {
+                    span: begin.span,
+                    body: vec![begin],
+                }
+            }
+            _ => body,
+        };
         self.skip_newlines();
         self.expect(&[LK::End]);
+        self.pop_scope();
 
-        N::Def { name, params, body }
+        N::Def {
+            singleton,
+            name,
+            params,
+            body,
+        }
     }
 
-    fn params(&mut self) -> Vec<N::RequiredParam> {
-        let mut params = vec![];
+    /// Parse the params of a method or block, up to and including `close`: `)` or `|`.
+    /// Each kind of param must come after the kinds before it in `DEF_PARAMS`,
+    ///     except that required params can also come after the optional and rest params.
+    /// Pre: The opening `(` or `|` has been consumed
+    /// ```ebnf
+    /// DEF_PARAMS = ( DEF_PARAM ( "," DEF_PARAM )* )? close
+    /// DEF_PARAM = identifier | "(" MULTI_TARGET ")" | identifier "=" EXPR | "*" identifier
+    ///           | label EXPR? | "**" identifier | "&" identifier
+    /// ```
+    fn def_params(&mut self, close: LexemeKind) -> N::Params {
+        let mut params = N::Params::default();
+        if self.consume_if_found(close.clone()).is_some() {
+            return params;
+        }
+        // A block's `|` ends its params, rather than being an operator in a default.
+        let default_binding_power = match close {
+            LK::Pipe => LK::Pipe.binding_power().0 + 1,
+            _ => 0,
+        };
         loop {
-            match self.lexer.peek().kind {
+            let lexeme = self.peek();
+            match lexeme.kind {
                 LK::Identifier { text } => {
-                    self.lexer.next();
-                    params.push(N::RequiredParam { name: text });
-                    match self.lexer.next().kind {
-                        LK::Comma => continue,
-                        LK::RightParen => break,
-                        _ => panic!("Expected `,` or `)`."),
+                    self.next();
+                    self.declare(&text);
+                    match self.peek().kind {
+                        LK::Equal => {
+                            self.next();
+                            let default = self.operand(default_binding_power);
+                            params.check_order(self, DefParamKind::Optional, lexeme.start);
+                            params.optional.push(N::OptionalParam {
+                                name: text,
+                                default,
+                                span: self.span_from(lexeme.start),
+                            });
+                        }
+                        LK::Colon => {
+                            self.next();
+                            let default = match self.peek().kind {
+                                LK::Comma => None,
+                                lk if lk == close => None,
+                                _ => Some(self.operand(default_binding_power)),
+                            };
+                            params.check_order(self, DefParamKind::Keyword, lexeme.start);
+                            params.keyword.push(N::KeywordParam {
+                                name: text,
+                                default,
+                                span: self.span_from(lexeme.start),
+                            });
+                        }
+                        _ => {
+                            let param = N::RequiredParam {
+                                name: text,
+                                targets: None,
+                                span: Span::new(lexeme.start, lexeme.len),
+                            };
+                            params.push_required(self, param, lexeme.start);
+                        }
                     }
                 }
-                LK::RightParen => {
-                    self.lexer.next();
-                    break;
+                LK::LeftParen => {
+                    self.next();
+                    let targets = self.multi_target(None, LK::RightParen);
+                    let param = N::RequiredParam {
+                        name: format!("%{}", params.required.len() + params.post.len()),
+                        targets: Some(targets),
+                        span: self.span_from(lexeme.start),
+                    };
+                    params.push_required(self, param, lexeme.start);
+                }
+                LK::Star | LK::StarStar => {
+                    self.next();
+                    let name = self.identifier().unwrap_or_default();
+                    self.declare(&name);
+                    let param = N::RestParam {
+                        name,
+                        span: self.span_from(lexeme.start),
+                    };
+                    if lexeme.kind == LK::Star {
+                        params.check_order(self, DefParamKind::Rest, lexeme.start);
+                        params.rest = Some(param);
+                    } else {
+                        params.check_order(self, DefParamKind::KeywordRest, lexeme.start);
+                        params.keyword_rest = Some(param);
+                    }
+                }
+                LK::Ampersand => {
+                    self.next();
+                    let name = self.identifier().unwrap_or_default();
+                    self.declare(&name);
+                    params.block = Some(N::BlockParam {
+                        name,
+                        span: self.span_from(lexeme.start),
+                    });
+                    if self.expect(std::slice::from_ref(&close)).is_none() {
+                        self.skip_past(close);
+                    }
+                    return params;
+                }
+                _ => {
+                    let expected = vec![
+                        LK::Identifier {
+                            text: String::new(),
+                        },
+                        LK::LeftParen,
+                        LK::Star,
+                        LK::StarStar,
+                        LK::Ampersand,
+                    ];
+                    self.unexpected(&lexeme, expected);
+                    self.skip_past(close);
+                    return params;
                 }
-                _ => panic!("Expected identifier or `)`."),
             }
-        }
-        params
-    }
-
-    fn unary_minus(&mut self) -> Option<N::Expr> {
-        self.expect(&[LK::Minus]);
-        let lhs = self.expr_bp(Lexeme::UNARY_MINUS_BINDING_POWER).unwrap();
-        Some(N::Expr::Call(Box::new(N::Call {
-            receiver: Some(lhs),
-            name: "-@".to_string(),
-            args: vec![],
-        })))
-    }
-
-    /// Parse args until terminator.
-    /// Pre: Opening `(` has been consumed
-    fn args(&mut self, terminator: LexemeKind) -> Vec<N::Expr> {
-        let mut args = vec![];
-        while let Some(arg) = self.expr() {
-            args.push(arg);
 
-            match self.lexer.peek().kind {
+            let lexeme = self.peek();
+            match lexeme.kind {
                 LK::Comma => {
-                    self.lexer.next();
+                    self.next();
+                    // A block's params can end with a comma, like `|a, |`
+                    if close == LK::Pipe && self.consume_if_found(LK::Pipe).is_some() {
+                        return params;
+                    }
+                }
+                lk if lk == close => {
+                    self.next();
+                    return params;
+                }
+                _ => {
+                    self.unexpected(&lexeme, vec![LK::Comma, close.clone()]);
+                    self.skip_past(close);
+                    return params;
                 }
-                lk if lk == terminator => break,
-                _ => panic!(),
             }
         }
-
-        self.lexer.next();
-        args
     }
 
-    fn array_literal(&mut self) -> N::Array {
-        self.debug_expect(&[LK::BracketLeft]);
+    /// ```ebnf
+    /// CLASS = "class" constant ( "<" EXPR )? STATEMENTS "end"
+    /// ```
+    fn class_expr(&mut self) -> N::Class {
+        self.debug_expect(&[LK::Class]);
+        let name = self.constant_name().unwrap_or_default();
+        let superclass = self.consume_if_found(LK::Less).map(|_| self.operand(0));
+        self.scopes.push(LocalScope::default());
+        let body = self.statements();
+        self.pop_scope();
+        self.expect(&[LK::End]);
 
-        let mut vals = vec![];
-        loop {
-            vals.push(self.expr().unwrap());
-            self.skip_newlines();
-            match self.lexer.next().kind {
-                LK::Comma => {}
-                LK::BracketRight => break,
-                _ => panic!("Expected `,` or `]`"),
-            }
+        N::Class {
+            name,
+            superclass,
+            body,
         }
-        N::Array { vals }
     }
 
-    /// Once we see "if", should be irrefutable.
-    /// Pre: `self.lexer.next().kind == LexemeKind::If`
-    fn if_expr(&mut self) -> N::If {
-        // By default, we need to see an "end" lexeme.
-        //  But if we see "elsif", then the nested `If`-expr will take care of it.
-        let mut expects_end_lexeme = true;
+    /// ```ebnf
+    /// MODULE = "module" constant STATEMENTS "end"
+    /// ```
+    fn module_expr(&mut self) -> N::Module {
+        self.debug_expect(&[LK::Module]);
+        let name = self.constant_name().unwrap_or_default();
+        self.scopes.push(LocalScope::default());
+        let body = self.statements();
+        self.pop_scope();
+        self.expect(&[LK::End]);
 
-        self.debug_expect(&[LK::If]);
-        let predicate = self.expr().unwrap();
-        self.consume_if_found(LK::Then);
-        let then_statements = self.statements();
+        N::Module { name, body }
+    }
 
-        let subsequent: N::Subsequent = match self.lexer.peek().kind {
-            LK::Else => {
-                self.lexer.next(); // Consume "else"
-                let else_statements = self.statements();
-                N::Subsequent::Else(N::Else {
-                    statements: else_statements,
-                })
+    /// ```ebnf
+    /// SUPER = "super" ( "(" ARGS ")" | COMMAND_ARGS )?
+    /// ```
+    fn super_expr(&mut self) -> N::ExprKind {
+        self.debug_expect(&[LK::Super]);
+        let args = match self.consume_if_found(LK::LeftParen) {
+            Some(_) => self.args(LK::RightParen),
+            None if self.starts_command_arg() => self.command_args_without_block(),
+            None => return N::ExprKind::ForwardingSuper,
+        };
+        N::ExprKind::Super(Box::new(N::Super { args }))
+    }
+
+    /// The block after a call's args, if there is one.
+    /// ```ebnf
+    /// BLOCK = "{" BLOCK_PARAMS? STATEMENTS "}" | "do" BLOCK_PARAMS? STATEMENTS "end"
+    /// BLOCK_PARAMS = "||" | "|" DEF_PARAMS
+    /// ```
+    fn block(&mut self) -> Option<N::Block> {
+        let close = match self.peek().kind {
+            LK::BraceLeft => LK::BraceRight,
+            LK::Do if !self.in_loop_header && !self.in_command_args => LK::End,
+            _ => return None,
+        };
+        self.next();
+        self.scopes.push(LocalScope {
+            is_block: true,
+            ..LocalScope::default()
+        });
+        let params = match self.peek().kind {
+            LK::PipePipe => {
+                self.next();
+                N::Params::default()
             }
-            LK::Elsif => {
-                expects_end_lexeme = false;
-                let if_expr = self.if_expr();
-                N::Subsequent::Elsif(Box::new(if_expr))
+            LK::Pipe => {
+                self.next();
+                self.def_params(LK::Pipe)
             }
-            _ => N::Subsequent::None,
+            _ => N::Params::default(),
         };
+        let body = self.block_body(close);
+        self.pop_scope();
 
-        if expects_end_lexeme {
-            self.expect(&[LK::End]);
-        }
-
-        N::If {
-            predicate,
-            statements: then_statements,
-            subsequent,
-        }
+        Some(N::Block { params, body })
     }
 
-    /// Parse global variable into a `GlobalRead`
-    /// Pre: `self.lexer.next().kind == LexemeKind::GlobalVariable`
-    fn global_variable(&mut self) -> N::GlobalVariableRead {
-        let global = self.lexer.next();
-        let Lexeme {
-            kind: LK::GlobalVariable { text },
-            ..
-        } = global
-        else {
-            unreachable!("Not a `GlobalVariable`: {:?}", global)
+    /// ```ebnf
+    /// LAMBDA = "->" ( "(" DEF_PARAMS )? ( "{" STATEMENTS "}" | "do" STATEMENTS "end" )
+    /// ```
+    fn lambda_expr(&mut self) -> N::Block {
+        self.debug_expect(&[LK::MinusGreater]);
+        self.scopes.push(LocalScope {
+            is_block: true,
+            ..LocalScope::default()
+        });
+        let params = match self.consume_if_found(LK::LeftParen) {
+            Some(_) => self.def_params(LK::RightParen),
+            None => N::Params::default(),
+        };
+        let close = match self.expect(&[LK::BraceLeft, LK::Do]) {
+            Some(Lexeme { kind: LK::Do, .. }) => LK::End,
+            _ => LK::BraceRight,
         };
+        let body = self.block_body(close);
+        self.pop_scope();
 
-        let name = text.chars().skip(1).collect();
-        N::GlobalVariableRead { name }
+        N::Block { params, body }
     }
 
-    /// Parse a constant into either `ConstantWrite` or `ConstantRead`
-    /// Pre: `self.lexer.next().kind == LexemeKind::Constant`
-    fn constant(&mut self) -> N::Expr {
-        let constant = self.lexer.next();
-        let Lexeme {
-            kind: LK::Constant { text },
-            ..
-        } = constant
-        else {
-            unreachable!("Not a `Constant`: {:?}", constant)
-        };
+    /// The statements of a block or lambda, up to and including `close`.
+    /// Inside them, `do` starts a block again, even within a loop header or command args.
+    fn block_body(&mut self, close: LexemeKind) -> N::Statements {
+        let in_loop_header = std::mem::replace(&mut self.in_loop_header, false);
+        let in_command_args = std::mem::replace(&mut self.in_command_args, false);
+        let body = self.statements();
+        self.expect(&[close]);
+        self.in_loop_header = in_loop_header;
+        self.in_command_args = in_command_args;
+        body
+    }
 
-        match self.lexer.peek().kind {
-            LK::Equal => {
-                self.lexer.next();
-                let rhs = self.expr().expect("Assignment with no RHS");
-                N::Expr::ConstantWrite(Box::new(N::ConstantWrite {
-                    name: text,
-                    expr: rhs,
-                }))
-            }
-            _ => N::Expr::ConstantRead(Box::new(N::ConstantRead { name: text })),
-        }
+    /// Statements grouped in parentheses, like `(1 + 2) * 3` or `(a; b)`.
+    /// ```ebnf
+    /// PARENTHESES = "(" STATEMENTS ")"
+    /// ```
+    fn parentheses(&mut self) -> N::Statements {
+        self.debug_expect(&[LK::LeftParen]);
+        let in_loop_header = std::mem::replace(&mut self.in_loop_header, false);
+        let in_command_args = std::mem::replace(&mut self.in_command_args, false);
+        let statements =
+            self.statements_until(|kind| *kind == LK::RightParen || ends_statements(kind));
+        self.expect(&[LK::RightParen]);
+        self.in_loop_header = in_loop_header;
+        self.in_command_args = in_command_args;
+        statements
     }
 
-    /// Once we see "while", should be irrefutable.
-    /// Pre: `self.lexer.next().kind == LexemeKind::While`
-    fn while_expr(&mut self) -> N::While {
-        self.debug_expect(&[LK::While]);
-        let predicate = self.expr().unwrap();
-        self.consume_if_found(LK::Do);
-        let statements = self.statements();
-        self.expect(&[LK::End]);
+    /// The statements of a `while`, `until` or `for` loop, which `break`, `next` and `redo` act on.
+    fn loop_body(&mut self) -> N::Statements {
+        let in_loop = std::mem::replace(&mut self.scope().in_loop, true);
+        let body = self.statements();
+        self.scope().in_loop = in_loop;
+        body
+    }
 
-        N::While {
-            predicate,
-            statements,
-        }
+    /// Parse a header of a `while`, `until` or `for`, where `do` doesn't start a block.
+    fn loop_header(&mut self) -> N::Expr {
+        let in_loop_header = std::mem::replace(&mut self.in_loop_header, true);
+        let header = self.operand(0);
+        self.in_loop_header = in_loop_header;
+        header
     }
 
-    /// Once we see "until", should be irrefutable.
-    /// Pre: `self.lexer.next().kind == LexemeKind::Until`
-    fn until_expr(&mut self) -> N::Until {
-        self.debug_expect(&[LK::Until]);
-        let predicate = self.expr().unwrap();
-        self.consume_if_found(LK::Do);
-        let statements = self.statements();
-        self.expect(&[LK::End]);
-
-        N::Until {
-            predicate,
-            statements,
-        }
+    /// ```ebnf
+    /// YIELD = "yield" ( "(" ARGS ")" | COMMAND_ARGS )?
+    /// ```
+    fn yield_expr(&mut self) -> N::Yield {
+        self.debug_expect(&[LK::Yield]);
+        let args = match self.consume_if_found(LK::LeftParen) {
+            Some(_) => self.args(LK::RightParen),
+            None if self.starts_command_arg() => self.command_args_without_block(),
+            None => vec![],
+        };
+        N::Yield { args }
     }
 
-    /// Pre: `self.lexer.next().kind == LexemeKind::IntegerLiteral`
-    fn integer_literal(&mut self) -> Option<N::Expr> {
-        let lexeme = self.lexer.next();
-        match lexeme.kind {
-            LK::IntegerLiteral { text } => Some(N::Expr::Integer(i64::from_str(&text).unwrap())),
-            _ => unreachable!(),
-        }
+    fn unary_minus(&mut self) -> Option<N::ExprKind> {
+        self.debug_expect(&[LK::Minus]);
+        let lhs = self.operand(Lexeme::UNARY_MINUS_BINDING_POWER);
+        Some(N::ExprKind::Call(Box::new(N::Call {
+            receiver: Some(lhs),
+            name: "-@".to_string(),
+            args: vec![],
+            block: None,
+        })))
     }
 
-    /// Pre: `self.lexer.next().kind == LexemeKind::SingleQuoteStringLiteral`
-    fn single_quote_string_literal(&mut self) -> Option<N::Expr> {
-        let lexeme = self.lexer.next();
-        match lexeme.kind {
-            LK::SingleQuoteStringLiteral { text } => {
-                let inner = text[1..text.len() - 1].to_string();
-                Some(N::Expr::SingleQuoteString(inner))
-            }
-            _ => unreachable!(),
-        }
+    /// `!x`, `~x` or `+x`, as a call of `!`, `~` or `+@` on `x`.
+    fn unary(&mut self) -> Option<N::ExprKind> {
+        let op = self.debug_expect(&[LK::Bang, LK::TildeOrTildeAt, LK::Plus]);
+        let name = match op.kind {
+            LK::Plus => "+@".to_string(),
+            kind => kind.spelling().unwrap().to_string(),
+        };
+        let receiver = self.operand(Lexeme::UNARY_BINDING_POWER);
+        Some(N::ExprKind::Call(Box::new(N::Call {
+            receiver: Some(receiver),
+            name,
+            args: vec![],
+            block: None,
+        })))
     }
 
-    /// Peek the next token. If it's of kind `expected`, consume it.
-    fn consume_if_found(&mut self, expected: LK) -> Option<Lexeme> {
-        match self.lexer.peek() {
-            Lexeme { kind, .. } if kind == expected => Some(self.lexer.next()),
-            _ => None,
-        }
+    /// `not <EXPR>` calls `!`, like `!<EXPR>`, but takes everything up to a modifier as its operand.
+    fn not_expr(&mut self) -> N::ExprKind {
+        self.debug_expect(&[LK::Not]);
+        let receiver = self.operand_before_modifier();
+        N::ExprKind::Call(Box::new(N::Call {
+            receiver: Some(receiver),
+            name: "!".to_string(),
+            args: vec![],
+            block: None,
+        }))
     }
 
-    /// expected: Set of acceptable lexeme kinds
-    fn debug_expect(&mut self, expected: &[LexemeKind]) -> Lexeme {
-        let lexeme = self.lexer.next();
-        debug_assert!(expected.contains(&lexeme.kind));
-        lexeme
+    /// Parse args up to and including terminator.
+    /// Pre: Opening `(` has been consumed
+    fn args(&mut self, terminator: LexemeKind) -> Vec<N::Expr> {
+        let (args, block_arg) = self.args_and_block_arg(Some(terminator));
+        self.without_block_arg(args, block_arg)
     }
 
-    /// expected: Set of acceptable lexeme kinds
-    fn expect(&mut self, expected: &[LexemeKind]) -> Lexeme {
-        let lexeme = self.lexer.next();
-        assert!(expected.contains(&lexeme.kind));
-        lexeme
+    /// Parse the args of a command call, like `yield x, y`, which can't take a block.
+    fn command_args_without_block(&mut self) -> Vec<N::Expr> {
+        let (args, block_arg) = self.args_and_block_arg(None);
+        self.without_block_arg(args, block_arg)
     }
 
-    fn skip_newlines(&mut self) {
-        while let LK::Newline = self.lexer.peek().kind {
-            self.lexer.next();
+    fn without_block_arg(
+        &mut self,
+        args: Vec<N::Expr>,
+        block_arg: Option<N::Expr>,
+    ) -> Vec<N::Expr> {
+        if let Some(block_arg) = block_arg {
+            self.error(block_arg.span, vec![], "block argument should not be given");
         }
+        args
     }
-}
-
-// TODO `nil ;;;;;` is a valid ruby program.
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use expect_test::expect;
-    use serde_lexpr::print;
-    use std::process::Command;
 
-    /// Parse the given text
-    /// and return pretty-printed sexpr
-    /// This only works so long as the lexer can be driven independently of the parser.
-    fn parse_to_sexpr(text: &str) -> String {
-        let parser = Parser::new(Lexer::new(text));
-        let program = parser.parse();
+    /// The args of a call, up to and including the closing `)`, and its block.
+    /// Pre: Opening `(` has been consumed
+    /// ```ebnf
+    /// CALL_ARGS = ( EXPR "," )* ( EXPR | "&" EXPR )? ")" BLOCK?
+    /// ```
+    fn call_args(&mut self) -> (Vec<N::Expr>, Option<N::CallBlock>) {
+        let (args, block_arg) = self.args_and_block_arg(Some(LK::RightParen));
+        let block = self.call_block(block_arg);
+        (args, block)
+    }
 
-        // TODO: formatting as elisp, cause we're using emacs for pretty-printing...
-        let sexpr = serde_lexpr::to_string_custom(&program, print::Options::elisp()).unwrap();
-        format_sexpr(&sexpr)
+    /// The args of a command call, without parens, and its block.
+    /// A `do` block after them is the command's, not the last arg's.
+    /// ```ebnf
+    /// COMMAND_ARGS = ( EXPR "," )* ( EXPR | "&" EXPR ) BLOCK?
+    /// ```
+    fn command_args(&mut self) -> (Vec<N::Expr>, Option<N::CallBlock>) {
+        let (args, block_arg) = self.args_and_block_arg(None);
+        let block = self.call_block(block_arg);
+        (args, block)
     }
 
-    /// Pretty print given sexpr
-    /// TODO -- Remove the emacs dependency...
-    fn format_sexpr(sexpr: &str) -> String {
-        let pp_command = format!(
-            "(pp (car (read-from-string \"{}\"))))",
-            sexpr.replace(r#"""#, r#"\""#)
-        );
-        let output = Command::new("emacs")
-            .args(["--batch", "--eval", &pp_command])
-            .output()
-            .unwrap();
-        String::from_utf8(output.stdout).unwrap()
+    /// Whether the next lexeme starts the first arg of a command call, like `puts x`.
+    /// As in Ruby, `x -1` and `x [1]` are commands, but `x - 1`, `x-1` and `x[1]` aren't.
+    fn starts_command_arg(&mut self) -> bool {
+        let next = self.peek();
+        let space_before = next.start.0 > self.prev_end.0;
+        let end = (next.start + next.len).0;
+        let space_after = self.lexer.text()[end..].starts_with(char::is_whitespace);
+        match next.kind {
+            LK::IntegerLiteral { .. }
+            | LK::FloatLiteral { .. }
+            | LK::SingleQuoteStringLiteral { .. }
+            | LK::StringBegin
+            | LK::HeredocStart
+            | LK::SymbolLiteral { .. }
+            | LK::SymbolBegin
+            | LK::PercentLowerW
+            | LK::PercentUpperW
+            | LK::PercentLowerI
+            | LK::PercentUpperI
+            | LK::Identifier { .. }
+            | LK::Constant { .. }
+            | LK::GlobalVariable { .. }
+            | LK::InstanceVariable
+            | LK::True
+            | LK::False
+            | LK::Nil
+            | LK::SelfKeyword
+            | LK::Yield
+            | LK::Case
+            | LK::Begin
+            | LK::Not
+            | LK::MinusGreater
+            | LK::Bang
+            | LK::TildeOrTildeAt => true,
+            LK::BracketLeft | LK::BracketLeftRight => space_before,
+            LK::Minus | LK::Plus | LK::Star | LK::Ampersand | LK::ColonColon => {
+                space_before && !space_after
+            }
+            _ => false,
+        }
     }
 
-    // TODO -- There's gotta be a good way to test tree equality.
-    //  I'm sure rust-analyzer has it figured out.
-    //  I could just do everything with the textual output.
-    #[test]
-    fn integer_literal() {
-        let text = "22";
-        let parser = Parser::new(Lexer::new(text));
-        let program = parser.parse();
+    /// The block of a call: a literal block after its args, or its `&` block arg.
+    fn call_block(&mut self, block_arg: Option<N::Expr>) -> Option<N::CallBlock> {
+        match (block_arg, self.block()) {
+            (None, None) => None,
+            (Some(block_arg), None) => Some(N::CallBlock::Argument(block_arg)),
+            (None, Some(block)) => Some(N::CallBlock::Literal(Box::new(block))),
+            (Some(block_arg), Some(block)) => {
+                self.error(
+                    block_arg.span,
+                    vec![],
+                    "both block arg and actual block given",
+                );
+                Some(N::CallBlock::Literal(Box::new(block)))
+            }
+        }
+    }
 
-        let N::Program { statements } = program;
-        assert_eq!(1, statements.body.len());
-        assert_eq!(N::Expr::Integer(22), statements.body[0]);
+    /// Args up to and including `terminator`, the last of which may be a `&` block argument.
+    /// Without a `terminator`, they're a command's args, ending at the first arg not followed by `,`.
+    /// Trailing `<KEY>: <VAL>` keyword args are collected into one `Hash` arg.
+    /// Any positional arg can be a `*` splat.
+    fn args_and_block_arg(
+        &mut self,
+        terminator: Option<LexemeKind>,
+    ) -> (Vec<N::Expr>, Option<N::Expr>) {
+        let in_command_args = std::mem::replace(&mut self.in_command_args, terminator.is_none());
+        let args = self.args_and_block_arg_inner(terminator);
+        self.in_command_args = in_command_args;
+        args
     }
 
-    #[test]
-    fn single_quote_string_literal() {
-        let text = "'22'";
-        let parser = Parser::new(Lexer::new(text));
-        let program = parser.parse();
+    fn args_and_block_arg_inner(
+        &mut self,
+        terminator: Option<LexemeKind>,
+    ) -> (Vec<N::Expr>, Option<N::Expr>) {
+        let mut args = vec![];
+        let mut pairs = vec![];
+        let mut pairs_start = None;
+        let mut block_arg = None;
+        loop {
+            if self.consume_if_found(LK::Ampersand).is_some() {
+                block_arg = Some(self.operand_before_modifier());
+                break;
+            }
+            let Some(arg) = self.arg() else {
+                break;
+            };
+            match self.label(&arg) {
+                Some(key) => {
+                    self.next();
+                    pairs_start.get_or_insert(arg.span.start);
+                    let val = self.operand_before_modifier();
+                    pairs.push(N::Pair { key, val });
+                }
+                None if !pairs.is_empty() => {
+                    self.error(
+                        arg.span,
+                        vec![],
+                        "positional argument after keyword arguments",
+                    );
+                }
+                None => args.push(arg),
+            }
 
-        match program {
-            N::Program { statements } => {
-                assert_eq!(1, statements.body.len());
-                // Strips quotes
-                assert_eq!(
-                    N::Expr::SingleQuoteString("22".to_string()),
-                    statements.body[0]
-                );
+            let lexeme = self.peek();
+            match (lexeme.kind.clone(), &terminator) {
+                (LK::Comma, _) => {
+                    self.next();
+                }
+                (lk, Some(terminator)) if lk == *terminator => break,
+                (_, None) => break,
+                (_, Some(terminator)) => {
+                    self.unexpected(&lexeme, vec![LK::Comma, terminator.clone()]);
+                    self.skip_past(terminator.clone());
+                    return (args, None);
+                }
             }
         }
-    }
 
-    #[test]
-    fn integer() {
-        let text = "'22'";
-        let parser = Parser::new(Lexer::new(text));
-        let program = parser.parse();
+        if let Some(start) = pairs_start {
+            let hash = N::ExprKind::Hash(Box::new(N::Hash { pairs }));
+            args.push(N::Expr::new(hash, self.span_from(start)));
+        }
+        if let Some(terminator) = terminator {
+            self.expect(&[terminator]);
+        }
+        (args, block_arg)
+    }
 
-        let N::Program { statements } = program;
-        assert_eq!(1, statements.body.len());
-        // Strips quotes
-        assert_eq!(
-            N::Expr::SingleQuoteString("22".to_string()),
-            statements.body[0]
-        );
+    /// ```ebnf
+    /// ARG = "*" EXPR | EXPR
+    /// ```
+    fn arg(&mut self) -> Option<N::Expr> {
+        let Some(star) = self.consume_if_found(LK::Star) else {
+            return self.expr_before_modifier();
+        };
+        let val = self.operand_before_modifier();
+        let splat = N::ExprKind::SplatArg(Box::new(val));
+        Some(N::Expr::new(splat, self.span_from(star.start)))
     }
 
-    #[test]
-    fn single_addition() {
-        let text = "1 + 2";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// If `arg` is the key of a keyword arg, `<KEY>:`, its name.
+    /// The `:` is the next lexeme, right after the key.
+    fn label(&mut self, arg: &N::Expr) -> Option<String> {
+        let name = match &arg.kind {
+            N::ExprKind::LocalVariableRead(read) => &read.name,
+            N::ExprKind::Call(call) if is_vcall(call) => &call.name,
+            _ => return None,
+        };
+        let colon = self.peek();
+        (colon.kind == LK::Colon && colon.start == arg.span.start + arg.span.len)
+            .then(|| name.clone())
     }
 
-    #[test]
-    fn multiple_addition() {
-        let text = "1 + 2 + 3";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call
-                (receiver
-                 (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))
-                (name . "+") (args (Integer . 3))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// ```ebnf
+    /// ARRAY = "[" ( EXPR ( "," EXPR )* ","? )? "]"
+    /// ```
+    fn array_literal(&mut self) -> N::Array {
+        self.debug_expect(&[LK::BracketLeft]);
+
+        let mut vals = vec![];
+        loop {
+            self.skip_newlines();
+            if self.consume_if_found(LK::BracketRight).is_some() {
+                break;
+            }
+            vals.push(self.operand(0));
+            self.skip_newlines();
+            let lexeme = self.peek();
+            match lexeme.kind {
+                LK::Comma => {
+                    self.next();
+                }
+                LK::BracketRight => {
+                    self.next();
+                    break;
+                }
+                _ => {
+                    self.unexpected(&lexeme, vec![LK::Comma, LK::BracketRight]);
+                    self.skip_past(LK::BracketRight);
+                    break;
+                }
+            }
+        }
+        N::Array { vals }
     }
 
-    #[test]
-    fn add_sub_mul_div() {
-        let text = "1 + 2 - 3 * 4 / 5";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call
-                (receiver
-                 (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))
-                (name . "-")
-                (args
-                 (Call
-                  (receiver
-                   (Call (receiver (Integer . 3)) (name . "*")
-            	     (args (Integer . 4))))
-                  (name . "/") (args (Integer . 5))))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// ```ebnf
+    /// CASE = "case" EXPR? ( "when" EXPR ( "," EXPR )* "then"? STATEMENTS )+
+    ///        ( "else" STATEMENTS )? "end"
+    /// ```
+    fn case_expr(&mut self) -> N::Case {
+        self.debug_expect(&[LK::Case]);
+        let subject = match self.peek().kind {
+            LK::Newline | LK::Semicolon | LK::When => None,
+            _ => Some(self.operand(0)),
+        };
+        self.skip_newlines();
+
+        let mut whens = vec![];
+        while self.consume_if_found(LK::When).is_some() {
+            let mut patterns = vec![self.operand(0)];
+            while self.consume_if_found(LK::Comma).is_some() {
+                self.skip_newlines();
+                patterns.push(self.operand(0));
+            }
+            self.consume_if_found(LK::Then);
+            let statements = self.statements();
+            whens.push(N::When {
+                patterns,
+                statements,
+            });
+        }
+        if whens.is_empty() {
+            self.expect(&[LK::When]);
+        }
+
+        let else_clause = self.consume_if_found(LK::Else).map(|_| N::Else {
+            statements: self.statements(),
+        });
+        self.expect(&[LK::End]);
+
+        N::Case {
+            subject,
+            whens,
+            else_clause,
+        }
     }
 
-    #[test]
-    fn matklad_example() {
-        // Example from https://matklad.github.io/2020/04/13/simple-but-powerful-pratt-parsing.html
-        let text = "1 + 2 * 3 * 4 + 5";
-        // (+ (+ 1
-        //       (* (* 2 3)
-        //          4)
-        //    5)
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call
-                (receiver
-                 (Call (receiver (Integer . 1)) (name . "+")
-            	   (args
-            	    (Call
-            	     (receiver
-            	      (Call (receiver (Integer . 2)) (name . "*")
-            		    (args (Integer . 3))))
-            	     (name . "*") (args (Integer . 4))))))
-                (name . "+") (args (Integer . 5))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// `return`, `break` or `next`, with the value on the rest of the line up to any modifier.
+    /// ```ebnf
+    /// JUMP = ( "return" | "break" | "next" ) ( EXPR ( "," EXPR )* )?
+    /// ```
+    fn jump(&mut self, kind: LexemeKind) -> N::Jump {
+        self.check_jump(kind.clone());
+        self.debug_expect(&[kind]);
+        let next = self.peek().kind;
+        if matches!(next, LK::Newline | LK::Semicolon)
+            || ends_statements(&next)
+            || is_modifier(&next)
+        {
+            return N::Jump { value: None };
+        }
+        let start = self.peek().start;
+        let mut vals = vec![self.operand_before_modifier()];
+        while self.consume_if_found(LK::Comma).is_some() {
+            vals.push(self.operand_before_modifier());
+        }
+        let value = if vals.len() == 1 {
+            vals.remove(0)
+        } else {
+            N::Expr::new(
+                N::ExprKind::Array(Box::new(N::Array { vals })),
+                self.span_from(start),
+            )
+        };
+        N::Jump { value: Some(value) }
     }
 
-    #[test]
-    fn method_call() {
-        let text = "$foo.bar()";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call (receiver (GlobalVariableRead (name . "foo"))) (name . "bar")
-            	 (args)))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// ```ebnf
+    /// BEGIN = "begin" STATEMENTS RESCUE* ( "else" STATEMENTS )? ( "ensure" STATEMENTS )? "end"
+    /// ```
+    fn begin_expr(&mut self) -> N::Begin {
+        self.debug_expect(&[LK::Begin]);
+        let statements = self.statements();
+        let begin = self.begin_clauses(statements);
+        self.expect(&[LK::End]);
+        begin
     }
 
-    #[test]
-    fn method_call_multiple_args() {
-        let text = "$foo.bar(1, 2, 3)";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call (receiver (GlobalVariableRead (name . "foo"))) (name . "bar")
-            	 (args (Integer . 1) (Integer . 2) (Integer . 3))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// The clauses after the body of a `begin` or `def`, up to its `end`.
+    /// ```ebnf
+    /// RESCUE = "rescue" ( EXPR ( "," EXPR )* )? ( "=>" identifier )? "then"? STATEMENTS
+    /// ```
+    fn begin_clauses(&mut self, statements: N::Statements) -> N::Begin {
+        let mut rescues = vec![];
+        while self.consume_if_found(LK::Rescue).is_some() {
+            let mut classes = vec![];
+            if !matches!(
+                self.peek().kind,
+                LK::Newline | LK::Semicolon | LK::Then | LK::EqualGreater
+            ) {
+                classes.push(self.operand(0));
+                while self.consume_if_found(LK::Comma).is_some() {
+                    self.skip_newlines();
+                    classes.push(self.operand(0));
+                }
+            }
+            let variable = match self.consume_if_found(LK::EqualGreater) {
+                Some(_) => self.identifier(),
+                None => None,
+            };
+            if let Some(variable) = &variable {
+                self.declare(variable);
+            }
+            self.consume_if_found(LK::Then);
+            let in_rescue = std::mem::replace(&mut self.scope().in_rescue, true);
+            let statements = self.statements();
+            self.scope().in_rescue = in_rescue;
+            rescues.push(N::Rescue {
+                classes,
+                variable,
+                statements,
+            });
+        }
+        let else_clause = self.consume_if_found(LK::Else).map(|_| N::Else {
+            statements: self.statements(),
+        });
+        let ensure_clause = self.consume_if_found(LK::Ensure).map(|_| self.statements());
+
+        N::Begin {
+            statements,
+            rescues,
+            else_clause,
+            ensure_clause,
+        }
     }
 
-    #[test]
-    fn method_call_in_operators() {
-        let text = "1 + $foo.bar() * 2 + 3";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call
-                (receiver
-                 (Call (receiver (Integer . 1)) (name . "+")
-            	   (args
-            	    (Call
-            	     (receiver
-            	      (Call (receiver (GlobalVariableRead (name . "foo")))
-            		    (name . "bar") (args)))
-            	     (name . "*") (args (Integer . 2))))))
-                (name . "+") (args (Integer . 3))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Once we see "if", should be irrefutable.
+    /// Pre: `self.next().kind == LexemeKind::If`
+    fn if_expr(&mut self) -> N::If {
+        // By default, we need to see an "end" lexeme.
+        //  But if we see "elsif", then the nested `If`-expr will take care of it.
+        let mut expects_end_lexeme = true;
+
+        self.debug_expect(&[LK::If, LK::Elsif]);
+        let predicate = self.operand(0);
+        self.consume_if_found(LK::Then);
+        let then_statements = self.statements();
+
+        let subsequent: N::Subsequent = match self.peek().kind {
+            LK::Else => {
+                self.next(); // Consume "else"
+                let else_statements = self.statements();
+                N::Subsequent::Else(N::Else {
+                    statements: else_statements,
+                })
+            }
+            LK::Elsif => {
+                expects_end_lexeme = false;
+                let if_expr = self.if_expr();
+                N::Subsequent::Elsif(Box::new(if_expr))
+            }
+            _ => N::Subsequent::None,
+        };
+
+        if expects_end_lexeme {
+            self.expect(&[LK::End]);
+        }
+
+        N::If {
+            predicate,
+            statements: then_statements,
+            subsequent,
+        }
     }
 
-    #[test]
-    fn unary_minus() {
-        let text = "-1";
-        let expected = expect![[r#"
-            ((statements
-              (body (Call (receiver (Integer . 1)) (name . "-@") (args)))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Lowered to an `If` with the branches swapped.
+    /// Pre: `self.next().kind == LexemeKind::Unless`
+    /// ```ebnf
+    /// UNLESS = "unless" EXPR "then"? STATEMENTS ( "else" STATEMENTS )? "end"
+    /// ```
+    fn unless_expr(&mut self) -> N::If {
+        self.debug_expect(&[LK::Unless]);
+        let predicate = self.operand(0);
+        self.consume_if_found(LK::Then);
+        let statements = self.statements();
+        let empty = N::Statements {
+            body: vec![],
+            span: Span::new(self.peek().start, ByteDifference(0)),
+        };
+        let else_statements = match self.peek().kind {
+            LK::Else => {
+                self.next();
+                let else_statements = self.statements();
+                self.expect(&[LK::End]);
+                else_statements
+            }
+            LK::Elsif => {
+                let lexeme = self.peek();
+                self.unexpected(&lexeme, vec![LK::Else, LK::End]);
+                // Parse the rest like the `elsif` of an `if`, which consumes the `end`.
+                self.if_expr();
+                empty
+            }
+            _ => {
+                self.expect(&[LK::End]);
+                empty
+            }
+        };
+
+        N::If {
+            predicate,
+            statements: else_statements,
+            subsequent: N::Subsequent::Else(N::Else { statements }),
+        }
     }
 
-    #[test]
-    fn unary_minus_multi_digit() {
-        let text = "-9999";
-        let expected = expect![[r#"
-            ((statements
-              (body (Call (receiver (Integer . 9999)) (name . "-@") (args)))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// A modifier after a statement, lowered onto the node of its full form:
+    ///     `a if b` is `if b then a end`, and `a while b` is `while b do a end`.
+    /// Pre: the modifier has been consumed.
+    fn modifier(&mut self, modifier: LexemeKind, expr: N::Expr) -> N::ExprKind {
+        if matches!(modifier, LK::While | LK::Until) {
+            let (start, end) = (expr.span.start.0, expr.span.end().0);
+            self.scope()
+                .stray_jumps
+                .retain(|(span, _)| !(start..end).contains(&span.start.0));
+        }
+        let (_, r_bp) = modifier.binding_power();
+        let predicate = self.operand(r_bp);
+        let end = Span::new(expr.span.end(), ByteDifference(0));
+        let do_while = matches!(expr.kind, N::ExprKind::Begin(_));
+        let statements = single_statement(expr);
+        match modifier {
+            LK::If => N::ExprKind::If(Box::new(N::If {
+                predicate,
+                statements,
+                subsequent: N::Subsequent::None,
+            })),
+            LK::Unless => N::ExprKind::If(Box::new(N::If {
+                predicate,
+                statements: N::Statements {
+                    body: vec![],
+                    span: end,
+                },
+                subsequent: N::Subsequent::Else(N::Else { statements }),
+            })),
+            LK::While => N::ExprKind::While(Box::new(N::While {
+                predicate,
+                statements,
+                do_while,
+            })),
+            LK::Until => N::ExprKind::Until(Box::new(N::Until {
+                predicate,
+                statements,
+                do_while,
+            })),
+            other => unreachable!("Lexeme kind {:?} is not a modifier.", other),
+        }
     }
 
-    #[test]
-    fn logical_ops() {
-        let text = "false && true || false";
-        let expected = expect![[r#"
-            ((statements
-              (body (Or (lhs And (lhs . False) (rhs . True)) (rhs . False)))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Parse global variable into a `GlobalRead`
+    /// Pre: `self.next().kind == LexemeKind::GlobalVariable`
+    fn global_variable(&mut self) -> N::GlobalVariableRead {
+        let global = self.next();
+        let Lexeme {
+            kind: LK::GlobalVariable { text },
+            ..
+        } = global
+        else {
+            unreachable!("Not a `GlobalVariable`: {:?}", global)
+        };
+
+        let name = text.chars().skip(1).collect();
+        N::GlobalVariableRead { name }
     }
 
-    #[test]
-    fn gt_lt() {
-        let text = "1 < 2 && 3 < 4";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (And
-                (lhs Call (receiver (Integer . 1)) (name . "<")
-            	 (args (Integer . 2)))
-                (rhs Call (receiver (Integer . 3)) (name . "<")
-            	 (args (Integer . 4)))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Parse instance variable into an `InstanceVariableRead`
+    /// Pre: `self.next().kind == LexemeKind::InstanceVariable`
+    fn instance_variable(&mut self) -> N::InstanceVariableRead {
+        let ivar = self.debug_expect(&[LK::InstanceVariable]);
+        let name = ivar.to_source(self.lexer.text()).chars().skip(1).collect();
+        N::InstanceVariableRead { name }
     }
 
-    #[test]
-    fn call_brackets() {
-        let text = "true.[](0)";
-        let expected = expect![[r#"
-            ((statements
-              (body (Call (receiver True) (name . "[]") (args (Integer . 0))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Parse a constant into either `ConstantWrite` or `ConstantRead`
+    /// Pre: `self.next().kind == LexemeKind::Constant`
+    /// `::<CONSTANT>`. Every constant is top-level, so it's the same as `<CONSTANT>`.
+    fn top_level_constant(&mut self) -> N::ExprKind {
+        self.debug_expect(&[LK::ColonColon]);
+        let lexeme = self.peek();
+        match lexeme.kind {
+            LK::Constant { .. } => self.constant(),
+            _ => {
+                let expected = LK::Constant {
+                    text: String::new(),
+                };
+                self.unexpected(&lexeme, vec![expected]);
+                N::ExprKind::Missing
+            }
+        }
     }
 
-    #[test]
-    fn parse_eq_eq() {
-        let text = "22 == 44";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call (receiver (Integer . 22)) (name . "==") (args (Integer . 44))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    fn constant(&mut self) -> N::ExprKind {
+        let constant = self.next();
+        let Lexeme {
+            kind: LK::Constant { text },
+            ..
+        } = constant
+        else {
+            unreachable!("Not a `Constant`: {:?}", constant)
+        };
+
+        match self.peek().kind {
+            LK::Equal => {
+                self.next();
+                let rhs = self.operand(0);
+                N::ExprKind::ConstantWrite(Box::new(N::ConstantWrite {
+                    name: text,
+                    expr: rhs,
+                }))
+            }
+            _ => N::ExprKind::ConstantRead(Box::new(N::ConstantRead { name: text })),
+        }
     }
 
-    #[test]
-    fn parse_complicated_equal_equal() {
-        let text = "$a[44] + 2 * $b == $c[$d]";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Call
-                (receiver
-                 (Call
-                  (receiver
-                   (Call (receiver (GlobalVariableRead (name . "a")))
-            	     (name . "[]") (args (Integer . 44))))
-                  (name . "+")
-                  (args
-                   (Call (receiver (Integer . 2)) (name . "*")
-            	     (args (GlobalVariableRead (name . "b")))))))
-                (name . "==")
-                (args
-                 (Call (receiver (GlobalVariableRead (name . "c"))) (name . "[]")
-            	   (args (GlobalVariableRead (name . "d")))))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Once we see "while", should be irrefutable.
+    /// Pre: `self.next().kind == LexemeKind::While`
+    fn while_expr(&mut self) -> N::While {
+        self.debug_expect(&[LK::While]);
+        let predicate = self.loop_header();
+        self.consume_if_found(LK::Do);
+        let statements = self.loop_body();
+        self.expect(&[LK::End]);
+
+        N::While {
+            predicate,
+            statements,
+            do_while: false,
+        }
     }
 
-    #[test]
-    fn parse_def() {
-        let text = "
-            def x(n)
-                n
-            end
-        ";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Def (name . "x") (params ((name . "n")))
-            	(body (body (LocalVariableRead (name . "n"))))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Once we see "until", should be irrefutable.
+    /// Pre: `self.next().kind == LexemeKind::Until`
+    fn until_expr(&mut self) -> N::Until {
+        self.debug_expect(&[LK::Until]);
+        let predicate = self.loop_header();
+        self.consume_if_found(LK::Do);
+        let statements = self.loop_body();
+        self.expect(&[LK::End]);
+
+        N::Until {
+            predicate,
+            statements,
+            do_while: false,
+        }
     }
 
-    #[test]
-    fn def_no_args() {
-        let text = "
-            def x()
-                22
-            end
-        ";
-        let expected = expect![[r#"
-            ((statements
-              (body (Def (name . "x") (params) (body (body (Integer . 22)))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Pre: `self.next().kind == LexemeKind::IntegerLiteral`
+    fn integer_literal(&mut self) -> Option<N::ExprKind> {
+        let lexeme = self.next();
+        match lexeme.kind {
+            LK::IntegerLiteral { text } => Some(N::ExprKind::Integer(lexer::integer_value(&text))),
+            _ => unreachable!(),
+        }
     }
 
-    #[test]
-    fn def_multiple_args() {
-        let text = "
-            def x(a, b)
-                a + b
-            end
-        ";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Def (name . "x") (params ((name . "a")) ((name . "b")))
-            	(body
-            	 (body
-            	  (Call (receiver (LocalVariableRead (name . "a")))
-            		(name . "+") (args (LocalVariableRead (name . "b"))))))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    fn float_literal(&mut self) -> N::ExprKind {
+        let LK::FloatLiteral { text } = self.next().kind else {
+            unreachable!()
+        };
+        match f64::from_str(&text.replace('_', "")) {
+            Ok(val) => N::ExprKind::Float(N::Float(val)),
+            // The lexer has reported the exponent without digits, like `1e-`.
+            Err(_) => N::ExprKind::Missing,
+        }
     }
 
-    #[test]
-    fn method_call_no_receiver() {
-        let text = "x()";
-        let expected = expect![[r#"
-            ((statements (body (Call (receiver) (name . "x") (args)))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Pre: `self.next().kind == LexemeKind::SingleQuoteStringLiteral`
+    fn single_quote_string_literal(&mut self) -> Option<N::ExprKind> {
+        let lexeme = self.next();
+        match lexeme.kind {
+            LK::SingleQuoteStringLiteral { text } => {
+                let inner = text.strip_prefix('\'').unwrap_or(&text);
+                // A string left open at EOF has no closing `'`, though it may end with `\'`.
+                let inner = match inner.strip_suffix('\'') {
+                    Some(rest) if !ends_with_escape(rest) => rest,
+                    _ => inner,
+                };
+                Some(N::ExprKind::String(inner.to_string()))
+            }
+            _ => unreachable!(),
+        }
     }
 
-    #[test]
-    fn def_then_call() {
-        let text = "
-            def x(n)
-                n
-            end
-            x(22)
-        ";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (Def (name . "x") (params ((name . "n")))
-            	(body (body (LocalVariableRead (name . "n")))))
-               (Call (receiver) (name . "x") (args (Integer . 22))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Parse a double-quoted string or heredoc into `String` if it has no interpolations,
+    /// or `InterpolatedString` if it does.
+    /// ```ebnf
+    /// DOUBLE_QUOTE_STRING = string_begin ( string_content | INTERPOLATION )* string_end
+    /// HEREDOC = heredoc_start ( string_content | INTERPOLATION )* heredoc_end
+    /// INTERPOLATION = emb_expr_begin STATEMENTS emb_expr_end
+    /// ```
+    /// Pre: `self.next().kind` is `LexemeKind::StringBegin` or `LexemeKind::HeredocStart`
+    fn double_quote_string_literal(&mut self) -> N::ExprKind {
+        self.debug_expect(&[LK::StringBegin, LK::HeredocStart]);
+        let parts = self.string_parts();
+
+        match parts.as_slice() {
+            [] => N::ExprKind::String(String::new()),
+            [N::StringPart::Literal(text)] => N::ExprKind::String(text.clone()),
+            _ => N::ExprKind::InterpolatedString(Box::new(N::InterpolatedString { parts })),
+        }
     }
 
-    #[test]
-    fn for_in() {
-        let text = "for row in [1, 2, 3] do row end";
-        let expected = expect![[r#"
-            ((statements
-              (body
-               (For (idx . "row")
-            	(collection Array
-            		    (vals (Integer . 1) (Integer . 2) (Integer . 3)))
-            	(stmts (body (LocalVariableRead (name . "row"))))))))
-        "#]];
-        let actual = parse_to_sexpr(text);
-        expected.assert_eq(&actual);
+    /// Parse the contents of a double-quoted string or heredoc,
+    /// up to and including `StringEnd` or `HeredocEnd`.
+    /// Adjacent literal parts are merged.
+    fn string_parts(&mut self) -> Vec<N::StringPart> {
+        let mut parts = vec![];
+        loop {
+            match self.next().kind {
+                LK::StringContent { text } => match parts.last_mut() {
+                    Some(N::StringPart::Literal(prev)) => prev.push_str(&text),
+                    _ => parts.push(N::StringPart::Literal(text)),
+                },
+                LK::EmbExprBegin => {
+                    let statements = self.interpolation();
+                    parts.push(N::StringPart::Interpolation(statements));
+                }
+                // The lexer reports a string left open at EOF, without closing it.
+                LK::StringEnd | LK::HeredocEnd | LK::Eof => break,
+                other => unreachable!("Unexpected {:?} in double-quoted string.", other),
+            }
+        }
+        parts
     }
 
-    mod globals {
-        use crate::parser::tests::parse_to_sexpr;
-        use expect_test::expect;
+    fn symbol_literal(&mut self) -> N::ExprKind {
+        let LK::SymbolLiteral { text } = self.next().kind else {
+            unreachable!()
+        };
+        N::ExprKind::Symbol(text)
+    }
 
-        #[test]
-        fn global_read() {
-            let text = "$asdf";
-            let expected = expect![[r#"
-                ((statements (body (GlobalVariableRead (name . "asdf")))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// `:"..."`
+    fn quoted_symbol_literal(&mut self) -> N::ExprKind {
+        self.debug_expect(&[LK::SymbolBegin]);
+        let parts = self.string_parts();
+        match parts.as_slice() {
+            [] => N::ExprKind::Symbol(String::new()),
+            _ => word_symbol(parts),
         }
+    }
 
-        #[test]
-        fn global_write() {
-            let text = "$asdf = 22";
-            let expected = expect![[r#"
-                ((statements
-                  (body (GlobalVariableWrite (name . "asdf") (expr Integer . 22)))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// `%w[...]` and `%W[...]` are arrays of strings, `%i[...]` and `%I[...]` of symbols.
+    /// ```ebnf
+    /// WORD_LIST = percent_word_list ( words_sep | WORD )* string_end
+    /// WORD = ( string_content | INTERPOLATION )+
+    /// ```
+    fn word_list_literal(&mut self) -> N::ExprKind {
+        let symbols = match self.next().kind {
+            LK::PercentLowerW | LK::PercentUpperW => false,
+            LK::PercentLowerI | LK::PercentUpperI => true,
+            _ => unreachable!(),
+        };
+        let mut vals = vec![];
+        let mut word = vec![];
+        let mut word_start = self.prev_end;
+        loop {
+            let lexeme = self.peek();
+            if word.is_empty() {
+                word_start = lexeme.start;
+            }
+            match lexeme.kind {
+                LK::StringContent { text } => {
+                    self.next();
+                    word.push(N::StringPart::Literal(text));
+                }
+                LK::EmbExprBegin => {
+                    self.next();
+                    let statements = self.interpolation();
+                    word.push(N::StringPart::Interpolation(statements));
+                }
+                // The lexer reports a word list left open at EOF, without closing it.
+                kind @ (LK::WordsSep | LK::StringEnd | LK::Eof) => {
+                    if !word.is_empty() {
+                        let parts = std::mem::take(&mut word);
+                        let kind = if symbols {
+                            word_symbol(parts)
+                        } else {
+                            word_string(parts)
+                        };
+                        vals.push(N::Expr::new(kind, self.span_from(word_start)));
+                    }
+                    self.next();
+                    if kind != LK::WordsSep {
+                        break;
+                    }
+                }
+                other => unreachable!("Unexpected {:?} in word list.", other),
+            }
         }
+        N::ExprKind::Array(Box::new(N::Array { vals }))
+    }
 
-        #[test]
-        fn global_write_then_read() {
-            let text = "$asdf = 22
-            $asdf";
-            let expected = expect![[r#"
-                ((statements
-                  (body (GlobalVariableWrite (name . "asdf") (expr Integer . 22))
-                	(GlobalVariableRead (name . "asdf")))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// Parse the statements of a `#{...}`, up to and including the `}`.
+    /// Pre: `EmbExprBegin` has been consumed
+    fn interpolation(&mut self) -> N::Statements {
+        let statements = self.statements();
+        let lexeme = self.peek();
+        if lexeme.kind != LK::EmbExprEnd {
+            self.unexpected(&lexeme, vec![LK::EmbExprEnd]);
+            // The lexer is still in the interpolation, so it'll close it eventually.
+            while !matches!(self.peek().kind, LK::EmbExprEnd | LK::Eof) {
+                self.next();
+            }
         }
+        self.consume_if_found(LK::EmbExprEnd);
+        statements
     }
 
-    mod constants {
-        use crate::parser::tests::parse_to_sexpr;
-        use expect_test::expect;
+    /// Parse an expression which must be there, like the rhs of an operator.
+    /// If it's missing, report it and return `ExprKind::Missing` in its place.
+    fn operand(&mut self, min_bp: u8) -> N::Expr {
+        if let Some(expr) = self.expr_bp(min_bp) {
+            return expr;
+        }
+        let lexeme = self.peek();
+        let message = format!(
+            "unexpected {}; expected an expression",
+            lexeme.kind.describe()
+        );
+        self.error(Span::new(lexeme.start, lexeme.len), vec![], message);
+        N::Expr::new(
+            N::ExprKind::Missing,
+            Span::new(lexeme.start, ByteDifference(0)),
+        )
+    }
 
-        #[test]
-        fn object() {
-            let text = "Object";
-            let expected = expect![[r#"
-                ((statements (body (ConstantRead (name . "Object")))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// Parse an expression that stops before a modifier `if`, `unless`, `while` or `until`,
+    ///     which applies to the whole statement, as in `return x if done` or `puts x if debug`.
+    fn expr_before_modifier(&mut self) -> Option<N::Expr> {
+        let (_, r_bp) = LK::If.binding_power();
+        self.expr_bp(r_bp)
+    }
+
+    fn operand_before_modifier(&mut self) -> N::Expr {
+        let (_, r_bp) = LK::If.binding_power();
+        self.operand(r_bp)
+    }
+
+    /// Parse an identifier which must be there, like a method name after `def`.
+    fn identifier(&mut self) -> Option<String> {
+        let lexeme = self.peek();
+        match lexeme.kind {
+            LK::Identifier { text } => {
+                self.next();
+                Some(text)
+            }
+            _ => {
+                self.unexpected(
+                    &lexeme,
+                    vec![LK::Identifier {
+                        text: String::new(),
+                    }],
+                );
+                None
+            }
         }
     }
 
-    mod arrays {
-        use crate::parser::tests::parse_to_sexpr;
+    /// Parse the name of a method being defined: an identifier, or an operator like `===`.
+    fn method_name(&mut self) -> Option<String> {
+        match self.peek().kind {
+            kind @ (LK::EqualEqual
+            | LK::EqualEqualEqual
+            | LK::BangEqual
+            | LK::LessEqualGreater
+            | LK::Greater
+            | LK::GreaterEqual
+            | LK::Less
+            | LK::LessEqual
+            | LK::Pipe
+            | LK::Caret
+            | LK::Ampersand
+            | LK::LessLess
+            | LK::GreaterGreater
+            | LK::Minus
+            | LK::Plus
+            | LK::Slash
+            | LK::Star
+            | LK::Percent
+            | LK::StarStar
+            | LK::Bang
+            | LK::TildeOrTildeAt
+            | LK::MinusAt
+            | LK::PlusAt) => {
+                self.next();
+                kind.spelling().map(str::to_string)
+            }
+            _ => {
+                let name = self.identifier()?;
+                // A setter, like `def x=(val)`
+                let equal = self.peek();
+                if equal.kind == LK::Equal && equal.start == self.prev_end {
+                    self.next();
+                    return Some(format!("{}=", name));
+                }
+                Some(name)
+            }
+        }
+    }
 
-        use expect_test::expect;
+    /// Parse a constant which must be there, like a class name after `class`.
+    fn constant_name(&mut self) -> Option<String> {
+        let lexeme = self.peek();
+        match lexeme.kind {
+            LK::Constant { text } => {
+                self.next();
+                Some(text)
+            }
+            _ => {
+                let expected = vec![LK::Constant {
+                    text: String::new(),
+                }];
+                self.unexpected(&lexeme, expected);
+                None
+            }
+        }
+    }
 
-        #[test]
-        fn empty_arr() {
-            let text = "[]";
-            let expected = expect![[r#"
-                ((statements (body (Array (vals)))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// Peek the next lexeme, skipping comments.
+    fn peek(&mut self) -> Lexeme {
+        while matches!(self.lexer.peek().kind, LK::InlineComment | LK::EmbDoc) {
+            self.lexer.next();
         }
+        self.lexer.peek()
+    }
 
-        #[test]
-        fn non_empty_arr() {
-            let text = "[1, 2, 3]";
-            let expected = expect![[r#"
-                ((statements
-                  (body (Array (vals (Integer . 1) (Integer . 2) (Integer . 3))))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// Consume the next lexeme, skipping comments, and remembering where it ends.
+    fn next(&mut self) -> Lexeme {
+        self.peek();
+        let lexeme = self.lexer.next();
+        self.prev_end = lexeme.start + lexeme.len;
+        lexeme
+    }
+
+    /// Span from `start` to the end of the last consumed lexeme.
+    fn span_from(&self, start: ByteIdx) -> Span {
+        Span::new(
+            start,
+            ByteDifference(self.prev_end.0.saturating_sub(start.0)),
+        )
+    }
+
+    /// Peek the next token. If it's of kind `expected`, consume it.
+    fn consume_if_found(&mut self, expected: LK) -> Option<Lexeme> {
+        match self.peek() {
+            Lexeme { kind, .. } if kind == expected => Some(self.next()),
+            _ => None,
         }
+    }
 
-        #[test]
-        fn array_indexing() {
-            let text = "[1, 2, 3][1]";
-            let expected = expect![[r#"
-                ((statements
-                  (body
-                   (Call
-                    (receiver (Array (vals (Integer . 1) (Integer . 2) (Integer . 3))))
-                    (name . "[]") (args (Integer . 1))))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// expected: Set of acceptable lexeme kinds
+    fn debug_expect(&mut self, expected: &[LexemeKind]) -> Lexeme {
+        let lexeme = self.next();
+        debug_assert!(expected.contains(&lexeme.kind));
+        lexeme
+    }
+
+    /// expected: Set of acceptable lexeme kinds
+    /// If the next lexeme isn't one of them, report it and leave it unconsumed.
+    fn expect(&mut self, expected: &[LexemeKind]) -> Option<Lexeme> {
+        let lexeme = self.peek();
+        if expected.contains(&lexeme.kind) {
+            Some(self.next())
+        } else {
+            self.unexpected(&lexeme, expected.to_vec());
+            None
         }
+    }
 
-        #[test]
-        fn array_index_assignment() {
-            let text = "$x = [1, 2, 3]
-                $x[1] = 0
-                $x";
-            let expected = expect![[r#"
-                ((statements
-                  (body
-                   (GlobalVariableWrite (name . "x")
-                			(expr Array
-                			      (vals (Integer . 1) (Integer . 2)
-                				    (Integer . 3))))
-                   (Call (receiver (GlobalVariableRead (name . "x"))) (name . "[]=")
-                	 (args (Integer . 1) (Integer . 0)))
-                   (GlobalVariableRead (name . "x")))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+    /// The innermost scope.
+    fn scope(&mut self) -> &mut LocalScope {
+        self.scopes.last_mut().unwrap()
+    }
+
+    /// Leave the innermost scope, reporting its stray jumps.
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().unwrap();
+        self.report_stray_jumps(scope);
+    }
+
+    /// Check that the `break`, `next`, `redo` or `retry` about to be consumed has somewhere to go.
+    /// Whether `break`, `next` or `redo` does can depend on a modifier after it,
+    ///     so it's only reported when its scope ends.
+    fn check_jump(&mut self, kind: LexemeKind) {
+        let lexeme = self.peek();
+        let span = Span::new(lexeme.start, lexeme.len);
+        let scope = self.scope();
+        match kind {
+            LK::Retry if !scope.in_rescue => {
+                self.error(span, vec![], "Invalid retry without rescue")
+            }
+            LK::Break | LK::Next | LK::Redo if !scope.in_loop && !scope.is_block => {
+                scope.stray_jumps.push((span, kind))
+            }
+            _ => (),
         }
     }
 
-    mod demo {
-        use crate::parser::tests::parse_to_sexpr;
-        use expect_test::expect;
+    fn report_stray_jumps(&mut self, scope: LocalScope) {
+        for (span, kind) in scope.stray_jumps {
+            let keyword = kind.spelling().unwrap();
+            self.error(span, vec![], format!("Invalid {}", keyword));
+        }
+    }
 
-        #[test]
-        pub fn cells() {
-            let text = "
-                $cells = [
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
-                ]
+    fn error(&mut self, span: Span, expected: Vec<LexemeKind>, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic {
+            span,
+            expected,
+            message: message.into(),
+        });
+    }
 
-                def get_cells()
-                    $cells
-                end
-            ";
-            let expected = expect![[r#"
-                ((statements
+    /// Report `lexeme`, which isn't one of `expected`.
+    /// Like Prism, says "unexpected `)`; expected `end`".
+    fn unexpected(&mut self, lexeme: &Lexeme, expected: Vec<LexemeKind>) {
+        let mut message = format!("unexpected {}", lexeme.kind.describe());
+        if !expected.is_empty() {
+            let expected: Vec<_> = expected.iter().map(LexemeKind::describe).collect();
+            message.push_str(&format!("; expected {}", expected.join(" or ")));
+        }
+        self.error(Span::new(lexeme.start, lexeme.len), expected, message);
+    }
+
+    /// Skip to the end of a statement with a syntax error,
+    /// so we can carry on parsing from the next one.
+    fn synchronize(&mut self) {
+        while !is_synchronization_point(&self.peek().kind) {
+            self.next();
+        }
+    }
+
+    /// Skip past the closing `kind` of a bracketed list with a syntax error.
+    /// Stops short at the end of the statement, if the list isn't closed before it.
+    fn skip_past(&mut self, kind: LexemeKind) {
+        loop {
+            let lexeme = self.peek();
+            if lexeme.kind == kind {
+                self.next();
+                return;
+            }
+            if is_synchronization_point(&lexeme.kind) {
+                return;
+            }
+            self.next();
+        }
+    }
+
+    /// Skip statement terminators: newlines and semicolons.
+    fn skip_newlines(&mut self) {
+        while let LK::Newline | LK::Semicolon = self.peek().kind {
+            self.next();
+        }
+    }
+}
+
+/// Whether a statement modifier like `x += 1 while x < 10` starts with this lexeme.
+fn is_modifier(kind: &LexemeKind) -> bool {
+    matches!(kind, LK::If | LK::Unless | LK::While | LK::Until)
+}
+
+/// A statement list made of a single expression, like a branch of a ternary.
+fn single_statement(expr: N::Expr) -> N::Statements {
+    N::Statements {
+        span: expr.span,
+        body: vec![expr],
+    }
+}
+
+/// Can this lexeme follow the last of a sequence of statements?
+fn ends_statements(kind: &LexemeKind) -> bool {
+    matches!(
+        kind,
+        LK::End
+            | LK::When
+            | LK::Rescue
+            | LK::Ensure
+            | LK::Else
+            | LK::Elsif
+            | LK::BraceRight
+            | LK::EmbExprEnd
+            | LK::Eof
+            | LK::UnderscoreEnd
+    )
+}
+
+/// Where we can resume parsing after a syntax error:
+/// at the end of the statement, or of the body it's in.
+fn is_synchronization_point(kind: &LexemeKind) -> bool {
+    matches!(
+        kind,
+        LK::Newline
+            | LK::Semicolon
+            | LK::End
+            | LK::BraceRight
+            | LK::EmbExprEnd
+            | LK::Eof
+            | LK::UnderscoreEnd
+    )
+}
+
+/// A word of a `%w` or `%W` list, as a string.
+/// Can `expr` be written, as the target of a compound or multiple assignment?
+fn is_writable(expr: &N::Expr) -> bool {
+    match &expr.kind {
+        N::ExprKind::LocalVariableRead(_)
+        | N::ExprKind::GlobalVariableRead(_)
+        | N::ExprKind::ConstantRead(_)
+        | N::ExprKind::InstanceVariableRead(_) => true,
+        N::ExprKind::Call(call) => is_index(call) || is_attribute(call),
+        _ => false,
+    }
+}
+
+/// Is `call` just an identifier, like `foo`, which could have been a local variable?
+fn is_vcall(call: &N::Call) -> bool {
+    call.receiver.is_none()
+        && call.args.is_empty()
+        && call.block.is_none()
+        && !call.name.ends_with(['?', '!'])
+}
+
+/// Is `call` an index, like `a[i]`, that `a[i] = x` can write?
+fn is_index(call: &N::Call) -> bool {
+    call.name == "[]" && call.receiver.is_some() && call.block.is_none()
+}
+
+/// Is `call` an attribute, like `obj.x`, that `obj.x = val` can write with `x=`?
+fn is_attribute(call: &N::Call) -> bool {
+    call.receiver.is_some()
+        && call.args.is_empty()
+        && call.block.is_none()
+        && call
+            .name
+            .starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && !call.name.ends_with(['?', '!', '='])
+}
+
+/// The operator of a compound assignment, from its lexeme, like `+` from `+=`.
+fn write_operator(kind: &LexemeKind) -> N::WriteOperator {
+    match kind {
+        LK::PipePipeEqual => N::WriteOperator::Or,
+        LK::AmpersandAmpersandEqual => N::WriteOperator::And,
+        kind => {
+            let spelling = kind.spelling().unwrap();
+            N::WriteOperator::Call(spelling.trim_end_matches('=').to_string())
+        }
+    }
+}
+
+fn word_string(parts: Vec<N::StringPart>) -> N::ExprKind {
+    match parts.as_slice() {
+        [N::StringPart::Literal(text)] => N::ExprKind::String(text.clone()),
+        _ => N::ExprKind::InterpolatedString(Box::new(N::InterpolatedString { parts })),
+    }
+}
+
+/// Does `text` end with an odd number of `\`s, escaping whatever follows?
+fn ends_with_escape(text: &str) -> bool {
+    text.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+/// A word of a `%i` or `%I` list, as a symbol.
+/// Interpolated symbols are interned at runtime.
+fn word_symbol(parts: Vec<N::StringPart>) -> N::ExprKind {
+    match parts.as_slice() {
+        [N::StringPart::Literal(text)] => N::ExprKind::Symbol(text.clone()),
+        _ => N::ExprKind::InterpolatedSymbol(Box::new(N::InterpolatedString { parts })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+    use serde_lexpr::print;
+    use std::process::Command;
+
+    /// Parse the given text
+    /// and return pretty-printed sexpr
+    /// This only works so long as the lexer can be driven independently of the parser.
+    fn parse_to_sexpr(text: &str) -> String {
+        let parser = Parser::new(Lexer::new(text));
+        let (program, _) = parser.parse();
+
+        // TODO: formatting as elisp, cause we're using emacs for pretty-printing...
+        let sexpr = serde_lexpr::to_string_custom(&program, print::Options::elisp()).unwrap();
+        format_sexpr(&sexpr)
+    }
+
+    /// Pretty print given sexpr
+    /// TODO -- Remove the emacs dependency...
+    fn format_sexpr(sexpr: &str) -> String {
+        let pp_command = format!(
+            "(pp (car (read-from-string \"{}\"))))",
+            sexpr.replace(r#"""#, r#"\""#)
+        );
+        let output = Command::new("emacs")
+            .args(["--batch", "--eval", &pp_command])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // TODO -- There's gotta be a good way to test tree equality.
+    //  I'm sure rust-analyzer has it figured out.
+    //  I could just do everything with the textual output.
+    #[test]
+    fn integer_literal() {
+        let text = "22";
+        let parser = Parser::new(Lexer::new(text));
+        let (program, _) = parser.parse();
+
+        let N::Program { statements, .. } = program;
+        assert_eq!(1, statements.body.len());
+        assert_eq!(N::ExprKind::Integer(22), statements.body[0].kind);
+    }
+
+    #[test]
+    fn single_quote_string_literal() {
+        let text = "'22'";
+        let parser = Parser::new(Lexer::new(text));
+        let (program, _) = parser.parse();
+
+        match program {
+            N::Program { statements, .. } => {
+                assert_eq!(1, statements.body.len());
+                // Strips quotes
+                assert_eq!(
+                    N::ExprKind::String("22".to_string()),
+                    statements.body[0].kind
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn integer() {
+        let text = "'22'";
+        let parser = Parser::new(Lexer::new(text));
+        let (program, _) = parser.parse();
+
+        let N::Program { statements, .. } = program;
+        assert_eq!(1, statements.body.len());
+        // Strips quotes
+        assert_eq!(
+            N::ExprKind::String("22".to_string()),
+            statements.body[0].kind
+        );
+    }
+
+    #[test]
+    fn single_addition() {
+        let text = "1 + 2";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn multiple_addition() {
+        let text = "1 + 2 + 3";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call
+                (receiver
+                 (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))
+                (name . "+") (args (Integer . 3))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn add_sub_mul_div() {
+        let text = "1 + 2 - 3 * 4 / 5";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call
+                (receiver
+                 (Call (receiver (Integer . 1)) (name . "+") (args (Integer . 2))))
+                (name . "-")
+                (args
+                 (Call
+                  (receiver
+                   (Call (receiver (Integer . 3)) (name . "*")
+            	     (args (Integer . 4))))
+                  (name . "/") (args (Integer . 5))))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn matklad_example() {
+        // Example from https://matklad.github.io/2020/04/13/simple-but-powerful-pratt-parsing.html
+        let text = "1 + 2 * 3 * 4 + 5";
+        // (+ (+ 1
+        //       (* (* 2 3)
+        //          4)
+        //    5)
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call
+                (receiver
+                 (Call (receiver (Integer . 1)) (name . "+")
+            	   (args
+            	    (Call
+            	     (receiver
+            	      (Call (receiver (Integer . 2)) (name . "*")
+            		    (args (Integer . 3))))
+            	     (name . "*") (args (Integer . 4))))))
+                (name . "+") (args (Integer . 5))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn method_call() {
+        let text = "$foo.bar()";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call (receiver (GlobalVariableRead (name . "foo"))) (name . "bar")
+            	 (args)))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn method_call_multiple_args() {
+        let text = "$foo.bar(1, 2, 3)";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call (receiver (GlobalVariableRead (name . "foo"))) (name . "bar")
+            	 (args (Integer . 1) (Integer . 2) (Integer . 3))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn method_call_in_operators() {
+        let text = "1 + $foo.bar() * 2 + 3";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call
+                (receiver
+                 (Call (receiver (Integer . 1)) (name . "+")
+            	   (args
+            	    (Call
+            	     (receiver
+            	      (Call (receiver (GlobalVariableRead (name . "foo")))
+            		    (name . "bar") (args)))
+            	     (name . "*") (args (Integer . 2))))))
+                (name . "+") (args (Integer . 3))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn unary_minus() {
+        let text = "-1";
+        let expected = expect![[r#"
+            ((statements
+              (body (Call (receiver (Integer . 1)) (name . "-@") (args)))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn unary_minus_multi_digit() {
+        let text = "-9999";
+        let expected = expect![[r#"
+            ((statements
+              (body (Call (receiver (Integer . 9999)) (name . "-@") (args)))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn logical_ops() {
+        let text = "false && true || false";
+        let expected = expect![[r#"
+            ((statements
+              (body (Or (lhs And (lhs . False) (rhs . True)) (rhs . False)))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn gt_lt() {
+        let text = "1 < 2 && 3 < 4";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (And
+                (lhs Call (receiver (Integer . 1)) (name . "<")
+            	 (args (Integer . 2)))
+                (rhs Call (receiver (Integer . 3)) (name . "<")
+            	 (args (Integer . 4)))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn call_brackets() {
+        let text = "true.[](0)";
+        let expected = expect![[r#"
+            ((statements
+              (body (Call (receiver True) (name . "[]") (args (Integer . 0))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn parse_eq_eq() {
+        let text = "22 == 44";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call (receiver (Integer . 22)) (name . "==") (args (Integer . 44))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn parse_complicated_equal_equal() {
+        let text = "$a[44] + 2 * $b == $c[$d]";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Call
+                (receiver
+                 (Call
+                  (receiver
+                   (Call (receiver (GlobalVariableRead (name . "a")))
+            	     (name . "[]") (args (Integer . 44))))
+                  (name . "+")
+                  (args
+                   (Call (receiver (Integer . 2)) (name . "*")
+            	     (args (GlobalVariableRead (name . "b")))))))
+                (name . "==")
+                (args
+                 (Call (receiver (GlobalVariableRead (name . "c"))) (name . "[]")
+            	   (args (GlobalVariableRead (name . "d")))))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn parse_def() {
+        let text = "
+            def x(n)
+                n
+            end
+        ";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Def (name . "x") (params ((name . "n")))
+            	(body (body (LocalVariableRead (name . "n"))))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn def_no_args() {
+        let text = "
+            def x()
+                22
+            end
+        ";
+        let expected = expect![[r#"
+            ((statements
+              (body (Def (name . "x") (params) (body (body (Integer . 22)))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn def_multiple_args() {
+        let text = "
+            def x(a, b)
+                a + b
+            end
+        ";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Def (name . "x") (params ((name . "a")) ((name . "b")))
+            	(body
+            	 (body
+            	  (Call (receiver (LocalVariableRead (name . "a")))
+            		(name . "+") (args (LocalVariableRead (name . "b"))))))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn method_call_no_receiver() {
+        let text = "x()";
+        let expected = expect![[r#"
+            ((statements (body (Call (receiver) (name . "x") (args)))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn def_then_call() {
+        let text = "
+            def x(n)
+                n
+            end
+            x(22)
+        ";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (Def (name . "x") (params ((name . "n")))
+            	(body (body (LocalVariableRead (name . "n")))))
+               (Call (receiver) (name . "x") (args (Integer . 22))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    #[test]
+    fn for_in() {
+        let text = "for row in [1, 2, 3] do row end";
+        let expected = expect![[r#"
+            ((statements
+              (body
+               (For (idx . "row")
+            	(collection Array
+            		    (vals (Integer . 1) (Integer . 2) (Integer . 3)))
+            	(stmts (body (LocalVariableRead (name . "row"))))))))
+        "#]];
+        let actual = parse_to_sexpr(text);
+        expected.assert_eq(&actual);
+    }
+
+    mod globals {
+        use crate::parser::tests::parse_to_sexpr;
+        use expect_test::expect;
+
+        #[test]
+        fn global_read() {
+            let text = "$asdf";
+            let expected = expect![[r#"
+                ((statements (body (GlobalVariableRead (name . "asdf")))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+
+        #[test]
+        fn global_write() {
+            let text = "$asdf = 22";
+            let expected = expect![[r#"
+                ((statements
+                  (body (GlobalVariableWrite (name . "asdf") (expr Integer . 22)))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+
+        #[test]
+        fn global_write_then_read() {
+            let text = "$asdf = 22
+            $asdf";
+            let expected = expect![[r#"
+                ((statements
+                  (body (GlobalVariableWrite (name . "asdf") (expr Integer . 22))
+                	(GlobalVariableRead (name . "asdf")))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+    }
+
+    mod constants {
+        use crate::parser::tests::parse_to_sexpr;
+        use expect_test::expect;
+
+        #[test]
+        fn object() {
+            let text = "Object";
+            let expected = expect![[r#"
+                ((statements (body (ConstantRead (name . "Object")))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+    }
+
+    mod arrays {
+        use crate::parser::tests::parse_to_sexpr;
+
+        use expect_test::expect;
+
+        #[test]
+        fn empty_arr() {
+            let text = "[]";
+            let expected = expect![[r#"
+                ((statements (body (Array (vals)))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+
+        #[test]
+        fn non_empty_arr() {
+            let text = "[1, 2, 3]";
+            let expected = expect![[r#"
+                ((statements
+                  (body (Array (vals (Integer . 1) (Integer . 2) (Integer . 3))))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+
+        #[test]
+        fn array_indexing() {
+            let text = "[1, 2, 3][1]";
+            let expected = expect![[r#"
+                ((statements
+                  (body
+                   (Call
+                    (receiver (Array (vals (Integer . 1) (Integer . 2) (Integer . 3))))
+                    (name . "[]") (args (Integer . 1))))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+
+        #[test]
+        fn array_index_assignment() {
+            let text = "$x = [1, 2, 3]
+                $x[1] = 0
+                $x";
+            let expected = expect![[r#"
+                ((statements
+                  (body
+                   (GlobalVariableWrite (name . "x")
+                			(expr Array
+                			      (vals (Integer . 1) (Integer . 2)
+                				    (Integer . 3))))
+                   (Call (receiver (GlobalVariableRead (name . "x"))) (name . "[]=")
+                	 (args (Integer . 1) (Integer . 0)))
+                   (GlobalVariableRead (name . "x")))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+    }
+
+    mod demo {
+        use crate::parser::tests::parse_to_sexpr;
+        use expect_test::expect;
+
+        #[test]
+        pub fn cells() {
+            let text = "
+                $cells = [
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+                ]
+
+                def get_cells()
+                    $cells
+                end
+            ";
+            let expected = expect![[r#"
+                ((statements
                   (body
                    (GlobalVariableWrite (name . "cells")
                 			(expr Array
@@ -1108,256 +2902,1810 @@ mod tests {
         }
 
         #[test]
-        pub fn count_neighbors() {
-            let text = "
-def count_neighbors(row, col)
-  count = 0
-  for neighbor in [
-    [row-1, col-1], [row-1, col], [row-1, col+1],
-    [row, col-1], [row, col+1],
-    [row+1, col-1], [row+1, col], [row+1, col+1]
-  ] do
-    neighbor_row = neighbor[0]
-    neighbor_col = neighbor[1]
-    row_lo = neighbor_row == 0 || neighbor_row > 0
-    col_lo = neighbor_col == 0 || neighbor_col > 0
-    row_hi = neighbor_row < 9 || neighbor_row == 9
-    col_lo = neighbor_col < 0 || neighbor_col == 0
-    in_bounds = row_lo && col_lo && row_hi && col_hi
-    alive = in_bounds && $cells[neighbor_row][neighbor_col] == 1
-    if alive
-      count = count + 1
-    end
-  end
-  count
-end
-            ";
-            let expected = expect![[r#"
-                ((statements
-                  (body
-                   (Def (name . "count_neighbors")
-                	(params ((name . "row")) ((name . "col")))
-                	(body
-                	 (body (LocalVariableWrite (name . "count") (val Integer . 0))
-                	       (For (idx . "neighbor")
-                		    (collection Array
-                				(vals
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "-") (args (Integer . 1)))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "-") (args (Integer . 1)))))
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "-") (args (Integer . 1)))
-                				   (LocalVariableRead (name . "col"))))
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "-") (args (Integer . 1)))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "+") (args (Integer . 1)))))
-                				 (Array
-                				  (vals
-                				   (LocalVariableRead (name . "row"))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "-") (args (Integer . 1)))))
-                				 (Array
-                				  (vals
-                				   (LocalVariableRead (name . "row"))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "+") (args (Integer . 1)))))
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "+") (args (Integer . 1)))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "-") (args (Integer . 1)))))
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "+") (args (Integer . 1)))
-                				   (LocalVariableRead (name . "col"))))
-                				 (Array
-                				  (vals
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "row")))
-                				    (name . "+") (args (Integer . 1)))
-                				   (Call
-                				    (receiver
-                				     (LocalVariableRead (name . "col")))
-                				    (name . "+") (args (Integer . 1)))))))
-                		    (stmts
-                		     (body
-                		      (LocalVariableWrite (name . "neighbor_row")
-                					  (val Call
-                					       (receiver
-                						(LocalVariableRead
-                						 (name . "neighbor")))
-                					       (name . "[]")
-                					       (args (Integer . 0))))
-                		      (LocalVariableWrite (name . "neighbor_col")
-                					  (val Call
-                					       (receiver
-                						(LocalVariableRead
-                						 (name . "neighbor")))
-                					       (name . "[]")
-                					       (args (Integer . 1))))
-                		      (LocalVariableWrite (name . "row_lo")
-                					  (val Or
-                					       (lhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_row")))
-                						    (name . "==")
-                						    (args
-                						     (Integer . 0)))
-                					       (rhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_row")))
-                						    (name . ">")
-                						    (args
-                						     (Integer . 0)))))
-                		      (LocalVariableWrite (name . "col_lo")
-                					  (val Or
-                					       (lhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_col")))
-                						    (name . "==")
-                						    (args
-                						     (Integer . 0)))
-                					       (rhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_col")))
-                						    (name . ">")
-                						    (args
-                						     (Integer . 0)))))
-                		      (LocalVariableWrite (name . "row_hi")
-                					  (val Or
-                					       (lhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_row")))
-                						    (name . "<")
-                						    (args
-                						     (Integer . 9)))
-                					       (rhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_row")))
-                						    (name . "==")
-                						    (args
-                						     (Integer . 9)))))
-                		      (LocalVariableWrite (name . "col_lo")
-                					  (val Or
-                					       (lhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_col")))
-                						    (name . "<")
-                						    (args
-                						     (Integer . 0)))
-                					       (rhs Call
-                						    (receiver
-                						     (LocalVariableRead
-                						      (name
-                						       . "neighbor_col")))
-                						    (name . "==")
-                						    (args
-                						     (Integer . 0)))))
-                		      (LocalVariableWrite (name . "in_bounds")
-                					  (val And
-                					       (lhs And
-                						    (lhs And
-                							 (lhs
-                							  LocalVariableRead
-                							  (name
-                							   . "row_lo"))
-                							 (rhs
-                							  LocalVariableRead
-                							  (name
-                							   . "col_lo")))
-                						    (rhs
-                						     LocalVariableRead
-                						     (name . "row_hi")))
-                					       (rhs LocalVariableRead
-                						    (name . "col_hi"))))
-                		      (LocalVariableWrite (name . "alive")
-                					  (val And
-                					       (lhs LocalVariableRead
-                						    (name
-                						     . "in_bounds"))
-                					       (rhs Call
-                						    (receiver
-                						     (Call
-                						      (receiver
-                						       (Call
-                							(receiver
-                							 (GlobalVariableRead
-                							  (name
-                							   . "cells")))
-                							(name . "[]")
-                							(args
-                							 (LocalVariableRead
-                							  (name
-                							   . "neighbor_row")))))
-                						      (name . "[]")
-                						      (args
-                						       (LocalVariableRead
-                							(name
-                							 . "neighbor_col")))))
-                						    (name . "==")
-                						    (args
-                						     (Integer . 1)))))
-                		      (If
-                		       (predicate LocalVariableRead (name . "alive"))
-                		       (statements
-                			(body
-                			 (LocalVariableWrite (name . "count")
-                					     (val Call
-                						  (receiver
-                						   (LocalVariableRead
-                						    (name . "count")))
-                						  (name . "+")
-                						  (args (Integer . 1))))))
-                		       (subsequent . None)))))
-                	       (LocalVariableRead (name . "count"))))))))
-            "#]];
-            let actual = parse_to_sexpr(text);
-            expected.assert_eq(&actual);
+        pub fn count_neighbors() {
+            let text = "
+def count_neighbors(row, col)
+  count = 0
+  for neighbor in [
+    [row-1, col-1], [row-1, col], [row-1, col+1],
+    [row, col-1], [row, col+1],
+    [row+1, col-1], [row+1, col], [row+1, col+1]
+  ] do
+    neighbor_row = neighbor[0]
+    neighbor_col = neighbor[1]
+    row_lo = neighbor_row == 0 || neighbor_row > 0
+    col_lo = neighbor_col == 0 || neighbor_col > 0
+    row_hi = neighbor_row < 9 || neighbor_row == 9
+    col_lo = neighbor_col < 0 || neighbor_col == 0
+    in_bounds = row_lo && col_lo && row_hi && col_hi
+    alive = in_bounds && $cells[neighbor_row][neighbor_col] == 1
+    if alive
+      count = count + 1
+    end
+  end
+  count
+end
+            ";
+            let expected = expect![[r#"
+                ((statements
+                  (body
+                   (Def (name . "count_neighbors")
+                	(params ((name . "row")) ((name . "col")))
+                	(body
+                	 (body (LocalVariableWrite (name . "count") (val Integer . 0))
+                	       (For (idx . "neighbor")
+                		    (collection Array
+                				(vals
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "-") (args (Integer . 1)))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "-") (args (Integer . 1)))))
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "-") (args (Integer . 1)))
+                				   (LocalVariableRead (name . "col"))))
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "-") (args (Integer . 1)))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "+") (args (Integer . 1)))))
+                				 (Array
+                				  (vals
+                				   (LocalVariableRead (name . "row"))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "-") (args (Integer . 1)))))
+                				 (Array
+                				  (vals
+                				   (LocalVariableRead (name . "row"))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "+") (args (Integer . 1)))))
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "+") (args (Integer . 1)))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "-") (args (Integer . 1)))))
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "+") (args (Integer . 1)))
+                				   (LocalVariableRead (name . "col"))))
+                				 (Array
+                				  (vals
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "row")))
+                				    (name . "+") (args (Integer . 1)))
+                				   (Call
+                				    (receiver
+                				     (LocalVariableRead (name . "col")))
+                				    (name . "+") (args (Integer . 1)))))))
+                		    (stmts
+                		     (body
+                		      (LocalVariableWrite (name . "neighbor_row")
+                					  (val Call
+                					       (receiver
+                						(LocalVariableRead
+                						 (name . "neighbor")))
+                					       (name . "[]")
+                					       (args (Integer . 0))))
+                		      (LocalVariableWrite (name . "neighbor_col")
+                					  (val Call
+                					       (receiver
+                						(LocalVariableRead
+                						 (name . "neighbor")))
+                					       (name . "[]")
+                					       (args (Integer . 1))))
+                		      (LocalVariableWrite (name . "row_lo")
+                					  (val Or
+                					       (lhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_row")))
+                						    (name . "==")
+                						    (args
+                						     (Integer . 0)))
+                					       (rhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_row")))
+                						    (name . ">")
+                						    (args
+                						     (Integer . 0)))))
+                		      (LocalVariableWrite (name . "col_lo")
+                					  (val Or
+                					       (lhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_col")))
+                						    (name . "==")
+                						    (args
+                						     (Integer . 0)))
+                					       (rhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_col")))
+                						    (name . ">")
+                						    (args
+                						     (Integer . 0)))))
+                		      (LocalVariableWrite (name . "row_hi")
+                					  (val Or
+                					       (lhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_row")))
+                						    (name . "<")
+                						    (args
+                						     (Integer . 9)))
+                					       (rhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_row")))
+                						    (name . "==")
+                						    (args
+                						     (Integer . 9)))))
+                		      (LocalVariableWrite (name . "col_lo")
+                					  (val Or
+                					       (lhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_col")))
+                						    (name . "<")
+                						    (args
+                						     (Integer . 0)))
+                					       (rhs Call
+                						    (receiver
+                						     (LocalVariableRead
+                						      (name
+                						       . "neighbor_col")))
+                						    (name . "==")
+                						    (args
+                						     (Integer . 0)))))
+                		      (LocalVariableWrite (name . "in_bounds")
+                					  (val And
+                					       (lhs And
+                						    (lhs And
+                							 (lhs
+                							  LocalVariableRead
+                							  (name
+                							   . "row_lo"))
+                							 (rhs
+                							  LocalVariableRead
+                							  (name
+                							   . "col_lo")))
+                						    (rhs
+                						     LocalVariableRead
+                						     (name . "row_hi")))
+                					       (rhs LocalVariableRead
+                						    (name . "col_hi"))))
+                		      (LocalVariableWrite (name . "alive")
+                					  (val And
+                					       (lhs LocalVariableRead
+                						    (name
+                						     . "in_bounds"))
+                					       (rhs Call
+                						    (receiver
+                						     (Call
+                						      (receiver
+                						       (Call
+                							(receiver
+                							 (GlobalVariableRead
+                							  (name
+                							   . "cells")))
+                							(name . "[]")
+                							(args
+                							 (LocalVariableRead
+                							  (name
+                							   . "neighbor_row")))))
+                						      (name . "[]")
+                						      (args
+                						       (LocalVariableRead
+                							(name
+                							 . "neighbor_col")))))
+                						    (name . "==")
+                						    (args
+                						     (Integer . 1)))))
+                		      (If
+                		       (predicate LocalVariableRead (name . "alive"))
+                		       (statements
+                			(body
+                			 (LocalVariableWrite (name . "count")
+                					     (val Call
+                						  (receiver
+                						   (LocalVariableRead
+                						    (name . "count")))
+                						  (name . "+")
+                						  (args (Integer . 1))))))
+                		       (subsequent . None)))))
+                	       (LocalVariableRead (name . "count"))))))))
+            "#]];
+            let actual = parse_to_sexpr(text);
+            expected.assert_eq(&actual);
+        }
+    }
+
+    mod spans {
+        use super::*;
+        use crate::lexeme::ByteDifference;
+
+        fn span(start: usize, len: usize) -> Span {
+            Span::new(ByteIdx(start), ByteDifference(len))
+        }
+
+        #[test]
+        fn infix_spans_both_operands() {
+            let program = Parser::new(Lexer::new("x = 1 + foo(2)\n  y")).parse().0;
+            let body = &program.statements.body;
+            assert_eq!(span(0, 14), body[0].span);
+            assert_eq!(span(17, 1), body[1].span);
+            assert_eq!(span(0, 18), program.statements.span);
+
+            let N::ExprKind::LocalVariableWrite(write) = &body[0].kind else {
+                panic!()
+            };
+            assert_eq!(span(4, 10), write.val.span);
+            let N::ExprKind::Call(call) = &write.val.kind else {
+                panic!()
+            };
+            assert_eq!(span(4, 1), call.receiver.as_ref().unwrap().span);
+            assert_eq!(span(8, 6), call.args[0].span);
+        }
+
+        #[test]
+        fn def_and_params() {
+            let program = Parser::new(Lexer::new("def f(a, b)\n  a\nend")).parse().0;
+            let def = &program.statements.body[0];
+            assert_eq!(span(0, 19), def.span);
+            let N::ExprKind::Def(def) = &def.kind else {
+                panic!()
+            };
+            assert_eq!(span(6, 1), def.params.required[0].span);
+            assert_eq!(span(9, 1), def.params.required[1].span);
+            assert_eq!(span(14, 1), def.body.span);
+        }
+    }
+
+    mod classes {
+        use super::*;
+
+        fn parse(text: &str) -> N::Class {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Class(class) = program.statements.body[0].kind.clone() else {
+                panic!()
+            };
+            *class
+        }
+
+        #[test]
+        fn with_superclass() {
+            let class = parse("class Dog < Animal\n  def speak()\n    1\n  end\nend");
+            assert_eq!("Dog", class.name);
+            assert_eq!(
+                Some(N::ExprKind::ConstantRead(Box::new(N::ConstantRead {
+                    name: "Animal".to_string()
+                }))),
+                class.superclass.map(|superclass| superclass.kind)
+            );
+            assert_eq!(1, class.body.body.len());
+        }
+
+        #[test]
+        fn without_superclass() {
+            let class = parse("class Dog; end");
+            assert_eq!("Dog", class.name);
+            assert_eq!(None, class.superclass);
+            assert!(class.body.body.is_empty());
+        }
+
+        #[test]
+        fn keyword_method_name() {
+            let (program, diagnostics) = Parser::new(Lexer::new("x.class()")).parse();
+            assert!(diagnostics.is_empty());
+            let N::ExprKind::Call(call) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!("class", call.name);
+        }
+
+        #[test]
+        fn missing_name() {
+            let (_, diagnostics) = Parser::new(Lexer::new("class\n  1\nend")).parse();
+            assert_eq!(
+                vec!["unexpected newline; expected constant"],
+                diagnostics
+                    .iter()
+                    .map(|diagnostic| diagnostic.message.as_str())
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    mod supers {
+        use super::*;
+
+        fn parse(text: &str) -> N::ExprKind {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            program.statements.body[0].kind.clone()
+        }
+
+        #[test]
+        fn with_args() {
+            let N::ExprKind::Super(super_expr) = parse("super(1, x)") else {
+                panic!()
+            };
+            assert_eq!(2, super_expr.args.len());
+        }
+
+        #[test]
+        fn empty_parens() {
+            assert_eq!(
+                N::ExprKind::Super(Box::new(N::Super { args: vec![] })),
+                parse("super()")
+            );
+        }
+
+        #[test]
+        fn forwarding() {
+            assert_eq!(N::ExprKind::ForwardingSuper, parse("super"));
+        }
+    }
+
+    mod blocks {
+        use super::*;
+
+        fn parse_call(text: &str) -> N::Call {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Call(call) = program.statements.body[0].kind.clone() else {
+                panic!()
+            };
+            *call
+        }
+
+        fn literal_block(call: N::Call) -> N::Block {
+            let Some(N::CallBlock::Literal(block)) = call.block else {
+                panic!()
+            };
+            *block
+        }
+
+        #[test]
+        fn braces() {
+            let call = parse_call("each_pair(h) { |k, v| k }");
+            assert_eq!(1, call.args.len());
+            let block = literal_block(call);
+            let params: Vec<_> = block
+                .params
+                .required
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
+            assert_eq!(vec!["k", "v"], params);
+            assert_eq!(1, block.body.body.len());
+        }
+
+        #[test]
+        fn do_end() {
+            let block = literal_block(parse_call("arr.each do |x|\n  x\n  x\nend"));
+            assert_eq!(1, block.params.required.len());
+            assert_eq!(2, block.body.body.len());
+        }
+
+        #[test]
+        fn without_parens_or_params() {
+            let call = parse_call("run { 1 }");
+            assert!(call.args.is_empty());
+            assert!(literal_block(call).params.required.is_empty());
+            assert!(
+                literal_block(parse_call("obj.run { || 1 }"))
+                    .params
+                    .required
+                    .is_empty()
+            );
+        }
+
+        #[test]
+        fn while_do_is_not_a_block() {
+            let (program, diagnostics) =
+                Parser::new(Lexer::new("x = true\nwhile x do\n  1\nend")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::While(while_expr) = &program.statements.body[1].kind else {
+                panic!()
+            };
+            assert!(matches!(
+                while_expr.predicate.kind,
+                N::ExprKind::LocalVariableRead(_)
+            ));
+        }
+
+        #[test]
+        fn block_arg() {
+            let call = parse_call("run(1, &blk)");
+            assert_eq!(1, call.args.len());
+            assert!(matches!(call.block, Some(N::CallBlock::Argument(_))));
+        }
+
+        #[test]
+        fn block_param() {
+            let (program, diagnostics) = Parser::new(Lexer::new("def run(a, &blk)\nend")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Def(def) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(1, def.params.required.len());
+            assert_eq!("blk", def.params.block.as_ref().unwrap().name);
+        }
+
+        #[test]
+        fn block_arg_and_block() {
+            let (_, diagnostics) = Parser::new(Lexer::new("run(&blk) { 1 }")).parse();
+            assert_eq!(1, diagnostics.len());
+        }
+
+        #[test]
+        fn lambda_call() {
+            let call = parse_call("lambda { |row, col|\n  toggle(row, col)\n}");
+            assert_eq!("lambda", call.name);
+            assert_eq!(2, literal_block(call).params.required.len());
+        }
+
+        #[test]
+        fn stabby_lambda() {
+            let (program, diagnostics) =
+                Parser::new(Lexer::new("->(a, b) { a }\n-> do\n  1\nend")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Lambda(lambda) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, lambda.params.required.len());
+            let N::ExprKind::Lambda(lambda) = &program.statements.body[1].kind else {
+                panic!()
+            };
+            assert!(lambda.params.required.is_empty());
+            assert_eq!(1, lambda.body.body.len());
+        }
+
+        #[test]
+        fn dot_parens_calls_call() {
+            let call = parse_call("square.(3)");
+            assert_eq!("call", call.name);
+            assert_eq!(1, call.args.len());
+        }
+
+        #[test]
+        fn yield_args() {
+            let (program, diagnostics) = Parser::new(Lexer::new("yield(1, 2)")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Yield(yield_expr) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, yield_expr.args.len());
+        }
+    }
+
+    mod modules {
+        use super::*;
+
+        #[test]
+        fn module() {
+            let (program, diagnostics) =
+                Parser::new(Lexer::new("module Greet\n  def hi()\n    1\n  end\nend")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Module(module) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!("Greet", module.name);
+            assert_eq!(1, module.body.body.len());
+        }
+
+        #[test]
+        fn superclass_is_error() {
+            let (_, diagnostics) = Parser::new(Lexer::new("module Greet < Object\nend")).parse();
+            assert_eq!(1, diagnostics.len());
+        }
+    }
+
+    mod cases {
+        use super::*;
+
+        fn parse_case(text: &str) -> N::Case {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Case(case_expr) = program.statements.body[0].kind.clone() else {
+                panic!()
+            };
+            *case_expr
+        }
+
+        #[test]
+        fn subject() {
+            let case_expr = parse_case(
+                "case x
+when 1, 2 then 3
+when Integer
+  4
+  5
+else
+  6
+end",
+            );
+            assert!(case_expr.subject.is_some());
+            assert_eq!(2, case_expr.whens.len());
+            assert_eq!(2, case_expr.whens[0].patterns.len());
+            assert_eq!(1, case_expr.whens[0].statements.body.len());
+            assert_eq!(2, case_expr.whens[1].statements.body.len());
+            assert!(case_expr.else_clause.is_some());
+        }
+
+        #[test]
+        fn no_subject() {
+            let case_expr = parse_case(
+                "case
+when x > 1 then 2
+end",
+            );
+            assert!(case_expr.subject.is_none());
+            assert_eq!(1, case_expr.whens.len());
+            assert!(case_expr.else_clause.is_none());
+            assert!(parse_case("case when x then 1 end").subject.is_none());
+        }
+
+        #[test]
+        fn range_pattern() {
+            let case_expr = parse_case("case x when 1..5, 6...9 then 1 end");
+            let ranges: Vec<_> = case_expr.whens[0]
+                .patterns
+                .iter()
+                .map(|pattern| match &pattern.kind {
+                    N::ExprKind::Range(range) => range.exclusive,
+                    _ => panic!(),
+                })
+                .collect();
+            assert_eq!(vec![false, true], ranges);
+        }
+
+        #[test]
+        fn no_whens_is_error() {
+            let (_, diagnostics) = Parser::new(Lexer::new(
+                "case x
+else 1
+end",
+            ))
+            .parse();
+            assert_eq!(1, diagnostics.len());
+        }
+
+        #[test]
+        fn def_operator() {
+            let (program, diagnostics) = Parser::new(Lexer::new(
+                "def ===(x)
+  true
+end",
+            ))
+            .parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Def(def) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!("===", def.name);
+        }
+    }
+
+    mod jumps {
+        use super::*;
+
+        fn parse(text: &str) -> Vec<N::ExprKind> {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            program
+                .statements
+                .body
+                .into_iter()
+                .map(|expr| expr.kind)
+                .collect()
+        }
+
+        /// The statements of `text`, in a loop so that `break`, `next` and `redo` are valid.
+        fn parse_in_loop(text: &str) -> Vec<N::ExprKind> {
+            let kinds = parse(&format!("while true\n{}\nend", text));
+            let [N::ExprKind::While(while_expr)] = kinds.as_slice() else {
+                panic!("{:?}", kinds)
+            };
+            let body = &while_expr.statements.body;
+            body.iter().map(|expr| expr.kind.clone()).collect()
+        }
+
+        fn value(kind: &N::ExprKind) -> &Option<N::Expr> {
+            match kind {
+                N::ExprKind::Return(jump) | N::ExprKind::Break(jump) | N::ExprKind::Next(jump) => {
+                    &jump.value
+                }
+                _ => panic!("{:?}", kind),
+            }
+        }
+
+        #[test]
+        fn without_value() {
+            let kinds = parse_in_loop("return\nbreak; next\nredo");
+            assert_eq!(4, kinds.len());
+            assert!(value(&kinds[0]).is_none());
+            assert!(value(&kinds[1]).is_none());
+            assert!(value(&kinds[2]).is_none());
+            assert_eq!(N::ExprKind::Redo, kinds[3]);
+        }
+
+        #[test]
+        fn with_value() {
+            let kinds = parse_in_loop("return x + 1\nbreak 2");
+            assert!(matches!(
+                value(&kinds[0]).as_ref().unwrap().kind,
+                N::ExprKind::Call(_)
+            ));
+            assert_eq!(
+                N::ExprKind::Integer(2),
+                value(&kinds[1]).as_ref().unwrap().kind
+            );
+        }
+
+        #[test]
+        fn several_values_are_array() {
+            let kinds = parse("return 1, 2");
+            let N::ExprKind::Array(array) = &value(&kinds[0]).as_ref().unwrap().kind else {
+                panic!()
+            };
+            assert_eq!(2, array.vals.len());
+        }
+
+        #[test]
+        fn in_brace_block() {
+            let kinds = parse("f() { |x| next }");
+            let N::ExprKind::Call(call) = &kinds[0] else {
+                panic!()
+            };
+            let Some(N::CallBlock::Literal(block)) = &call.block else {
+                panic!()
+            };
+            assert!(value(&block.body.body[0].kind).is_none());
+        }
+    }
+
+    mod exceptions {
+        use super::*;
+
+        fn parse_begin(text: &str) -> N::Begin {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Begin(begin_expr) = program.statements.body[0].kind.clone() else {
+                panic!()
+            };
+            *begin_expr
+        }
+
+        #[test]
+        fn all_clauses() {
+            let begin_expr = parse_begin(
+                "begin
+  1
+  2
+rescue TypeError, ArgumentError => e
+  3
+rescue
+  4
+else
+  5
+ensure
+  6
+end",
+            );
+            assert_eq!(2, begin_expr.statements.body.len());
+            assert_eq!(2, begin_expr.rescues.len());
+            assert_eq!(2, begin_expr.rescues[0].classes.len());
+            assert_eq!(Some("e".to_string()), begin_expr.rescues[0].variable);
+            assert!(begin_expr.rescues[1].classes.is_empty());
+            assert!(begin_expr.rescues[1].variable.is_none());
+            assert!(begin_expr.else_clause.is_some());
+            assert!(begin_expr.ensure_clause.is_some());
+        }
+
+        #[test]
+        fn rescue_then() {
+            let begin_expr = parse_begin("begin 1 rescue => e then 2 end");
+            assert!(begin_expr.rescues[0].classes.is_empty());
+            assert_eq!(Some("e".to_string()), begin_expr.rescues[0].variable);
+            assert_eq!(1, begin_expr.rescues[0].statements.body.len());
+        }
+
+        #[test]
+        fn ensure_only() {
+            let begin_expr = parse_begin("begin; 1; ensure; 2; end");
+            assert!(begin_expr.rescues.is_empty());
+            assert!(begin_expr.ensure_clause.is_some());
+        }
+
+        #[test]
+        fn retry() {
+            let begin_expr = parse_begin("begin; 1; rescue; retry; end");
+            assert_eq!(
+                N::ExprKind::Retry,
+                begin_expr.rescues[0].statements.body[0].kind
+            );
+        }
+
+        #[test]
+        fn def_body() {
+            let (program, diagnostics) = Parser::new(Lexer::new(
+                "def f()
+  1
+rescue
+  2
+end",
+            ))
+            .parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Def(def) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            let N::ExprKind::Begin(begin_expr) = &def.body.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(1, begin_expr.rescues.len());
+        }
+    }
+
+    mod params {
+        use super::*;
+
+        fn parse_def(text: &str) -> N::Def {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Def(def) = program.statements.body[0].kind.clone() else {
+                panic!()
+            };
+            *def
+        }
+
+        fn messages(text: &str) -> Vec<String> {
+            let (_, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.message)
+                .collect()
+        }
+
+        #[test]
+        fn all_kinds() {
+            let def = parse_def(
+                "def f(a, b = 1, *rest, c:, d: 2, **opts, &blk)
+end",
+            );
+            assert_eq!("a", def.params.required[0].name);
+            assert_eq!("b", def.params.optional[0].name);
+            assert_eq!(N::ExprKind::Integer(1), def.params.optional[0].default.kind);
+            assert_eq!("rest", def.params.rest.unwrap().name);
+            let keywords: Vec<_> = def
+                .params
+                .keyword
+                .iter()
+                .map(|param| (param.name.as_str(), param.default.is_some()))
+                .collect();
+            assert_eq!(vec![("c", false), ("d", true)], keywords);
+            assert_eq!("opts", def.params.keyword_rest.unwrap().name);
+            assert_eq!("blk", def.params.block.unwrap().name);
+        }
+
+        #[test]
+        fn post_params() {
+            let def = parse_def(
+                "def f(a, b = 1, *rest, c, (d, e))
+end",
+            );
+            assert_eq!("a", def.params.required[0].name);
+            assert_eq!("rest", def.params.rest.unwrap().name);
+            let post: Vec<_> = def
+                .params
+                .post
+                .iter()
+                .map(|param| param.name.as_str())
+                .collect();
+            assert_eq!(vec!["c", "%2"], post);
+        }
+
+        #[test]
+        fn param_spans() {
+            let def = parse_def(
+                "def f(b = 10, *r, k: 2)
+end",
+            );
+            assert_eq!(
+                Span::new(ByteIdx(6), ByteDifference(6)),
+                def.params.optional[0].span
+            );
+            assert_eq!(
+                Span::new(ByteIdx(14), ByteDifference(2)),
+                def.params.rest.unwrap().span
+            );
+            assert_eq!(
+                Span::new(ByteIdx(18), ByteDifference(4)),
+                def.params.keyword[0].span
+            );
+        }
+
+        #[test]
+        fn out_of_order() {
+            assert_eq!(
+                vec!["optional param after required param"],
+                messages(
+                    "def f(a = 1, b, c = 2)
+end"
+                )
+            );
+            assert_eq!(
+                vec!["required param after keyword param"],
+                messages(
+                    "def f(k:, a)
+end"
+                )
+            );
+            assert_eq!(
+                vec!["optional param after keyword param"],
+                messages(
+                    "def f(k:, a = 1)
+end"
+                )
+            );
+            assert_eq!(
+                vec!["rest param after rest param"],
+                messages(
+                    "def f(*a, *b)
+end"
+                )
+            );
+        }
+
+        #[test]
+        fn keyword_args() {
+            let (program, diagnostics) = Parser::new(Lexer::new("f(1, a: 2, b: x)")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Call(call) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, call.args.len());
+            let N::ExprKind::Hash(hash) = &call.args[1].kind else {
+                panic!("{:?}", call.args[1])
+            };
+            let keys: Vec<_> = hash.pairs.iter().map(|pair| pair.key.as_str()).collect();
+            assert_eq!(vec!["a", "b"], keys);
+            assert_eq!(Span::new(ByteIdx(5), ByteDifference(10)), call.args[1].span);
+        }
+
+        #[test]
+        fn positional_arg_after_keyword_args() {
+            assert_eq!(
+                vec!["positional argument after keyword arguments"],
+                messages("f(a: 1, 2)")
+            );
+        }
+    }
+
+    mod commands {
+        use super::*;
+
+        fn parse_statements(text: &str) -> Vec<N::Expr> {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            program.statements.body
+        }
+
+        fn call(expr: &N::Expr) -> &N::Call {
+            let N::ExprKind::Call(call) = &expr.kind else {
+                panic!("{:?}", expr)
+            };
+            call
+        }
+
+        fn is_local_read(expr: &N::Expr) -> bool {
+            matches!(expr.kind, N::ExprKind::LocalVariableRead(_))
+        }
+
+        #[test]
+        fn command_call() {
+            let statements = parse_statements("raise ArgumentError, \"bad\"");
+            let raise = call(&statements[0]);
+            assert_eq!("raise", raise.name);
+            assert!(raise.receiver.is_none());
+            assert_eq!(2, raise.args.len());
+        }
+
+        #[test]
+        fn command_call_after_dot() {
+            let statements = parse_statements("obj.send :x, 1");
+            let send = call(&statements[0]);
+            assert_eq!("send", send.name);
+            assert_eq!(2, send.args.len());
+            assert_eq!("obj", call(send.receiver.as_ref().unwrap()).name);
+        }
+
+        #[test]
+        fn zero_arg_call_after_dot() {
+            let statements = parse_statements("x = 1\nx.class.name");
+            let name = call(&statements[1]);
+            assert_eq!("name", name.name);
+            assert!(name.args.is_empty());
+            let class = call(name.receiver.as_ref().unwrap());
+            assert_eq!("class", class.name);
+            assert!(is_local_read(class.receiver.as_ref().unwrap()));
+        }
+
+        #[test]
+        fn identifier_is_call_unless_local() {
+            let statements = parse_statements("foo\nfoo = 1\nfoo");
+            assert!(call(&statements[0]).args.is_empty());
+            assert!(is_local_read(&statements[2]));
+        }
+
+        #[test]
+        fn local_is_not_a_command() {
+            let statements = parse_statements("x = 1\nx -1\nx [1]");
+            let minus = call(&statements[1]);
+            assert_eq!("-", minus.name);
+            assert!(is_local_read(minus.receiver.as_ref().unwrap()));
+            let index = call(&statements[2]);
+            assert_eq!("[]", index.name);
+            assert!(is_local_read(index.receiver.as_ref().unwrap()));
+        }
+
+        #[test]
+        fn spacing_decides_command_args() {
+            let statements = parse_statements("foo -1\nfoo - 1\nfoo [1]\nfoo[1]");
+            let command = call(&statements[0]);
+            assert_eq!(("foo", 1), (command.name.as_str(), command.args.len()));
+            assert_eq!("-", call(&statements[1]).name);
+            let command = call(&statements[2]);
+            assert_eq!(("foo", 1), (command.name.as_str(), command.args.len()));
+            assert_eq!("[]", call(&statements[3]).name);
+        }
+
+        #[test]
+        fn splat_args() {
+            let statements = parse_statements("foo *a\nfoo * a\nfoo(*a, 1)");
+            let command = call(&statements[0]);
+            assert_eq!(("foo", 1), (command.name.as_str(), command.args.len()));
+            assert!(matches!(command.args[0].kind, N::ExprKind::SplatArg(_)));
+            assert_eq!("*", call(&statements[1]).name);
+            let args = &call(&statements[2]).args;
+            assert!(matches!(args[0].kind, N::ExprKind::SplatArg(_)));
+            assert!(matches!(args[1].kind, N::ExprKind::Integer(1)));
+        }
+
+        #[test]
+        fn blocks_see_enclosing_locals() {
+            let statements = parse_statements(
+                "x = 1
+run { |y| [x, y] }
+def f()
+  x
+end",
+            );
+            let block = match &call(&statements[1]).block {
+                Some(N::CallBlock::Literal(block)) => block,
+                other => panic!("{:?}", other),
+            };
+            let N::ExprKind::Array(array) = &block.body.body[0].kind else {
+                panic!()
+            };
+            assert!(array.vals.iter().all(is_local_read));
+            let N::ExprKind::Def(def) = &statements[2].kind else {
+                panic!()
+            };
+            assert_eq!("x", call(&def.body.body[0]).name);
+        }
+
+        #[test]
+        fn params_are_locals() {
+            let statements = parse_statements(
+                "def f(a, b = 1, *c, d:, **e, &g)
+  [a, b, c, d, e, g]
+end",
+            );
+            let N::ExprKind::Def(def) = &statements[0].kind else {
+                panic!()
+            };
+            let N::ExprKind::Array(array) = &def.body.body[0].kind else {
+                panic!()
+            };
+            assert!(array.vals.iter().all(is_local_read));
+        }
+
+        #[test]
+        fn do_block_belongs_to_command() {
+            let statements = parse_statements("each_of xs.map do |x| x end");
+            let each_of = call(&statements[0]);
+            assert!(matches!(each_of.block, Some(N::CallBlock::Literal(_))));
+            assert!(call(&each_of.args[0]).block.is_none());
+        }
+
+        #[test]
+        fn brace_block_belongs_to_last_arg() {
+            let statements = parse_statements("each_of xs.map { |x| x }");
+            let each_of = call(&statements[0]);
+            assert!(each_of.block.is_none());
+            assert!(call(&each_of.args[0]).block.is_some());
+        }
+
+        #[test]
+        fn keyword_and_block_args() {
+            let statements = parse_statements("foo 1, a: 2, &blk");
+            let foo = call(&statements[0]);
+            assert_eq!(2, foo.args.len());
+            assert!(matches!(foo.args[1].kind, N::ExprKind::Hash(_)));
+            assert!(matches!(foo.block, Some(N::CallBlock::Argument(_))));
+        }
+
+        #[test]
+        fn yield_and_super_commands() {
+            let statements = parse_statements(
+                "def f(a)
+  yield a, 1
+  super a
+end",
+            );
+            let N::ExprKind::Def(def) = &statements[0].kind else {
+                panic!()
+            };
+            let N::ExprKind::Yield(yield_expr) = &def.body.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, yield_expr.args.len());
+            let N::ExprKind::Super(super_expr) = &def.body.body[1].kind else {
+                panic!()
+            };
+            assert_eq!(1, super_expr.args.len());
+        }
+    }
+
+    mod operator_writes {
+        use super::*;
+
+        fn parse_write(text: &str) -> N::OperatorWrite {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::OperatorWrite(write) = program.statements.body[0].kind.clone() else {
+                panic!("{:?}", program.statements.body[0])
+            };
+            *write
+        }
+
+        #[test]
+        fn operators() {
+            let write = parse_write("x += 1");
+            assert!(matches!(
+                write.target.kind,
+                N::ExprKind::LocalVariableRead(_)
+            ));
+            assert_eq!(N::WriteOperator::Call("+".to_string()), write.operator);
+            assert_eq!(N::ExprKind::Integer(1), write.value.kind);
+            assert_eq!(N::WriteOperator::Or, parse_write("x ||= 1").operator);
+            assert_eq!(N::WriteOperator::And, parse_write("x &&= 1").operator);
+            assert_eq!(
+                N::WriteOperator::Call("<<".to_string()),
+                parse_write("x <<= 1").operator
+            );
+            assert_eq!(
+                N::WriteOperator::Call("**".to_string()),
+                parse_write("x **= 1").operator
+            );
+        }
+
+        #[test]
+        fn targets() {
+            assert!(matches!(
+                parse_write("$g -= 1").target.kind,
+                N::ExprKind::GlobalVariableRead(_)
+            ));
+            assert!(matches!(
+                parse_write("X *= 1").target.kind,
+                N::ExprKind::ConstantRead(_)
+            ));
+            let N::ExprKind::InstanceVariableRead(read) = parse_write("@a /= 1").target.kind else {
+                panic!()
+            };
+            assert_eq!("a", read.name);
+            let N::ExprKind::Call(index) = parse_write("a[i] ||= []").target.kind else {
+                panic!()
+            };
+            assert_eq!(("[]", 1), (index.name.as_str(), index.args.len()));
+            let N::ExprKind::Call(attribute) = parse_write("obj.x &&= y").target.kind else {
+                panic!()
+            };
+            assert_eq!("x", attribute.name);
+        }
+
+        #[test]
+        fn right_associative() {
+            let write = parse_write("a += b -= 1");
+            assert!(matches!(write.value.kind, N::ExprKind::OperatorWrite(_)));
+        }
+
+        #[test]
+        fn declares_local() {
+            let (program, diagnostics) = Parser::new(Lexer::new("x ||= 1\nx")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            assert!(matches!(
+                program.statements.body[1].kind,
+                N::ExprKind::LocalVariableRead(_)
+            ));
+        }
+
+        #[test]
+        fn attribute_write() {
+            let (program, diagnostics) = Parser::new(Lexer::new("obj.x = 1")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Call(call) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(("x=", 1), (call.name.as_str(), call.args.len()));
+        }
+
+        #[test]
+        fn setter_def() {
+            let (program, diagnostics) = Parser::new(Lexer::new("def x=(val)\nend")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Def(def) = &program.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!("x=", def.name);
+        }
+
+        #[test]
+        fn target_cannot_be_written() {
+            let (_, diagnostics) = Parser::new(Lexer::new("f(1) += 2")).parse();
+            let messages: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect();
+            assert_eq!(vec!["unexpected `+=`; target cannot be written"], messages);
+        }
+    }
+
+    mod multi_writes {
+        use super::*;
+
+        fn parse_write(text: &str) -> N::MultiWrite {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::MultiWrite(write) = program.statements.body[0].kind.clone() else {
+                panic!("{:?}", program.statements.body[0])
+            };
+            *write
+        }
+
+        fn local(target: &N::Target) -> &str {
+            match target {
+                N::Target::Single(N::Expr {
+                    kind: N::ExprKind::LocalVariableRead(read),
+                    ..
+                }) => &read.name,
+                other => panic!("{:?}", other),
+            }
+        }
+
+        #[test]
+        fn swap() {
+            let write = parse_write("a, b = b, a");
+            let names: Vec<_> = write.targets.lefts.iter().map(local).collect();
+            assert_eq!(vec!["a", "b"], names);
+            let N::ExprKind::Array(array) = write.value.kind else {
+                panic!()
+            };
+            assert_eq!(2, array.vals.len());
+            assert_eq!(Span::new(ByteIdx(7), ByteDifference(4)), write.value.span);
+        }
+
+        #[test]
+        fn splat() {
+            let write = parse_write("first, *rest, last = list");
+            assert_eq!("first", local(&write.targets.lefts[0]));
+            let splat = write.targets.rest.unwrap();
+            assert_eq!("rest", local(splat.target.as_ref().unwrap()));
+            assert_eq!("last", local(&write.targets.rights[0]));
+            assert!(matches!(write.value.kind, N::ExprKind::Call(_)));
+        }
+
+        #[test]
+        fn leading_and_anonymous_splat() {
+            let write = parse_write("*init, last = list");
+            assert!(write.targets.lefts.is_empty());
+            assert_eq!(1, write.targets.rights.len());
+            let write = parse_write("first, * = list");
+            assert_eq!(None, write.targets.rest.unwrap().target);
+        }
+
+        #[test]
+        fn nested() {
+            let write = parse_write("x, (y, *z) = 1, [2, 3]");
+            let N::Target::Nested(nested) = &write.targets.lefts[1] else {
+                panic!()
+            };
+            assert_eq!("y", local(&nested.lefts[0]));
+            assert!(nested.rest.is_some());
+            let write = parse_write("(a, b), c = list");
+            assert!(matches!(write.targets.lefts[0], N::Target::Nested(_)));
+        }
+
+        #[test]
+        fn trailing_comma() {
+            let write = parse_write("first, = list");
+            assert_eq!(1, write.targets.lefts.len());
+            assert!(write.targets.rest.is_none());
+        }
+
+        #[test]
+        fn other_targets() {
+            let write = parse_write("$g, @i, C, a[0], obj.x = list");
+            let targets: Vec<_> = write
+                .targets
+                .lefts
+                .iter()
+                .map(|target| match target {
+                    N::Target::Single(expr) => is_writable(expr),
+                    N::Target::Nested(_) => false,
+                })
+                .collect();
+            assert_eq!(vec![true; 5], targets);
+        }
+
+        #[test]
+        fn declares_locals() {
+            let (program, diagnostics) = Parser::new(Lexer::new("a, b = 1, 2\n[a, b]")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let N::ExprKind::Array(array) = &program.statements.body[1].kind else {
+                panic!()
+            };
+            assert!(
+                array
+                    .vals
+                    .iter()
+                    .all(|val| matches!(val.kind, N::ExprKind::LocalVariableRead(_)))
+            );
+        }
+
+        #[test]
+        fn errors() {
+            let messages = |text: &str| -> Vec<String> {
+                let (_, diagnostics) = Parser::new(Lexer::new(text)).parse();
+                diagnostics
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.message)
+                    .collect()
+            };
+            assert_eq!(vec!["target cannot be written"], messages("a, f(1) = list"));
+            assert_eq!(
+                vec!["multiple splats in one assignment"],
+                messages("*a, *b = list")
+            );
+        }
+    }
+
+    mod modifiers {
+        use super::*;
+
+        fn parse_stmt(text: &str) -> N::ExprKind {
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            program.statements.body.last().unwrap().kind.clone()
+        }
+
+        #[test]
+        fn if_modifier_wraps_assignment() {
+            let N::ExprKind::If(if_expr) = parse_stmt("x = 1 if cond") else {
+                panic!()
+            };
+            assert!(matches!(if_expr.predicate.kind, N::ExprKind::Call(_)));
+            assert!(matches!(
+                if_expr.statements.body[0].kind,
+                N::ExprKind::LocalVariableWrite(_)
+            ));
+            assert_eq!(N::Subsequent::None, if_expr.subsequent);
+        }
+
+        #[test]
+        fn unless_modifier_swaps_branches() {
+            let N::ExprKind::If(if_expr) = parse_stmt("ok = true\nreturn unless ok") else {
+                panic!()
+            };
+            assert!(if_expr.statements.body.is_empty());
+            let N::Subsequent::Else(else_clause) = if_expr.subsequent else {
+                panic!()
+            };
+            let N::ExprKind::Return(jump) = &else_clause.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(None, jump.value);
+        }
+
+        #[test]
+        fn loop_modifiers() {
+            let text = "retry_count = 0\nretry_count += 1 while pending";
+            let N::ExprKind::While(while_expr) = parse_stmt(text) else {
+                panic!()
+            };
+            assert!(matches!(
+                while_expr.statements.body[0].kind,
+                N::ExprKind::OperatorWrite(_)
+            ));
+            assert!(matches!(parse_stmt("x until done"), N::ExprKind::Until(_)));
+        }
+
+        #[test]
+        fn modifiers_chain_left_to_right() {
+            let N::ExprKind::While(while_expr) = parse_stmt("x if a while b") else {
+                panic!()
+            };
+            assert!(matches!(
+                while_expr.statements.body[0].kind,
+                N::ExprKind::If(_)
+            ));
+        }
+
+        #[test]
+        fn modifier_applies_to_whole_command() {
+            let N::ExprKind::If(if_expr) = parse_stmt("puts x, y if debug") else {
+                panic!()
+            };
+            let N::ExprKind::Call(call) = &if_expr.statements.body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, call.args.len());
+            let N::ExprKind::If(if_expr) = parse_stmt("return 1, 2 if done") else {
+                panic!()
+            };
+            assert!(matches!(
+                if_expr.statements.body[0].kind,
+                N::ExprKind::Return(_)
+            ));
+            let N::ExprKind::If(_) = parse_stmt("a, b = 1, 2 if c") else {
+                panic!()
+            };
+        }
+
+        #[test]
+        fn modifier_on_next_line_is_a_statement() {
+            let (program, diagnostics) = Parser::new(Lexer::new("x = 1\nif c then 2 end")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            assert_eq!(2, program.statements.body.len());
+        }
+
+        #[test]
+        fn unless_else() {
+            let N::ExprKind::If(if_expr) = parse_stmt("unless c\n  1\nelse\n  2\nend") else {
+                panic!()
+            };
+            assert_eq!(N::ExprKind::Integer(2), if_expr.statements.body[0].kind);
+            let N::Subsequent::Else(else_clause) = if_expr.subsequent else {
+                panic!()
+            };
+            assert_eq!(N::ExprKind::Integer(1), else_clause.statements.body[0].kind);
+        }
+
+        #[test]
+        fn ternary() {
+            let N::ExprKind::LocalVariableWrite(write) = parse_stmt("x = a == b ? 1 : 2") else {
+                panic!()
+            };
+            let N::ExprKind::If(if_expr) = write.val.kind else {
+                panic!()
+            };
+            assert!(matches!(if_expr.predicate.kind, N::ExprKind::Call(_)));
+            assert_eq!(N::ExprKind::Integer(1), if_expr.statements.body[0].kind);
+        }
+
+        #[test]
+        fn ternary_is_right_associative() {
+            let N::ExprKind::If(if_expr) = parse_stmt("a ? 1 : b ? 2 : 3") else {
+                panic!()
+            };
+            let N::Subsequent::Else(else_clause) = if_expr.subsequent else {
+                panic!()
+            };
+            assert!(matches!(
+                else_clause.statements.body[0].kind,
+                N::ExprKind::If(_)
+            ));
+        }
+
+        #[test]
+        fn ternary_across_lines() {
+            let N::ExprKind::If(_) = parse_stmt("a ?\n  1 :\n  2") else {
+                panic!()
+            };
+        }
+
+        #[test]
+        fn begin_end_while_is_do_while() {
+            let N::ExprKind::While(while_expr) = parse_stmt("begin\n  x\nend while c") else {
+                panic!()
+            };
+            assert!(while_expr.do_while);
+            let N::ExprKind::While(while_expr) = parse_stmt("x while c") else {
+                panic!()
+            };
+            assert!(!while_expr.do_while);
+            let N::ExprKind::Until(until_expr) = parse_stmt("begin\n  x\nend until c") else {
+                panic!()
+            };
+            assert!(until_expr.do_while);
+        }
+
+        #[test]
+        fn elsif_in_unless() {
+            let text = "unless a\n  1\nelsif b\n  2\nend\n3";
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+            assert_eq!(
+                vec!["unexpected `elsif`; expected `else` or `end`"],
+                messages
+            );
+            assert_eq!(2, program.statements.body.len());
+        }
+    }
+
+    mod operators {
+        use super::*;
+
+        /// Renders calls of operators with their operands in parens, to show how they group.
+        fn grouping(text: &str) -> String {
+            fn render(expr: &N::Expr) -> String {
+                match &expr.kind {
+                    N::ExprKind::Call(call) => match (&call.receiver, call.args.as_slice()) {
+                        (Some(receiver), [arg]) => {
+                            format!("({} {} {})", render(receiver), call.name, render(arg))
+                        }
+                        (Some(receiver), []) => format!("({} {})", call.name, render(receiver)),
+                        (None, []) => call.name.clone(),
+                        _ => panic!("{:?}", call),
+                    },
+                    N::ExprKind::Integer(n) => n.to_string(),
+                    N::ExprKind::And(and) => {
+                        format!("({} && {})", render(&and.lhs), render(&and.rhs))
+                    }
+                    other => panic!("{:?}", other),
+                }
+            }
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            render(&program.statements.body[0])
+        }
+
+        #[test]
+        fn comparison_and_equality() {
+            assert_eq!("((a <= b) && (c >= d))", grouping("a <= b && c >= d"));
+            assert_eq!("((a < b) != (c > d))", grouping("a < b != c > d"));
+            assert_eq!("((a + 1) <=> b)", grouping("a + 1 <=> b"));
+        }
+
+        #[test]
+        fn arithmetic() {
+            assert_eq!("(a + ((b * c) % d))", grouping("a + b * c % d"));
+            assert_eq!("(a * (b ** c))", grouping("a * b ** c"));
+        }
+
+        #[test]
+        fn exponent_is_right_associative() {
+            assert_eq!("(a ** (b ** c))", grouping("a ** b ** c"));
+        }
+
+        #[test]
+        fn unary_minus_binds_looser_than_exponent() {
+            assert_eq!("(-@ (2 ** 2))", grouping("-2 ** 2"));
+            assert_eq!("(a ** (-@ b))", grouping("a ** -b"));
+        }
+
+        #[test]
+        fn unary_binds_tighter_than_binary() {
+            assert_eq!("((! a) == b)", grouping("!a == b"));
+            assert_eq!("((~ a) ** 2)", grouping("~a ** 2"));
+            assert_eq!("((+@ a) + b)", grouping("+a + b"));
+            assert_eq!("(! (! a))", grouping("!!a"));
+        }
+
+        #[test]
+        fn bitwise() {
+            assert_eq!("((a | (b & c)) ^ d)", grouping("a | b & c ^ d"));
+            assert_eq!(
+                "(((a + 1) << 2) & (b >> 1))",
+                grouping("a + 1 << 2 & b >> 1")
+            );
+            assert_eq!("((a & b) == c)", grouping("a & b == c"));
+        }
+
+        #[test]
+        fn unary_command_args() {
+            let (program, diagnostics) = Parser::new(Lexer::new("f !a\ng +1\nh + 1")).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let args: Vec<_> = program
+                .statements
+                .body
+                .iter()
+                .map(|stmt| match &stmt.kind {
+                    N::ExprKind::Call(call) => call.args.len(),
+                    other => panic!("{:?}", other),
+                })
+                .collect();
+            assert_eq!(vec![1, 1, 1], args);
+            let N::ExprKind::Call(call) = &program.statements.body[2].kind else {
+                panic!()
+            };
+            assert_eq!("+", call.name);
+        }
+
+        #[test]
+        fn operator_method_names() {
+            let text = "def <=>(other)\nend\ndef **(other)\nend\ndef !()\nend";
+            let (program, diagnostics) = Parser::new(Lexer::new(text)).parse();
+            assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+            let names: Vec<_> = program
+                .statements
+                .body
+                .iter()
+                .map(|stmt| match &stmt.kind {
+                    N::ExprKind::Def(def) => def.name.clone(),
+                    other => panic!("{:?}", other),
+                })
+                .collect();
+            assert_eq!(vec!["<=>", "**", "!"], names);
+        }
+    }
+
+    mod diagnostics {
+        use super::*;
+
+        fn parse(text: &str) -> (N::Program, Vec<Diagnostic>) {
+            Parser::new(Lexer::new(text)).parse()
+        }
+
+        fn messages(diagnostics: &[Diagnostic]) -> Vec<&str> {
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect()
+        }
+
+        #[test]
+        fn one_per_statement() {
+            let (program, diagnostics) = parse("x = )\n1 = 2\ny = 3\nz = 1 +");
+            assert_eq!(
+                vec![
+                    "unexpected `)`; expected an expression",
+                    "unexpected `=`; target cannot be written",
+                    "unexpected end-of-input; expected an expression",
+                ],
+                messages(&diagnostics)
+            );
+            assert_eq!(4, program.statements.body.len());
+            assert_eq!(
+                Span::new(ByteIdx(4), ByteDifference(1)),
+                diagnostics[0].span
+            );
+        }
+
+        #[test]
+        fn in_is_not_an_operator() {
+            let (_, diagnostics) = parse("1 in 2\nx in\nf(in");
+            assert_eq!(
+                vec![
+                    "unexpected `in`; expected newline or `;`",
+                    "unexpected `in`; expected newline or `;`",
+                    "unexpected `in`; expected `)`",
+                ],
+                messages(&diagnostics)
+            );
+        }
+
+        #[test]
+        fn statements_need_separator() {
+            let (program, diagnostics) = parse("1 2\nx = 1 2\n3; 4");
+            assert_eq!(
+                vec![
+                    "unexpected integer; expected newline or `;`",
+                    "unexpected integer; expected newline or `;`",
+                ],
+                messages(&diagnostics)
+            );
+            assert_eq!(vec![LK::Newline, LK::Semicolon], diagnostics[0].expected);
+            assert_eq!(
+                Span::new(ByteIdx(2), ByteDifference(1)),
+                diagnostics[0].span
+            );
+            assert_eq!(4, program.statements.body.len());
+        }
+
+        #[test]
+        fn jumps_need_somewhere_to_go() {
+            let (_, diagnostics) = parse(
+                "break
+def f()
+  next
+end
+retry
+[1].each { break }
+redo while true
+begin
+  redo
+end until false
+begin
+rescue
+  retry
+end",
+            );
+            assert_eq!(
+                vec![
+                    "Invalid break",
+                    "Invalid next",
+                    "Invalid retry without rescue"
+                ],
+                messages(&diagnostics)
+            );
+            assert_eq!(
+                Span::new(ByteIdx(16), ByteDifference(4)),
+                diagnostics[1].span
+            );
+        }
+
+        #[test]
+        fn synchronizes_at_end() {
+            let (program, diagnostics) = parse(
+                "def f(a b)
+  x = ]
+  a
+end
+f(1)",
+            );
+            assert_eq!(
+                vec![
+                    "unexpected local variable or method; expected `,` or `)`",
+                    "unexpected `]`; expected an expression",
+                ],
+                messages(&diagnostics)
+            );
+            assert_eq!(vec![LK::Comma, LK::RightParen], diagnostics[0].expected);
+            let body = &program.statements.body;
+            assert_eq!(2, body.len());
+            let N::ExprKind::Def(def) = &body[0].kind else {
+                panic!()
+            };
+            assert_eq!(2, def.body.body.len());
+        }
+
+        #[test]
+        fn stray_end() {
+            let (program, diagnostics) = parse("1\nend\n2");
+            assert_eq!(vec!["unexpected `end`"], messages(&diagnostics));
+            assert_eq!(2, program.statements.body.len());
+        }
+
+        #[test]
+        fn missing_end() {
+            let (_, diagnostics) = parse("if true\n  1\n");
+            assert_eq!(
+                vec!["unexpected end-of-input; expected `end`"],
+                messages(&diagnostics)
+            );
+            assert_eq!(vec![LK::End], diagnostics[0].expected);
+        }
+
+        #[test]
+        fn bad_interpolation() {
+            let (program, diagnostics) = parse("\"#{ end }\"\n1");
+            assert_eq!(
+                vec!["unexpected `end`; expected `}`"],
+                messages(&diagnostics)
+            );
+            assert_eq!(2, program.statements.body.len());
+        }
+
+        #[test]
+        fn interpolation_open_at_eof() {
+            let texts = [
+                "\"#{",
+                "\"#{1",
+                ":\"#{",
+                "%W[#{",
+                "%W[a #{1",
+                "<<~EOS\n  #{\n",
+            ];
+            for text in texts {
+                let (_, diagnostics) = parse(text);
+                assert!(!diagnostics.is_empty(), "{:?}", text);
+            }
+        }
+
+        #[test]
+        fn float_exponent_without_digits() {
+            for text in ["1e-", "1.5e+", "1e+x"] {
+                let (program, diagnostics) = parse(text);
+                assert_eq!(
+                    vec!["expected digits in float exponent"],
+                    messages(&diagnostics)[..1]
+                );
+                assert_eq!(N::ExprKind::Missing, program.statements.body[0].kind);
+            }
+        }
+
+        #[test]
+        fn single_quote_string_open_at_eof() {
+            for (text, expected) in [("'", ""), ("'ab", "ab"), ("'a\\'", "a\\'"), ("'ab'", "ab")] {
+                let (program, _) = parse(text);
+                let expected = N::ExprKind::String(expected.to_string());
+                assert_eq!(expected, program.statements.body[0].kind);
+            }
+        }
+
+        #[test]
+        fn includes_lex_errors() {
+            let (_, diagnostics) = parse("x = )\ny = @\nz = 1_ +");
+            assert_eq!(
+                vec![
+                    "unexpected `)`; expected an expression",
+                    "'@' without identifiers is not allowed as an instance variable name",
+                    "trailing '_' in number",
+                    "unexpected end-of-input; expected an expression",
+                ],
+                messages(&diagnostics)
+            );
+        }
+
+        #[test]
+        fn semicolons_separate_statements() {
+            let (program, diagnostics) = parse("nil ;;;;; 1; 2");
+            assert!(diagnostics.is_empty());
+            assert_eq!(3, program.statements.body.len());
+        }
+
+        #[test]
+        fn render() {
+            let text = "x = 1\ndef (a)\nend";
+            let (_, diagnostics) = parse(text);
+            let expected = concat!(
+                "error: unexpected `(`; expected local variable or method\n",
+                " --> 2:5\n",
+                "  |\n",
+                "2 | def (a)\n",
+                "  |     ^\n",
+            );
+            assert_eq!(expected, diagnostics[0].render(text));
         }
     }
 }
@@ -11,8 +11,8 @@ use wat_defs::global::Global;
 use wat_defs::instr::UnfoldedInstr::{
     I32WrapI64, I64Add, I64Xor, Nop, RefAsNonNull, RefI31, Return,
 };
-use wat_defs::instr::{Instr, UnfoldedInstr};
-use wat_defs::module::{Module, TypeDef};
+use wat_defs::instr::{Catch, Instr, UnfoldedInstr};
+use wat_defs::module::{Module, Tag, TypeDef};
 use wat_defs::ty::{
     AbsHeapType, ArrayType, BlockType, CompType, Field, FieldType, Final, FuncType, GlobalType,
     HeapType, Mutable, Nullable, NumType, PackType, RefType, StorageType, StructType, SubType,
@@ -39,6 +39,7 @@ fn module_to_doc(module: &Module) -> Doc {
         types,
         globals,
         funcs,
+        tags,
         start_fn,
     } = module;
 
@@ -48,6 +49,7 @@ fn module_to_doc(module: &Module) -> Doc {
 
     let module_fields = [
         Some(module_type_defs_to_doc(types)),
+        Some(module_tags_to_doc(tags)),
         Some(module_functions_to_doc(funcs)),
         Some(module_globals_to_doc(globals)),
         start_fn,
@@ -94,6 +96,35 @@ fn global_to_doc(global: &Global) -> Doc {
         .group()
 }
 
+/// ```wat
+/// <tag>*
+/// ```
+fn module_tags_to_doc(tags: &[Tag]) -> Doc {
+    let tags = tags.iter().map(tag_to_doc);
+    intersperse(tags, hardline())
+}
+
+/// ```wat
+/// (tag <id> (param <val_type>)*)
+/// ```
+fn tag_to_doc(tag: &Tag) -> Doc {
+    let Tag { name, params } = tag;
+    let params = params.iter().map(|param| {
+        text("(param")
+            .append(line())
+            .append(val_type_to_doc(param))
+            .append(")")
+            .nest(INDENT)
+            .group()
+    });
+    text(format!("(tag ${}", name))
+        .append(line())
+        .append(intersperse(params, line()))
+        .append(")")
+        .nest(INDENT)
+        .group()
+}
+
 /// ```wat
 /// <type>*
 /// ```
@@ -283,6 +314,9 @@ fn unfolded_instr_to_doc(instr: &UnfoldedInstr) -> Doc {
         I64GtU => text("i64.gt_u"),
         I64Add => text("i64.add"),
         I64Sub => text("i64.sub"),
+        I64Mul => text("i64.mul"),
+        I64DivS => text("i64.div_s"),
+        I64RemS => text("i64.rem_s"),
         I64And => text("i64.and"),
         I64Or => text("i64.or"),
         I64Xor => text("i64.xor"),
@@ -291,15 +325,55 @@ fn unfolded_instr_to_doc(instr: &UnfoldedInstr) -> Doc {
         I64ShrU => text("i64.shr_u"),
         I64ExtendI32S => text("i64.extend_i32_s"),
         I64ExtendI32U => text("i64.extend_i32_u"),
+        F64Eq => text("f64.eq"),
+        F64Lt => text("f64.lt"),
+        F64Gt => text("f64.gt"),
+        F64Le => text("f64.le"),
+        F64Ge => text("f64.ge"),
+        F64Add => text("f64.add"),
+        F64Sub => text("f64.sub"),
+        F64Mul => text("f64.mul"),
+        F64Div => text("f64.div"),
+        F64Neg => text("f64.neg"),
+        F64Floor => text("f64.floor"),
+        F64ConvertI64S => text("f64.convert_i64_s"),
+        I64TruncF64S => text("i64.trunc_f64_s"),
+        I64ReinterpretF64 => text("i64.reinterpret_f64"),
+        F64ReinterpretI64 => text("f64.reinterpret_i64"),
+        F64Const { val } => text("f64.const").append(space()).append(f64_to_doc(*val)),
         Br { label } => text(format!("br ${}", label)),
-        BrIf { label } => text(format!("br.if ${}", label)),
+        BrIf { label } => text(format!("br_if ${}", label)),
+        BrTable { labels, default } => {
+            let labels: String = labels.iter().map(|label| format!(" ${}", label)).collect();
+            text(format!("br_table{} ${}", labels, default))
+        }
         Return => text("return"),
-        Block { label } => text(format!("block ${}", label)),
+        Block { label, block_type } => text(format!("block ${}", label)).append(match block_type {
+            Some(block_type) => text(" ").append(block_type_to_doc(block_type)),
+            None => nil(),
+        }),
         Loop { label, block_type } => text(format!("loop ${}", label,)).append(match block_type {
-            Some(block_type) => block_type_to_doc(block_type),
+            Some(block_type) => text(" ").append(block_type_to_doc(block_type)),
             None => nil(),
         }),
         If { .. } => unreachable!(),
+        TryTable {
+            block_type,
+            catches,
+        } => {
+            let block_type = match block_type {
+                Some(block_type) => text(" ").append(block_type_to_doc(block_type)),
+                None => nil(),
+            };
+            let catches = catches.iter().map(|catch| match catch {
+                Catch::Catch { tag, label } => text(format!(" (catch ${} ${})", tag, label)),
+                Catch::CatchAll { label } => text(format!(" (catch_all ${})", label)),
+            });
+            text("try_table")
+                .append(block_type)
+                .append(RcDoc::concat(catches))
+        }
+        Throw { tag } => text(format!("throw ${}", tag)),
         RefNull { ty } => text("ref.null ").append(heap_type_to_doc(ty)),
         RefFunc { name } => text(format!("ref.func ${}", name)),
         RefI31 => text("ref.i31"),
@@ -321,7 +395,10 @@ fn unfolded_instr_to_doc(instr: &UnfoldedInstr) -> Doc {
         ArrayGetU { ty } => text(format!("array.get_u ${}", ty)),
         ArraySet { ty } => text(format!("array.set ${}", ty)),
         ArrayLen => text("array.len"),
-        ArrayCopy { type_idx_into, type_idx_from } => text(format!("array.copy ${} ${}", type_idx_into, type_idx_from)),
+        ArrayCopy {
+            type_idx_into,
+            type_idx_from,
+        } => text(format!("array.copy ${} ${}", type_idx_into, type_idx_from)),
         StructNew { ty } => text(format!("struct.new ${}", ty)),
         StructGet { ty, field } => text(format!("struct.get ${} ${}", ty, field)),
         StructSet { ty, field } => text(format!("struct.set ${} ${}", ty, field)),
@@ -535,8 +612,17 @@ fn num_type_to_doc(ty: &NumType) -> Doc {
     text(match ty {
         NumType::I32 => "i32",
         NumType::I64 => "i64",
-        // NumType::F32 => "f32",
-        // NumType::F64 => "f64",
+        NumType::F64 => "f64",
+    })
+}
+
+/// Rust's `Debug` output for finite floats is valid .wat, and round-trips exactly.
+fn f64_to_doc(val: f64) -> Doc {
+    text(match val {
+        val if val.is_nan() => "nan".to_string(),
+        f64::INFINITY => "inf".to_string(),
+        f64::NEG_INFINITY => "-inf".to_string(),
+        val => format!("{:?}", val),
     })
 }
 
@@ -1,11 +1,16 @@
 use crate::compiler::RUBY_TOP_LEVEL_FUNCTION_NAME;
 use crate::corelib::add_core_items;
-use crate::lexeme::LexemeKind;
+use crate::corelib::global::{GET_SOURCE_POSITION, INSPECT};
+use crate::corelib::tag::DESCRIBE_EXCEPTION;
+use crate::lexeme::{ByteIdx, LexemeKind, LineIndex};
 use crate::lexer::Lexer;
-use crate::parser::Parser;
+use crate::parser::{Diagnostic, Parser};
 use crate::unitype::{Unitype, WasmtimeRefEq};
 use crate::{CompileCtx, compiler, print_wat, run};
-use wasmtime::{Config, Engine, Instance, Module, Store};
+use wasmtime::{
+    AnyRef, Config, Engine, EqRef, Instance, Linker, Module, Rooted, Store, ThrownException,
+    TypedFunc, Val,
+};
 use wat_defs::module;
 
 pub fn lex(text: &str) -> String {
@@ -15,23 +20,54 @@ pub fn lex(text: &str) -> String {
         let lexeme = lexer.next();
         res.push_str(&format!("{:?}\n", lexeme));
         if let LexemeKind::Eof = lexeme.kind {
+            for error in lexer.errors() {
+                res.push('\n');
+                res.push_str(&error.render(text));
+            }
             return res;
         }
     }
 }
 
+/// Renders each diagnostic, separated by blank lines.
+pub fn render_diagnostics(text: &str, diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn run_text(text: String) -> String {
-    run_wat(compile_ctx_to_wat(&text_to_compile_ctx(text)))
+    run_source("-e", text)
+}
+
+/// Compiles and runs `text`, the contents of `file_name`.
+/// Runtime errors are reported at `file_name:line:col`.
+/// Returns the rendered syntax errors instead, if `text` has any.
+pub fn run_source(file_name: &str, text: String) -> String {
+    try_run_source(file_name, text).unwrap_or_else(|diagnostics| diagnostics)
+}
+
+/// Like `run_source`, but returns the rendered syntax errors as an `Err`.
+pub fn try_run_source(file_name: &str, text: String) -> Result<String, String> {
+    let line_index = LineIndex::new(&text);
+    let wat = compile_ctx_to_wat(&text_to_compile_ctx(text.clone())?);
+    Ok(run_wat_in_source(wat, Some((file_name, &line_index))))
 }
 
-pub fn text_to_compile_ctx(text: String) -> CompileCtx {
+/// Returns every syntax error in `text` rendered, if it has any.
+pub fn text_to_compile_ctx(text: String) -> Result<CompileCtx, String> {
     let parser = Parser::new(Lexer::new(&text));
-    let program = parser.parse();
+    let (program, diagnostics) = parser.parse();
+    if !diagnostics.is_empty() {
+        return Err(render_diagnostics(&text, &diagnostics));
+    }
     let module = module::Module::new();
     let mut ctx = CompileCtx::new(module);
     compiler::compile(&mut ctx, &program);
     add_core_items(&mut ctx);
-    ctx
+    Ok(ctx)
 }
 
 /// Writes out `module` as a .wat file, includes the corelib definitions,
@@ -41,21 +77,40 @@ pub fn compile_ctx_to_wat(ctx: &CompileCtx) -> String {
 }
 
 pub fn run_wat(wat: String) -> String {
+    run_wat_in_source(wat, None)
+}
+
+/// Runs `wat`, which was compiled from the file `source`, if given.
+fn run_wat_in_source(wat: String, source: Option<(&str, &LineIndex)>) -> String {
     let mut config = Config::new();
-    config.wasm_function_references(true).wasm_gc(true);
+    config
+        .wasm_function_references(true)
+        .wasm_gc(true)
+        .wasm_exceptions(true);
     let engine = Engine::new(&config).unwrap();
     let module = Module::new(&engine, wat).unwrap();
-    // let mut linker = Linker::new(&engine);
+    // The JS-interop imports are only used by exported defs,
+    //  which aren't called when running natively.
+    let mut linker = Linker::new(&engine);
+    linker.define_unknown_imports_as_traps(&module).unwrap();
     let mut store = Store::new(&engine, ());
-    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let instance = linker.instantiate(&mut store, &module).unwrap();
 
     if let Ok(top_level) =
         instance.get_typed_func::<(), WasmtimeRefEq>(&mut store, RUBY_TOP_LEVEL_FUNCTION_NAME)
     {
         // Ruby main is `() -> (ref eq)`
-        let res = top_level.call(&mut store, ()).unwrap();
-
-        Unitype::parse_ref_eq(res, &mut store).to_pretty()
+        // Showing the result may call its `inspect` method, which can raise or trap too.
+        let res = top_level.call(&mut store, ()).and_then(|res| {
+            Unitype::parse_ref_eq(res, &inspect(&instance, &mut store), &mut store)
+        });
+        match res {
+            Ok(res) => res.to_pretty(),
+            Err(err) if err.is::<ThrownException>() => {
+                exception_message(&instance, &mut store, source)
+            }
+            Err(err) => panic!("{}", trap_message(&instance, &mut store, source, err)),
+        }
     } else if let Ok(top_level) =
         instance.get_typed_func::<(), i32>(&mut store, RUBY_TOP_LEVEL_FUNCTION_NAME)
     {
@@ -70,3 +125,65 @@ pub fn run_wat(wat: String) -> String {
         panic!("Can't find RUBY_TOP_LEVEL_FUNCTION_NAME");
     }
 }
+
+/// Describes an uncaught Ruby exception, as `<location>: <message> (<class>)`.
+fn exception_message(
+    instance: &Instance,
+    store: &mut Store<()>,
+    source: Option<(&str, &LineIndex)>,
+) -> String {
+    // Describing the exception may call Ruby methods, which move the source position.
+    let location = source_location(instance, store, source);
+    let exception = store
+        .take_pending_exception()
+        .expect("Thrown exception should be pending");
+    // The `$ruby_exception` tag's only field is the `$exception` object.
+    let Val::AnyRef(Some(exception)) = exception.field(&mut *store, 0).unwrap() else {
+        panic!("Expected a Ruby exception");
+    };
+    let describe_exception = instance
+        .get_typed_func::<Rooted<AnyRef>, WasmtimeRefEq>(&mut *store, DESCRIBE_EXCEPTION)
+        .unwrap();
+    let description = describe_exception.call(&mut *store, exception).unwrap();
+    let description = Unitype::parse_ref_eq(description, &inspect(instance, store), store);
+    let Ok(Unitype::String(description)) = description else {
+        panic!("Expected exception description to be a string");
+    };
+    match location {
+        Some(location) => format!("{}: {}", location, description),
+        None => description,
+    }
+}
+
+/// The exported corelib `$inspect`, which describes a value as a string.
+fn inspect(instance: &Instance, store: &mut Store<()>) -> TypedFunc<Rooted<EqRef>, Rooted<AnyRef>> {
+    instance.get_typed_func(store, INSPECT).unwrap()
+}
+
+/// The source location of the last call, if known.
+fn source_location(
+    instance: &Instance,
+    store: &mut Store<()>,
+    source: Option<(&str, &LineIndex)>,
+) -> Option<String> {
+    let (file_name, line_index) = source?;
+    let position = instance
+        .get_typed_func::<(), i32>(&mut *store, GET_SOURCE_POSITION)
+        .and_then(|get_position| get_position.call(&mut *store, ()))
+        .ok()
+        .and_then(|position| usize::try_from(position).ok())?;
+    Some(line_index.location(file_name, ByteIdx(position)))
+}
+
+/// Describes a trap, starting with the source location of the call that caused it, if known.
+fn trap_message(
+    instance: &Instance,
+    store: &mut Store<()>,
+    source: Option<(&str, &LineIndex)>,
+    err: wasmtime::Error,
+) -> String {
+    match source_location(instance, store, source) {
+        Some(location) => format!("{}: {}\n{:?}", location, err.root_cause(), err),
+        None => format!("{:?}", err),
+    }
+}
@@ -1,17 +1,18 @@
 //! Ruby values lowered to a union of all possible values.
 //! Converted into Wasm `(ref eq)` subtypes
 
+use crate::lexer::OPERATOR_METHOD_NAMES;
 use pretty::RcDoc;
 use serde::Serialize;
 use wasmtime as WT;
-use wasmtime::{AnyRef, AsContextMut, ExternRef, OwnedRooted, RootScope, Rooted, RootedGcRef};
+use wasmtime::{AnyRef, ArrayRef, AsContextMut, EqRef, Rooted, RootedGcRef, StructRef};
 use wat_defs::ty::RefType;
 use wat_macro::wat;
 
 /// `wasmtime`'s Rust-side representation of a Wasm `(ref eq)` value
 pub type WasmtimeRefEq = Rooted<AnyRef>;
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub enum Unitype {
     True,
     False,
@@ -19,8 +20,13 @@ pub enum Unitype {
     // Internally, stored as the actual number, without the marker bit
     Fixnum(Fixnum),
     HeapNum(i64),
+    /// As `inspect` formats it, so that Floats have one formatter, in the corelib.
+    Float(String),
     String(String),
+    Symbol(String),
     Array(Vec<Unitype>),
+    /// Any other object, as its `inspect` describes it.
+    Object(String),
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Serialize)]
@@ -37,6 +43,7 @@ impl Unitype {
     // pub const REF_I31: RefType = RefType::new_abstract(AbsHeapType::I31, Nullability::NonNullable);
 
     pub const STRING_TYPE_IDENTIFIER: &'static str = "str";
+    pub const SYMBOL_TYPE_IDENTIFIER: &'static str = "sym";
     // pub const STRING_TYPE: CompType = CompType::Array(ArrayType {
     //     field: FieldType {
     //         mutability: Mutability::Const,
@@ -105,68 +112,96 @@ impl Unitype {
 
     /// Parse a Wasm `(ref eq)` value into a `UnitypeValue`.
     /// Used only for displaying `wasmtime` output.
+    /// Objects are described by `inspect`, the exported corelib `$inspect`,
+    ///     which fails if it raises.
     pub fn parse_ref_eq(
         ref_eq: impl RootedGcRef<AnyRef>,
+        inspect: &WT::TypedFunc<Rooted<EqRef>, Rooted<AnyRef>>,
         mut store: &mut impl AsContextMut,
-    ) -> Self {
+    ) -> WT::Result<Self> {
         let is_i31 = ref_eq.is_i31(&store).unwrap();
         if is_i31 {
             let value = ref_eq.unwrap_i31(&store).unwrap().get_u32() as i32;
-            Self::from_i31_bits(value)
-        } else {
-            match ref_eq {
-                arr if ref_eq.is_array(&store).unwrap() => {
-                    // TODO -- assuming all arrays are string arrays
-                    let arr = arr.as_array(&store).unwrap().unwrap();
-                    let is_string =
-                        arr.ty(&store)
-                            .unwrap()
-                            .field_type()
-                            .matches(&wasmtime::FieldType::new(
-                                wasmtime::Mutability::Const,
-                                wasmtime::StorageType::I8,
-                            ));
-                    if is_string {
-                        let bytes: Vec<u8> = arr
-                            .elems(&mut store)
-                            .unwrap()
-                            .map(|byte| {
-                                // `arr.elems` zero-extends `i8` and `i16` into `Val::I32`
-                                let byte = byte.i32().unwrap();
-                                byte as u8
-                            })
-                            .collect();
-                        let string = String::from_utf8(bytes).unwrap();
-                        Unitype::String(string)
-                    } else {
-                        let mut unitype_elems = vec![];
-                        let len = arr.len(&store).unwrap();
-                        for idx in 0..len {
-                            let val = arr.get(&mut store, idx).unwrap();
-                            let val = val
-                                .unwrap_any_ref()
-                                .unwrap()
-                                .to_owned_rooted(&mut store)
-                                .unwrap();
-                            let res = Self::parse_ref_eq(val, store);
-                            unitype_elems.push(res);
-                        }
-                        Unitype::Array(unitype_elems)
-                    }
-                }
-                strukt if strukt.is_struct(&store).unwrap() => {
-                    let strukt = strukt.as_struct(&store).unwrap().unwrap();
-                    if let Some(n) = strukt.field(store, 0).ok().and_then(|f| f.i64()) {
-                        Unitype::HeapNum(n)
-                    } else {
-                        todo!("Unknown struct type {:?}", strukt)
+            return Ok(Self::from_i31_bits(value));
+        }
+        let res = match ref_eq {
+            arr if ref_eq.is_array(&store).unwrap() => {
+                let arr = arr.as_array(&store).unwrap().unwrap();
+                let is_string =
+                    arr.ty(&store)
+                        .unwrap()
+                        .field_type()
+                        .matches(&wasmtime::FieldType::new(
+                            wasmtime::Mutability::Var,
+                            wasmtime::StorageType::I8,
+                        ));
+                if is_string {
+                    Unitype::String(Self::parse_string(arr, store))
+                } else {
+                    let mut unitype_elems = vec![];
+                    let len = arr.len(&store).unwrap();
+                    for idx in 0..len {
+                        let val = arr.get(&mut store, idx).unwrap();
+                        // Keep `val` LIFO-rooted: wasmtime can't downcast owned roots.
+                        let val = *val.unwrap_any_ref().unwrap();
+                        let res = Self::parse_ref_eq(val, inspect, store)?;
+                        unitype_elems.push(res);
                     }
+                    Unitype::Array(unitype_elems)
                 }
-                other => {
-                    panic!("Unknown type: {:?}", other.ty(&store))
+            }
+            strukt if strukt.is_struct(&store).unwrap() => {
+                let strukt = strukt.as_struct(&store).unwrap().unwrap();
+                let field = strukt.field(&mut store, 0).unwrap();
+                if let Some(n) = field.i64() {
+                    Unitype::HeapNum(n)
+                } else if field.f64().is_some() {
+                    Unitype::Float(Self::inspect(strukt, inspect, store)?)
+                } else if let Some(name) = field
+                    .any_ref()
+                    .flatten()
+                    .copied()
+                    .filter(|name| name.is_array(&store).unwrap())
+                {
+                    // `$sym` is the only struct whose first field is a `$str`
+                    let name = name.as_array(&store).unwrap().unwrap();
+                    Unitype::Symbol(Self::parse_string(name, store))
+                } else {
+                    // Every other struct is an `$obj`, whose first field is its class.
+                    Unitype::Object(Self::inspect(strukt, inspect, store)?)
                 }
             }
-        }
+            other => {
+                panic!("Unknown type: {:?}", other.ty(&store))
+            }
+        };
+        Ok(res)
+    }
+
+    /// What `inspect` gives for the struct `strukt`.
+    fn inspect(
+        strukt: Rooted<StructRef>,
+        inspect: &WT::TypedFunc<Rooted<EqRef>, Rooted<AnyRef>>,
+        store: &mut impl AsContextMut,
+    ) -> WT::Result<String> {
+        let val = strukt.to_anyref().unwrap_eqref(&store).unwrap();
+        let description = inspect.call(&mut *store, val)?;
+        let description = description.as_array(&store).unwrap().unwrap();
+        Ok(Self::parse_string(description, store))
+    }
+
+    /// The contents of a `$str`.
+    fn parse_string(string: Rooted<ArrayRef>, mut store: &mut impl AsContextMut) -> String {
+        let bytes: Vec<u8> = string
+            .elems(&mut store)
+            .unwrap()
+            .map(|byte| {
+                // `arr.elems` zero-extends `i8` and `i16` into `Val::I32`
+                let byte = byte.i32().unwrap();
+                byte as u8
+            })
+            .collect();
+        String::from_utf8(bytes).unwrap()
     }
 
     pub fn to_i31_bits(self) -> i32 {
@@ -175,7 +210,12 @@ impl Unitype {
             Unitype::False => Self::FALSE_BIT_PATTERN,
             Unitype::Nil => Self::NIL_BIT_PATTERN,
             Unitype::Fixnum(Fixnum(val)) => val | Self::FIXNUM_MARKER,
-            Unitype::HeapNum(_) | Unitype::String(_) | Unitype::Array(_) => {
+            Unitype::HeapNum(_)
+            | Unitype::Float(_)
+            | Unitype::String(_)
+            | Unitype::Symbol(_)
+            | Unitype::Array(_)
+            | Unitype::Object(_) => {
                 panic!("Not an i31 value: {:?}", self)
             }
         }
@@ -210,7 +250,10 @@ impl Unitype {
             Unitype::Nil => RcDoc::text("nil".to_owned()),
             Unitype::Fixnum(Fixnum(n)) => RcDoc::text(format!("{}", n)),
             Unitype::HeapNum(n) => RcDoc::text(format!("{}", n)),
+            Unitype::Float(description) => RcDoc::text(description),
             Unitype::String(s) => RcDoc::text(format!("\"{}\"", s)),
+            Unitype::Symbol(s) if is_plain_symbol_name(&s) => RcDoc::text(format!(":{}", s)),
+            Unitype::Symbol(s) => RcDoc::text(format!(":\"{}\"", s)),
             Unitype::Array(vals) => RcDoc::text("[")
                 .append(RcDoc::intersperse(
                     vals.into_iter().map(Self::module_to_doc),
@@ -219,10 +262,24 @@ impl Unitype {
                 .append(RcDoc::text("]"))
                 .nest(2)
                 .group(),
+            Unitype::Object(description) => RcDoc::text(description),
         }
     }
 }
 
+/// Can the symbol `:<name>` be written without quotes?
+fn is_plain_symbol_name(name: &str) -> bool {
+    let ident = name.trim_start_matches(['@', '$']);
+    let ident = ident.strip_suffix(['?', '!', '=']).unwrap_or(ident);
+    let is_ident = ident
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_')
+        && ident.chars().all(|c| c.is_alphanumeric() || c == '_');
+    let is_operator = OPERATOR_METHOD_NAMES.contains(&name);
+    is_ident || is_operator
+}
+
 #[cfg(test)]
 mod tests {
     use crate::unitype::{Fixnum, Unitype};
@@ -38,3 +38,43 @@ fn messier() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn shovel() {
+    // Like `push`, `<<` gives a new array rather than changing the receiver.
+    let text = "
+a = [1]
+b = a << 2
+[a, b, [] << 1 << 2]
+";
+    let expected = expect!["[[1], [1, 2], [1, 2]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn push_several() {
+    let text = "a = [1]\n[a.push, a.push(2, 3)]";
+    let expected = expect!["[[1], [1, 2, 3]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn index_with_several_args() {
+    // Other than one index, `[]` and `[]=` call the receiver's own methods, which Arrays don't have yet.
+    let text = "
+def message()
+  yield
+rescue NoMethodError => e
+  e.message
+end
+a = [1]
+[message { a[0, 1] = 2 }, message { a[0, 1] }]
+";
+    let expected = expect![[r#"
+        ["undefined method '[]=' for an instance of Array",
+          "undefined method '[]' for an instance of Array"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,252 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn yield_to_block() {
+    let text = "
+def twice(x)
+  yield(x)
+  yield(x + 1)
+end
+total = 0
+twice(5) { |n| total = total + n }
+\"#{total}\"
+";
+    let expected = expect![[r#""11""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn do_end_block() {
+    let text = "
+def once(x)
+  yield(x)
+end
+once(1) do |n|
+  \"got #{n}\"
+end
+";
+    let expected = expect![[r#""got 1""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_given() {
+    let text = "
+def given()
+  block_given?()
+end
+\"#{given()} #{given() { 1 }}\"
+";
+    let expected = expect![[r#""false true""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_given_with_args() {
+    let text = "block_given?(1)";
+    let expected =
+        expect!["-e:1:1: wrong number of arguments (given 1, expected 0) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_param() {
+    let text = "
+def keep(&blk)
+  blk
+end
+add = keep { |a, b| a + b }
+\"#{add.call(1, 2)} #{add.class()}\"
+";
+    let expected = expect![[r#""3 Proc""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_arg() {
+    let text = "
+def each_of_two()
+  yield(1)
+  yield(2)
+end
+def run(&blk)
+  each_of_two(&blk)
+end
+sum = 0
+run { |i| sum = sum + i }
+\"#{sum}\"
+";
+    let expected = expect![[r#""3""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn captures_by_reference() {
+    let text = "
+def call_now()
+  yield()
+end
+x = 1
+call_now() { x = 2 }
+def keep(&blk)
+  blk
+end
+inc = keep { x = x + 10 }
+inc.call()
+inc.call()
+\"#{x}\"
+";
+    let expected = expect![[r#""22""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn nested_blocks() {
+    let text = "
+def once(x)
+  yield(x)
+end
+x = 0
+once(1) do |i|
+  y = 10
+  once(2) { |j| x = i + j + y }
+end
+\"#{x}\"
+";
+    let expected = expect![[r#""13""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_self() {
+    let text = "
+class Greeter
+  def greet_all()
+    each_name { |name| greet(name) }
+  end
+  def each_name()
+    yield(\"world\")
+  end
+  def greet(name)
+    \"hello #{name}\"
+  end
+end
+Greeter.new().greet_all()
+";
+    let expected = expect![[r#""hello world""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn yield_in_block() {
+    let text = "
+def inner()
+  yield(1)
+end
+def outer(x)
+  inner() { |y| yield(x + y) }
+end
+outer(1) { |z| \"#{z}\" }
+";
+    let expected = expect![[r#""2""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_arity() {
+    let text = "
+def yield_one(x)
+  yield(x)
+end
+\"#{yield_one(1) { |a, b| \"#{a}|#{b}\" }} #{yield_one([2, 3]) { |a, b| \"#{a}|#{b}\" }}\"
+";
+    let expected = expect![[r#""1| 2|3""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn yield_without_block() {
+    let text = "
+def twice()
+  yield()
+  yield()
+end
+begin
+  twice
+rescue LocalJumpError => e
+  e.message()
+end
+";
+    let expected = expect![[r#""no block given (yield)""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rest_and_optional_block_params() {
+    let text = "
+[
+  proc { |a, *r| [a, r] }.call(1, 2, 3),
+  proc { |a, *r| [a, r] }.call([1, 2, 3]),
+  proc { |a, b = 5| [a, b] }.call(1),
+  proc { |a, b = 5| [a, b] }.call(1, 2),
+  proc { |*r| r }.call([1, 2]),
+  proc { |a = 5| a }.call([1, 2]),
+]
+";
+    let expected = expect!["[[1, [2, 3]], [1, [2, 3]], [1, 5], [1, 2], [[1, 2]], [1, 2]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn destructuring_block_params() {
+    let text = "
+a = 10
+[
+  proc { |(a, b)| [a, b] }.call([1, 2]),
+  proc { |(x, (y, *z)), w| [x, y, z, w] }.call([1, [2, 3]], 4),
+  proc { |(x, y), z| [x, y, z] }.call([[1, 2], 3]),
+  a,
+]
+";
+    let expected = expect!["[[1, 2], [1, 2, [3], 4], [1, 2, 3], 10]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_param_of_block() {
+    let text = "
+pr = proc { |x, &b| b.call(x) }
+[pr.call(2) { |y| y * 10 }, proc { |&b| b }.call]
+";
+    let expected = expect!["[20, nil]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_param_defaults() {
+    let text = "
+n = 5
+add = proc { |a, b = n + a, k: b| [a, b, k] }
+n = 7
+[add.call(1), add.call(1, 2), add.call(1, k: 3)]
+";
+    let expected = expect!["[[1, 8, 8], [1, 2, 2], [1, 8, 3]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,191 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn integer_whens() {
+    let text = "
+def size(n)
+  case n
+  when 0 then \"none\"
+  when 1, 2 then \"few\"
+  when 3
+    \"three\"
+  else
+    \"many\"
+  end
+end
+[size(0), size(2), size(3), size(40)]
+";
+    let expected = expect![[r#"["none", "few", "three", "many"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn first_matching_when_wins() {
+    let text = "
+case 1
+when 1 then \"first\"
+when 1 then \"second\"
+end
+";
+    let expected = expect![[r#""first""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn no_match_without_else_is_nil() {
+    let text = "
+case 5
+when 1 then \"one\"
+end
+";
+    let expected = expect!["nil"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn integer_whens_match_floats() {
+    let text = "
+def size(n)
+  case n
+  when 1, 2 then \"small\"
+  else \"other\"
+  end
+end
+[size(2.0), size(2.5), size(\"2\")]
+";
+    let expected = expect![[r#"["small", "other", "other"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn class_whens() {
+    let text = "
+class Point
+end
+def kind(x)
+  case x
+  when Integer then \"integer\"
+  when String then \"string\"
+  when Point then \"point\"
+  else \"other\"
+  end
+end
+[kind(1), kind(\"a\"), kind(Point.new()), kind(:a), kind(nil)]
+";
+    let expected = expect![[r#"["integer", "string", "point", "other", "other"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn range_whens() {
+    let text = "
+def grade(score)
+  case score
+  when 90..100 then \"A\"
+  when 80...90 then \"B\"
+  else \"C\"
+  end
+end
+[grade(100), grade(90), grade(89.5), grade(80), grade(79)]
+";
+    let expected = expect![[r#"["A", "A", "B", "B", "C"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn string_whens() {
+    let text = "
+case \"b\"
+when \"a\" then 1
+when \"b\" then 2
+end
+";
+    let expected = expect!["2"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn no_subject() {
+    let text = "
+x = 7
+case
+when x < 5 then \"low\"
+when x < 10 then \"mid\"
+else \"high\"
+end
+";
+    let expected = expect![[r#""mid""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_case_eq() {
+    let text = "
+class Even
+  def ===(x)
+    x == 2 || x == 4
+  end
+end
+def parity(x)
+  case x
+  when Even.new() then \"even\"
+  else \"odd\"
+  end
+end
+[parity(4), parity(3)]
+";
+    let expected = expect![[r#"["even", "odd"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn subject_is_evaluated_once() {
+    let text = "
+$calls = 0
+def subject()
+  $calls = $calls + 1
+  3
+end
+case subject()
+when 1 then 1
+when 2 then 2
+when Integer then 3
+end
+$calls
+";
+    let expected = expect!["1"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn case_eq() {
+    let text = "
+r = 1...3
+[Integer === 1, String === 1, r === 2, r === 3, 1 === 1]
+";
+    let expected = expect!["[true, false, true, false, true]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn range_methods() {
+    let text = "
+r = 1...5
+[r.begin(), r.end(), r.exclude_end?(), r.to_s()]
+";
+    let expected = expect![[r#"[1, 5, true, "1...5"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,408 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn instance_methods() {
+    let text = "
+class Point
+  def sum(x, y)
+    x + y
+  end
+end
+Point.new().sum(1, 2)
+";
+    let expected = expect![["3"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn superclass() {
+    let text = "
+class Animal
+end
+class Dog < Animal
+end
+[Dog.name(), Dog.superclass().name(), Animal.superclass().name(), BasicObject.superclass()]
+";
+    let expected = expect![[r#"["Dog", "Animal", "Object", nil]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn reopen() {
+    let text = "
+class Dog
+  def speak()
+    \"woof\"
+  end
+end
+class Dog
+  def legs()
+    4
+  end
+  def speak()
+    \"woof woof\"
+  end
+end
+d = Dog.new()
+[d.speak(), d.legs()]
+";
+    let expected = expect![[r#"["woof woof", 4]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn reopen_object() {
+    let text = "
+class Object
+  def greet()
+    \"hi\"
+  end
+end
+[greet(), Object.new().greet()]
+";
+    let expected = expect![[r#"["hi", "hi"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn class_body_value() {
+    let text = "
+class Empty
+end
+class Full
+  1
+  2
+end
+[Empty.name(), Full.name()]
+";
+    let expected = expect![[r#"["Empty", "Full"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn superclass_mismatch() {
+    let text = "
+class Dog
+end
+class Dog < Module
+end
+";
+    let expected = expect!["-e:4:1: superclass mismatch for class Dog (TypeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn bad_superclasses() {
+    let text = "
+def error()
+  yield
+rescue NameError, TypeError, NotImplementedError => e
+  \"#{e.class}: #{e.message}\"
+end
+module M
+end
+[error { class A < Missing; end },
+ error { class B < M; end },
+ error { x = Object; class C < x; end }]
+";
+    let expected = expect![[r#"
+        ["NameError: uninitialized constant Missing",
+          "TypeError: superclass must be a Class (M given)",
+          "NotImplementedError: superclass of `C` that isn't a constant isn't supported"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn new_calls_initialize() {
+    let text = "
+class A
+  def initialize(x)
+    @x = x
+  end
+
+  def get()
+    @x
+  end
+end
+class B < A
+  def initialize(x, y)
+    super(x + y)
+  end
+end
+[A.new(4).get, B.new(1, 2).get]
+";
+    let expected = expect!["[4, 3]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn inherited_methods() {
+    let text = "
+class Animal
+  def legs()
+    4
+  end
+end
+class Dog < Animal
+end
+class Puppy < Dog
+end
+[Puppy.new().legs(), Puppy.new().class().name(), Puppy.class().name()]
+";
+    let expected = expect![[r#"[4, "Puppy", "Class"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_with_args() {
+    let text = "
+class Animal
+  def speak(sound, times)
+    \"#{sound} x#{times}\"
+  end
+end
+class Dog < Animal
+  def speak(sound, times)
+    super(\"woof\", times + 1)
+  end
+end
+Dog.new().speak(\"hi\", 1)
+";
+    let expected = expect![[r#""woof x2""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_without_args() {
+    let text = "
+class Animal
+  def speak(sound)
+    \"animal says #{sound}\"
+  end
+end
+class Dog < Animal
+  def speak(sound)
+    sound = \"#{sound}!\"
+    super
+  end
+end
+class Puppy < Dog
+  def speak(sound)
+    \"#{super} (quietly)\"
+  end
+end
+Puppy.new().speak(\"woof\")
+";
+    let expected = expect![[r#""animal says woof! (quietly)""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_skips_classes_without_method() {
+    let text = "
+class Animal
+  def name()
+    \"animal\"
+  end
+end
+class Dog < Animal
+end
+class Puppy < Dog
+  def name()
+    \"puppy of #{super()}\"
+  end
+end
+Puppy.new().name()
+";
+    let expected = expect![[r#""puppy of animal""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_without_superclass_method() {
+    let text = "
+class Dog
+  def speak()
+    super
+  end
+end
+Dog.new.speak
+";
+    let expected = expect![
+        "-e:4:5: super: no superclass method 'speak' for an instance of Dog (NoMethodError)"
+    ];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_outside_method() {
+    let expected = expect!["-e:2:1: super called outside of method (RuntimeError)"];
+    let actual = run::run_text("x = 1\nsuper".to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_forwards_rest_and_keyword_params() {
+    let text = "
+class Base
+  def f(a, *rest, k:, **opts)
+    [a, rest, k, opts]
+  end
+end
+class Derived < Base
+  def f(a, *rest, k: 2, **opts)
+    a = 10
+    super
+  end
+end
+[Derived.new.f(1, 2, 3, j: 4), Derived.new.f(1, k: 5)]
+";
+    let expected = expect!["[[10, [2, 3], 2, {j: 4}], [10, [], 5, {}]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_in_block() {
+    let text = "
+class Base
+  def f(a, b = 2)
+    a + b
+  end
+end
+class Derived < Base
+  def call()
+    yield
+  end
+
+  def f(a, b = 3)
+    call { super }
+  end
+end
+Derived.new.f(1)
+";
+    let expected = expect!["4"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn self_in_class_body() {
+    let text = "
+module Named
+  def label()
+    \"class #{name()}\"
+  end
+
+  def sound()
+    @sound
+  end
+
+  def block_label()
+    @label
+  end
+
+  def call()
+    yield
+  end
+end
+class Dog
+  extend Named
+  @sound = label()
+  @label = call { label() }
+end
+[Dog.sound, Dog.block_label]
+";
+    let expected = expect![[r#"["class Dog", "class Dog"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn undefined_method_in_class_body() {
+    let text = "
+class Dog
+  attr_reader :name
+end
+";
+    let expected = expect!["-e:3:3: undefined method 'attr_reader' for class Dog (NoMethodError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn self_keyword() {
+    let text = "
+class A
+  def me()
+    self
+  end
+end
+a = A.new
+x = self
+[class A; self; end, module M; [self]; end, a.me == a, x, \"#{self}\"]
+";
+    let expected = expect![[r#"[A, [M], true, main, "main"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn singleton_methods() {
+    let text = "
+module Named
+  def label()
+    \"named\"
+  end
+end
+module Util
+  def self.twice(x)
+    x * 2
+  end
+end
+class A
+  extend Named
+  def self.label()
+    \"own\"
+  end
+
+  def self.make()
+    new
+  end
+end
+class B < A
+end
+[A.label, B.label, B.make.class, Util.twice(4), A.ancestors]
+";
+    let expected = expect![[r#"["own", "own", B, 8, [A, Object, BasicObject]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn singleton_method_outside_class_body() {
+    let text = "
+def self.f()
+  1
+end
+";
+    let expected = expect![
+        "`def self.` outside of a class or module body isn't supported (NotImplementedError)"
+    ];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
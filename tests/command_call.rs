@@ -0,0 +1,198 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn raise_command() {
+    let text = "
+begin
+  raise ArgumentError, \"bad\"
+rescue ArgumentError => e
+  e.message
+end
+";
+    let expected = expect![[r#""bad""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn zero_arg_calls_after_dot() {
+    let text = "
+class Point
+end
+point = Point.new
+point.class.name
+";
+    let expected = expect![[r#""Point""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn command_with_receiver() {
+    let text = "
+class Pair
+  def make(a, b)
+    [a, b]
+  end
+end
+pair = Pair.new
+pair.make :x, 1
+";
+    let expected = expect![["[:x, 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn bare_identifier_calls_method() {
+    let text = "
+def two()
+  2
+end
+def four()
+  two * two
+end
+four
+";
+    let expected = expect![["4"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn local_shadows_method() {
+    let text = "
+def x()
+  1
+end
+a = x
+x = 10
+[a, x, x()]
+";
+    let expected = expect![["[1, 10, 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn local_minus_is_not_a_command() {
+    let text = "
+def f(a)
+  a
+end
+x = 5
+[x -1, x - 1, f -1]
+";
+    let expected = expect![["[4, 4, -1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn do_block_belongs_to_command() {
+    let text = "
+def each_of(xs)
+  [yield(xs[0]), yield(xs[1])]
+end
+xs = [1, 2]
+each_of xs do |x|
+  x * 10
+end
+";
+    let expected = expect![["[10, 20]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_given_without_parens() {
+    let text = "
+def f()
+  block_given?
+end
+[f, f { 1 }]
+";
+    let expected = expect![["[false, true]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_args_in_command() {
+    let text = "
+def f(a, b:)
+  [a, b]
+end
+f 1, b: 2
+";
+    let expected = expect![["[1, 2]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn yield_and_super_commands() {
+    let text = "
+class A
+  def f(a, b)
+    yield a, b
+  end
+end
+class B < A
+  def f(a)
+    super a, 2
+  end
+end
+B.new.f 1 do |x, y|
+  [x, y]
+end
+";
+    let expected = expect![["[1, 2]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_command_args() {
+    let text = "
+def id(x)
+  x
+end
+def twice()
+  id yield
+end
+module Greet
+  def hi()
+    :hi
+  end
+  extend self
+end
+a = id self
+b = id ::Greet
+c = id not nil
+[a, b, c, twice { 2 }, Greet.hi]
+";
+    let expected = expect!["[main, Greet, true, 2, :hi]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn statement_command_args() {
+    let text = "
+def id(x)
+  x
+end
+a = id case 1
+       when 1 then :one
+       end
+b = id begin
+  :begun
+end
+[a, b]
+";
+    let expected = expect!["[:one, :begun]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,31 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn comments_are_skipped() {
+    let text = "x = 1 # one
+        # two
+=begin
+x = 2
+=end
+        x + 1 # three";
+    let expected = expect![["2"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn data_section() {
+    let text = "[DATA, 1]\n__END__\nline one\nline two\n";
+    let expected = expect![["[\"line one\nline two\n\", 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn end_marker_stops_program() {
+    let text = "1\n__END__\n2 +";
+    let expected = expect![["1"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
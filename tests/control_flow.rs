@@ -8,3 +8,350 @@ fn if_else() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn elsif() {
+    let text = "
+def sign(n)
+  if n < 0
+    -1
+  elsif n == 0
+    0
+  else
+    1
+  end
+end
+[sign(-5), sign(0), sign(5)]
+";
+    let expected = expect![["[-1, 0, 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn while_is_nil() {
+    let text = "
+i = 0
+while i < 3
+  i = i + 1
+end
+";
+    let expected = expect!["nil"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn break_with_value() {
+    let text = "
+i = 0
+x = while true
+  i = i + 1
+  if i == 5
+    break i * 100
+  end
+end
+y = until false
+  break
+end
+[x, y]
+";
+    let expected = expect!["[500, nil]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn nested_loops() {
+    let text = "
+i = 0
+j = 0
+while i < 3
+  i = i + 1
+  while true
+    j = j + 1
+    break
+  end
+  if i == 2
+    next
+  end
+  j = j + 10
+end
+[i, j]
+";
+    let expected = expect!["[3, 23]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn redo_skips_condition() {
+    let text = "
+i = 0
+n = 0
+until i == 3
+  i = i + 1
+  n = n + 1
+  if n == 2
+    redo
+  end
+end
+[i, n]
+";
+    let expected = expect!["[3, 3]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn for_break_and_next() {
+    let text = "
+sum = 0
+last = for x in [1, 2, 3, 4, 5] do
+  if x == 2
+    next
+  end
+  if x == 4
+    break x
+  end
+  sum = sum + x
+end
+[sum, last]
+";
+    let expected = expect!["[4, 4]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_from_nested_expression() {
+    let text = "
+def size(x)
+  y = if x > 3
+    return \"big\"
+  else
+    \"small\"
+  end
+  \"#{y}!\"
+end
+def pair()
+  return 1, 2
+end
+def nothing()
+  return
+end
+[size(5), size(1), pair(), nothing()]
+";
+    let expected = expect![[r#"["big", "small!", [1, 2], nil]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_from_loop() {
+    let text = "
+def first_over(xs, n)
+  for x in xs do
+    while true
+      if x > n
+        return x
+      end
+      break
+    end
+  end
+  nil
+end
+[first_over([1, 5, 9], 4), first_over([1], 4)]
+";
+    let expected = expect!["[5, nil]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+const EACH_OF: &str = "
+def each_of(xs)
+  for x in xs do
+    yield(x)
+  end
+  \"done\"
+end
+";
+
+#[test]
+fn return_from_block() {
+    let text = "
+def find(xs)
+  each_of(xs) do |x|
+    if x > 1
+      return x
+    end
+  end
+  0
+end
+[find([1, 2, 3]), find([0])]
+";
+    let expected = expect!["[2, 0]"];
+    let actual = run::run_text([EACH_OF, text].concat());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_from_nested_block() {
+    let text = "
+def find(xss)
+  each_of(xss) do |xs|
+    each_of(xs) do |x|
+      if x > 2
+        return x
+      end
+    end
+  end
+  0
+end
+find([[1, 2], [3, 4]])
+";
+    let expected = expect!["3"];
+    let actual = run::run_text([EACH_OF, text].concat());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn break_from_block() {
+    let text = "
+x = each_of([1, 2, 3]) do |x|
+  if x == 2
+    break x * 10
+  end
+end
+[x, each_of([1]) { |x| break }, each_of([1]) { |x| x }]
+";
+    let expected = expect![[r#"[20, nil, "done"]"#]];
+    let actual = run::run_text([EACH_OF, text].concat());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn next_in_block() {
+    let text = "
+$sum = 0
+def add_all(xs)
+  each_of(xs) do |x|
+    if x == 2
+      next
+    end
+    $sum = $sum + x
+  end
+end
+add_all([1, 2, 3])
+$sum
+";
+    let expected = expect!["4"];
+    let actual = run::run_text([EACH_OF, text].concat());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn next_value_is_block_value() {
+    let text = "
+def twice()
+  [yield(1), yield(2)]
+end
+twice do |x|
+  if x == 1
+    next \"one\"
+  end
+  \"other\"
+end
+";
+    let expected = expect![[r#"["one", "other"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn redo_in_block() {
+    let text = "
+$n = 0
+each_of([1, 2]) do |x|
+  $n = $n + 1
+  if $n == 1
+    redo
+  end
+end
+$n
+";
+    let expected = expect!["3"];
+    let actual = run::run_text([EACH_OF, text].concat());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_and_break_in_lambda() {
+    let text = "
+add_one = -> (x) { return x + 1 }
+first = lambda do |x|
+  if x > 0
+    break \"positive\"
+  end
+  \"other\"
+end
+[add_one.call(1), first.call(1), first.call(0)]
+";
+    let expected = expect![[r#"[2, "positive", "other"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_from_finished_method() {
+    let text = "
+def make()
+  proc { return 1 }
+end
+make().call()
+";
+    let expected = expect!["-e:5:1: unexpected return (LocalJumpError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn break_from_finished_call() {
+    let text = "
+def make()
+  proc { break 1 }
+end
+make().call()
+";
+    let expected = expect!["-e:5:1: break from proc-closure (LocalJumpError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn break_outside_loop() {
+    let text = "break";
+    let expected = expect![[r#"
+        error: Invalid break
+         --> 1:1
+          |
+        1 | break
+          | ^^^^^
+    "#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn syntax_errors_are_returned() {
+    let text = "x = 1 2";
+    let expected = expect![[r#"
+        error: unexpected integer; expected newline or `;`
+         --> 1:7
+          |
+        1 | x = 1 2
+          |       ^
+    "#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,416 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn rescue_message() {
+    let text = "
+begin
+  raise(\"boom\")
+  \"not reached\"
+rescue => e
+  e.message()
+end
+";
+    let expected = expect![[r#""boom""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn no_exception_is_body_value() {
+    let text = "
+begin
+  1
+rescue
+  2
+end
+";
+    let expected = expect!["1"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rescue_by_class() {
+    let text = "
+def kind(error)
+  begin
+    raise(error)
+  rescue TypeError
+    \"type\"
+  rescue ArgumentError, IndexError => e
+    e.message()
+  rescue
+    \"standard\"
+  end
+end
+[kind(TypeError), kind(IndexError), kind(ArgumentError.new(\"bad\")), kind(ZeroDivisionError)]
+";
+    let expected = expect![[r#"["type", "IndexError", "bad", "standard"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn raise_class_with_message() {
+    let text = "
+begin
+  raise(ArgumentError, \"bad\")
+rescue => e
+  [e.class().name(), e.message()]
+end
+";
+    let expected = expect![[r#"["ArgumentError", "bad"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn default_message_is_class_name() {
+    let text = "
+begin
+  raise(IndexError)
+rescue => e
+  [e.message(), e.to_s()]
+end
+";
+    let expected = expect![[r#"["IndexError", "IndexError"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_exception_class() {
+    let text = "
+class MyError < StandardError
+end
+begin
+  raise(MyError, \"mine\")
+rescue MyError => e
+  [e.class().name(), e.message(), StandardError === e]
+end
+";
+    let expected = expect![[r#"["MyError", "mine", true]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn else_runs_without_exception() {
+    let text = "
+def f(fail)
+  begin
+    if fail
+      raise(\"boom\")
+    end
+    \"body\"
+  rescue
+    \"rescue\"
+  else
+    \"else\"
+  end
+end
+[f(true), f(false)]
+";
+    let expected = expect![[r#"["rescue", "else"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_runs_on_both_paths() {
+    let text = "
+$ensured = 0
+def f(fail)
+  begin
+    if fail
+      raise(\"boom\")
+    end
+    \"ok\"
+  rescue
+    \"rescued\"
+  ensure
+    $ensured = $ensured + 1
+    \"ignored\"
+  end
+end
+[f(true), f(false), $ensured]
+";
+    let expected = expect![[r#"["rescued", "ok", 2]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_without_rescue_reraises() {
+    let text = "
+$ensured = false
+def f()
+  begin
+    raise(\"boom\")
+  ensure
+    $ensured = true
+  end
+end
+begin
+  f()
+rescue => e
+  [e.message(), $ensured]
+end
+";
+    let expected = expect![[r#"["boom", true]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_runs_on_return() {
+    let text = "
+$log = []
+def f()
+  begin
+    begin
+      return 1
+    ensure
+      $log = $log.push(:inner)
+    end
+  ensure
+    $log = $log.push(:outer)
+  end
+  2
+end
+[f(), $log]
+";
+    let expected = expect![[r#"[1, [:inner, :outer]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_runs_on_loop_jumps() {
+    let text = "
+$log = []
+i = 0
+res = while i < 5
+  i += 1
+  begin
+    next if i == 1
+    redo if i == 2
+    break i * 10 if i == 4
+  ensure
+    $log = $log.push(i)
+  end
+end
+[res, $log]
+";
+    let expected = expect![[r#"[40, [1, 2, 3, 4]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_runs_on_block_jumps() {
+    let text = "
+$log = []
+def twice()
+  [yield(1), yield(2)]
+end
+def find()
+  begin
+    twice { |x| return x + 100 }
+  ensure
+    $log = $log.push(:find)
+  end
+end
+nexted = twice do |x|
+  begin
+    next x * 10
+  ensure
+    $log = $log.push(:next)
+  end
+end
+broken = twice do |x|
+  begin
+    break x
+  ensure
+    $log = $log.push(:break)
+  end
+end
+[nexted, broken, find(), $log]
+";
+    let expected = expect![[r#"[[10, 20], 1, 101, [:next, :next, :break, :find]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ensure_runs_on_retry() {
+    let text = "
+$log = []
+tries = 0
+res = begin
+  tries += 1
+  raise(\"boom\") if tries < 3
+  tries
+rescue
+  begin
+    retry
+  ensure
+    $log = $log.push(tries)
+  end
+end
+[res, $log]
+";
+    let expected = expect![[r#"[3, [1, 2]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn def_body_rescue() {
+    let text = "
+def f()
+  raise(\"boom\")
+rescue => e
+  [\"rescued\", e.message()]
+ensure
+  \"ignored\"
+end
+f()
+";
+    let expected = expect![[r#"["rescued", "boom"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn retry() {
+    let text = "
+$tries = 0
+begin
+  $tries = $tries + 1
+  if $tries < 3
+    raise(\"again\")
+  end
+  $tries
+rescue
+  retry
+end
+";
+    let expected = expect!["3"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn unmatched_exception_propagates() {
+    let text = "
+def inner()
+  begin
+    raise(TypeError, \"inner\")
+  rescue ArgumentError
+    \"wrong\"
+  end
+end
+def outer()
+  inner()
+  \"not reached\"
+end
+begin
+  outer()
+rescue TypeError => e
+  e.message()
+end
+";
+    let expected = expect![[r#""inner""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn raise_without_args_reraises() {
+    let text = "
+begin
+  begin
+    raise(IndexError, \"first\")
+  rescue
+    raise()
+  end
+rescue => e
+  [e.class().name(), e.message()]
+end
+";
+    let expected = expect![[r#"["IndexError", "first"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn standard_error_does_not_rescue_exception() {
+    let text = "
+begin
+  begin
+    raise(Exception, \"fatal\")
+  rescue
+    \"standard\"
+  end
+rescue Exception => e
+  e.message()
+end
+";
+    let expected = expect![[r#""fatal""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn raise_non_exception_is_type_error() {
+    let text = "
+begin
+  raise(1)
+rescue TypeError => e
+  e.message()
+end
+";
+    let expected = expect![[r#""exception class/object expected""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn uncaught_exception() {
+    let text = "
+x = 1
+raise(ArgumentError, \"bad\")
+";
+    let expected = expect!["-e:3:1: bad (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn uncaught_runtime_error() {
+    let text = "
+def f()
+  raise(\"boom\")
+end
+f()
+";
+    let expected = expect!["-e:3:3: boom (RuntimeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rescued_exception_result() {
+    let text = "
+begin
+  raise TypeError, 'bad'
+rescue => e
+  e
+end
+";
+    let expected = expect!["#<TypeError: bad>"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
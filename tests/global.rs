@@ -37,3 +37,70 @@ fn add_to_global() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn constant_set_then_get() {
+    let text = "
+LIMIT = 10
+def limit()
+  LIMIT
+end
+[LIMIT + 1, limit]
+";
+    let expected = expect!["[11, 10]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn undefined_constant() {
+    let text = "
+def limit()
+  LIMIT
+end
+def make()
+  Later.new
+end
+class Later
+end
+[begin limit rescue NameError => e then e.message end, make.class]
+";
+    let expected = expect![[r#"["uninitialized constant LIMIT", Later]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn undefined_constant_error() {
+    let text = "
+x = 1
+Missing
+";
+    let expected = expect!["-e:3:1: uninitialized constant Missing (NameError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn or_write_undefined_constant() {
+    let text = "
+LIMIT ||= 10
+LIMIT ||= 20
+LIMIT
+";
+    let expected = expect!["10"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn class_constant_cant_be_reassigned() {
+    let text = "
+class A
+end
+A = 1
+";
+    let expected = expect!["A is a class or module, and can't be reassigned (TypeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
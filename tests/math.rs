@@ -36,6 +36,19 @@ fn and_or() {
     expected.assert_eq(&actual);
 }
 
+#[test]
+fn and_or_short_circuit() {
+    let text = "
+def boom()
+  raise(\"evaluated\")
+end
+[nil && boom(), 2 && \"x\", false || 3, 4 || boom(), nil || false]
+";
+    let expected = expect![[r#"[nil, "x", 3, 4, false]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
 #[test]
 fn lt() {
     let text = "1 < 2 && -2 < -1";
@@ -43,3 +56,482 @@ fn lt() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn parentheses() {
+    let text = "
+a = (1)
+b = ((1))
+y = 1; (y + 2) * 3
+c = (y + 2) * 3
+x = (1 + 2) * 3
+[a, b, c, x, (), (y = 5; y * 2), -(2 + 3)]
+";
+    let expected = expect!["[1, 1, 9, 9, nil, 10, -5]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn float_literals() {
+    let text = "[1.5, 1e-3, 2.0e10, 1e20, -0.25]";
+    let expected = expect![["[1.5, 0.001, 20000000000.0, 1.0e+20, -0.25]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mixed_arithmetic() {
+    let text = "[1 + 1.5, 3.0 * 2, 7 / 2.0, 0.1 + 0.2, 5 - 0.5]";
+    let expected = expect![["[2.5, 6.0, 3.5, 0.30000000000000004, 4.5]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn integer_floor_division() {
+    let text = "[7 / 2, -7 / 2, 7 / -2, -7 / -2]";
+    let expected = expect![["[3, -4, -4, 3]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mixed_comparison() {
+    let text = "[1.5 < 2, 2 > 1.5, 1 == 1.0, 2 == 1.0]";
+    let expected = expect![["[true, true, true, false]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn radix_literals() {
+    let text = "[0xFF, 0b1010, 0o17, 017, 0d19, 1_000, 0xFF_FFFF_FFFF]";
+    let expected = expect![["[255, 10, 15, 15, 19, 1000, 1099511627775]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn comparisons() {
+    let text = "[1 <= 2, 2 <= 2, 3 <= 2, 2 >= 2, 1 >= 2, 1.5 <= 2, 2 >= 2.5]";
+    let expected = expect![["[true, true, false, true, false, true, false]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn not_equal() {
+    let text = "[1 != 2, 1 != 1, 1 != 1.0, \"a\" != \"a\", :a != :b, nil != false]";
+    let expected = expect![["[true, false, false, false, true, true]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn spaceship() {
+    let text = "[1 <=> 2, 2 <=> 2, 3 <=> 2, 1.5 <=> 1, 2 <=> 2.0, :a <=> :a, :a <=> :b]";
+    let expected = expect![["[-1, 0, 1, 1, 0, 0, nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn integer_modulo() {
+    // The modulo has the sign of the divisor.
+    let text = "[7 % 3, -7 % 3, 7 % -3, -7 % -3, 6 % 3]";
+    let expected = expect![["[1, 2, -2, -1, 0]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn float_modulo() {
+    let text = "[7.5 % 2, -7.5 % 2, 7 % 2.5]";
+    let expected = expect![["[1.5, 0.5, 2.0]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn exponent() {
+    let text = "[2 ** 10, 2 ** 3 ** 2, -2 ** 2, 10 ** 0, 1.5 ** 2, 2 ** -2]";
+    let expected = expect![["[1024, 512, -4, 1, 2.25, 0.25]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn float_exponents() {
+    let text = "
+third = 1.0 / 3
+[2 ** 0.5, 8 ** third, 2.5 ** 1.5, 4 ** 0.5, 2 ** 3.0, 1.0000001 ** 1000000000, 2.0 ** 1024]
+";
+    let expected = expect![[r#"
+        [1.4142135623730951,
+          2.0,
+          3.952847075210474,
+          2.0,
+          8.0,
+          2.6881038582144647e+43,
+          Infinity]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn negative_float_base() {
+    // Ruby gives a Complex for a fractional power of a negative number.
+    let text = "
+base = -2.0
+[base ** 3, base ** 2.0, base ** 0.5]
+";
+    let expected = expect!["[-8.0, 4.0, NaN]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn zero_to_negative_power() {
+    let text = "0 ** -1";
+    let expected = expect!["-e:1:1: divided by 0 (ZeroDivisionError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn unary_operators() {
+    let text = "[!true, !false, !nil, !0, ~5, ~-1, +3, !1 == false]";
+    let expected = expect![["[false, true, true, false, -6, 0, 3, true]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn negate_instance_variable() {
+    let text = "
+@x = 3
+[-@x, 1 -@x]
+";
+    let expected = expect!["[-3, -2]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn bitwise_operators() {
+    let text = "[6 & 3, 6 | 3, 6 ^ 3, 1 | 2 & 3, -8 & 0xff]";
+    let expected = expect![["[2, 7, 5, 3, 248]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn shifts() {
+    let text =
+        "[1 << 4, 256 >> 4, -16 >> 2, 1 << -1, 16 >> -1, 1 + 1 << 2, -1 >> 70, 0 << 70, -1 << 63]";
+    let expected = expect![["[16, 16, -4, 0, 32, 8, -1, 0, -9223372036854775808]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn shift_overflow() {
+    let text = "
+begin
+  1 << 64
+rescue RangeError => e
+  [e.message(), begin 3 << 62 rescue RangeError then 1 end]
+end
+";
+    let expected = expect![[r#"["integer too big; Integers are 64-bit", 1]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn floor_division_overflow() {
+    let text = "
+min = -9223372036854775807 - 1
+min / -1
+";
+    let expected = expect!["-e:3:1: integer too big; Integers are 64-bit (RangeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn add_overflow() {
+    let text = "
+max = 9223372036854775807
+max + 1
+";
+    let expected = expect!["-e:3:1: integer too big; Integers are 64-bit (RangeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn sub_overflow() {
+    let text = "
+min = -9223372036854775807 - 1
+[min - 0, -1 - min, begin min - 1 rescue RangeError then :overflow end,
+ begin -min rescue RangeError then :overflow end]
+";
+    let expected = expect!["[-9223372036854775808, 9223372036854775807, :overflow, :overflow]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mul_overflow() {
+    let text = "
+min = -9223372036854775807 - 1
+[min * 1, 4611686018427387904 * -2, 3037000499 * 3037000499,
+ begin 3037000500 * 3037000500 rescue RangeError then :overflow end,
+ begin 4611686018427387904 * 2 rescue RangeError then :overflow end]
+";
+    let expected = expect![[r#"
+        [-9223372036854775808,
+          -9223372036854775808,
+          9223372030926249001,
+          :overflow,
+          :overflow]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mul_by_zero() {
+    let text = "
+big = 4611686018427387904
+[0 * 5, 5 * 0, 0 * big, big * 0, 0 * -3, 0 ** 5, 0 ** 1, 0 ** 0]
+";
+    let expected = expect!["[0, 0, 0, 0, 0, 0, 0, 1]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mul_min_by_negative_one() {
+    let text = "
+min = -9223372036854775807 - 1
+[begin min * -1 rescue RangeError then :overflow end, begin -1 * min rescue RangeError then :overflow end]
+";
+    let expected = expect!["[:overflow, :overflow]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn exponent_overflow() {
+    let text = "
+[2 ** 62, 3 ** 39, -3 ** 39,
+ begin 3 ** 40 rescue RangeError then :overflow end,
+ begin 2 ** 63 rescue RangeError then :overflow end,
+ begin 2 ** 100 rescue RangeError then :overflow end]
+";
+    let expected = expect![[r#"
+        [4611686018427387904,
+          4052555153018976267,
+          -4052555153018976267,
+          :overflow,
+          :overflow,
+          :overflow]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn precedence() {
+    // Bitwise operators bind tighter than `==`: `(1 + 2) == ((3 & 1) | 2)`.
+    let text = "[1 + 2 * 3 ** 2 % 5, 2 * 3 <= 6 && 1 << 2 >= 4, 1 + 2 == 3 & 1 | 2]";
+    let expected = expect![["[4, true, true]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn operator_writes() {
+    let text = "
+a = 7
+a %= 4
+b = 3
+b **= 2
+c = 1
+c <<= 3
+d = 6
+d &= 3
+[a, b, c, d]
+";
+    let expected = expect![["[3, 9, 8, 2]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn division_by_zero() {
+    let text = "
+def divide(a, b)
+  a / b
+end
+divide(1, 0)
+";
+    let expected = expect!["-e:3:3: divided by 0 (ZeroDivisionError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn modulo_by_zero() {
+    let text = "5 % 0";
+    let expected = expect!["-e:1:1: divided by 0 (ZeroDivisionError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rescue_zero_division() {
+    let text = "
+begin
+  1 / 0
+rescue ZeroDivisionError => e
+  [e.message(), 1.0 / 0, 1 / 0.0]
+end
+";
+    let expected = expect![[r#"["divided by 0", Infinity, Infinity]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rescue_arithmetic_error() {
+    let text = "
+begin
+  1 % 0
+rescue ArithmeticError => e
+  [e.class, ZeroDivisionError.ancestors]
+end
+";
+    let expected = expect![[r#"
+        [ZeroDivisionError,
+          [ZeroDivisionError,
+            ArithmeticError,
+            StandardError,
+            Exception,
+            Object,
+            BasicObject]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_not() {
+    let text = "
+class Empty
+  def !()
+    :empty
+  end
+end
+class Plain
+end
+[!Empty.new, not(Empty.new), !Plain.new, !!Plain.new, !nil, !false, !0]
+";
+    let expected = expect!["[:empty, :empty, false, true, true, true, false]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_operators() {
+    let text = "
+class Money
+  def cents=(cents)
+    @cents = cents
+  end
+  def cents()
+    @cents
+  end
+  def +(other)
+    money(@cents + other.cents)
+  end
+  def -@()
+    money(-@cents)
+  end
+  def ==(other)
+    @cents == other.cents
+  end
+  def <=>(other)
+    @cents <=> other.cents
+  end
+end
+def money(cents)
+  m = Money.new
+  m.cents = cents
+  m
+end
+a = money(5)
+b = money(7)
+sum = a + b
+negated = -a
+[sum.cents, negated.cents, a == money(5), a != b, a <=> b, b <=> a]
+";
+    let expected = expect!["[12, -5, true, true, -1, 1]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_unary_plus() {
+    let text = "
+class S
+  def +@()
+    9
+  end
+end
+[+S.new, +2, +2.5]
+";
+    let expected = expect!["[9, 2, 2.5]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn operand_type_errors() {
+    let text = r##"
+def error()
+  yield
+rescue => e
+  "#{e.class}: #{e.message}"
+end
+[
+  error { 1 + "a" },
+  error { 1.5 * nil },
+  error { 1 < "a" },
+  error { 2.0 >= true },
+  error { 1 & 1.5 },
+  error { 1 << :a },
+  error { 2 ** "a" },
+  error { "a" - 1 },
+  error { -"a" },
+  error { ~1.5 },
+  error { "a" + 1 },
+  "a" + "b",
+]
+"##;
+    let expected = expect![[r#"
+        ["TypeError: String can't be coerced into Integer",
+          "TypeError: nil can't be coerced into Float",
+          "ArgumentError: comparison of Integer with String failed",
+          "ArgumentError: comparison of Float with true failed",
+          "TypeError: Float can't be coerced into Integer",
+          "TypeError: Symbol can't be coerced into Integer",
+          "TypeError: String can't be coerced into Integer",
+          "NoMethodError: undefined method '-' for an instance of String",
+          "NoMethodError: undefined method '-@' for an instance of String",
+          "NoMethodError: undefined method '~' for an instance of Float",
+          "TypeError: no implicit conversion of Integer into String",
+          "ab"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -49,3 +49,62 @@ fn def_then_call() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn locals_outside_def() {
+    let text = "
+            x = 1
+            def f()
+                x = 2
+                x
+            end
+            x + f()
+        ";
+    let expected = expect![["3"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn runtime_error_reports_call_location() {
+    let text = "
+def x()
+end
+x() + y(1)
+";
+    let expected = expect!["-e:4:7: undefined method 'y' for main (NoMethodError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn undefined_method_raises_no_method_error() {
+    let text = "
+class Foo
+end
+def message()
+  begin
+    yield
+  rescue NoMethodError => e
+    e.message
+  end
+end
+[message { Foo.new.bar }, message { Foo.bar }, message { nil.bar }, message { 5.bar }]
+";
+    let expected = expect![[r#"
+        ["undefined method 'bar' for an instance of Foo",
+          "undefined method 'bar' for class Foo",
+          "undefined method 'bar' for nil",
+          "undefined method 'bar' for an instance of Integer"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn call_object_methods_on_any_value() {
+    let text =
+        "[5.class.name, nil.class.name, \"ab\".class.name, 1.5.class.name, [1].class.name, 5 == 5]";
+    let expected = expect![[r#"["Integer", "NilClass", "String", "Float", "Array", true]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
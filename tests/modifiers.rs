@@ -0,0 +1,167 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn if_and_unless_modifiers() {
+    let text = "
+a = 1 if true
+b = 2 if false
+c = 3 unless false
+d = 4 unless true
+[a, b, c, d]
+";
+    let expected = expect![["[1, nil, 3, nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn while_and_until_modifiers() {
+    let text = "
+retry_count = 0
+retry_count += 1 while retry_count < 3
+n = 10
+n -= 2 until n < 5
+[retry_count, n]
+";
+    let expected = expect![["[3, 4]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_unless() {
+    let text = "
+def check(ok)
+  return :early unless ok
+  :late
+end
+[check(true), check(false)]
+";
+    let expected = expect![["[:late, :early]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn return_value_if() {
+    let text = "
+def f(n)
+  return n * 2 if n > 2
+  n
+end
+[f(1), f(3)]
+";
+    let expected = expect![["[1, 6]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn next_and_break_modifiers() {
+    let text = "
+i = 0
+sum = 0
+while i < 10
+  i += 1
+  next if i == 2
+  break unless i < 5
+  sum += i
+end
+sum
+";
+    let expected = expect![["8"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn unless_else() {
+    let text = "
+def describe(empty)
+  unless empty
+    :full
+  else
+    :empty
+  end
+end
+[describe(true), describe(false), unless true then 1 end]
+";
+    let expected = expect![["[:empty, :full, nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ternary() {
+    let text = "
+x = 3 > 2 ? :big : :small
+y = 3 < 2 ? :big : :small
+[x, y]
+";
+    let expected = expect![["[:big, :small]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn nested_ternary() {
+    let text = "
+def sign(n)
+  n > 0 ? 1 : n == 0 ? 0 : -1
+end
+[sign(5), sign(0), sign(-5)]
+";
+    let expected = expect![["[1, 0, -1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn modifier_applies_to_command() {
+    let text = "
+$log = []
+def log(msg)
+  $log = $log.push(msg)
+end
+log :a if true
+log :b if false
+$log
+";
+    let expected = expect![["[:a]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn begin_end_while_runs_body_first() {
+    let text = "
+i = 0
+begin
+  i += 1
+end while false
+j = 10
+begin
+  j += 1
+end until j > 5
+[i, j]
+";
+    let expected = expect![["[1, 11]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn conditions_are_truthy() {
+    let text = "
+a = 1 if []
+b = 2 unless \"\"
+c = \"s\" ? 3 : 4
+n = 0
+n += 1 while n < 2 && :yes
+[a, b, c, n]
+";
+    let expected = expect![["[1, nil, 3, 2]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,321 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn include() {
+    let text = "
+module Greet
+  def greet()
+    \"hi from #{name()}\"
+  end
+end
+class Dog
+  include(Greet)
+  def name()
+    \"dog\"
+  end
+end
+Dog.new().greet()
+";
+    let expected = expect![[r#""hi from dog""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn ancestors() {
+    let text = "
+module A
+end
+module B
+  include(A)
+end
+module C
+end
+class Animal
+end
+class Dog < Animal
+  include(B, C)
+end
+\"#{Dog.ancestors()} #{B.ancestors()}\"
+";
+    let expected = expect![[r#""[Dog, B, A, C, Animal, Object, BasicObject] [B, A]""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn class_method_before_module() {
+    let text = "
+module Speak
+  def speak()
+    \"...\"
+  end
+end
+class Dog
+  include(Speak)
+  def speak()
+    \"woof\"
+  end
+end
+Dog.new().speak()
+";
+    let expected = expect![[r#""woof""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn prepend() {
+    let text = "
+module Loud
+  def speak()
+    \"WOOF\"
+  end
+end
+class Dog
+  prepend(Loud)
+  def speak()
+    \"woof\"
+  end
+end
+\"#{Dog.ancestors()} #{Dog.new().speak()}\"
+";
+    let expected = expect![[r#""[Loud, Dog, Object, BasicObject] WOOF""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn module_before_superclass() {
+    let text = "
+module Quiet
+  def speak()
+    \"...\"
+  end
+end
+class Animal
+  def speak()
+    \"noise\"
+  end
+end
+class Dog < Animal
+  include(Quiet)
+end
+Dog.new().speak()
+";
+    let expected = expect![[r#""...""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn extend() {
+    let text = "
+module Named
+  def label()
+    \"class #{name()}\"
+  end
+end
+class Animal
+  extend(Named)
+end
+class Dog < Animal
+end
+[Animal.label(), Dog.label()]
+";
+    let expected = expect![[r#"["class Animal", "class Dog"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn mixin_with_receiver() {
+    let text = "
+module Greet
+  def greet()
+    \"hi from #{name()}\"
+  end
+end
+class Dog
+  def name()
+    \"dog\"
+  end
+end
+Dog.include(Greet)
+Dog.extend(Greet)
+[Dog.new.greet, Dog.greet, \"#{Dog.ancestors}\"]
+";
+    let expected =
+        expect![[r#"["hi from dog", "hi from Dog", "[Dog, Greet, Object, BasicObject]"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn reopen() {
+    let text = "
+module Greet
+end
+class Dog
+  include(Greet)
+end
+module Greet
+  def greet()
+    \"hi\"
+  end
+end
+Dog.new().greet()
+";
+    let expected = expect![[r#""hi""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn to_s() {
+    let text = "
+module Greet
+end
+\"#{Greet} #{Greet.name()}\"
+";
+    let expected = expect![[r#""Greet Greet""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn module_is_not_a_class() {
+    let text = "
+module Greet
+end
+class Greet
+end
+";
+    let expected = expect!["-e:4:1: Greet is not a class (TypeError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_into_module() {
+    let text = "
+module Loud
+  def speak()
+    \"#{super()}!\"
+  end
+end
+class Animal
+  def speak()
+    \"noise\"
+  end
+end
+class Dog < Animal
+  include(Loud)
+  def speak()
+    \"woof, #{super()}\"
+  end
+end
+Dog.new().speak()
+";
+    let expected = expect![[r#""woof, noise!""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn super_in_extended_module() {
+    let text = "
+module M
+  def hi()
+    super()
+  end
+end
+class C
+  extend M
+end
+C.hi
+";
+    let expected = expect!["-e:4:5: super: no superclass method 'hi' for class C (NoMethodError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn include_module_superclass_includes() {
+    let text = "
+module M
+  def hi()
+    :m
+  end
+end
+class Foo
+  include M
+  def hi()
+    :foo
+  end
+end
+class Bar < Foo
+  include M
+end
+[Bar.new.hi, \"#{Bar.ancestors}\"]
+";
+    let expected = expect![[r#"[:foo, "[Bar, Foo, M, Object, BasicObject]"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn include_errors() {
+    let text = "
+def error()
+  yield
+rescue NameError, TypeError, NotImplementedError => e
+  \"#{e.class}: #{e.message}\"
+end
+class Foo
+end
+[error { class A; include Missing; end },
+ error { class B; include Foo; end },
+ error { class C; m = Foo; include m; end },
+ error { extend Foo },
+ error { Object.new.extend Foo }]
+";
+    let expected = expect![[r#"
+        ["NameError: uninitialized constant Missing",
+          "TypeError: wrong argument type Class (expected Module)",
+          "NotImplementedError: `include` of a module that isn't a constant isn't supported",
+          "NotImplementedError: `extend` at the top level isn't supported",
+          "NotImplementedError: `extend` of a receiver that isn't a class or module constant isn't supported"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn cyclic_include() {
+    let text = "
+def error()
+  yield
+rescue ArgumentError => e
+  e.message
+end
+module A
+end
+module B
+  include A
+end
+[error { module M; include M; end },
+ error { module A; include B; end },
+ error { module A; prepend B; end },
+ error { module A; extend A; end },
+ \"#{A.ancestors} #{B.ancestors}\"]
+";
+    let expected = expect![[r#"
+        ["cyclic include detected",
+          "cyclic include detected",
+          "cyclic prepend detected",
+          A,
+          "[A] [B, A]"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
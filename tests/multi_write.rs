@@ -0,0 +1,135 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn swap() {
+    let text = "
+a, b = 1, 2
+a, b = b, a
+[a, b]
+";
+    let expected = expect![["[2, 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn array_value_is_destructured() {
+    let text = "
+list = [1, 2, 3]
+a, b = list
+[a, b]
+";
+    let expected = expect![["[1, 2]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn missing_values_are_nil() {
+    let text = "
+a, b, c = 1, 2
+d, e = 3
+[a, b, c, d, e]
+";
+    let expected = expect![["[1, 2, nil, 3, nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn splat() {
+    let text = "
+first, *rest = [1, 2, 3]
+*init, last = 1, 2, 3
+a, *middle, z = [1, 2, 3, 4]
+b, *empty, c = [5]
+[first, rest, init, last, middle, a, z, b, empty, c]
+";
+    let expected = expect![["[1, [2, 3], [1, 2], 3, [2, 3], 1, 4, 5, [], nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn anonymous_splat_and_trailing_comma() {
+    let text = "
+a, * = [1, 2]
+b, = [3, 4]
+[a, b]
+";
+    let expected = expect![["[1, 3]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn nested() {
+    let text = "
+x, (y, z) = 1, [2, 3]
+(a, b), *c = [4, 5], 6, 7
+d, (e, *f) = 8, 9
+[x, y, z, a, b, c, d, e, f]
+";
+    let expected = expect![["[1, 2, 3, 4, 5, [6, 7], 8, 9, []]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn statements_starting_with_parentheses() {
+    let text = "
+(a, b) = 1, 2
+(c), d = [3, 4]
+(a + b) * (c + d)
+";
+    let expected = expect!["21"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn other_targets() {
+    let text = "
+arr = [0, 0]
+$g, arr[1] = :g, :i
+[$g, arr]
+";
+    let expected = expect![["[:g, [0, :i]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn evaluates_to_value_array() {
+    let text = "
+def f()
+  a, b = 1, 2, 3
+end
+def g()
+  a, b = 4
+end
+[f(), g()]
+";
+    let expected = expect![["[[1, 2, 3], [4]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn in_block() {
+    let text = "
+def pairs()
+  yield [1, [2, 3]]
+end
+total = 0
+pairs { |pair|
+  x, (y, z) = pair
+  total = x + y + z
+}
+total
+";
+    let expected = expect![["6"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
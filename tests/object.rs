@@ -7,3 +7,150 @@ fn object_new_name() {
     // let actual = ruby_wasm::run_text(text.to_owned());
     // expected.assert_eq(&actual);
 }
+
+#[test]
+fn instance_variables() {
+    let text = "
+class Point
+  def set(x, y)
+    @x = x
+    @y = y
+  end
+  def sum()
+    @x + @y
+  end
+end
+a = Point.new
+b = Point.new
+a.set(1, 2)
+b.set(10, 20)
+[a.sum, b.sum]
+";
+    let expected = expect!["[3, 30]"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn unset_instance_variable_is_nil() {
+    let text = "
+class A
+  def x()
+    @x
+  end
+end
+[A.new.x, @top]
+";
+    let expected = expect!["[nil, nil]"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn instance_variables_of_main() {
+    let text = "
+@count = 1
+def bump()
+  @count += 1
+end
+bump
+bump
+@count
+";
+    let expected = expect!["3"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn instance_variables_of_values() {
+    let text = "
+class Object
+  def mark()
+    @mark = @mark
+  end
+end
+5.mark
+";
+    let expected = expect!["-e:7:1: can't modify frozen Integer (FrozenError)"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn writes_evaluate_to_value() {
+    let text = "
+def id(x)
+  x
+end
+[@x = 3, C = 4, id(@y = 5), [@x, C, @y]]
+";
+    let expected = expect!["[3, 4, 5, [3, 4, 5]]"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn object_equality() {
+    let text = "
+o = Object.new
+[o == o, o == Object.new, o != o, o <=> o, o <=> 1]
+";
+    let expected = expect!["[true, false, false, 0, nil]"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn inspect_results() {
+    let text = r#"
+class Foo
+end
+module M
+end
+[Foo.new, Foo, M, 1.."b", 1...2, RuntimeError.new("boom"), ArgumentError.new]
+"#;
+    let expected = expect![[r#"
+        [#<Foo>,
+          Foo,
+          M,
+          1.."b",
+          1...2,
+          #<RuntimeError: boom>,
+          #<ArgumentError: ArgumentError>]"#]];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_inspect() {
+    let text = r#"
+class Foo
+  def inspect()
+    "a foo"
+  end
+end
+def h(**kw)
+  kw
+end
+[[Foo.new, h(x: Foo.new)].inspect, Foo.new]
+"#;
+    let expected = expect![[r#"["[a foo, {x: a foo}]", a foo]"#]];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn raise_in_inspect() {
+    let text = r#"
+class Bar
+  def inspect()
+    raise "can't inspect"
+  end
+end
+[1, Bar.new]
+"#;
+    let expected = expect!["-e:4:5: can't inspect (RuntimeError)"];
+    let actual = ruby_wasm::run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,172 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn arithmetic_on_local() {
+    let text = "
+x = 1
+x += 2
+x *= 3
+[x, x -= 1, x / 2]
+";
+    let expected = expect![["[9, 8, 4]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn or_and_write_short_circuit() {
+    let text = "
+a ||= 1
+b = false
+b ||= 2
+c = 3
+c ||= 4
+d = 5
+d &&= 6
+e = nil
+e &&= 7
+[a, b, c, d, e]
+";
+    let expected = expect![["[1, 2, 3, 6, nil]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn or_write_does_not_evaluate_value() {
+    let text = "
+$calls = 0
+def value()
+  $calls += 1
+end
+x = 1
+x ||= value()
+y = nil
+y &&= value()
+$calls
+";
+    let expected = expect![["0"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn global() {
+    let text = "
+$count = 10
+$count -= 3
+$other ||= $count
+[$count, $other]
+";
+    let expected = expect![["[7, 7]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn index() {
+    let text = "
+a = [1, nil, 3]
+a[0] += 10
+a[1] ||= 20
+a[2] &&= 30
+a
+";
+    let expected = expect![["[11, 20, 30]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn index_receiver_and_arg_evaluated_once() {
+    let text = "
+$calls = []
+$arr = [1, 2]
+def arr()
+  $calls = $calls.push(:arr)
+  $arr
+end
+def idx()
+  $calls = $calls.push(:idx)
+  1
+end
+arr[idx] += 5
+[$arr, $calls]
+";
+    let expected = expect![["[[1, 7], [:arr, :idx]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn attribute() {
+    let text = "
+$calls = 0
+class Counter
+  def count()
+    $count
+  end
+
+  def count=(val)
+    $count = val
+  end
+end
+def counter()
+  $calls += 1
+  Counter.new
+end
+counter.count = 1
+counter.count += 1
+counter.count ||= 100
+counter.count &&= 7
+[$count, $calls]
+";
+    let expected = expect![["[7, 4]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn evaluates_to_value_written() {
+    let text = "
+x = 1
+y = nil
+[x += 1, x ||= 5, y ||= 6, y &&= 7]
+";
+    let expected = expect![["[2, 2, 6, 7]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn variable_shared_with_block() {
+    let text = "
+def twice()
+  yield 1
+  yield 2
+end
+total = 10
+twice { |i| total -= i }
+total
+";
+    let expected = expect![["7"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn instance_variable_and_constant() {
+    let text = "
+@a ||= 1
+@a ||= 2
+@b = 5
+@b *= 3
+C = 1
+C += 1
+[@a, @b, C]
+";
+    let expected = expect!["[1, 15, 2]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,369 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn all_kinds_of_params() {
+    let text = "
+def f(a, b = 1, *rest, c:, d: 2, **opts, &blk)
+  [a, b, rest, c, d, opts, blk.call()]
+end
+f(1, 2, 3, 4, c: 5, e: 6) { 7 }
+";
+    let expected = expect![["[1, 2, [3, 4], 5, 2, {e: 6}, 7]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn optional_params() {
+    let text = "
+def f(a, b = 10, c = 20)
+  [a, b, c]
+end
+[f(1), f(1, 2), f(1, 2, 3)]
+";
+    let expected = expect![["[[1, 10, 20], [1, 2, 20], [1, 2, 3]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn default_uses_earlier_param() {
+    let text = "
+def f(a, b = a * 2)
+  b
+end
+f(4)
+";
+    let expected = expect![["8"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn default_is_evaluated_on_each_call() {
+    let text = "
+$calls = 0
+def count()
+  $calls = $calls + 1
+end
+def f(a = count())
+  a
+end
+f()
+f(5)
+f()
+$calls
+";
+    let expected = expect![["2"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn default_calls_method_on_self() {
+    let text = "
+class Greeter
+  def name()
+    \"world\"
+  end
+
+  def greet(who = name())
+    \"hello #{who}\"
+  end
+end
+Greeter.new().greet()
+";
+    let expected = expect![[r#""hello world""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rest_param() {
+    let text = "
+def f(a, *rest)
+  [a, rest]
+end
+[f(1), f(1, 2, 3)]
+";
+    let expected = expect![["[[1, []], [1, [2, 3]]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rest_param_after_optional() {
+    let text = "
+def f(a = 1, *rest)
+  [a, rest]
+end
+[f(), f(5), f(5, 6, 7)]
+";
+    let expected = expect![["[[1, []], [5, []], [5, [6, 7]]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn post_params() {
+    let text = "
+def f(a, b = 2, c)
+  [a, b, c]
+end
+def g(a, *r, y, z)
+  [a, r, y, z]
+end
+def h(a = 1, b = 2, *r, c)
+  [a, b, r, c]
+end
+[f(1, 3), f(1, 5, 3), g(1, 2, 3), g(1, 2, 3, 4, 5), h(9), h(8, 9), h(6, 7, 8, 9)]
+";
+    let expected = expect![[r#"
+        [[1, 2, 3],
+          [1, 5, 3],
+          [1, [], 2, 3],
+          [1, [2, 3], 4, 5],
+          [1, 2, [], 9],
+          [8, 2, [], 9],
+          [6, 7, [8], 9]]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn post_params_of_blocks_and_super() {
+    let text = "
+class A
+  def m(*args)
+    args
+  end
+end
+class B < A
+  def m(a, b = 2, c)
+    super
+  end
+end
+pr = proc { |a, b = 5, c| [a, b, c] }
+[pr.call(1, 2), pr.call([1, 2, 3]), pr.call(1), ->(a, b = 1, c) { c }.arity, B.new.m(1, 3)]
+";
+    let expected = expect![["[[1, 5, 2], [1, 2, 3], [1, 5, nil], -3, [1, 2, 3]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn too_few_args_with_post_params() {
+    let text = "
+def f(a, b = 2, c)
+end
+f(1)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 1, expected 2..3) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_params() {
+    let text = "
+def f(a:, b: a + 1)
+  [a, b]
+end
+[f(a: 1), f(b: 5, a: 2)]
+";
+    let expected = expect![["[[1, 2], [2, 5]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_rest_param() {
+    let text = "
+def f(a: 1, **opts)
+  [a, opts, opts[:b], opts.size(), opts.key?(:c)]
+end
+[f(), f(b: 2, a: 3, c: 4)]
+";
+    let expected = expect![["[[1, {}, nil, 0, false], [3, {b: 2, c: 4}, 2, 2, true]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_args_to_method_without_keyword_params() {
+    let text = "
+def f(a, h)
+  [a, h, h.class().name()]
+end
+f(1, x: 2, y: \"z\")
+";
+    let expected = expect![[r#"[1, {x: 2, y: "z"}, "Hash"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn hash_to_s() {
+    let text = "
+def f(**opts)
+  \"opts: #{opts}\"
+end
+f(a: 1, b: [:c])
+";
+    let expected = expect![[r#""opts: {a: 1, b: [:c]}""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn block_param_with_other_params() {
+    let text = "
+def f(a, *rest, &blk)
+  blk.call(a, rest)
+end
+f(1, 2, 3) { |x, y| [y, x] }
+";
+    let expected = expect![["[[2, 3], 1]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn params_shared_with_block() {
+    let text = "
+def f(a, b = 2, *rest, c: 3)
+  get = lambda { [a, b, rest, c] }
+  b = 20
+  get.call()
+end
+f(1, c: 30)
+";
+    let expected = expect![["[1, 20, [], 30]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn too_few_args() {
+    let text = "
+def f(a, b)
+end
+f(1)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 1, expected 2) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn too_many_args() {
+    let text = "
+def f(a, b = 1)
+end
+f(1, 2, 3)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 3, expected 1..2) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn too_few_args_with_rest() {
+    let text = "
+def f(a, b, *rest)
+end
+f(1)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 1, expected 2+) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn args_to_method_without_params() {
+    let text = "
+def f()
+end
+f(1)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 1, expected 0) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn keyword_args_are_not_counted() {
+    let text = "
+def f(a, b: 1)
+end
+f(1, 2, b: 3)
+";
+    let expected =
+        expect!["-e:4:1: wrong number of arguments (given 2, expected 1) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn missing_keyword() {
+    let text = "
+def f(a:, b: 2)
+end
+f(b: 3)
+";
+    let expected = expect!["-e:4:1: missing keyword: :a (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn unknown_keyword() {
+    let text = "
+def f(a: 1)
+end
+f(a: 2, z: 3)
+";
+    let expected = expect!["-e:4:1: unknown keyword: :z (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn rescue_argument_error() {
+    let text = "
+def f(a)
+  a
+end
+begin
+  f()
+rescue ArgumentError => e
+  e.message()
+end
+";
+    let expected = expect![[r#""wrong number of arguments (given 0, expected 1)""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn splat_args() {
+    let text = "
+def f(a, b, c = 0, k: 1)
+  [a, b, c, k]
+end
+def g(*args)
+  yield(*args, 3)
+end
+x = [2, 3]
+[f(1, *x, k: 4), f(*x), f(*[], 5, *6), g(*[1], 2) { |a, b, c| a + b + c }]
+";
+    let expected = expect!["[[1, 2, 3, 4], [2, 3, 0, 1], [5, 6, 0, 1], 6]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,176 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn lambda() {
+    let text = "
+square = lambda { |x| x * x }
+square.call(3)
+";
+    let expected = expect!["9"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn stabby_lambda() {
+    let text = "
+add = ->(a, b) { a + b }
+one = -> do
+  1
+end
+add.call(one.call(), 2)
+";
+    let expected = expect!["3"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn proc_and_proc_new() {
+    let text = "
+double = proc { |x| x * 2 }
+triple = Proc.new { |x| x * 3 }
+\"#{double.call(2)} #{triple.call(2)} #{double.class()}\"
+";
+    let expected = expect![[r#""4 6 Proc""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn call_syntaxes() {
+    let text = "
+inc = ->(x) { x + 1 }
+\"#{inc.call(1)} #{inc.(2)} #{inc[3]}\"
+";
+    let expected = expect![[r#""2 3 4""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn call_syntaxes_without_args() {
+    let text = "
+one = -> { 1 }
+[one.call, one.(), one[], one.[]]
+";
+    let expected = expect!["[1, 1, 1, 1]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn captures_variables() {
+    let text = "
+count = 0
+increment = lambda { count = count + 1 }
+increment.call()
+increment.()
+count
+";
+    let expected = expect!["2"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn proc_is_lenient() {
+    let text = "
+pair = proc { |a, b| \"#{a}|#{b}\" }
+\"#{pair.call(1)} #{pair.call(1, 2, 3)} #{pair.call([4, 5])}\"
+";
+    let expected = expect![[r#""1| 1|2 4|5""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn lambda_checks_arity() {
+    let text = "
+pair = lambda { |a, b| a }
+pair.call(1)
+";
+    let expected =
+        expect!["-e:3:1: wrong number of arguments (given 1, expected 2) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn user_defined_proc_and_lambda() {
+    // Without a literal block, `proc` and `lambda` are ordinary method calls.
+    let text = "
+def proc()
+  1
+end
+def lambda()
+  2
+end
+[proc, lambda, proc { 3 }.call()]
+";
+    let expected = expect!["[1, 2, 3]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn lambda_and_arity() {
+    let text = "
+\"#{lambda { |a| a }.lambda?()} #{proc { |a| a }.lambda?()} #{->(a, b) { a }.arity()}\"
+";
+    let expected = expect![[r#""true false 2""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn inspect() {
+    let text = "
+[lambda { 1 }, proc { 2 }]
+";
+    let expected = expect!["[#<Proc:... (lambda)>, #<Proc:...>]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn lambda_params() {
+    let text = "
+l = ->(a, b = 2, *r) { [a, b, r] }
+[l.call(1), l.call(1, 3, 4, 5), l.arity(), ->(a, (b, c)) { [a, b, c] }.call(1, [2, 3])]
+";
+    let expected = expect!["[[1, 2, []], [1, 3, [4, 5]], -2, [1, 2, 3]]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn lambda_with_optional_params_checks_arity() {
+    let text = "
+l = lambda { |a, b = 2| a }
+l.call(1, 2, 3)
+";
+    let expected =
+        expect!["-e:3:1: wrong number of arguments (given 3, expected 1..2) (ArgumentError)"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn arity_of_params() {
+    let text = "
+[
+  proc { |x, y = 0| }.arity,
+  lambda { |x, y = 0| }.arity,
+  proc { |x = 0| }.arity,
+  proc { |*x| }.arity,
+  proc { |x, *y| }.arity,
+  proc { |(x, y), z| }.arity,
+  proc { |x, &b| }.arity,
+]
+";
+    let expected = expect!["[1, -2, 0, -1, -2, 2, 1]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -9,3 +9,174 @@ fn single_quote_string_roundtrip() {
     let actual = run::run_text(text.to_owned());
     expected.assert_eq(&actual);
 }
+
+#[test]
+fn double_quote_string_roundtrip() {
+    let text = "\"22\"";
+    let expected = expect![["\"22\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn double_quote_string_escapes() {
+    let text = r#""a\tb\x41\u{48 49}""#;
+    let expected = expect![["\"a\tbAHI\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_integer() {
+    let text = "\"a#{1 + 2}b\"";
+    let expected = expect![["\"a3b\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_negative_integer() {
+    let text = "\"#{-22}\"";
+    let expected = expect![["\"-22\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_nil_true_false() {
+    let text = "\"#{nil}#{true}#{false}\"";
+    let expected = expect![["\"truefalse\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_float() {
+    let text = "
+x = 2.5
+[\"#{1.5}\", \"v=#{x}\", \"#{[1.5, -0.0]}\", \"#{0.1 + 0.2}\", \"#{1.0 / 3}\"]
+";
+    let expected = expect![[
+        r#"["1.5", "v=2.5", "[1.5, -0.0]", "0.30000000000000004", "0.3333333333333333"]"#
+    ]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_float_notation() {
+    // Floats are fixed-point with the decimal point within 16 digits, else scientific.
+    let text = "
+[\"#{1e15}\", \"#{1e16}\", \"#{0.0001}\", \"#{0.00001}\", \"#{1.2345e-20}\",
+ \"#{1e23}\", \"#{1.0 / 0}\", \"#{-1.0 / 0}\", \"#{0.0 / 0}\"]
+";
+    let expected = expect![[r#"
+        ["1000000000000000.0",
+          "1.0e+16",
+          "0.0001",
+          "1.0e-05",
+          "1.2345e-20",
+          "1.0e+23",
+          "Infinity",
+          "-Infinity",
+          "NaN"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_array() {
+    let text = "\"#{[1, \"a\", nil]}\"";
+    let expected = expect![["\"[1, \"a\", nil]\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_local() {
+    let text = "
+            x = \"world\"
+            \"hello #{x}\"
+        ";
+    let expected = expect![["\"hello world\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn nested_interpolation() {
+    let text = "\"a#{\"b#{\"c\"}\"}d\"";
+    let expected = expect![["\"abcd\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_object_calls_to_s() {
+    let text = "\"#{Object.new()}\"";
+    let expected = expect![["\"#<Object>\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_object_user_to_s() {
+    let text = "
+            def to_s()
+                \"hi\"
+            end
+            \"#{Object.new()}\"
+        ";
+    let expected = expect![["\"hi\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn builtin_to_s() {
+    let text =
+        "[1.to_s, 1.5.to_s, \"a\".to_s, :a.to_s, nil.to_s, true.to_s, false.to_s, [1, :b].to_s]";
+    let expected = expect![[r#"["1", "1.5", "a", "a", "", "true", "false", "[1, :b]"]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn squiggly_heredoc() {
+    let text = "x = 3\ns = <<~EOS\n  a #{x}\n    b\nEOS\ns";
+    let expected = expect![["\"a 3\n  b\n\""]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn heredocs_on_one_line() {
+    let text = "[<<-A, <<'B']\n  a\n  A\n#{b}\nB\n";
+    let expected = expect![["[\"  a\n\", \"#{b}\n\"]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn percent_strings() {
+    let text = "x = 1\n[%q(a (b) #{x}), %Q[#{x}\\t], %|c|]";
+    let expected = expect![["[\"a (b) #{x}\", \"1\t\", \"c\"]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn word_lists() {
+    let text = "x = 1\n[%w[a b\\ c], %W(#{x}y z)]";
+    let expected = expect![["[[\"a\", \"b c\"], [\"1y\", \"z\"]]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn compare_strings() {
+    let text = r#"["a" <=> "b", "b" <=> "a", "ab" <=> "a", "a" <=> "a", "a" == "a"]"#;
+    let expected = expect!["[-1, 1, 1, 0, true]"];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
@@ -0,0 +1,105 @@
+use expect_test::expect;
+use ruby_wasm::run;
+
+#[test]
+fn symbol_roundtrip() {
+    let text = ":foo";
+    let expected = expect![[":foo"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn quoted_symbol() {
+    let text = ":\"foo bar\"";
+    let expected = expect![[r#":"foo bar""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn quoted_symbol_is_same_symbol() {
+    let text = ":\"foo\" == :foo";
+    let expected = expect![["true"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn operator_symbols() {
+    let text = "[:+, :[]=, :@x, :a?]";
+    let expected = expect![["[:+, :[]=, :@x, :a?]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn symbol_list() {
+    let text = "%i[a b c]";
+    let expected = expect![["[:a, :b, :c]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn symbols_are_interned() {
+    let text = "
+            x = :foo
+            y = :foo
+            x == y
+        ";
+    let expected = expect![["true"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn different_symbols_differ() {
+    let text = ":foo == :bar";
+    let expected = expect![["false"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolate_symbol() {
+    let text = "\"#{:foo} #{[:foo]}\"";
+    let expected = expect![[r#""foo [:foo]""#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn method_name_symbol_shared_with_call() {
+    let text = "
+            def foo()
+                :foo
+            end
+            foo() == :foo
+        ";
+    let expected = expect![["true"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolated_symbol() {
+    let text = "
+            x = 1
+            [:\"a#{x}\", :\"a#{x}\" == :a1, :\"b#{x}\" == :\"b#{x}\"]
+        ";
+    let expected = expect![[r#"[:a1, true, true]"#]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
+
+#[test]
+fn interpolated_symbol_list() {
+    let text = "
+            x = 1
+            %I[a#{x} b]
+        ";
+    let expected = expect![["[:a1, :b]"]];
+    let actual = run::run_text(text.to_owned());
+    expected.assert_eq(&actual);
+}
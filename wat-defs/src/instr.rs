@@ -27,6 +27,9 @@ pub enum UnfoldedInstr {
     I64GtU,
     I64Add,
     I64Sub,
+    I64Mul,
+    I64DivS,
+    I64RemS,
     I64And,
     I64Or,
     I64Xor,
@@ -35,12 +38,32 @@ pub enum UnfoldedInstr {
     I64ShrU,
     I64ExtendI32U,
     I64ExtendI32S,
-
+    F64Eq,
+    F64Lt,
+    F64Gt,
+    F64Le,
+    F64Ge,
+    F64Add,
+    F64Sub,
+    F64Mul,
+    F64Div,
+    F64Neg,
+    F64Floor,
+    F64ConvertI64S,
+    I64TruncF64S,
+    I64ReinterpretF64,
+    F64ReinterpretI64,
+
+    /// Integer constant. Float constants are `F64Const`.
     Const {
         ty: NumType,
         val: i64,
     },
 
+    F64Const {
+        val: f64,
+    },
+
     Br {
         label: String,
     },
@@ -49,10 +72,17 @@ pub enum UnfoldedInstr {
         label: String,
     },
 
+    /// Branch to `labels[i]`, or to `default` if `i` is out of range.
+    BrTable {
+        labels: Vec<String>,
+        default: String,
+    },
+
     Return,
 
     Block {
         label: String,
+        block_type: Option<BlockType>,
     },
 
     Loop {
@@ -68,6 +98,18 @@ pub enum UnfoldedInstr {
         else_block: Vec<Instr>,
     },
 
+    /// Runs the folded instrs. If they throw an exception that one of `catches` matches,
+    ///     branches to its label instead.
+    TryTable {
+        block_type: Option<BlockType>,
+        catches: Vec<Catch>,
+    },
+
+    /// Throws an exception with tag `tag`, and the tag's params from the stack.
+    Throw {
+        tag: String,
+    },
+
     RefNull {
         ty: HeapType,
     },
@@ -163,6 +205,15 @@ pub enum UnfoldedInstr {
     Unreachable,
 }
 
+/// A `try_table` clause: which exceptions it catches, and where it branches to.
+#[derive(Debug, Clone)]
+pub enum Catch {
+    /// `(catch $tag $label)`: exceptions with `tag`, branching with their params.
+    Catch { tag: String, label: String },
+    /// `(catch_all $label)`: every exception, branching with no values.
+    CatchAll { label: String },
+}
+
 #[derive(Debug, Clone)]
 pub struct Instr {
     pub unfolded_instr: UnfoldedInstr,
@@ -198,6 +249,9 @@ impl Instr {
             || str == "i64_gt_u"
             || str == "i64_add"
             || str == "i64_sub"
+            || str == "i64_mul"
+            || str == "i64_div_s"
+            || str == "i64_rem_s"
             || str == "i64_and"
             || str == "i64_or"
             || str == "i64_xor"
@@ -206,12 +260,31 @@ impl Instr {
             || str == "i64_shr_u"
             || str == "i64_extend_i32_u"
             || str == "i64_extend_i32_s"
+            || str == "f64_eq"
+            || str == "f64_lt"
+            || str == "f64_gt"
+            || str == "f64_le"
+            || str == "f64_ge"
+            || str == "f64_add"
+            || str == "f64_sub"
+            || str == "f64_mul"
+            || str == "f64_div"
+            || str == "f64_neg"
+            || str == "f64_floor"
+            || str == "f64_convert_i64_s"
+            || str == "i64_trunc_f64_s"
+            || str == "i64_reinterpret_f64"
+            || str == "f64_reinterpret_i64"
+            || str == "const_f64"
             || str == "br"
             || str == "br_if"
+            || str == "br_table"
             || str == "return"
             || str == "block"
             || str == "loop"
             || str == "if"
+            || str == "try_table"
+            || str == "throw"
             || str == "ref_null"
             || str == "ref_func"
             || str == "ref_i31"
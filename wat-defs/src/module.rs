@@ -1,12 +1,13 @@
 use crate::func::Func;
 use crate::global::Global;
-use crate::ty::SubType;
+use crate::ty::{SubType, ValType};
 
 #[derive(Debug, Default)]
 pub struct Module {
     pub types: Vec<TypeDef>,
     pub globals: Vec<Global>,
     pub funcs: Vec<Func>,
+    pub tags: Vec<Tag>,
     pub start_fn: Option<String>,
 }
 
@@ -21,3 +22,10 @@ pub struct TypeDef {
     pub name: String,
     pub ty: SubType,
 }
+
+/// Exception tag. Exceptions thrown with it carry one value of each param type.
+#[derive(Debug)]
+pub struct Tag {
+    pub name: String,
+    pub params: Vec<ValType>,
+}
@@ -16,6 +16,7 @@ pub struct GlobalType {
 pub enum NumType {
     I32,
     I64,
+    F64,
 }
 
 #[derive(Debug, Clone)]
@@ -2,6 +2,7 @@ use crate::parse::func::{parse_func, parse_local};
 use crate::parse::global::parse_global;
 use crate::parse::instr::parse_instr_seq;
 use crate::parse::parse_stream::ParseStream;
+use crate::parse::tag::parse_tag;
 use crate::parse::ty::parse_ref_type;
 use crate::parse::type_def::parse_type_def;
 use crate::parse::util::{check_quasi_quote, peek_open_paren_named};
@@ -13,6 +14,7 @@ mod func;
 mod global;
 mod instr;
 mod parse_stream;
+mod tag;
 mod ty;
 mod type_def;
 mod util;
@@ -43,6 +45,8 @@ pub fn parse_item(input: TokenStream) -> Result<TokenStream> {
             parse_local(input)
         } else if peek_open_paren_named(&["func"], input).is_some() {
             parse_func(input)
+        } else if peek_open_paren_named(&["tag"], input).is_some() {
+            parse_tag(input)
         } else if peek_open_paren_named(&["ref"], input).is_some() {
             parse_ref_type(input)
         } else if peek_sym(input).is_some() {
@@ -35,6 +35,29 @@ pub fn parse_instr(input: ParseInput) -> Result<TokenStream> {
     })
 }
 
+/// `(br_table $label* $default ...)`, or `(br_table ,(labels) ,(default) ...)`
+/// Pre: Parens and `br_table` have been read.
+fn parse_br_table(input: ParseInput) -> Result<TokenStream> {
+    let path = quote![wat_defs::instr::UnfoldedInstr];
+    if let Ok(labels) = expect_quasi_quote(input) {
+        let default = parse_name(input)?;
+        return Ok(quote![ #path::BrTable { labels: #labels, default: #default } ]);
+    }
+    let mut labels = vec![];
+    while let Ok(label) = expect_sym(input) {
+        labels.push(label.to_string());
+    }
+    let Some(default) = labels.pop() else {
+        return Err(error(input, "Expected a default label for `br_table`."));
+    };
+    Ok(quote! {
+        #path::BrTable {
+            labels: vec![ #(#labels.to_string()),* ],
+            default: #default.to_string(),
+        }
+    })
+}
+
 /// Pre: Parens and `if` have been read.
 fn parse_if(input: ParseInput) -> Result<TokenStream> {
     let label = match expect_sym(input) {
@@ -107,6 +130,9 @@ fn parse_unfolded_instr(name: Ident, input: ParseInput) -> Result<TokenStream> {
         "i64_gt_u" => quote![ #path::I64GtU ],
         "i64_add" => quote![ #path::I64Add ],
         "i64_sub" => quote![ #path::I64Sub ],
+        "i64_mul" => quote![ #path::I64Mul ],
+        "i64_div_s" => quote![ #path::I64DivS ],
+        "i64_rem_s" => quote![ #path::I64RemS ],
         "i64_and" => quote![ #path::I64And ],
         "i64_or" => quote![ #path::I64Or ],
         "i64_xor" => quote![ #path::I64Xor ],
@@ -116,6 +142,25 @@ fn parse_unfolded_instr(name: Ident, input: ParseInput) -> Result<TokenStream> {
         "i64_extend_i32_s" => quote![ #path::I64ExtendI32S ],
         "i64_extend_i32_u" => quote![ #path::I64ExtendI32U ],
         "const_i64" => parse_const(NumType::I64, input)?,
+        "f64_eq" => quote![ #path::F64Eq ],
+        "f64_lt" => quote![ #path::F64Lt ],
+        "f64_gt" => quote![ #path::F64Gt ],
+        "f64_le" => quote![ #path::F64Le ],
+        "f64_ge" => quote![ #path::F64Ge ],
+        "f64_add" => quote![ #path::F64Add ],
+        "f64_sub" => quote![ #path::F64Sub ],
+        "f64_mul" => quote![ #path::F64Mul ],
+        "f64_div" => quote![ #path::F64Div ],
+        "f64_neg" => quote![ #path::F64Neg ],
+        "f64_floor" => quote![ #path::F64Floor ],
+        "f64_convert_i64_s" => quote![ #path::F64ConvertI64S ],
+        "i64_trunc_f64_s" => quote![ #path::I64TruncF64S ],
+        "i64_reinterpret_f64" => quote![ #path::I64ReinterpretF64 ],
+        "f64_reinterpret_i64" => quote![ #path::F64ReinterpretI64 ],
+        "const_f64" => {
+            let val = expect_float_literal(input)?;
+            quote![ #path::F64Const { val: #val } ]
+        }
         "br" => {
             let label = parse_name(input)?;
             quote![ #path::Br { label: #label } ]
@@ -124,10 +169,16 @@ fn parse_unfolded_instr(name: Ident, input: ParseInput) -> Result<TokenStream> {
             let label = parse_name(input)?;
             quote![ #path::BrIf { label: #label } ]
         }
+        "br_table" => parse_br_table(input)?,
         "return" => quote![ #path::Return ],
         "block" => parse_block(input)?,
         "loop" => parse_loop(input)?,
         "if" => return Err(error(input, "panic!: Can't parse unfolded if.")),
+        "try_table" => parse_try_table(input)?,
+        "throw" => {
+            let tag = parse_name(input)?;
+            quote![ #path::Throw { tag: #tag } ]
+        }
         "ref_null" => {
             let ty = ty::parse_heap_type(input)?;
             quote![ #path::RefNull { ty: #ty } ]
@@ -246,13 +297,47 @@ fn parse_const(ty: NumType, input: ParseInput) -> Result<TokenStream> {
     Ok(quote! { wat_defs::instr::UnfoldedInstr::Const { ty: #ty, val: #val } })
 }
 
+/// `(block $label <block_type>? ...)`
 fn parse_block(input: ParseInput) -> Result<TokenStream> {
     let label = parse_name(input)?;
-    // let block_type = match ty::parse_block_type(input) {
-    //     Ok(res) => quote![ Some(#res) ],
-    //     Err(_) => quote![None],
-    // };
-    Ok(quote! { wat_defs::instr::UnfoldedInstr::Block { label: #label } })
+    let block_type = parse_opt_block_type(input)?;
+    Ok(quote! { wat_defs::instr::UnfoldedInstr::Block { label: #label, block_type: #block_type } })
+}
+
+/// `(try_table <block_type>? (catch $tag $label)* (catch_all $label)? ...)`
+fn parse_try_table(input: ParseInput) -> Result<TokenStream> {
+    let path = quote![wat_defs::instr::Catch];
+    let block_type = parse_opt_block_type(input)?;
+    let mut catches = vec![];
+    while let Some((_, name)) = peek_open_paren_named(&["catch", "catch_all"], input) {
+        let (mut input, _) = expect_open_paren_named(&["catch", "catch_all"], input)?;
+        let input = &mut input;
+        if name == "catch" {
+            let tag = parse_name(input)?;
+            let label = parse_name(input)?;
+            catches.push(quote![ #path::Catch { tag: #tag, label: #label } ]);
+        } else {
+            let label = parse_name(input)?;
+            catches.push(quote![ #path::CatchAll { label: #label } ]);
+        }
+    }
+    Ok(quote! {
+        wat_defs::instr::UnfoldedInstr::TryTable {
+            block_type: #block_type,
+            catches: vec![ #(#catches),* ],
+        }
+    })
+}
+
+/// A block type, only if it's spelled out.
+/// (`parse_block_type` would take a quasi-quoted first instr for one.)
+fn parse_opt_block_type(input: ParseInput) -> Result<TokenStream> {
+    if peek_open_paren_named(&["result", "type"], input).is_some() {
+        let block_type = ty::parse_block_type(input)?;
+        Ok(quote![ Some(#block_type) ])
+    } else {
+        Ok(quote![None])
+    }
 }
 
 fn parse_loop(input: ParseInput) -> Result<TokenStream> {
@@ -292,6 +377,7 @@ fn num_type_to_tokens(ty: NumType) -> TokenStream {
     match ty {
         NumType::I32 => quote![ #path::I32 ],
         NumType::I64 => quote![ #path::I64 ],
+        NumType::F64 => quote![ #path::F64 ],
     }
 }
 
@@ -0,0 +1,25 @@
+use crate::parse::parse_stream::ParseInput;
+use crate::parse::ty;
+use crate::parse::util::*;
+use crate::result::Result;
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// `(tag $<name> (param <val_type>)*)`
+pub fn parse_tag(input: ParseInput) -> Result<TokenStream> {
+    let (mut input, _) = expect_open_paren_named(&["tag"], input)?;
+    let input = &mut input;
+
+    let name = parse_name(input)?;
+    let mut params = vec![];
+    while let Ok((mut input, _)) = expect_open_paren_named(&["param"], input) {
+        params.push(ty::parse_val_type(&mut input)?);
+    }
+
+    Ok(quote! {
+        wat_defs::module::Tag {
+            name: #name,
+            params: vec![ #(#params),* ],
+        }
+    })
+}
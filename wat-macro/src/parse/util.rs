@@ -70,6 +70,20 @@ pub fn expect_int_literal(input: ParseInput) -> Result<TokenStream> {
     })
 }
 
+/// Accepts integer literals as well, so `(const_f64 1)` works.
+/// Post: On failure, does not mutate `input`.
+pub fn expect_float_literal(input: ParseInput) -> Result<TokenStream> {
+    check_quasi_quote!(input => {
+        match peek_float_literal(input) {
+            Some(n) => {
+                input.next();
+                Ok(quote!(#n))
+            }
+            None => Err(error(input, "Expected float literal.")),
+        }
+    })
+}
+
 /// Post: On failure, does not mutate `input`.
 pub fn expect_string_literal(input: ParseInput) -> Result<TokenStream> {
     check_quasi_quote!(input => {
@@ -179,6 +193,13 @@ pub fn peek_int_literal(input: ParseInput) -> Option<i64> {
     }
 }
 
+pub fn peek_float_literal(input: ParseInput) -> Option<f64> {
+    match input.peek() {
+        Some(TokenTree::Literal(lit)) => lit.to_string().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
 /// Post: On failure, does not mutate `input`.
 pub fn peek_string_literal(input: ParseInput) -> Option<String> {
     match input.peek() {
@@ -49,3 +49,34 @@ pub fn array_new_fixed() {
     ]];
     expected.assert_eq(actual);
 }
+
+#[test]
+pub fn f64_add_folded_consts() {
+    let actual = wat! { (f64_add (const_f64 1.5) (const_f64 2.0)) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![
+        "[Instr { unfolded_instr: F64Add, folded_instrs: [Instr { unfolded_instr: F64Const { val: 1.5 }, folded_instrs: [] }, Instr { unfolded_instr: F64Const { val: 2.0 }, folded_instrs: [] }] }]"
+    ];
+    expected.assert_eq(actual);
+}
+
+#[test]
+pub fn br_table() {
+    let actual = wat! { (br_table $a $b $default (local_get $idx)) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"[Instr { unfolded_instr: BrTable { labels: ["a", "b"], default: "default" }, folded_instrs: [Instr { unfolded_instr: LocalGet { name: "idx" }, folded_instrs: [] }] }]"#
+    ]];
+    expected.assert_eq(actual);
+}
+
+#[test]
+pub fn br_table_quasi_quoted() {
+    let labels = vec!["a".to_string()];
+    let actual = wat! { (br_table ,(labels) ,("default".to_string()) (local_get $idx)) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"[Instr { unfolded_instr: BrTable { labels: ["a"], default: "default" }, folded_instrs: [Instr { unfolded_instr: LocalGet { name: "idx" }, folded_instrs: [] }] }]"#
+    ]];
+    expected.assert_eq(actual);
+}
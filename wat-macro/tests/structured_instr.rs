@@ -66,3 +66,38 @@ pub fn if_no_label_type_then_else() {
     ]];
     expected.assert_eq(actual);
 }
+
+#[test]
+pub fn block_result() {
+    let actual = wat! { (block $caught (result i32) (const_i32 1)) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"[Instr { unfolded_instr: Block { label: "caught", block_type: Some(Result(Num(I32))) }, folded_instrs: [Instr { unfolded_instr: Const { ty: I32, val: 1 }, folded_instrs: [] }] }]"#
+    ]];
+    expected.assert_eq(actual);
+}
+
+#[test]
+pub fn block_quasi_quoted_body() {
+    let body = wat! { (nop) };
+    let actual = wat! { (block $exit ,(body)) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"[Instr { unfolded_instr: Block { label: "exit", block_type: None }, folded_instrs: [Instr { unfolded_instr: Nop, folded_instrs: [] }] }]"#
+    ]];
+    expected.assert_eq(actual);
+}
+
+#[test]
+pub fn try_table() {
+    let label = "caught".to_string();
+    let actual = wat! {
+        (try_table (result i32) (catch $exn ,(label)) (catch_all $any)
+            (throw $exn (const_i32 1)))
+    };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"[Instr { unfolded_instr: TryTable { block_type: Some(Result(Num(I32))), catches: [Catch { tag: "exn", label: "caught" }, CatchAll { label: "any" }] }, folded_instrs: [Instr { unfolded_instr: Throw { tag: "exn" }, folded_instrs: [Instr { unfolded_instr: Const { ty: I32, val: 1 }, folded_instrs: [] }] }] }]"#
+    ]];
+    expected.assert_eq(actual);
+}
@@ -22,3 +22,13 @@ pub fn struct_def() {
     ]];
     expected.assert_eq(actual);
 }
+
+#[test]
+pub fn tag() {
+    let actual = wat! { (tag $exception (param (ref $obj))) };
+    let actual = &format!("{:?}", actual);
+    let expected = expect![[
+        r#"Tag { name: "exception", params: [Ref(RefType { null: NonNullable, heap_type: TypeIdx("obj") })] }"#
+    ]];
+    expected.assert_eq(actual);
+}